@@ -0,0 +1,402 @@
+use super::environment::Environment;
+use super::errors::*;
+use super::models::*;
+
+/// Render a type the way it is written in a specification.
+pub fn type_display(ty: &Type) -> String {
+    match *ty {
+        Type::Double => "double".to_owned(),
+        Type::Float => "float".to_owned(),
+        Type::Signed(Some(size)) => format!("signed/{}", size),
+        Type::Signed(None) => "signed".to_owned(),
+        Type::Unsigned(Some(size)) => format!("unsigned/{}", size),
+        Type::Unsigned(None) => "unsigned".to_owned(),
+        Type::Boolean => "boolean".to_owned(),
+        Type::String => "string".to_owned(),
+        Type::Bytes => "bytes".to_owned(),
+        Type::Any => "any".to_owned(),
+        Type::Custom(ref custom) => {
+            if let Some(ref prefix) = custom.prefix {
+                format!("{}::{}", prefix, custom.parts.join("."))
+            } else {
+                custom.parts.join(".")
+            }
+        }
+        Type::Array(ref inner) => format!("[{}]", type_display(inner)),
+        Type::Map(ref key, ref value) => {
+            format!("{{{}: {}}}", type_display(key), type_display(value))
+        }
+    }
+}
+
+fn value_display(value: &Value) -> String {
+    match *value {
+        Value::String(ref string) => format!("{:?}", string),
+        Value::Number(number) => number.to_string(),
+        Value::Boolean(boolean) => boolean.to_string(),
+        Value::Identifier(ref identifier) => identifier.clone(),
+        Value::Type(ref ty) => type_display(ty),
+        Value::Instance(ref instance) => format!("{:?}", instance.inner),
+        Value::Constant(ref constant) => format!("{:?}", constant.inner),
+    }
+}
+
+/// The value that an enum value is serialized as, which is its name unless `serialized_as` is
+/// used.
+fn serialized_value(body: &EnumBody, value: &EnumValue) -> Option<Value> {
+    match body.serialized_as {
+        Some(ref s) => {
+            body.fields
+                .iter()
+                .position(|f| f.name == s.inner)
+                .and_then(|index| value.arguments.get(index))
+                .map(|argument| argument.inner.clone())
+        }
+        None => Some(Value::String(value.name.clone())),
+    }
+}
+
+fn modifier_display(modifier: &Modifier) -> &'static str {
+    match *modifier {
+        Modifier::Required => "required",
+        Modifier::Optional => "optional",
+        Modifier::Repeated => "repeated",
+    }
+}
+
+/// Compares the declarations of an old and a new environment.
+pub struct Compat<'a> {
+    old: &'a Environment,
+    new: &'a Environment,
+    errors: Vec<Error>,
+}
+
+impl<'a> Compat<'a> {
+    pub fn new(old: &'a Environment, new: &'a Environment) -> Compat<'a> {
+        Compat {
+            old: old,
+            new: new,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Check all declarations of the old environment against the new one.
+    ///
+    /// Returns one error for every detected breaking change.
+    pub fn check(mut self) -> Vec<Error> {
+        let old = self.old;
+        let new = self.new;
+
+        for (key, old_decl) in &old.types {
+            let &(ref package, _) = key;
+
+            let new_decl = match new.types.get(key) {
+                Some(new_decl) => new_decl,
+                None => {
+                    self.push(format!("{} removed", old_decl.display()), &old_decl.pos);
+                    continue;
+                }
+            };
+
+            self.check_decl(package, old_decl, new_decl);
+        }
+
+        self.errors
+    }
+
+    fn push(&mut self, message: String, pos: &Pos) {
+        self.errors.push(Error::pos(message, pos.clone()));
+    }
+
+    /// Resolve the package that the prefix of a custom type refers to.
+    fn resolve_prefix(env: &Environment, package: &Package, custom: &Custom) -> Option<Package> {
+        match custom.prefix {
+            Some(ref prefix) => {
+                env.used.get(&(package.clone(), prefix.clone())).map(Clone::clone)
+            }
+            None => Some(package.clone()),
+        }
+    }
+
+    /// Test if two types are equivalent, resolving the import aliases of custom types.
+    fn same_type(&self, package: &Package, old: &Type, new: &Type) -> bool {
+        match (old, new) {
+            (&Type::Custom(ref old), &Type::Custom(ref new)) => {
+                let old_package = Self::resolve_prefix(self.old, package, old);
+                let new_package = Self::resolve_prefix(self.new, package, new);
+                old_package == new_package && old.parts == new.parts
+            }
+            (&Type::Array(ref old), &Type::Array(ref new)) => self.same_type(package, old, new),
+            (&Type::Map(ref old_key, ref old_value), &Type::Map(ref new_key, ref new_value)) => {
+                self.same_type(package, old_key, new_key) &&
+                self.same_type(package, old_value, new_value)
+            }
+            (old, new) => old == new,
+        }
+    }
+
+    fn check_decl(&mut self, package: &Package, old: &Token<Decl>, new: &Token<Decl>) {
+        match (&old.inner, &new.inner) {
+            (&Decl::Type(ref old), &Decl::Type(ref new)) => {
+                self.check_fields(package, &old.fields, &new.fields);
+            }
+            (&Decl::Tuple(ref old), &Decl::Tuple(ref new)) => {
+                self.check_fields(package, &old.fields, &new.fields);
+                self.check_field_order(&old.fields, &new.fields);
+            }
+            (&Decl::Enum(ref old), &Decl::Enum(ref new)) => {
+                self.check_enum(old, new);
+            }
+            (&Decl::Interface(ref old), &Decl::Interface(ref new)) => {
+                self.check_interface(package, old, new);
+            }
+            (_, _) => {
+                self.push(format!("{} changed to {}", old.display(), new.display()),
+                          &new.pos);
+            }
+        }
+    }
+
+    fn check_fields(&mut self,
+                    package: &Package,
+                    old: &Vec<Token<Field>>,
+                    new: &Vec<Token<Field>>) {
+        // fields are identified by the name they are serialized with.
+        for old_field in old {
+            let new_field = match new.iter().find(|f| f.name() == old_field.name()) {
+                Some(new_field) => new_field,
+                None => {
+                    match new.iter().find(|f| f.name == old_field.name) {
+                        Some(new_field) => {
+                            self.push(format!("field `{}` renamed from `{}` to `{}`",
+                                              new_field.name,
+                                              old_field.name(),
+                                              new_field.name()),
+                                      &new_field.pos);
+                        }
+                        None => {
+                            self.push(format!("field `{}` removed", old_field.name),
+                                      &old_field.pos);
+                        }
+                    }
+
+                    continue;
+                }
+            };
+
+            if old_field.modifier != new_field.modifier {
+                self.push(format!("field `{}` changed from {} to {}",
+                                  new_field.name,
+                                  modifier_display(&old_field.modifier),
+                                  modifier_display(&new_field.modifier)),
+                          &new_field.pos);
+            }
+
+            if !self.same_type(package, &old_field.ty, &new_field.ty) {
+                self.push(format!("field `{}` changed type from `{}` to `{}`",
+                                  new_field.name,
+                                  type_display(&old_field.ty),
+                                  type_display(&new_field.ty)),
+                          &new_field.pos);
+            }
+        }
+
+        // old clients do not send fields that they do not know about.
+        for new_field in new {
+            if new_field.modifier != Modifier::Required {
+                continue;
+            }
+
+            // renamed fields have already been reported.
+            if !old.iter().any(|f| f.name() == new_field.name() || f.name == new_field.name) {
+                self.push(format!("required field `{}` added", new_field.name),
+                          &new_field.pos);
+            }
+        }
+    }
+
+    /// Tuples are serialized by position, so fields must keep their relative order.
+    fn check_field_order(&mut self, old: &Vec<Token<Field>>, new: &Vec<Token<Field>>) {
+        for (index, old_field) in old.iter().enumerate() {
+            if let Some(new_index) = new.iter().position(|f| f.name == old_field.name) {
+                if new_index != index {
+                    self.push(format!("field `{}` moved from position {} to {}",
+                                      old_field.name,
+                                      index,
+                                      new_index),
+                              &new[new_index].pos);
+                }
+            }
+        }
+    }
+
+    fn check_enum(&mut self, old: &EnumBody, new: &EnumBody) {
+        for old_value in &old.values {
+            let new_value = match new.values.iter().find(|v| v.name == old_value.name) {
+                Some(new_value) => new_value,
+                None => {
+                    self.push(format!("enum value `{}` removed", old_value.name),
+                              &old_value.pos);
+                    continue;
+                }
+            };
+
+            if old_value.ordinal != new_value.ordinal {
+                self.push(format!("enum value `{}` changed ordinal from {} to {}",
+                                  new_value.name,
+                                  old_value.ordinal,
+                                  new_value.ordinal),
+                          &new_value.pos);
+            }
+
+            let old_serialized = serialized_value(old, old_value);
+            let new_serialized = serialized_value(new, new_value);
+
+            if old_serialized != new_serialized {
+                let display = |value: Option<Value>| {
+                    value.map(|v| value_display(&v)).unwrap_or_else(|| "nothing".to_owned())
+                };
+
+                self.push(format!("enum value `{}` changed serialized value from {} to {}",
+                                  new_value.name,
+                                  display(old_serialized),
+                                  display(new_serialized)),
+                          &new_value.pos);
+            }
+        }
+    }
+
+    fn check_interface(&mut self, package: &Package, old: &InterfaceBody, new: &InterfaceBody) {
+        self.check_fields(package, &old.fields, &new.fields);
+
+        for (name, old_sub_type) in &old.sub_types {
+            let new_sub_type = match new.sub_types.get(name) {
+                Some(new_sub_type) => new_sub_type,
+                None => {
+                    self.push(format!("sub type `{}` removed", name), &old_sub_type.pos);
+                    continue;
+                }
+            };
+
+            let old_names: Vec<&str> = old_sub_type.names.iter().map(|n| n.as_str()).collect();
+            let new_names: Vec<&str> = new_sub_type.names.iter().map(|n| n.as_str()).collect();
+
+            if old_names != new_names {
+                self.push(format!("sub type `{}` changed names from {:?} to {:?}",
+                                  name,
+                                  old_names,
+                                  new_names),
+                          &new_sub_type.pos);
+            }
+
+            self.check_fields(package, &old_sub_type.fields, &new_sub_type.fields);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+
+    fn environment(root: &str, content: &str) -> Environment {
+        let mut env = Environment::new(vec![PathBuf::from(root)]);
+        env.add_source(PathBuf::from(root).join("a.reproto"), content.to_owned());
+        env.import(&Package::new(vec!["a".to_owned()])).unwrap();
+        env.verify().unwrap();
+        env
+    }
+
+    fn check(old: &str, new: &str) -> Vec<String> {
+        let old = environment("old", old);
+        let new = environment("new", new);
+
+        Compat::new(&old, &new)
+            .check()
+            .into_iter()
+            .map(|e| match e {
+                Error(ErrorKind::Pos(message, _), _) => message,
+                e => panic!("unexpected error: {}", e),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compatible() {
+        let old = "package a; type T { a: string; } enum E { A; }";
+        let new = "package a; type T { a: string; b?: string; } enum E { A; B; } type U {}";
+        assert_eq!(Vec::<String>::new(), check(old, new));
+    }
+
+    #[test]
+    fn test_fields() {
+        let old = "package a; type T { a: string; b?: string; c: string; d: string; }";
+        let new = "package a; type T { a: string as \"A\"; b: string; d: signed; y: string; }";
+
+        assert_eq!(vec!["field `a` renamed from `a` to `A`",
+                        "field `b` changed from optional to required",
+                        "field `c` removed",
+                        "field `d` changed type from `string` to `signed`",
+                        "required field `y` added"],
+                   check(old, new));
+    }
+
+    #[test]
+    fn test_fields_by_serialized_name() {
+        let old = "package a; type T { d: string; }";
+        let new = "package a; type T { dd: string as \"d\"; }";
+        assert_eq!(Vec::<String>::new(), check(old, new));
+
+        let old = "package a; type T { d: string; e: string; }";
+        let new = "package a; type T { dd: string as \"d\"; e: string as \"ee\"; }";
+        assert_eq!(vec!["field `e` renamed from `e` to `ee`"], check(old, new));
+    }
+
+    #[test]
+    fn test_tuple_order() {
+        let old = "package a; tuple T { a: string; b: string; }";
+        let new = "package a; tuple T { b: string; a: string; }";
+
+        assert_eq!(vec!["field `a` moved from position 0 to 1",
+                        "field `b` moved from position 1 to 0"],
+                   check(old, new));
+    }
+
+    #[test]
+    fn test_enum() {
+        let old = "package a; enum E { A = 1; B = 2; }";
+        let new = "package a; enum E { A = 3; }";
+
+        assert_eq!(vec!["enum value `A` changed ordinal from 1 to 3", "enum value `B` removed"],
+                   check(old, new));
+    }
+
+    #[test]
+    fn test_enum_serialized_as() {
+        let old = "package a; enum E { A(\"a\"); B(\"b\"); serialized_as v; v: string; }";
+        let new = "package a; enum E { A(\"x\"); B(\"b\"); serialized_as v; v: string; }";
+
+        assert_eq!(vec!["enum value `A` changed serialized value from \"a\" to \"x\""],
+                   check(old, new));
+    }
+
+    #[test]
+    fn test_interface() {
+        let old = "package a; interface I { A { name \"a\"; } B {} }";
+        let new = "package a; interface I { A { name \"x\"; x: string; } C {} }";
+
+        assert_eq!(vec!["sub type `A` changed names from [\"a\"] to [\"x\"]",
+                        "required field `x` added",
+                        "sub type `B` removed"],
+                   check(old, new));
+    }
+
+    #[test]
+    fn test_decl_removed_and_changed() {
+        let old = "package a; type T {} tuple U {}";
+        let new = "package a; enum T {}";
+
+        let errors = check(old, new);
+        assert_eq!(2, errors.len(), "{:?}", errors);
+    }
+}
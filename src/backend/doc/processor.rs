@@ -0,0 +1,608 @@
+use backend::*;
+use backend::errors::*;
+use backend::json::{Json, json_string};
+use backend::models as m;
+use options::Options;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::path::Path;
+
+const EXT: &str = "html";
+const INDEX: &str = "index";
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em auto; max-width: 60em; color: #222; }
+a { color: #0366d6; text-decoration: none; }
+a:hover { text-decoration: underline; }
+section.decl { border-top: 1px solid #ddd; margin-top: 2em; }
+section.sub-type { margin-left: 1em; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ddd; padding: 0.3em 0.6em; text-align: left; }
+code, pre { font-family: monospace; }
+pre { background: #f6f8fa; padding: 0.6em; }
+";
+
+pub trait Listeners {
+    fn configure(&self, _processor: &mut ProcessorOptions) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A vector of listeners is a valid listener.
+impl Listeners for Vec<Box<Listeners>> {
+    fn configure(&self, processor: &mut ProcessorOptions) -> Result<()> {
+        for listeners in self {
+            listeners.configure(processor)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ProcessorOptions {
+    parent: Options,
+}
+
+impl ProcessorOptions {
+    pub fn new(options: Options) -> ProcessorOptions {
+        ProcessorOptions { parent: options }
+    }
+}
+
+fn escape(input: &str) -> String {
+    let mut out = String::new();
+
+    for c in input.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Render a documentation comment, where empty lines separate paragraphs.
+fn comment_html(comment: &Vec<String>) -> String {
+    let mut paragraphs: Vec<Vec<String>> = vec![Vec::new()];
+
+    for line in comment {
+        if line.is_empty() {
+            paragraphs.push(Vec::new());
+        } else if let Some(last) = paragraphs.last_mut() {
+            last.push(escape(line));
+        }
+    }
+
+    paragraphs.into_iter()
+        .filter(|p| !p.is_empty())
+        .map(|p| format!("<p>{}</p>", p.join("\n")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn value_json(value: &m::Value) -> Json {
+    match *value {
+        m::Value::String(ref string) => Json::String(string.to_owned()),
+        m::Value::Number(ref number) => Json::Number(number.to_string()),
+        m::Value::Boolean(ref boolean) => Json::Boolean(*boolean),
+        _ => Json::Null,
+    }
+}
+
+fn value_html(value: &m::Value) -> String {
+    match *value {
+        m::Value::String(ref string) => escape(&json_string(string)),
+        m::Value::Number(ref number) => number.to_string(),
+        m::Value::Boolean(ref boolean) => boolean.to_string(),
+        m::Value::Identifier(ref identifier) => escape(identifier),
+        ref value => escape(&value.to_string()),
+    }
+}
+
+pub struct Processor {
+    options: ProcessorOptions,
+    env: Environment,
+    package_prefix: Option<m::Package>,
+}
+
+impl Processor {
+    pub fn new(options: ProcessorOptions,
+               env: Environment,
+               package_prefix: Option<m::Package>)
+               -> Processor {
+        Processor {
+            options: options,
+            env: env,
+            package_prefix: package_prefix,
+        }
+    }
+
+    /// Build the documented package of a given package.
+    ///
+    /// This includes the prefixed configured in `self.options`, if specified.
+    fn package(&self, package: &m::Package) -> m::Package {
+        self.package_prefix
+            .clone()
+            .map(|prefix| prefix.join(package))
+            .unwrap_or_else(|| package.clone())
+    }
+
+    fn page_name(&self, package: &m::Package) -> String {
+        format!("{}.{}", self.package(package), EXT)
+    }
+
+    /// Resolve the declaration that the given custom type refers to.
+    fn lookup_custom<'a>(&'a self,
+                         pos: &m::Pos,
+                         package: &'a m::Package,
+                         custom: &m::Custom)
+                         -> Result<(&'a m::Package, &'a m::Token<m::Decl>)> {
+        let target = if let Some(ref prefix) = custom.prefix {
+            self.env.lookup_used(pos, package, prefix)?
+        } else {
+            package
+        };
+
+        let key = (target.clone(), custom.parts.clone());
+
+        match self.env.types.get_key_value(&key) {
+            Some((&(ref target, _), decl)) => Ok((target, decl)),
+            None => {
+                Err(Error::pos(format!("no such type: {}", custom.parts.join(".")), pos.clone()))
+            }
+        }
+    }
+
+    /// Render the given type as HTML, where custom types link to their declaration.
+    fn type_html(&self, pos: &m::Pos, package: &m::Package, ty: &m::Type) -> Result<String> {
+        Ok(format!("<code>{}</code>", self.type_html_inner(pos, package, ty)?))
+    }
+
+    fn type_html_inner(&self, pos: &m::Pos, package: &m::Package, ty: &m::Type) -> Result<String> {
+        let out = match *ty {
+            m::Type::Double => "double".to_owned(),
+            m::Type::Float => "float".to_owned(),
+            m::Type::Signed(ref size) => {
+                size.map(|s| format!("signed/{}", s)).unwrap_or_else(|| "signed".to_owned())
+            }
+            m::Type::Unsigned(ref size) => {
+                size.map(|s| format!("unsigned/{}", s)).unwrap_or_else(|| "unsigned".to_owned())
+            }
+            m::Type::Boolean => "boolean".to_owned(),
+            m::Type::String => "string".to_owned(),
+            m::Type::Bytes => "bytes".to_owned(),
+            m::Type::Any => "any".to_owned(),
+            m::Type::Array(ref inner) => {
+                format!("[{}]", self.type_html_inner(pos, package, inner)?)
+            }
+            m::Type::Map(ref key, ref value) => {
+                format!("{{{}: {}}}",
+                        self.type_html_inner(pos, package, key)?,
+                        self.type_html_inner(pos, package, value)?)
+            }
+            m::Type::Custom(ref custom) => {
+                let (target, _) = self.lookup_custom(pos, package, custom)?;
+
+                let name = custom.parts.join(".");
+
+                let display = if let Some(ref prefix) = custom.prefix {
+                    format!("{}::{}", prefix, name)
+                } else {
+                    name.clone()
+                };
+
+                let href = if target == package {
+                    format!("#{}", name)
+                } else {
+                    format!("{}#{}", self.page_name(target), name)
+                };
+
+                format!("<a href=\"{}\">{}</a>", escape(&href), escape(&display))
+            }
+        };
+
+        Ok(out)
+    }
+
+    /// Build an example value of the given type.
+    ///
+    /// `visited` keeps track of the declarations currently being expanded, so that recursive
+    /// declarations terminate.
+    fn type_example(&self,
+                    pos: &m::Pos,
+                    package: &m::Package,
+                    ty: &m::Type,
+                    visited: &mut Vec<(m::Package, Vec<String>)>)
+                    -> Result<Json> {
+        let example = match *ty {
+            m::Type::Double | m::Type::Float => Json::Number("3.14".to_owned()),
+            m::Type::Signed(_) => Json::Number("-42".to_owned()),
+            m::Type::Unsigned(_) => Json::Number("42".to_owned()),
+            m::Type::Boolean => Json::Boolean(true),
+            m::Type::String => Json::String("string".to_owned()),
+            m::Type::Bytes => Json::String("Ynl0ZXM=".to_owned()),
+            m::Type::Any => Json::Null,
+            m::Type::Array(ref inner) => {
+                Json::Array(vec![self.type_example(pos, package, inner, visited)?])
+            }
+            m::Type::Map(ref key, ref value) => {
+                let key = match self.type_example(pos, package, key, visited)? {
+                    Json::String(_) => "key".to_owned(),
+                    Json::Number(number) => number,
+                    _ => "key".to_owned(),
+                };
+
+                let value = self.type_example(pos, package, value, visited)?;
+                Json::Object(vec![(key, value)])
+            }
+            m::Type::Custom(ref custom) => {
+                let (target, decl) = self.lookup_custom(pos, package, custom)?;
+                let key = (target.clone(), custom.parts.clone());
+
+                if visited.contains(&key) {
+                    return Ok(Json::Null);
+                }
+
+                visited.push(key);
+                let example = self.decl_example(target, decl, visited);
+                visited.pop();
+                example?
+            }
+        };
+
+        Ok(example)
+    }
+
+    fn fields_example(&self,
+                      package: &m::Package,
+                      fields: &[&m::Token<m::Field>],
+                      visited: &mut Vec<(m::Package, Vec<String>)>)
+                      -> Result<Vec<(String, Json)>> {
+        let mut entries = Vec::new();
+
+        for field in fields {
+            let value = self.type_example(&field.pos, package, &field.ty, visited)?;
+            entries.push((field.name().to_owned(), value));
+        }
+
+        Ok(entries)
+    }
+
+    fn sub_type_example(&self,
+                        package: &m::Package,
+                        interface: &m::InterfaceBody,
+                        sub_type: &m::SubType,
+                        visited: &mut Vec<(m::Package, Vec<String>)>)
+                        -> Result<Json> {
+        let fields: Vec<_> = interface.fields.iter().chain(sub_type.fields.iter()).collect();
+        let mut entries = self.fields_example(package, &fields, visited)?;
+
+        let example = match interface.type_info {
+            m::TypeInfo::TypeField(ref type_field) => {
+                entries.insert(0, (type_field.to_owned(), Json::String(sub_type.name())));
+                Json::Object(entries)
+            }
+            m::TypeInfo::ObjectKeys => {
+                Json::Object(vec![(sub_type.name(), Json::Object(entries))])
+            }
+        };
+
+        Ok(example)
+    }
+
+    fn enum_serialized(&self, body: &m::EnumBody, value: &m::EnumValue) -> Result<Json> {
+        if let Some(ref s) = body.serialized_as {
+            let index = body.fields
+                .iter()
+                .position(|f| f.name == s.inner)
+                .ok_or_else(|| Error::pos(format!("no field named: {}", s.inner), s.pos.clone()))?;
+
+            if let Some(argument) = value.arguments.get(index) {
+                return Ok(value_json(&argument.inner));
+            }
+        }
+
+        Ok(Json::String(value.name.to_owned()))
+    }
+
+    fn decl_example(&self,
+                    package: &m::Package,
+                    decl: &m::Decl,
+                    visited: &mut Vec<(m::Package, Vec<String>)>)
+                    -> Result<Json> {
+        let example = match *decl {
+            m::Decl::Type(ref body) => {
+                let fields: Vec<_> = body.fields.iter().collect();
+                Json::Object(self.fields_example(package, &fields, visited)?)
+            }
+            m::Decl::Tuple(ref body) => {
+                let fields: Vec<_> = body.fields.iter().collect();
+                let entries = self.fields_example(package, &fields, visited)?;
+                Json::Array(entries.into_iter().map(|(_, value)| value).collect())
+            }
+            m::Decl::Enum(ref body) => {
+                match body.values.iter().next() {
+                    Some(value) => self.enum_serialized(body, value)?,
+                    None => Json::Null,
+                }
+            }
+            m::Decl::Interface(ref body) => {
+                match body.sub_types.values().next() {
+                    Some(sub_type) => self.sub_type_example(package, body, sub_type, visited)?,
+                    None => Json::Null,
+                }
+            }
+        };
+
+        Ok(example)
+    }
+
+    fn write_example(&self, out: &mut String, example: Json) -> Result<()> {
+        let mut content = String::new();
+        example.format(0, &mut content);
+        writeln!(out, "<h4>Example</h4>")?;
+        writeln!(out, "<pre>{}</pre>", escape(&content))?;
+        Ok(())
+    }
+
+    fn write_fields(&self,
+                    out: &mut String,
+                    package: &m::Package,
+                    fields: &[&m::Token<m::Field>])
+                    -> Result<()> {
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(out, "<table class=\"fields\">")?;
+        writeln!(out,
+                 "<tr><th>Field</th><th>Type</th><th>Required</th><th>Description</th></tr>")?;
+
+        for field in fields {
+            let ty = self.type_html(&field.pos, package, &field.ty)?;
+            let required = if field.is_optional() { "no" } else { "yes" };
+
+            writeln!(out,
+                     "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                     escape(field.name()),
+                     ty,
+                     required,
+                     comment_html(&field.comment))?;
+        }
+
+        writeln!(out, "</table>")?;
+        Ok(())
+    }
+
+    fn write_type(&self, out: &mut String, package: &m::Package, body: &m::TypeBody) -> Result<()> {
+        let fields: Vec<_> = body.fields.iter().collect();
+        self.write_fields(out, package, &fields)?;
+        let example = self.decl_example(package, &m::Decl::Type(body.clone()), &mut vec![])?;
+        self.write_example(out, example)
+    }
+
+    fn write_tuple(&self,
+                   out: &mut String,
+                   package: &m::Package,
+                   body: &m::TupleBody)
+                   -> Result<()> {
+        writeln!(out, "<p>Serialized as an array, with one element per field.</p>")?;
+        let fields: Vec<_> = body.fields.iter().collect();
+        self.write_fields(out, package, &fields)?;
+        let example = self.decl_example(package, &m::Decl::Tuple(body.clone()), &mut vec![])?;
+        self.write_example(out, example)
+    }
+
+    fn write_enum(&self, out: &mut String, package: &m::Package, body: &m::EnumBody) -> Result<()> {
+        writeln!(out, "<table class=\"values\">")?;
+        write!(out, "<tr><th>Value</th><th>Serialized</th><th>Description</th>")?;
+
+        for field in &body.fields {
+            let ty = self.type_html(&field.pos, package, &field.ty)?;
+            write!(out, "<th><code>{}</code>: {}</th>", escape(&field.name), ty)?;
+        }
+
+        writeln!(out, "</tr>")?;
+
+        for value in &body.values {
+            let mut serialized = String::new();
+            self.enum_serialized(body, value)?.format(0, &mut serialized);
+
+            write!(out,
+                   "<tr><td><code>{}</code></td><td><code>{}</code></td><td>{}</td>",
+                   escape(&value.name),
+                   escape(&serialized),
+                   comment_html(&value.comment))?;
+
+            for argument in &value.arguments {
+                write!(out, "<td><code>{}</code></td>", value_html(&argument.inner))?;
+            }
+
+            writeln!(out, "</tr>")?;
+        }
+
+        writeln!(out, "</table>")?;
+        Ok(())
+    }
+
+    fn write_interface(&self,
+                       out: &mut String,
+                       package: &m::Package,
+                       body: &m::InterfaceBody)
+                       -> Result<()> {
+        match body.type_info {
+            m::TypeInfo::TypeField(ref type_field) => {
+                writeln!(out,
+                         "<p>Sub types are identified by the <code>{}</code> field.</p>",
+                         escape(&json_string(type_field)))?;
+            }
+            m::TypeInfo::ObjectKeys => {
+                writeln!(out,
+                         "<p>Sub types are serialized as an object with a single key, \
+                          identifying the sub type.</p>")?;
+            }
+        }
+
+        let fields: Vec<_> = body.fields.iter().collect();
+        self.write_fields(out, package, &fields)?;
+
+        for (_, sub_type) in &body.sub_types {
+            let id = format!("{}.{}", body.name, sub_type.name);
+
+            writeln!(out, "<section class=\"sub-type\" id=\"{}\">", escape(&id))?;
+            writeln!(out, "<h3>{}</h3>", escape(&sub_type.name))?;
+
+            if !sub_type.comment.is_empty() {
+                writeln!(out, "{}", comment_html(&sub_type.comment))?;
+            }
+
+            let names: Vec<_> = sub_type.names
+                .iter()
+                .map(|n| format!("<code>{}</code>", escape(&json_string(&n.inner))))
+                .collect();
+
+            let names = if names.is_empty() {
+                format!("<code>{}</code>", escape(&json_string(&sub_type.name)))
+            } else {
+                names.join(", ")
+            };
+
+            writeln!(out, "<p>Identified by: {}</p>", names)?;
+
+            let fields: Vec<_> = sub_type.fields.iter().collect();
+            self.write_fields(out, package, &fields)?;
+
+            let example = self.sub_type_example(package, body, sub_type, &mut vec![])?;
+            self.write_example(out, example)?;
+            writeln!(out, "</section>")?;
+        }
+
+        Ok(())
+    }
+
+    fn write_decl(&self, out: &mut String, package: &m::Package, decl: &m::Decl) -> Result<()> {
+        writeln!(out,
+                 "<section class=\"decl\" id=\"{}\">",
+                 escape(decl.name()))?;
+        writeln!(out, "<h2>{}</h2>", escape(&decl.display()))?;
+
+        let comment = match *decl {
+            m::Decl::Type(ref body) => &body.comment,
+            m::Decl::Tuple(ref body) => &body.comment,
+            m::Decl::Enum(ref body) => &body.comment,
+            m::Decl::Interface(ref body) => &body.comment,
+        };
+
+        if !comment.is_empty() {
+            writeln!(out, "{}", comment_html(comment))?;
+        }
+
+        match *decl {
+            m::Decl::Type(ref body) => self.write_type(out, package, body)?,
+            m::Decl::Tuple(ref body) => self.write_tuple(out, package, body)?,
+            m::Decl::Enum(ref body) => self.write_enum(out, package, body)?,
+            m::Decl::Interface(ref body) => self.write_interface(out, package, body)?,
+        }
+
+        writeln!(out, "</section>")?;
+        Ok(())
+    }
+
+    fn write_header(&self, out: &mut String, title: &str) -> Result<()> {
+        writeln!(out, "<!DOCTYPE html>")?;
+        writeln!(out, "<html>")?;
+        writeln!(out, "<head>")?;
+        writeln!(out, "<meta charset=\"utf-8\">")?;
+        writeln!(out, "<title>{}</title>", escape(title))?;
+        writeln!(out, "<style>{}</style>", STYLE)?;
+        writeln!(out, "</head>")?;
+        writeln!(out, "<body>")?;
+        Ok(())
+    }
+
+    fn write_footer(&self, out: &mut String) -> Result<()> {
+        writeln!(out, "</body>")?;
+        writeln!(out, "</html>")?;
+        Ok(())
+    }
+
+    fn write_package(&self,
+                     package: &m::Package,
+                     decls: &Vec<&m::Token<m::Decl>>)
+                     -> Result<String> {
+        let title = format!("package {}", self.package(package));
+
+        let mut out = String::new();
+        self.write_header(&mut out, &title)?;
+        writeln!(out, "<p><a href=\"{}.{}\">index</a></p>", INDEX, EXT)?;
+        writeln!(out, "<h1>{}</h1>", escape(&title))?;
+
+        writeln!(out, "<ul class=\"decls\">")?;
+
+        for decl in decls {
+            writeln!(out,
+                     "<li><a href=\"#{}\">{}</a></li>",
+                     escape(decl.name()),
+                     escape(&decl.display()))?;
+        }
+
+        writeln!(out, "</ul>")?;
+
+        for decl in decls {
+            self.write_decl(&mut out, package, &decl.inner)?;
+        }
+
+        self.write_footer(&mut out)?;
+        Ok(out)
+    }
+
+    fn write_index(&self, packages: &Vec<&m::Package>) -> Result<String> {
+        let mut out = String::new();
+        self.write_header(&mut out, "Index")?;
+        writeln!(out, "<h1>Packages</h1>")?;
+        writeln!(out, "<ul class=\"packages\">")?;
+
+        for package in packages {
+            writeln!(out,
+                     "<li><a href=\"{}\">{}</a></li>",
+                     escape(&self.page_name(package)),
+                     escape(&self.package(package).to_string()))?;
+        }
+
+        writeln!(out, "</ul>")?;
+        self.write_footer(&mut out)?;
+        Ok(out)
+    }
+
+    fn write_file(&self, name: &str, content: String) -> Result<()> {
+        debug!("+page: {}", name);
+        self.options.parent.output.write(Path::new(name), content.as_bytes())
+    }
+}
+
+impl Backend for Processor {
+    fn process(&self) -> Result<()> {
+        let mut packages: BTreeMap<&m::Package, Vec<&m::Token<m::Decl>>> = BTreeMap::new();
+
+        for (&(ref package, _), decl) in &self.env.types {
+            packages.entry(package).or_insert_with(Vec::new).push(decl);
+        }
+
+        for (package, decls) in &packages {
+            let content = self.write_package(package, decls)?;
+            self.write_file(&self.page_name(package), content)?;
+        }
+
+        let content = self.write_index(&packages.keys().cloned().collect())?;
+        self.write_file(&format!("{}.{}", INDEX, EXT), content)?;
+        Ok(())
+    }
+
+    fn verify(&self) -> Result<Vec<Error>> {
+        Ok(vec![])
+    }
+}
@@ -0,0 +1,26 @@
+//! Helpers for emitting documentation comments in generated code.
+
+/// Build a `/** ... */` documentation comment, as understood by both Javadoc and JSDoc.
+///
+/// Returns no lines if the comment is empty.
+pub fn block_comment(comment: &Vec<String>) -> Vec<String> {
+    if comment.is_empty() {
+        return Vec::new();
+    }
+
+    let mut out = Vec::new();
+    out.push("/**".to_owned());
+
+    for line in comment {
+        let line = line.replace("*/", "*&#47;");
+
+        if line.is_empty() {
+            out.push(" *".to_owned());
+        } else {
+            out.push(format!(" * {}", line));
+        }
+    }
+
+    out.push(" */".to_owned());
+    out
+}
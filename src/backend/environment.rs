@@ -1,7 +1,7 @@
 use parser::ast;
 use parser;
 use std::collections::btree_map::Entry;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use super::errors::*;
 use super::into_model::IntoModel;
@@ -10,11 +10,23 @@ use super::models::*;
 
 const EXT: &str = "reproto";
 
+fn custom_display(custom: &Custom) -> String {
+    if let Some(ref prefix) = custom.prefix {
+        format!("{}::{}", prefix, custom.parts.join("."))
+    } else {
+        custom.parts.join(".")
+    }
+}
+
 pub type TypeId = (Package, Vec<String>);
 
 pub struct Environment {
     paths: Vec<PathBuf>,
     visited: HashSet<Package>,
+    /// Content to use instead of what is stored on disk, like for files being edited.
+    sources: HashMap<PathBuf, String>,
+    /// Files which have already been parsed, and do not need to be read again.
+    parsed: HashMap<PathBuf, ast::File>,
     pub types: BTreeMap<TypeId, Token<Decl>>,
     pub used: BTreeMap<(Package, String), Package>,
 }
@@ -24,6 +36,8 @@ impl Environment {
         Environment {
             paths: paths,
             visited: HashSet::new(),
+            sources: HashMap::new(),
+            parsed: HashMap::new(),
             types: BTreeMap::new(),
             used: BTreeMap::new(),
         }
@@ -92,10 +106,91 @@ impl Environment {
         return Err(Error::pos("no such type".to_owned(), pos.clone()).into());
     }
 
+    /// Lookup the fields of the type instantiated through the given custom type.
+    ///
+    /// Sub types are referenced as `Interface.SubType`, and include the fields of the interface
+    /// before their own, in the same order as they are passed to constructors.
+    pub fn lookup_instance_fields<'a>(&'a self,
+                                      pos: &Pos,
+                                      package: &'a Package,
+                                      custom: &Custom)
+                                      -> Result<Vec<&'a Token<Field>>> {
+        let package = if let Some(ref prefix) = custom.prefix {
+            self.lookup_used(pos, package, prefix)?
+        } else {
+            package
+        };
+
+        let mut parts = custom.parts.iter();
+
+        let first = parts.next().ok_or_else(|| Error::pos("empty type".to_owned(), pos.clone()))?;
+        let key = (package.clone(), vec![first.clone()]);
+
+        let decl = self.types
+            .get(&key)
+            .ok_or_else(|| Error::pos("no such type".to_owned(), pos.clone()))?;
+
+        let fields = match (&decl.inner, parts.next(), parts.next()) {
+            (&Decl::Type(ref body), None, _) => body.fields.iter().collect(),
+            (&Decl::Tuple(ref body), None, _) => body.fields.iter().collect(),
+            (&Decl::Interface(ref body), Some(sub_type), None) => {
+                let sub_type = body.sub_types
+                    .get(sub_type)
+                    .ok_or_else(|| Error::pos("no such sub type".to_owned(), pos.clone()))?;
+
+                body.fields.iter().chain(sub_type.fields.iter()).collect()
+            }
+            (decl, _, _) => {
+                return Err(Error::pos(format!("cannot make instance of {}", decl.display()),
+                                      pos.clone()));
+            }
+        };
+
+        Ok(fields)
+    }
+
+    /// Use the given content for the file at the given path, instead of what is stored on disk.
+    pub fn add_source(&mut self, path: PathBuf, content: String) {
+        self.sources.insert(path, content);
+    }
+
+    /// Use an already parsed file for the given path, instead of parsing it again.
+    pub fn add_parsed(&mut self, path: PathBuf, file: ast::File) {
+        self.parsed.insert(path, file);
+    }
+
+    /// All packages that have been imported, including those which failed to import.
+    pub fn imported(&self) -> Vec<&Package> {
+        self.visited.iter().collect()
+    }
+
+    /// All paths where files for the given package can be found.
+    pub fn candidates(&self, package: &Package) -> Vec<PathBuf> {
+        self.paths
+            .iter()
+            .map(|p| {
+                let mut path = p.clone();
+
+                for part in &package.parts {
+                    path.push(part);
+                }
+
+                path.set_extension(EXT);
+                path
+            })
+            .collect()
+    }
+
     pub fn import_file(&mut self, path: &Path, package: Option<&Package>) -> Result<()> {
         debug!("in: {}", path.display());
 
-        let file = parser::parse_file(&path)?;
+        let file = if let Some(file) = self.parsed.get(path) {
+            file.clone()
+        } else if let Some(content) = self.sources.get(path) {
+            parser::parse_string(&path, content)?
+        } else {
+            parser::parse_file(&path)?
+        };
 
         if let Some(package) = package {
             if *file.package != *package {
@@ -107,18 +202,31 @@ impl Environment {
             }
         }
 
+        let mut errors = Vec::new();
+
         for use_decl in &file.uses {
-            self.register_alias(&file.package, use_decl)?;
-            self.import(&use_decl.package)?;
+            if let Err(e) = self.register_alias(&file.package, use_decl) {
+                errors.extend(e.into_errors());
+            }
+
+            if let Err(e) = self.import(&use_decl.package) {
+                errors.extend(e.into_errors());
+            }
         }
 
+        let file_package = &file.package;
+
         for decl in file.decls {
             let pos = (path.to_owned(), decl.pos.0, decl.pos.1);
-            let decl = decl.into_model(&pos)?;
-            self.register_type(&file.package, decl)?;
+            let result = decl.into_model(&pos)
+                .and_then(|decl| self.register_type(file_package, decl));
+
+            if let Err(e) = result {
+                errors.extend(e.into_errors());
+            }
         }
 
-        Ok(())
+        from_errors(errors)
     }
 
     pub fn import(&mut self, package: &Package) -> Result<()> {
@@ -130,22 +238,11 @@ impl Environment {
 
         let mut files: Vec<PathBuf> = Vec::new();
 
-        let candidates: Vec<PathBuf> = self.paths
-            .iter()
-            .map(|p| {
-                let mut path = p.clone();
-
-                for part in &package.parts {
-                    path.push(part);
-                }
-
-                path.set_extension(EXT);
-                path
-            })
-            .collect();
+        let candidates = self.candidates(package);
 
         for path in &candidates {
-            if !path.is_file() {
+            if !path.is_file() && !self.sources.contains_key(path) &&
+               !self.parsed.contains_key(path) {
                 continue;
             }
 
@@ -165,23 +262,321 @@ impl Environment {
                 .into());
         }
 
+        let mut errors = Vec::new();
+
         for path in files {
-            self.import_file(&path, Some(package))?;
+            if let Err(e) = self.import_file(&path, Some(package)) {
+                errors.extend(e.into_errors());
+            }
         }
 
-        Ok(())
+        from_errors(errors)
     }
 
-    pub fn verify(&mut self) -> Result<()> {
-        for (_, ref ty) in &self.types {
-            match ty.inner {
-                Decl::Type(ref ty) => {
-                    ty.verify()?;
+    /// Resolve the package that types with the given prefix are declared in.
+    fn lookup_package<'a>(&'a self,
+                          pos: &Pos,
+                          package: &'a Package,
+                          prefix: &Option<String>)
+                          -> Result<&'a Package> {
+        let package = if let Some(ref prefix) = *prefix {
+            self.lookup_used(pos, package, prefix)?
+        } else {
+            package
+        };
+
+        if !self.types.keys().any(|&(ref p, _)| p == package) {
+            return Err(Error::pos(format!("package `{}` has not been imported", package),
+                                  pos.clone()));
+        }
+
+        Ok(package)
+    }
+
+    /// Verify that a custom type refers to a declaration, or a sub type of an interface.
+    fn verify_custom(&self, pos: &Pos, package: &Package, custom: &Custom) -> Result<()> {
+        let package = self.lookup_package(pos, package, &custom.prefix)?;
+        let unknown = || {
+            Error::pos(format!("no such type: {}", custom_display(custom)), pos.clone())
+        };
+
+        let mut parts = custom.parts.iter();
+        let first = parts.next().ok_or_else(&unknown)?;
+
+        let decl = self.types.get(&(package.clone(), vec![first.clone()])).ok_or_else(&unknown)?;
+
+        match (&decl.inner, parts.next(), parts.next()) {
+            (_, None, _) => Ok(()),
+            (&Decl::Interface(ref body), Some(sub_type), None) => {
+                if body.sub_types.contains_key(sub_type) {
+                    Ok(())
+                } else {
+                    Err(unknown())
+                }
+            }
+            _ => Err(unknown()),
+        }
+    }
+
+    /// Verify that a constant refers to a declaration, or a value of an enum.
+    fn verify_constant(&self, pos: &Pos, package: &Package, constant: &Constant) -> Result<()> {
+        let custom = Custom {
+            prefix: constant.prefix.clone(),
+            parts: constant.parts.clone(),
+        };
+
+        if self.verify_custom(pos, package, &custom).is_ok() {
+            return Ok(());
+        }
+
+        let package = self.lookup_package(pos, package, &constant.prefix)?;
+
+        if let Some((value, parts)) = constant.parts.split_last() {
+            if let Some(decl) = self.types.get(&(package.clone(), parts.to_vec())) {
+                if let Decl::Enum(ref body) = decl.inner {
+                    if body.values.iter().any(|v| v.name == *value) {
+                        return Ok(());
+                    }
                 }
-                _ => {}
             }
         }
 
-        Ok(())
+        Err(Error::pos(format!("no such type or value: {}", custom_display(&custom)),
+                       pos.clone()))
+    }
+
+    fn verify_type(&self, pos: &Pos, package: &Package, ty: &Type) -> Result<()> {
+        match *ty {
+            Type::Custom(ref custom) => self.verify_custom(pos, package, custom),
+            Type::Array(ref inner) => self.verify_type(pos, package, inner),
+            Type::Map(ref key, ref value) => {
+                self.verify_type(pos, package, key)?;
+                self.verify_type(pos, package, value)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn verify_value(&self, package: &Package, value: &Token<Value>) -> Result<()> {
+        match value.inner {
+            Value::Type(ref ty) => self.verify_type(&value.pos, package, ty),
+            Value::Instance(ref instance) => {
+                self.verify_custom(&instance.pos, package, &instance.ty)?;
+
+                let mut errors = Vec::new();
+
+                for init in &instance.arguments {
+                    if let Err(e) = self.verify_value(package, &init.value) {
+                        errors.extend(e.into_errors());
+                    }
+                }
+
+                from_errors(errors)
+            }
+            Value::Constant(ref constant) => {
+                self.verify_constant(&constant.pos, package, constant)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Verify all references to other types made by a declaration.
+    fn verify_decl(&self, package: &Package, decl: &Decl) -> Vec<Error> {
+        let mut results = Vec::new();
+
+        let (fields, match_decl) = match *decl {
+            Decl::Type(ref body) => {
+                results.push(body.verify());
+                (body.fields.iter().collect(), &body.match_decl)
+            }
+            Decl::Tuple(ref body) => (body.fields.iter().collect(), &body.match_decl),
+            Decl::Interface(ref body) => {
+                let sub_type_fields = body.sub_types.values().flat_map(|s| s.fields.iter());
+                (body.fields.iter().chain(sub_type_fields).collect(), &body.match_decl)
+            }
+            Decl::Enum(ref body) => {
+                for value in &body.values {
+                    for argument in &value.arguments {
+                        results.push(self.verify_value(package, argument));
+                    }
+                }
+
+                (body.fields.iter().collect::<Vec<_>>(), &body.match_decl)
+            }
+        };
+
+        for field in fields {
+            results.push(self.verify_type(&field.pos, package, &field.ty));
+        }
+
+        for &(ref value, ref member) in &match_decl.by_value {
+            results.push(self.verify_value(package, value));
+            results.push(self.verify_value(package, &member.value));
+        }
+
+        for &(_, ref member) in &match_decl.by_type {
+            if let MatchCondition::Type(ref variable) = member.condition.inner {
+                results.push(self.verify_type(&member.condition.pos, package, &variable.ty));
+            }
+
+            results.push(self.verify_value(package, &member.value));
+        }
+
+        results.into_iter().filter_map(|r| r.err()).flat_map(Error::into_errors).collect()
+    }
+
+    pub fn verify(&mut self) -> Result<()> {
+        let mut errors = Vec::new();
+
+        for (&(ref package, _), ref decl) in &self.types {
+            errors.extend(self.verify_decl(package, &decl.inner));
+        }
+
+        from_errors(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT: &str = "root";
+
+    fn environment(files: &[(&str, &str)]) -> Environment {
+        let mut env = Environment::new(vec![PathBuf::from(ROOT)]);
+
+        for &(name, content) in files {
+            env.add_source(Path::new(ROOT).join(name), content.to_owned());
+        }
+
+        env
+    }
+
+    fn package(name: &str) -> Package {
+        Package::new(name.split('.').map(ToOwned::to_owned).collect())
+    }
+
+    /// All errors of the result, as the file and offset they are reported at, and their message.
+    fn errors(result: Result<()>) -> Vec<(String, usize, String)> {
+        let errors = match result {
+            Ok(()) => return vec![],
+            Err(e) => e.into_errors(),
+        };
+
+        let mut errors: Vec<_> = errors.into_iter()
+            .map(|e| match e {
+                Error(ErrorKind::Pos(message, pos), _) => {
+                    (pos.0.display().to_string(), pos.1, message)
+                }
+                e => panic!("unexpected error: {}", e),
+            })
+            .collect();
+
+        errors.sort();
+        errors
+    }
+
+    fn at(name: &str, content: &str, pattern: &str, message: &str) -> (String, usize, String) {
+        let offset = content.find(pattern).unwrap_or_else(|| panic!("not found: {}", pattern));
+        (Path::new(ROOT).join(name).display().to_string(), offset, message.to_owned())
+    }
+
+    #[test]
+    fn test_from_errors() {
+        assert!(from_errors(vec![]).is_ok());
+
+        let error = from_errors(vec!["a".into()]).unwrap_err();
+        assert_eq!("a", error.to_string());
+
+        let error = from_errors(vec!["a".into(), "b".into()]).unwrap_err();
+        assert_eq!("encountered 2 error(s)", error.to_string());
+
+        let messages: Vec<_> = error.into_errors().iter().map(ToString::to_string).collect();
+        assert_eq!(vec!["a", "b"], messages);
+    }
+
+    #[test]
+    fn test_import_collects_errors() {
+        let a = "package a;\n\nuse b;\n\ninterface I {\n  type_info array;\n}\n\n\
+                 interface J {\n  type_info wrapped;\n}\n";
+        let b = "package b;\n\ninterface K {\n  type_info array;\n}\n";
+
+        let mut env = environment(&[("a.reproto", a), ("b.reproto", b)]);
+
+        let unsupported = |value| {
+            format!("type_info: unsupported value `{}`, expected one of: type_field, object_keys",
+                    value)
+        };
+
+        assert_eq!(vec![at("a.reproto", a, "array", &unsupported("array")),
+                        at("a.reproto", a, "wrapped", &unsupported("wrapped")),
+                        at("b.reproto", b, "array", &unsupported("array"))],
+                   errors(env.import(&package("a"))));
+    }
+
+    #[test]
+    fn test_verify_collects_errors() {
+        let a = "package a;\n\nuse b;\n\ntype T {\n  x: Missing;\n  y: [Other];\n}\n\n\
+                 tuple U {\n  z: b::Gone;\n}\n";
+        let b = "package b;\n\ntype B {\n  x: {string: Lost};\n}\n";
+
+        let mut env = environment(&[("a.reproto", a), ("b.reproto", b)]);
+        env.import(&package("a")).unwrap();
+
+        assert_eq!(vec![at("a.reproto", a, "x: Missing", "no such type: Missing"),
+                        at("a.reproto", a, "y: [Other]", "no such type: Other"),
+                        at("a.reproto", a, "z: b::Gone", "no such type: b::Gone"),
+                        at("b.reproto", b, "x: {string", "no such type: Lost")],
+                   errors(env.verify()));
+    }
+    /// Import and verify the package `a` from the given file content.
+    fn verify(content: &str) -> Vec<(String, usize, String)> {
+        let mut env = environment(&[("a.reproto", content)]);
+        env.import(&package("a")).unwrap();
+        errors(env.verify())
+    }
+
+    #[test]
+    fn test_verify_unknown_type() {
+        let a = "package a; type T { x: Missing; }";
+        assert_eq!(vec![at("a.reproto", a, "x: ", "no such type: Missing")], verify(a));
+    }
+
+    #[test]
+    fn test_verify_unknown_sub_type() {
+        let a = "package a; interface I { A {} } type T { a: I.A; b: I.B; }";
+        assert_eq!(vec![at("a.reproto", a, "b: ", "no such type: I.B")], verify(a));
+    }
+
+    #[test]
+    fn test_verify_unknown_enum_value_in_match() {
+        let a = "package a; enum E { A; } type T { match { \"a\" => T(e: E.A); \"b\" => T(e: E.B); \
+                 } e: E; }";
+
+        assert_eq!(vec![at("a.reproto", a, "E.B", "no such type or value: E.B")], verify(a));
+    }
+
+    #[test]
+    fn test_verify_unknown_enum_value_in_constant() {
+        let a = "package a; enum E { A; } enum F { X(E.A); Y(E.B); e: E; }";
+        assert_eq!(vec![at("a.reproto", a, "E.B", "no such type or value: E.B")], verify(a));
+    }
+
+    #[test]
+    fn test_verify_missing_alias() {
+        let a = "package a; type T { x: b::T; }";
+        assert_eq!(vec![at("a.reproto", a, "x: ", "Missing import alias for (b)")], verify(a));
+    }
+
+    #[test]
+    fn test_verify_package_not_imported() {
+        let a = "package a; use b; type T { x: b::T; }";
+
+        // the package could not be imported, but its alias is still known.
+        let mut env = environment(&[("a.reproto", a)]);
+        assert!(env.import(&package("a")).is_err());
+
+        assert_eq!(vec![at("a.reproto", a, "x: ", "package `b` has not been imported")],
+                   errors(env.verify()));
     }
 }
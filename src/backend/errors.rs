@@ -8,6 +8,7 @@ error_chain! {
 
     foreign_links {
         IO(::std::io::Error);
+        Fmt(::std::fmt::Error);
     }
 
     errors {
@@ -39,6 +40,11 @@ error_chain! {
         MatchConflict(source: m::Pos, target: m::Pos) {
             description("match conflict")
         }
+
+        Errors(errors: Vec<Error>) {
+            description("errors")
+            display("encountered {} error(s)", errors.len())
+        }
     }
 }
 
@@ -62,4 +68,21 @@ impl Error {
     pub fn reserved_field(field_pos: m::Pos, reserved_pos: m::Pos) -> Error {
         ErrorKind::ReservedField(field_pos, reserved_pos).into()
     }
+
+    /// Split this error into all the errors that it was collected from.
+    pub fn into_errors(self) -> Vec<Error> {
+        match self {
+            Error(ErrorKind::Errors(errors), _) => errors,
+            error => vec![error],
+        }
+    }
+}
+
+/// Fail with all the collected errors, unless there are none.
+pub fn from_errors(mut errors: Vec<Error>) -> Result<()> {
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        _ => Err(ErrorKind::Errors(errors).into()),
+    }
 }
@@ -18,6 +18,9 @@ fn code(pos: &Pos, ast_pos: ast::Pos, context: String, lines: Vec<String>) -> To
     Token::new(code, pos)
 }
 
+/// Default name of the field indicating which sub type an interface is.
+const TYPE: &str = "type";
+
 type Fields = Vec<Token<Field>>;
 type Codes = Vec<Token<Code>>;
 type OptionVec = Vec<Token<OptionDecl>>;
@@ -107,6 +110,56 @@ impl OrdinalGenerator {
     }
 }
 
+/// Check that the size of every integer in the given type is supported.
+///
+/// Sizes must be a power of two, starting at 8 bits.
+fn check_type(ty: &Type, pos: &Pos) -> Result<()> {
+    match *ty {
+        Type::Signed(Some(size)) |
+        Type::Unsigned(Some(size)) => {
+            if size < 8 || !size.is_power_of_two() {
+                return Err(Error::pos(format!("unsupported integer size: {}", size),
+                                      pos.clone()));
+            }
+        }
+        Type::Array(ref inner) => check_type(inner, pos)?,
+        Type::Map(ref key, ref value) => {
+            check_type(key, pos)?;
+            check_type(value, pos)?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Assign the numbers given through `field_number <field>, <number>;` options to their fields.
+///
+/// Returns the numbers which don't name any of the fields.
+fn assign_field_numbers(options: &Options,
+                        fields: &mut Fields)
+                        -> Result<Vec<(Token<String>, Token<u32>)>> {
+    let mut unassigned = Vec::new();
+
+    for (name, number) in options.find_all_numbered("field_number")? {
+        match fields.iter_mut().find(|f| f.name == name.inner) {
+            Some(field) => field.inner.number = Some(number),
+            None => unassigned.push((name, number)),
+        }
+    }
+
+    Ok(unassigned)
+}
+
+fn no_such_field(unassigned: Vec<(Token<String>, Token<u32>)>) -> Result<()> {
+    if let Some((name, _)) = unassigned.into_iter().next() {
+        return Err(Error::pos(format!("field_number: no field named `{}`", name.inner),
+                              name.pos));
+    }
+
+    Ok(())
+}
+
 /// Adds the into_model() method for all types that supports conversion into models.
 pub trait IntoModel {
     type Output;
@@ -161,7 +214,7 @@ impl IntoModel for ast::InterfaceBody {
     type Output = InterfaceBody;
 
     fn into_model(self, pos: &Pos) -> Result<InterfaceBody> {
-        let (fields, codes, options, match_decl) = members_into_model(&pos, self.members)?;
+        let (mut fields, codes, options, match_decl) = members_into_model(&pos, self.members)?;
 
         let mut sub_types: BTreeMap<String, Token<SubType>> = BTreeMap::new();
 
@@ -179,14 +232,65 @@ impl IntoModel for ast::InterfaceBody {
             }
         }
 
-        let _options = Options::new(&pos, options);
+        let options = Options::new(&pos, options);
+
+        // remaining numbers are for the sub types of the interface.
+        let mut unassigned = Vec::new();
+
+        for (name, number) in assign_field_numbers(&options, &mut fields)? {
+            match sub_types.get_mut(&name.inner) {
+                Some(sub_type) => sub_type.inner.number = Some(number),
+                None => unassigned.push((name, number)),
+            }
+        }
+
+        no_such_field(unassigned)?;
+
+        let type_info_option = options.find_one_identifier("type_info")?;
+        let type_field_name = options.find_one_string("type_field_name")?;
+
+        let type_info_pos = type_info_option.as_ref()
+            .map(|t| t.pos.clone())
+            .or_else(|| type_field_name.as_ref().map(|t| t.pos.clone()));
+
+        // `None` indicates the default, which is a type field.
+        let type_info = if let Some(type_info) = type_info_option {
+            match type_info.inner.as_str() {
+                "type_field" => None,
+                "object_keys" => Some(TypeInfo::ObjectKeys),
+                other => {
+                    return Err(Error::pos(format!("type_info: unsupported value `{}`, \
+                                                   expected one of: type_field, object_keys",
+                                                  other),
+                                          type_info.pos.clone()));
+                }
+            }
+        } else {
+            None
+        };
+
+        let type_info = match (type_info, type_field_name) {
+            (Some(_), Some(type_field_name)) => {
+                return Err(Error::pos("type_field_name: only valid with `type_info type_field`"
+                                          .to_owned(),
+                                      type_field_name.pos));
+            }
+            (Some(type_info), None) => type_info,
+            (None, type_field_name) => {
+                let name = type_field_name.map(|t| t.inner).unwrap_or_else(|| TYPE.to_owned());
+                TypeInfo::TypeField(name)
+            }
+        };
 
         let interface_body = InterfaceBody {
             name: self.name,
+            comment: self.comment,
             fields: fields,
             codes: codes,
             match_decl: match_decl,
             sub_types: sub_types,
+            type_info: type_info,
+            type_info_pos: type_info_pos,
         };
 
         Ok(interface_body)
@@ -221,6 +325,7 @@ impl IntoModel for ast::EnumBody {
 
         let en = EnumBody {
             name: self.name,
+            comment: self.comment,
             values: values,
             fields: fields,
             codes: codes,
@@ -243,6 +348,7 @@ impl IntoModel for (ast::EnumValue, u32) {
 
         let value = EnumValue {
             name: value.name,
+            comment: value.comment,
             arguments: value.arguments.into_model(pos)?,
             ordinal: ordinal,
         };
@@ -255,15 +361,18 @@ impl IntoModel for ast::TypeBody {
     type Output = TypeBody;
 
     fn into_model(self, pos: &Pos) -> Result<TypeBody> {
-        let (fields, codes, options, match_decl) = members_into_model(&pos, self.members)?;
+        let (mut fields, codes, options, match_decl) = members_into_model(&pos, self.members)?;
 
         let options = Options::new(&pos, options);
 
+        no_such_field(assign_field_numbers(&options, &mut fields)?)?;
+
         let reserved: HashSet<Token<String>> =
             options.find_all_identifiers("reserved")?.into_iter().collect();
 
         let type_body = TypeBody {
             name: self.name,
+            comment: self.comment,
             fields: fields,
             codes: codes,
             match_decl: match_decl,
@@ -313,11 +422,15 @@ impl IntoModel for ast::SubType {
 
         let names = options.find_all_strings("name")?;
 
+        no_such_field(assign_field_numbers(&options, &mut fields)?)?;
+
         let sub_type = SubType {
             name: self.name,
+            comment: self.comment,
             fields: fields,
             codes: codes,
             names: names,
+            number: None,
         };
 
         Ok(sub_type)
@@ -334,6 +447,7 @@ impl IntoModel for ast::TupleBody {
 
         let tuple_body = TupleBody {
             name: self.name,
+            comment: self.comment,
             fields: fields,
             codes: codes,
             match_decl: match_decl,
@@ -375,6 +489,8 @@ impl IntoModel for ast::Field {
     type Output = Field;
 
     fn into_model(self, pos: &Pos) -> Result<Field> {
+        check_type(&self.ty, pos)?;
+
         let field_as = self.field_as.into_model(pos)?;
 
         let field_as = if let Some(field_as) = field_as {
@@ -388,10 +504,12 @@ impl IntoModel for ast::Field {
         };
 
         let field = Field {
+            comment: self.comment,
             modifier: self.modifier,
             name: self.name,
             ty: self.ty,
             field_as: field_as,
+            number: None,
         };
 
         Ok(field)
@@ -467,6 +585,8 @@ impl IntoModel for ast::MatchVariable {
     type Output = MatchVariable;
 
     fn into_model(self, pos: &Pos) -> Result<MatchVariable> {
+        check_type(&self.ty, pos)?;
+
         let match_variable = MatchVariable {
             name: self.name.into_model(pos)?,
             ty: self.ty,
@@ -501,3 +621,62 @@ impl IntoModel for ast::MatchMember {
         Ok(member)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use parser;
+    use std::path::{Path, PathBuf};
+    use super::*;
+
+    fn decl(content: &str) -> Result<Token<Decl>> {
+        let path = Path::new("test.reproto");
+        let file = parser::parse_string(path, content).unwrap();
+        let decl = file.decls.into_iter().next().unwrap();
+        let pos = (PathBuf::from(path), decl.pos.0, decl.pos.1);
+        decl.into_model(&pos)
+    }
+
+    #[test]
+    fn test_integer_sizes() {
+        let content = "package a; type T { a: signed/8; b: unsigned/64; c: [unsigned/128]; }";
+        assert!(decl(content).is_ok());
+    }
+
+    #[test]
+    fn test_unsupported_integer_size() {
+        for ty in &["signed/7", "unsigned/4", "[signed/24]", "{string: unsigned/0}"] {
+            match decl(&format!("package a; type T {{ a: {}; }}", ty)) {
+                Err(Error(ErrorKind::Pos(ref message, _), _)) => {
+                    assert!(message.starts_with("unsupported integer size"), "{}", message)
+                }
+                Err(e) => panic!("{}: unexpected error: {}", ty, e),
+                Ok(_) => panic!("{}: expected error", ty),
+            }
+        }
+    }
+
+    #[test]
+    fn test_field_number() {
+        let decl = decl("package a; type T { field_number b, 3; a: string; b: string; }").unwrap();
+
+        match decl.inner {
+            Decl::Type(ref body) => {
+                let numbers: Vec<_> =
+                    body.fields.iter().map(|f| f.number.as_ref().map(|n| n.inner)).collect();
+                assert_eq!(vec![None, Some(3)], numbers);
+            }
+            _ => panic!("expected type"),
+        }
+    }
+
+    #[test]
+    fn test_field_number_no_such_field() {
+        match decl("package a; type T { field_number b, 3; a: string; }") {
+            Err(Error(ErrorKind::Pos(ref message, _), _)) => {
+                assert_eq!("field_number: no field named `b`", message)
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected error"),
+        }
+    }
+}
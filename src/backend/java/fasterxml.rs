@@ -19,9 +19,18 @@ pub struct Module {
     serializer_provider: ClassType,
     parser: ClassType,
     deserialization_context: ClassType,
+    mapping_exception: ClassType,
     token: ClassType,
     string: ClassType,
+    byte_buffer: ClassType,
+    big_integer: ClassType,
     io_exception: ClassType,
+    object: ClassType,
+    optional: ClassType,
+    codec: ClassType,
+    node: ClassType,
+    type_reference: ClassType,
+    type_deserializer: ClassType,
 }
 
 impl Module {
@@ -44,9 +53,20 @@ impl Module {
             parser: Type::class("com.fasterxml.jackson.core", "JsonParser"),
             deserialization_context: Type::class("com.fasterxml.jackson.databind",
                                                  "DeserializationContext"),
+            mapping_exception: Type::class("com.fasterxml.jackson.databind",
+                                           "JsonMappingException"),
             token: Type::class("com.fasterxml.jackson.core", "JsonToken"),
             string: Type::class("java.lang", "String"),
+            byte_buffer: Type::class("java.nio", "ByteBuffer"),
+            big_integer: Type::class("java.math", "BigInteger"),
             io_exception: Type::class("java.io", "IOException"),
+            object: Type::class("java.lang", "Object"),
+            optional: Type::class("java.util", "Optional"),
+            codec: Type::class("com.fasterxml.jackson.core", "ObjectCodec"),
+            node: Type::class("com.fasterxml.jackson.databind", "JsonNode"),
+            type_reference: Type::class("com.fasterxml.jackson.core.type", "TypeReference"),
+            type_deserializer: Type::class("com.fasterxml.jackson.databind.jsontype",
+                                           "TypeDeserializer"),
         }
     }
 
@@ -126,7 +146,7 @@ impl Module {
                         Ok((Some((test, "VALUE_NUMBER_INT")), stmt![parser, ".getLongValue()"]))
                     }
                     INTEGER => {
-                        Ok((Some((test, "VALUE_NUMBER_INT")), stmt![parser, ".getIntegerValue()"]))
+                        Ok((Some((test, "VALUE_NUMBER_INT")), stmt![parser, ".getIntValue()"]))
                     }
                     FLOAT => {
                         Ok((Some((test, "VALUE_NUMBER_FLOAT")), stmt![parser, ".getFloatValue()"]))
@@ -144,6 +164,19 @@ impl Module {
                     return Ok((token, stmt![parser, ".getText()"]));
                 }
 
+                if *class == self.big_integer {
+                    let test = stmt!["!", parser, ".nextToken().isNumeric()"];
+                    let token = Some((test, "VALUE_NUMBER_INT"));
+                    return Ok((token, stmt![parser, ".getBigIntegerValue()"]));
+                }
+
+                if *class == self.byte_buffer {
+                    let test = stmt![&parser, ".nextToken() != ", &self.token, ".VALUE_STRING"];
+                    let token = Some((test, "VALUE_STRING"));
+                    let value = stmt![parser, ".getBinaryValue()"];
+                    return Ok((token, stmt![&self.byte_buffer, ".wrap(", value, ")"]));
+                }
+
                 if class.arguments.is_empty() {
                     return Ok((None, stmt![parser, ".readValueAs(", class, ".class)"]));
                 }
@@ -160,16 +193,25 @@ impl Module {
     fn wrong_token_exception(&self,
                              ctxt: &ArgumentSpec,
                              parser: &ArgumentSpec,
+                             class_type: &ClassType,
                              token: &str)
                              -> Statement {
         let mut arguments = Statement::new();
         arguments.push(parser);
+        arguments.push(stmt![class_type, ".class"]);
         arguments.push(stmt![&self.token, ".", token]);
         arguments.push("null");
 
         stmt!["throw ", ctxt, ".wrongTokenException(", arguments.join(", "), ");"]
     }
 
+    /// Throw an exception for input which can't be decoded, with the given message.
+    fn mapping_exception<S>(&self, message: S) -> Statement
+        where S: Into<Statement>
+    {
+        stmt!["throw ", &self.mapping_exception, ".from(ctxt, ", message.into(), ");"]
+    }
+
     /// Custom deserialize implementation for tuples.
     fn tuple_deserializer(&self,
                           fields: &Vec<m::JavaField>,
@@ -196,7 +238,7 @@ impl Module {
 
         let mut start_array = Elements::new();
         start_array.push(stmt!["if (", &current_token, " != ", &self.token, ".START_ARRAY) {"]);
-        start_array.push_nested(self.wrong_token_exception(&ctxt, &parser, "START_ARRAY"));
+        start_array.push_nested(self.wrong_token_exception(&ctxt, &parser, class_type, "START_ARRAY"));
         start_array.push("}");
         deserialize.push(start_array);
 
@@ -208,9 +250,15 @@ impl Module {
             if let Some((test, expected)) = token {
                 let mut field_check = Elements::new();
                 field_check.push(stmt!["if (", &test, ") {"]);
-                field_check.push_nested(self.wrong_token_exception(&ctxt, &parser, expected));
+                field_check.push_nested(self.wrong_token_exception(&ctxt,
+                                                                   &parser,
+                                                                   class_type,
+                                                                   expected));
                 field_check.push("}");
                 deserialize.push(field_check);
+            } else {
+                // values are read starting at the current token.
+                deserialize.push(stmt![&parser, ".nextToken();"]);
             }
 
             let variable = stmt!["v_", &field.spec.name];
@@ -223,7 +271,7 @@ impl Module {
 
         let mut end_array = Elements::new();
         end_array.push(stmt!["if (", &next_token, " != ", &self.token, ".END_ARRAY) {"]);
-        end_array.push_nested(self.wrong_token_exception(&ctxt, &parser, "END_ARRAY"));
+        end_array.push_nested(self.wrong_token_exception(&ctxt, &parser, class_type, "END_ARRAY"));
         end_array.push("}");
         deserialize.push(end_array);
 
@@ -232,6 +280,238 @@ impl Module {
         deserializer.push(deserialize);
         Ok(deserializer)
     }
+
+    /// Read the given tree node as a value of the given type.
+    fn read_node<S>(&self, codec: &Statement, node: S, ty: &Type) -> Statement
+        where S: Into<Statement>
+    {
+        let reference = self.type_reference.with_arguments(vec![ty]);
+        let tokens = stmt![codec, ".treeAsTokens(", node.into(), ")"];
+        stmt![codec, ".readValue(", tokens, ", new ", reference, "() {})"]
+    }
+
+    /// Build the check that a tree node matches the given match member.
+    fn match_check(&self, node: &Statement, m: &m::JavaMatch) -> Statement {
+        let is_kind = match m.kind {
+            m::MatchKind::Any => stmt!["true"],
+            m::MatchKind::Object => stmt![node, ".isObject()"],
+            m::MatchKind::Array => stmt![node, ".isArray()"],
+            m::MatchKind::String => stmt![node, ".isTextual()"],
+            m::MatchKind::Boolean => stmt![node, ".isBoolean()"],
+            m::MatchKind::Number => stmt![node, ".isNumber()"],
+        };
+
+        if let m::JavaMatchCondition::Value(ref literal) = m.condition {
+            let value = match m.kind {
+                m::MatchKind::String => stmt![node, ".asText().equals(", literal, ")"],
+                m::MatchKind::Boolean => stmt![node, ".asBoolean() == ", literal],
+                _ => stmt![node, ".asDouble() == ", literal],
+            };
+
+            return stmt![is_kind, " && ", value];
+        }
+
+        is_kind
+    }
+
+    /// Custom deserialize implementation which tries each match, before running the fallback.
+    ///
+    /// The value is read as a tree, which is available to the fallback as `node`.
+    fn match_deserializer(&self,
+                          name: &str,
+                          ty: &Type,
+                          matches: &Vec<m::JavaMatch>,
+                          fallback: Elements,
+                          with_type: bool)
+                          -> Result<ClassSpec> {
+        let mut deserializer = ClassSpec::new(mods![Modifier::Public, Modifier::Static], name);
+
+        deserializer.extends(self.deserializer.with_arguments(vec![ty]));
+
+        let parser = ArgumentSpec::new(mods![Modifier::Final], &self.parser, "parser");
+        let ctxt = ArgumentSpec::new(mods![Modifier::Final],
+                                     &self.deserialization_context,
+                                     "ctxt");
+
+        let mut deserialize = MethodSpec::new(mods![Modifier::Public], "deserialize");
+        deserialize.throws(&self.io_exception);
+        deserialize.push_argument(&parser);
+        deserialize.push_argument(&ctxt);
+        deserialize.push_annotation(&self.override_);
+        deserialize.returns(ty);
+
+        let codec = stmt!["codec"];
+        let node = stmt!["node"];
+
+        let mut body = Elements::new();
+
+        let mut read = Elements::new();
+        read.push(stmt!["final ", &self.codec, " ", &codec, " = ", &parser, ".getCodec();"]);
+        read.push(stmt!["final ", &self.node, " ", &node, " = ", &codec, ".readTree(", &parser,
+                        ");"]);
+        body.push(read);
+
+        for m in matches {
+            let mut check_body = Elements::new();
+
+            if let m::JavaMatchCondition::Type(ref variable, ref variable_ty) = m.condition {
+                let value = self.read_node(&codec, &node, variable_ty);
+                check_body.push(stmt!["final ", variable_ty, " ", variable, " = ", value, ";"]);
+            }
+
+            check_body.push(stmt!["return ", &m.instance, ";"]);
+
+            let mut check = Elements::new();
+            check.push(stmt!["if (", self.match_check(&node, m), ") {"]);
+            check.push_nested(check_body);
+            check.push("}");
+            body.push(check);
+        }
+
+        body.push(fallback);
+
+        deserialize.push(body.join(ElementSpec::Spacing));
+        deserializer.push(deserialize);
+
+        if with_type {
+            let type_deserializer = ArgumentSpec::new(mods![Modifier::Final],
+                                                      &self.type_deserializer,
+                                                      "typeDeserializer");
+
+            let mut deserialize_with_type = MethodSpec::new(mods![Modifier::Public],
+                                                            "deserializeWithType");
+            deserialize_with_type.throws(&self.io_exception);
+            deserialize_with_type.push_argument(&parser);
+            deserialize_with_type.push_argument(&ctxt);
+            deserialize_with_type.push_argument(&type_deserializer);
+            deserialize_with_type.push_annotation(&self.override_);
+            deserialize_with_type.returns(&self.object);
+            deserialize_with_type.push(stmt!["return deserialize(", &parser, ", ", &ctxt, ");"]);
+
+            deserializer.push(deserialize_with_type);
+        }
+
+        Ok(deserializer)
+    }
+
+    /// Fallback which decodes the fields of a type from an object node.
+    fn type_fallback(&self, fields: &Vec<m::JavaField>, class_type: &ClassType) -> Elements {
+        let codec = stmt!["codec"];
+        let node = stmt!["node"];
+
+        let mut elements = Elements::new();
+
+        let mut object_check = Elements::new();
+        object_check.push(stmt!["if (!", &node, ".isObject()) {"]);
+        let message = Variable::String("expected object".to_owned());
+        object_check.push_nested(self.mapping_exception(message));
+        object_check.push("}");
+        elements.push(object_check);
+
+        let mut arguments = Statement::new();
+
+        for field in fields {
+            let variable = stmt!["v_", &field.spec.name];
+            let name = Variable::String(field.json_name.clone());
+            let value = self.read_node(&codec, stmt![&node, ".get(", &name, ")"], &field.ty);
+
+            let mut read = Elements::new();
+
+            match field.modifier {
+                m::Modifier::Optional => {
+                    read.push(stmt!["final ", &field.spec.ty, " ", &variable, ";"]);
+                    read.push(stmt!["if (", &node, ".hasNonNull(", &name, ")) {"]);
+                    read.push_nested(stmt![&variable, " = ", &self.optional, ".of(", value, ");"]);
+                    read.push("} else {");
+                    read.push_nested(stmt![&variable, " = ", &self.optional, ".empty();"]);
+                    read.push("}");
+                }
+                _ => {
+                    let missing = Variable::String(format!("missing required field: {}",
+                                                           field.json_name));
+
+                    read.push(stmt!["if (!", &node, ".has(", &name, ")) {"]);
+                    read.push_nested(self.mapping_exception(missing));
+                    read.push("}");
+                    read.push(stmt!["final ", &field.spec.ty, " ", &variable, " = ", value, ";"]);
+                }
+            }
+
+            elements.push(read);
+            arguments.push(variable);
+        }
+
+        elements.push(stmt!["return new ", class_type, "(", arguments.join(", "), ");"]);
+        elements.join(ElementSpec::Spacing)
+    }
+
+    /// Fallback which decodes a tuple using its regular deserializer.
+    fn tuple_fallback(&self, deserializer: &ClassSpec) -> Elements {
+        let mut elements = Elements::new();
+        elements.push(stmt!["final ", &self.parser, " tokens = codec.treeAsTokens(node);"]);
+        elements.push("tokens.nextToken();");
+        elements.push(stmt!["return new ", &deserializer.name, "().deserialize(tokens, ctxt);"]);
+        elements
+    }
+
+    /// Fallback which decodes a sub type of an interface, as indicated by its type field.
+    fn interface_fallback(&self, interface: &m::InterfaceBody, spec: &InterfaceSpec) -> Elements {
+        let mut elements = Elements::new();
+
+        let mut object_check = Elements::new();
+        object_check.push("if (!node.isObject()) {");
+        let message = Variable::String("expected object".to_owned());
+        object_check.push_nested(self.mapping_exception(message));
+        object_check.push("}");
+        elements.push(object_check);
+
+        match interface.type_info {
+            m::TypeInfo::TypeField(ref name) => {
+                let type_field = Variable::String(name.to_owned());
+                elements.push(stmt!["final ",
+                                    &self.string,
+                                    " type = node.path(",
+                                    type_field,
+                                    ").asText();"]);
+            }
+            m::TypeInfo::ObjectKeys => {
+                let mut keys = Elements::new();
+                keys.push("if (node.size() != 1) {");
+                let message = Variable::String("expected exactly one key".to_owned());
+                keys.push_nested(self.mapping_exception(message));
+                keys.push("}");
+                keys.push(stmt!["final ", &self.string, " type = node.fieldNames().next();"]);
+                elements.push(keys);
+            }
+        }
+
+        for (key, sub_type) in &interface.sub_types {
+            for name in &sub_type.names {
+                let name = Variable::String(name.inner.to_owned());
+
+                let mut check = Elements::new();
+                check.push(stmt!["if (type.equals(", name, ")) {"]);
+                check.push_nested(stmt!["return codec.treeToValue(node, ",
+                                        &spec.name,
+                                        ".",
+                                        key,
+                                        ".class);"]);
+                check.push("}");
+                elements.push(check);
+            }
+        }
+
+        let bad_type = Variable::String("bad type: ".to_owned());
+        elements.push(self.mapping_exception(stmt![bad_type, " + type"]));
+        elements.join(ElementSpec::Spacing)
+    }
+
+    /// Annotate the given spec to use the named, nested deserializer.
+    fn deserialize_using(&self, name: &str, deserializer: &ClassSpec) -> AnnotationSpec {
+        let mut deserialize: AnnotationSpec = self.deserialize.clone().into();
+        deserialize.push_argument(stmt!["using = ", name, ".", &deserializer.name, ".class"]);
+        deserialize
+    }
 }
 
 impl Listeners for Module {
@@ -258,10 +538,21 @@ impl Listeners for Module {
 
         for (argument, field) in zipped {
             let mut property = AnnotationSpec::new(&self.property);
-            property.push_argument(stmt![Variable::String(field.name.clone())]);
+            property.push_argument(stmt![Variable::String(field.json_name.clone())]);
             argument.push_annotation(&property);
         }
 
+        if !event.matches.is_empty() {
+            let fallback = self.type_fallback(&event.fields, &event.class_type);
+            let ty: Type = event.class_type.into();
+
+            let deserializer =
+                self.match_deserializer("Deserializer", &ty, &event.matches, fallback, false)?;
+
+            event.spec.push_annotation(self.deserialize_using(&event.spec.name, &deserializer));
+            event.spec.push(deserializer);
+        }
+
         Ok(())
     }
 
@@ -284,15 +575,38 @@ impl Listeners for Module {
             Type::class(&event.class_type.package,
                         &format!("{}.{}", &event.class_type.name, deserializer.name));
 
-        let mut deserialize_annotation: AnnotationSpec = self.deserialize.clone().into();
-        deserialize_annotation.push_argument(stmt!["using = ", deserializer_type, ".class"]);
+        if event.matches.is_empty() {
+            let mut deserialize_annotation: AnnotationSpec = self.deserialize.clone().into();
+            deserialize_annotation.push_argument(stmt!["using = ", deserializer_type, ".class"]);
+            event.spec.push_annotation(deserialize_annotation);
+        } else {
+            let fallback = self.tuple_fallback(&deserializer);
+            let ty: Type = event.class_type.into();
+
+            let match_deserializer =
+                self.match_deserializer("MatchDeserializer", &ty, &event.matches, fallback, false)?;
+
+            event.spec
+                .push_annotation(self.deserialize_using(&event.spec.name, &match_deserializer));
+            event.spec.push(match_deserializer);
+        }
 
-        event.spec.push_annotation(deserialize_annotation);
         event.spec.push(deserializer);
 
         Ok(())
     }
 
+    fn getter_added(&self, event: &mut GetterAdded) -> Result<()> {
+        // getters are serialized under their own name, unless told otherwise.
+        if event.field.json_name != event.field.name {
+            let mut property = AnnotationSpec::new(&self.property);
+            property.push_argument(stmt![Variable::String(event.field.json_name.clone())]);
+            event.spec.push_annotation(&property);
+        }
+
+        Ok(())
+    }
+
     fn enum_added(&self, event: &mut EnumAdded) -> Result<()> {
         if let Some(ref mut from_value) = *event.from_value {
             from_value.push_annotation(&self.creator);
@@ -310,8 +624,16 @@ impl Listeners for Module {
             let mut arguments = Statement::new();
 
             arguments.push(stmt!["use=", &self.type_info, ".Id.NAME"]);
-            arguments.push(stmt!["include=", &self.type_info, ".As.PROPERTY"]);
-            arguments.push(stmt!["property=", Variable::String("type".to_owned())]);
+
+            match event.interface.type_info {
+                m::TypeInfo::TypeField(ref name) => {
+                    arguments.push(stmt!["include=", &self.type_info, ".As.PROPERTY"]);
+                    arguments.push(stmt!["property=", Variable::String(name.to_owned())]);
+                }
+                m::TypeInfo::ObjectKeys => {
+                    arguments.push(stmt!["include=", &self.type_info, ".As.WRAPPER_OBJECT"]);
+                }
+            }
 
             let mut type_info = AnnotationSpec::new(&self.type_info);
             type_info.push_argument(arguments.join(", "));
@@ -343,6 +665,28 @@ impl Listeners for Module {
             event.spec.push_annotation(&sub_types);
         }
 
+        if !event.matches.is_empty() {
+            let fallback = self.interface_fallback(&event.interface, &event.spec);
+            let ty: Type = Type::local(&event.spec.name).into();
+
+            let deserializer =
+                self.match_deserializer("Deserializer", &ty, &event.matches, fallback, true)?;
+
+            event.spec.push_annotation(&self.deserialize_using(&event.spec.name, &deserializer));
+            event.spec.push(deserializer);
+        }
+
+        Ok(())
+    }
+
+    fn sub_type_added(&self, event: &mut SubTypeAdded) -> Result<()> {
+        // sub types are decoded by the deserializer of the interface, which must not be inherited.
+        if !event.interface_matches.is_empty() {
+            let mut deserialize: AnnotationSpec = self.deserialize.clone().into();
+            deserialize.push_argument(stmt!["using = ", &self.deserializer, ".None.class"]);
+            event.spec.push_annotation(deserialize);
+        }
+
         Ok(())
     }
 }
@@ -6,12 +6,14 @@ use super::processor::ProcessorOptions;
 pub struct ClassAdded<'a> {
     pub fields: &'a Vec<m::JavaField>,
     pub class_type: &'a ClassType,
+    pub matches: &'a Vec<m::JavaMatch>,
     pub spec: &'a mut ClassSpec,
 }
 
 pub struct TupleAdded<'a> {
     pub fields: &'a Vec<m::JavaField>,
     pub class_type: &'a ClassType,
+    pub matches: &'a Vec<m::JavaMatch>,
     pub spec: &'a mut ClassSpec,
 }
 
@@ -26,16 +28,24 @@ pub struct EnumAdded<'a> {
 
 pub struct InterfaceAdded<'a> {
     pub interface: &'a m::InterfaceBody,
+    pub matches: &'a Vec<m::JavaMatch>,
     pub spec: &'a mut InterfaceSpec,
 }
 
 pub struct SubTypeAdded<'a> {
     pub fields: &'a Vec<m::JavaField>,
     pub interface: &'a m::InterfaceBody,
+    /// Matches of the interface.
+    pub interface_matches: &'a Vec<m::JavaMatch>,
     pub sub_type: &'a m::SubType,
     pub spec: &'a mut ClassSpec,
 }
 
+pub struct GetterAdded<'a> {
+    pub field: &'a m::JavaField,
+    pub spec: &'a mut MethodSpec,
+}
+
 pub trait Listeners {
     fn configure(&self, _options: &mut ProcessorOptions) -> Result<()> {
         Ok(())
@@ -60,6 +70,10 @@ pub trait Listeners {
     fn sub_type_added(&self, _: &mut SubTypeAdded) -> Result<()> {
         Ok(())
     }
+
+    fn getter_added(&self, _: &mut GetterAdded) -> Result<()> {
+        Ok(())
+    }
 }
 
 /// A vector of listeners is a valid listener.
@@ -111,4 +125,12 @@ impl Listeners for Vec<Box<Listeners>> {
 
         Ok(())
     }
+
+    fn getter_added(&self, event: &mut GetterAdded) -> Result<()> {
+        for l in self {
+            l.getter_added(event)?;
+        }
+
+        Ok(())
+    }
 }
@@ -5,9 +5,12 @@ use codeviz::java::{self, Statement, Modifiers};
 /// A single field.
 #[derive(Debug, Clone)]
 pub struct JavaField {
+    pub comment: Vec<String>,
     pub modifier: Modifier,
     pub camel_name: String,
     pub name: String,
+    /// Key of the field in JSON, which differs from its name if it is renamed.
+    pub json_name: String,
     pub ident: String,
     pub ty: java::Type,
     pub spec: java::FieldSpec,
@@ -22,7 +25,8 @@ impl JavaField {
         let name = format!("set{}", self.camel_name);
         let mut setter = java::MethodSpec::new(mods![java::Modifier::Public], &name);
 
-        let argument = java::ArgumentSpec::new(mods![java::Modifier::Final], &self.ty, &self.ident);
+        let argument =
+            java::ArgumentSpec::new(mods![java::Modifier::Final], &self.spec.ty, &self.ident);
 
         setter.push_argument(&argument);
         setter.returns(java::VOID);
@@ -38,8 +42,26 @@ impl JavaField {
     pub fn getter(&self) -> Result<java::MethodSpec> {
         let name = format!("get{}", self.camel_name);
         let mut getter = java::MethodSpec::new(mods![java::Modifier::Public], &name);
-        getter.returns(&self.ty);
+        getter.returns(&self.spec.ty);
         getter.push(stmt!["return this.", &self.ident, ";"]);
         Ok(getter)
     }
 }
+
+/// Condition of a single match member.
+#[derive(Debug, Clone)]
+pub enum JavaMatchCondition {
+    /// Match the given literal value.
+    Value(Statement),
+    /// Match any value of the kind, and bind it to a variable with the given name and type.
+    Type(String, java::Type),
+}
+
+/// A single member of a match declaration.
+#[derive(Debug, Clone)]
+pub struct JavaMatch {
+    pub kind: MatchKind,
+    pub condition: JavaMatchCondition,
+    /// Statement building the value that the member evaluates to.
+    pub instance: Statement,
+}
@@ -1,13 +1,11 @@
 pub use super::listeners::*;
 use backend::*;
+use backend::doc_comment::block_comment;
 use backend::errors::*;
 use backend::for_context::ForContext;
 use codeviz::java::*;
 use naming::{self, FromNaming};
 use options::Options;
-use std::fs::File;
-use std::fs;
-use std::io::Write;
 use std::path::PathBuf;
 use super::models as m;
 
@@ -65,6 +63,8 @@ pub struct Processor {
     list: ClassType,
     map: ClassType,
     string: ClassType,
+    byte_buffer: ClassType,
+    big_integer: ClassType,
     optional: ClassType,
     illegal_argument: ClassType,
 }
@@ -91,6 +91,8 @@ impl Processor {
             list: Type::class("java.util", "List"),
             map: Type::class("java.util", "Map"),
             string: Type::class("java.lang", "String"),
+            byte_buffer: Type::class("java.nio", "ByteBuffer"),
+            big_integer: Type::class("java.math", "BigInteger"),
             optional: Type::class("java.util", "Optional"),
             illegal_argument: Type::class("java.lang", "IllegalArgumentException"),
         }
@@ -107,6 +109,29 @@ impl Processor {
     }
 
     /// Create a new FileSpec from the given package.
+    /// Prefix the given element with a javadoc comment, if there is one.
+    fn javadoc<E>(&self, comment: &Vec<String>, element: E) -> Elements
+        where E: Into<ElementSpec>
+    {
+        let mut elements = Elements::new();
+        elements.push(block_comment(comment));
+        elements.push(element);
+        elements
+    }
+
+    /// Build the getter of the given field, and let listeners modify it.
+    fn getter(&self, field: &m::JavaField) -> Result<MethodSpec> {
+        let mut getter = field.getter()?;
+
+        self.listeners
+            .getter_added(&mut GetterAdded {
+                field: field,
+                spec: &mut getter,
+            })?;
+
+        Ok(getter)
+    }
+
     fn new_file_spec(&self, package: &m::Package) -> FileSpec {
         FileSpec::new(&self.java_package_name(package))
     }
@@ -153,24 +178,30 @@ impl Processor {
         Ok(Type::class(&package_name, &name).into())
     }
 
+    /// Convert a signed integer of the given size to the smallest java type that can hold it.
+    fn convert_integer(&self, size: usize) -> Type {
+        if size <= 32 {
+            INTEGER.into()
+        } else if size <= 64 {
+            LONG.into()
+        } else {
+            self.big_integer.clone().into()
+        }
+    }
+
     /// Convert the given type to a java type.
     fn convert_type(&self, pos: &m::Pos, package: &m::Package, ty: &m::Type) -> Result<Type> {
         let ty = match *ty {
             m::Type::String => self.string.clone().into(),
-            m::Type::Signed(ref size) |
-            m::Type::Unsigned(ref size) => {
-                // default to integer if unspecified.
-                // TODO: should we care about signedness?
-                // TODO: > 64 bits, use BitInteger?
-                if size.map(|s| s <= 32usize).unwrap_or(true) {
-                    INTEGER.into()
-                } else {
-                    LONG.into()
-                }
-            }
+            // default to integer if unspecified.
+            m::Type::Signed(size) => self.convert_integer(size.unwrap_or(32)),
+            // unsigned values need one bit more than the signed java types provide.
+            m::Type::Unsigned(size) => self.convert_integer(size.unwrap_or(32) + 1),
             m::Type::Float => FLOAT.into(),
             m::Type::Double => DOUBLE.into(),
             m::Type::Boolean => BOOLEAN.into(),
+            // unlike byte[], has equals and hashCode based on its content.
+            m::Type::Bytes => self.byte_buffer.clone().into(),
             m::Type::Array(ref ty) => {
                 let argument = self.convert_type(pos, package, ty)?;
                 self.list.with_arguments(vec![argument]).into()
@@ -184,9 +215,6 @@ impl Processor {
                 self.map.with_arguments(vec![key, value]).into()
             }
             m::Type::Any => self.object.clone().into(),
-            ref t => {
-                return Err(Error::pos(format!("unsupported type: {:?}", t), pos.clone()));
-            }
         };
 
         Ok(ty)
@@ -541,6 +569,163 @@ impl Processor {
                        pos.clone()))
     }
 
+    /// Resolve the class instantiated through the given custom type.
+    ///
+    /// Sub types are referenced as `Interface.SubType`, and are nested classes of the interface.
+    fn instance_type(&self,
+                     pos: &m::Pos,
+                     package: &m::Package,
+                     custom: &m::Custom)
+                     -> Result<Type> {
+        if custom.parts.len() == 1 {
+            return self.convert_custom(pos, package, custom);
+        }
+
+        let package = if let Some(ref prefix) = custom.prefix {
+            self.env.lookup_used(pos, package, prefix)?
+        } else {
+            package
+        };
+
+        let package_name = self.java_package_name(package);
+        Ok(Type::class(&package_name, &custom.parts.join(".")).into())
+    }
+
+    /// Resolve a variable bound by a match member.
+    fn match_variable(&self,
+                      value: &m::Token<m::Value>,
+                      variable: Option<&m::MatchVariable>)
+                      -> Result<Statement> {
+        if let m::Value::Identifier(ref identifier) = value.inner {
+            if variable.map(|v| v.name == *identifier).unwrap_or(false) {
+                return Ok(stmt![identifier]);
+            }
+
+            return Err(Error::pos(format!("no such variable: {}", identifier), value.pos.clone()));
+        }
+
+        Err(Error::pos("expected variable".to_owned(), value.pos.clone()))
+    }
+
+    /// Convert an argument to an instance built by a match member.
+    fn match_argument(&self,
+                      package: &m::Package,
+                      value: &m::Token<m::Value>,
+                      variable: Option<&m::MatchVariable>,
+                      ty: &Type)
+                      -> Result<Statement> {
+        match value.inner {
+            m::Value::Identifier(_) => self.match_variable(value, variable),
+            m::Value::Boolean(ref boolean) if *ty == BOOLEAN.into() => {
+                Ok(stmt![boolean.to_string()])
+            }
+            ref other => self.literal_value(&value.pos, package, other, ty),
+        }
+    }
+
+    /// Build the value that a match member evaluates to.
+    fn match_instance(&self,
+                      package: &m::Package,
+                      value: &m::Token<m::Value>,
+                      variable: Option<&m::MatchVariable>)
+                      -> Result<Statement> {
+        let instance = match value.inner {
+            m::Value::Instance(ref instance) => instance,
+            _ => return self.match_variable(value, variable),
+        };
+
+        let fields = self.env.lookup_instance_fields(&instance.pos, package, &instance.ty)?;
+
+        for init in &instance.arguments {
+            if !fields.iter().any(|f| f.name == *init.name) {
+                return Err(Error::pos(format!("no such field: {}", *init.name),
+                                      init.pos.clone()));
+            }
+        }
+
+        let mut arguments = Statement::new();
+
+        for field in fields {
+            let init = instance.arguments.iter().find(|a| *a.name == field.name);
+
+            let argument = match init {
+                Some(init) => {
+                    let ty = self.convert_type(&field.pos, package, &field.ty)?;
+                    let argument = self.match_argument(package, &init.value, variable, &ty)?;
+
+                    if field.is_optional() {
+                        stmt![&self.optional, ".of(", argument, ")"]
+                    } else {
+                        argument
+                    }
+                }
+                None => {
+                    if !field.is_optional() {
+                        return Err(Error::pos(format!("missing required field: {}", field.name),
+                                              instance.pos.clone()));
+                    }
+
+                    stmt![&self.optional, ".empty()"]
+                }
+            };
+
+            arguments.push(argument);
+        }
+
+        let ty = self.instance_type(&instance.pos, package, &instance.ty)?;
+        Ok(stmt!["new ", ty, "(", arguments.join(", "), ")"])
+    }
+
+    /// Convert all members of a match declaration.
+    ///
+    /// Value matches are converted first, followed by type matches.
+    fn convert_matches(&self,
+                       package: &m::Package,
+                       name: &str,
+                       match_decl: &m::MatchDecl)
+                       -> Result<Vec<m::JavaMatch>> {
+        let mut out = Vec::new();
+
+        for &(ref value, ref member) in &match_decl.by_value {
+            let (kind, literal) = match value.inner {
+                m::Value::String(ref string) => {
+                    (m::MatchKind::String, stmt![Variable::String(string.to_owned())])
+                }
+                m::Value::Number(ref number) => (m::MatchKind::Number, stmt![number.to_string()]),
+                m::Value::Boolean(ref boolean) => {
+                    (m::MatchKind::Boolean, stmt![boolean.to_string()])
+                }
+                _ => {
+                    return Err(Error::pos("unsupported match value".to_owned(), value.pos.clone()));
+                }
+            };
+
+            out.push(m::JavaMatch {
+                kind: kind,
+                condition: m::JavaMatchCondition::Value(literal),
+                instance: self.match_instance(package, &member.value, None)?,
+            });
+        }
+
+        for &(ref kind, ref member) in &match_decl.by_type {
+            if member.is_self_object(name) {
+                continue;
+            }
+
+            if let m::MatchCondition::Type(ref variable) = member.condition.inner {
+                let ty = self.convert_type(&member.condition.pos, package, &variable.ty)?;
+
+                out.push(m::JavaMatch {
+                    kind: kind.clone(),
+                    condition: m::JavaMatchCondition::Type(variable.name.clone(), ty),
+                    instance: self.match_instance(package, &member.value, Some(variable))?,
+                });
+            }
+        }
+
+        Ok(out)
+    }
+
     fn find_field(&self, fields: &Vec<m::JavaField>, name: &str) -> Option<m::JavaField> {
         for field in fields {
             if field.name == name {
@@ -610,7 +795,7 @@ impl Processor {
             spec.push_field(&field.spec);
 
             if self.options.build_getters {
-                spec.push(field.getter()?);
+                spec.push(self.javadoc(&field.comment, self.getter(field)?));
             }
 
             if self.options.build_setters {
@@ -638,6 +823,7 @@ impl Processor {
                 enum_stmt.push(stmt!["(", value_arguments.join(", "), ")"]);
             }
 
+            enum_value.push(block_comment(&enum_literal.comment));
             enum_value.push(enum_stmt);
             spec.push_value(enum_value);
         }
@@ -676,7 +862,7 @@ impl Processor {
         }
 
         let mut file_spec = self.new_file_spec(package);
-        file_spec.push(&spec);
+        file_spec.push(self.javadoc(&body.comment, &spec));
 
         Ok(file_spec)
     }
@@ -691,7 +877,7 @@ impl Processor {
             spec.push_field(&field.spec);
 
             if self.options.build_getters {
-                spec.push(field.getter()?);
+                spec.push(self.javadoc(&field.comment, self.getter(field)?));
             }
 
             if self.options.build_setters {
@@ -707,15 +893,18 @@ impl Processor {
 
         self.add_class(&class_type, &mut spec)?;
 
+        let matches = self.convert_matches(package, &body.name, &body.match_decl)?;
+
         self.listeners
             .tuple_added(&mut TupleAdded {
                 fields: &fields,
                 class_type: &class_type,
+                matches: &matches,
                 spec: &mut spec,
             })?;
 
         let mut file_spec = self.new_file_spec(package);
-        file_spec.push(&spec);
+        file_spec.push(self.javadoc(&body.comment, &spec));
 
         Ok(file_spec)
     }
@@ -730,7 +919,7 @@ impl Processor {
             spec.push_field(&field.spec);
 
             if self.options.build_getters {
-                spec.push(field.getter()?);
+                spec.push(self.javadoc(&field.comment, self.getter(field)?));
             }
 
             if self.options.build_setters {
@@ -746,15 +935,18 @@ impl Processor {
 
         self.add_class(&class_type, &mut spec)?;
 
+        let matches = self.convert_matches(package, &body.name, &body.match_decl)?;
+
         self.listeners
             .class_added(&mut ClassAdded {
                 fields: &fields,
                 class_type: &class_type,
+                matches: &matches,
                 spec: &mut spec,
             })?;
 
         let mut file_spec = self.new_file_spec(package);
-        file_spec.push(&spec);
+        file_spec.push(self.javadoc(&body.comment, &spec));
 
         Ok(file_spec)
     }
@@ -772,6 +964,9 @@ impl Processor {
             interface_spec.push(code.inner.lines);
         }
 
+        let matches = self.convert_matches(package, &interface.name, &interface.match_decl)?;
+        let no_matches = Vec::new();
+
         for (_, ref sub_type) in &interface.sub_types {
             let class_type = parent_type.extend(&sub_type.name);
 
@@ -793,9 +988,7 @@ impl Processor {
                 class.push_field(&field.spec);
 
                 if self.options.build_getters {
-                    let mut getter = field.getter()?;
-                    getter.push_annotation(&self.override_);
-                    class.push(getter);
+                    class.push(self.javadoc(&field.comment, self.getter(field)?));
                 }
 
                 if self.options.build_setters {
@@ -811,6 +1004,7 @@ impl Processor {
                 .class_added(&mut ClassAdded {
                     fields: &fields,
                     class_type: &class_type,
+                    matches: &no_matches,
                     spec: &mut class,
                 })?;
 
@@ -818,11 +1012,12 @@ impl Processor {
                 .sub_type_added(&mut SubTypeAdded {
                     fields: &fields,
                     interface: interface,
+                    interface_matches: &matches,
                     sub_type: sub_type,
                     spec: &mut class,
                 })?;
 
-            interface_spec.push(&class);
+            interface_spec.push(self.javadoc(&sub_type.comment, &class));
         }
 
         let mut file_spec = self.new_file_spec(package);
@@ -830,10 +1025,11 @@ impl Processor {
         self.listeners
             .interface_added(&mut InterfaceAdded {
                 interface: interface,
+                matches: &matches,
                 spec: &mut interface_spec,
             })?;
 
-        file_spec.push(&interface_spec);
+        file_spec.push(self.javadoc(&interface.comment, &interface_spec));
         Ok(file_spec)
     }
 
@@ -850,8 +1046,10 @@ impl Processor {
             let spec = self.build_field_spec(&ty, field)?;
 
             out.push(m::JavaField {
+                comment: field.comment.clone(),
                 modifier: field.modifier.clone(),
                 name: field.name.clone(),
+                json_name: field.name().to_owned(),
                 camel_name: camel_name,
                 ident: ident,
                 ty: ty,
@@ -876,14 +1074,12 @@ impl Processor {
     fn process_files<F>(&self, mut consumer: F) -> Result<()>
         where F: FnMut(PathBuf, &m::Package, &m::Decl) -> Result<()>
     {
-        let root_dir = &self.options.parent.out_path;
-
         // Process all types discovered so far.
         for (&(ref package, _), decl) in &self.env.types {
             let out_dir = self.java_package(package)
                 .parts
                 .iter()
-                .fold(root_dir.clone(), |current, next| current.join(next));
+                .fold(PathBuf::new(), |current, next| current.join(next));
 
             let full_path = out_dir.join(format!("{}.java", decl.name()));
 
@@ -908,23 +1104,8 @@ impl Backend for Processor {
         self.process_files(|full_path, package, decl| {
             debug!("+class: {}", full_path.display());
 
-            if let Some(out_dir) = full_path.parent() {
-                if !out_dir.is_dir() {
-                    debug!("+dir: {}", out_dir.display());
-                    fs::create_dir_all(&out_dir)?;
-                }
-            }
-
             let file_spec = self.build_file_spec(package, decl)?;
-
-            let out = file_spec.format();
-            let mut f = File::create(full_path)?;
-            let bytes = out.into_bytes();
-
-            f.write_all(&bytes)?;
-            f.flush()?;
-
-            Ok(())
+            self.options.parent.output.write(&full_path, file_spec.format().as_bytes())
         })
     }
 
@@ -1,6 +1,7 @@
-mod models;
+pub mod bigint;
+pub mod models;
 #[macro_use]
-mod utils;
+pub mod utils;
 pub mod processor;
 
 use backend::*;
@@ -8,9 +9,12 @@ use backend::models as m;
 use options::Options;
 
 fn setup_module(module: &str) -> Result<Box<processor::Listeners>> {
-    let _module: Box<processor::Listeners> = match module {
+    let module: Box<processor::Listeners> = match module {
+        "bigint" => Box::new(bigint::Module::new()),
         _ => return Err(format!("No such module: {}", module).into()),
     };
+
+    Ok(module)
 }
 
 pub fn resolve(options: Options, env: Environment) -> Result<processor::Processor> {
@@ -6,4 +6,5 @@ pub struct JsField {
     pub ty: Type,
     pub name: String,
     pub ident: String,
+    pub comment: Vec<String>,
 }
@@ -1,4 +1,5 @@
 use backend::*;
+use backend::doc_comment::block_comment;
 use backend::errors::*;
 use backend::for_context::ForContext;
 use codeviz::js::*;
@@ -6,19 +7,15 @@ use naming::{self, FromNaming};
 use options::Options;
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
-use std::fs::File;
-use std::fs;
-use std::io::Write;
 use std::path::PathBuf;
 use super::models::*;
 use super::utils::*;
 
-const TYPE: &str = "type";
 const EXT: &str = "js";
 const JS_CONTEXT: &str = "js";
 
-fn field_ident(_i: usize, field: &JsField) -> Variable {
-    string(&field.ident)
+fn field_name(_i: usize, field: &JsField) -> Variable {
+    string(&field.name)
 }
 
 fn field_index(i: usize, _field: &JsField) -> Variable {
@@ -46,6 +43,8 @@ pub struct ProcessorOptions {
     parent: Options,
     pub build_getters: bool,
     pub build_constructor: bool,
+    /// Represent integers which might not fit in a number as BigInt, encoded as strings.
+    pub bigint: bool,
 }
 
 impl ProcessorOptions {
@@ -54,6 +53,7 @@ impl ProcessorOptions {
             parent: options,
             build_getters: false,
             build_constructor: true,
+            bigint: false,
         }
     }
 }
@@ -64,7 +64,6 @@ pub struct Processor {
     package_prefix: Option<Package>,
     listeners: Box<Listeners>,
     to_lower_snake: Box<naming::Naming>,
-    type_var: Variable,
     values: Statement,
     enum_ordinal: Variable,
     enum_name: Variable,
@@ -82,7 +81,6 @@ impl Processor {
             package_prefix: package_prefix,
             listeners: listeners,
             to_lower_snake: naming::SnakeCase::new().to_lower_snake(),
-            type_var: string(TYPE),
             values: stmt!["values"],
             enum_ordinal: Variable::Literal("ordinal".to_owned()),
             enum_name: Variable::Literal("name".to_owned()),
@@ -121,16 +119,21 @@ impl Processor {
                         ty: o.ty,
                         name: f.name().to_owned(),
                         ident: ident,
+                        comment: o.comment,
                     }
                 })
             })
             .collect()
     }
 
+    /// Build the encode method.
+    ///
+    /// If `wrap_key` is set, the encoded data is wrapped in an object with it as its only key.
     fn encode_method<E, B>(&self,
                            package: &Package,
                            fields: &Vec<Token<JsField>>,
                            builder: B,
+                           wrap_key: Option<Statement>,
                            extra: E)
                            -> Result<MethodSpec>
         where E: FnOnce(&mut Elements) -> (),
@@ -147,7 +150,7 @@ impl Processor {
         let mut assign = Elements::new();
 
         for field in fields {
-            let var_string = string(field.ident.to_owned());
+            let var_string = string(field.name.to_owned());
             let field_stmt = stmt!["this.", &field.ident];
             let value_stmt = self.encode(package, &field.ty, &field_stmt)?;
 
@@ -169,7 +172,11 @@ impl Processor {
             body.push(assign.join(ElementSpec::Spacing));
         }
 
-        body.push(js![return data]);
+        if let Some(wrap_key) = wrap_key {
+            body.push(js![return "{[", wrap_key, "]: ", data, "}"]);
+        } else {
+            body.push(js![return data]);
+        }
 
         encode.push(body.join(ElementSpec::Spacing));
         Ok(encode)
@@ -236,10 +243,144 @@ impl Processor {
         Ok(decode)
     }
 
+    /// Build the check that `data` is equal to the given match value.
+    fn match_value_check(&self, data: &Statement, value: &Token<Value>) -> Result<Statement> {
+        let value = match value.inner {
+            Value::String(ref string) => stmt![Variable::String(string.to_owned())],
+            Value::Number(ref number) => stmt![number.to_string()],
+            Value::Boolean(ref boolean) => stmt![boolean.to_string()],
+            _ => return Err(Error::pos("unsupported match value".to_owned(), value.pos.clone())),
+        };
+
+        Ok(stmt![data, " === ", value])
+    }
+
+    /// Build the check that `data` is of the given match kind.
+    fn match_type_check(&self, data: &Statement, kind: &MatchKind) -> Statement {
+        match *kind {
+            MatchKind::Any => stmt!["true"],
+            MatchKind::Object => {
+                stmt!["typeof ",
+                      data,
+                      " === \"object\" && ",
+                      data,
+                      " !== null && !Array.isArray(",
+                      data,
+                      ")"]
+            }
+            MatchKind::Array => stmt!["Array.isArray(", data, ")"],
+            MatchKind::String => stmt!["typeof ", data, " === \"string\""],
+            MatchKind::Boolean => stmt!["typeof ", data, " === \"boolean\""],
+            MatchKind::Number => stmt!["typeof ", data, " === \"number\""],
+        }
+    }
+
+    /// Convert an argument to an instance built by a match member.
+    fn match_argument(&self,
+                      value: &Token<Value>,
+                      variable: Option<&MatchVariable>)
+                      -> Result<Statement> {
+        let argument = match value.inner {
+            Value::Identifier(ref identifier) => {
+                if !variable.map(|v| v.name == *identifier).unwrap_or(false) {
+                    return Err(Error::pos(format!("no such variable: {}", identifier),
+                                          value.pos.clone()));
+                }
+
+                stmt![identifier]
+            }
+            Value::String(ref string) => stmt![Variable::String(string.to_owned())],
+            Value::Number(ref number) => stmt![number.to_string()],
+            Value::Boolean(ref boolean) => stmt![boolean.to_string()],
+            _ => return Err(Error::pos("unsupported argument".to_owned(), value.pos.clone())),
+        };
+
+        Ok(argument)
+    }
+
+    /// Build the value that a match member evaluates to.
+    fn match_instance(&self,
+                      package: &Package,
+                      value: &Token<Value>,
+                      variable: Option<&MatchVariable>)
+                      -> Result<Statement> {
+        let instance = match value.inner {
+            Value::Instance(ref instance) => instance,
+            _ => return self.match_argument(value, variable),
+        };
+
+        let fields = self.env.lookup_instance_fields(&instance.pos, package, &instance.ty)?;
+
+        for init in &instance.arguments {
+            if !fields.iter().any(|f| f.name == *init.name) {
+                return Err(Error::pos(format!("no such field: {}", *init.name),
+                                      init.pos.clone()));
+            }
+        }
+
+        let mut arguments = Statement::new();
+
+        for field in fields {
+            if let Some(init) = instance.arguments.iter().find(|a| *a.name == field.name) {
+                arguments.push(self.match_argument(&init.value, variable)?);
+                continue;
+            }
+
+            if !field.is_optional() {
+                return Err(Error::pos(format!("missing required field: {}", field.name),
+                                      instance.pos.clone()));
+            }
+
+            arguments.push("null");
+        }
+
+        let name = self.instance_name(&instance.pos, package, &instance.ty)?;
+        Ok(js![new name, arguments])
+    }
+
+    /// Build the decoding of all members of a match declaration.
+    ///
+    /// Value matches are tried first, followed by type matches.
+    fn decode_match(&self,
+                    package: &Package,
+                    name: &str,
+                    match_decl: &MatchDecl,
+                    data: &Statement)
+                    -> Result<Elements> {
+        let mut elements = Elements::new();
+
+        for &(ref value, ref member) in &match_decl.by_value {
+            let cond = self.match_value_check(data, value)?;
+            let instance = self.match_instance(package, &member.value, None)?;
+            elements.push(js![if cond, js![return instance]]);
+        }
+
+        for &(ref kind, ref member) in &match_decl.by_type {
+            if member.is_self_object(name) {
+                continue;
+            }
+
+            if let MatchCondition::Type(ref variable) = member.condition.inner {
+                let pos = &member.condition.pos;
+                let decoded = self.decode(pos, package, &variable.ty, data)?;
+                let instance = self.match_instance(package, &member.value, Some(variable))?;
+
+                let mut body = Elements::new();
+                body.push(stmt!["const ", &variable.name, " = ", decoded, ";"]);
+                body.push(js![return instance]);
+
+                elements.push(js![if self.match_type_check(data, kind), body]);
+            }
+        }
+
+        Ok(elements.join(ElementSpec::Spacing))
+    }
+
     fn decode_method<F>(&self,
                         package: &Package,
                         fields: &Vec<Token<JsField>>,
                         class: &ClassSpec,
+                        match_decl: Option<&MatchDecl>,
                         variable_fn: F)
                         -> Result<MethodSpec>
         where F: Fn(usize, &JsField) -> Variable
@@ -249,11 +390,18 @@ impl Processor {
 
         decode.push_argument(&data);
 
+        let mut decode_match = Elements::new();
+
+        if let Some(match_decl) = match_decl {
+            decode_match = self.decode_match(package, &class.name, match_decl, &data)?;
+        }
+
         let mut arguments = Statement::new();
         let mut assign = Elements::new();
 
         for (i, field) in fields.iter().enumerate() {
-            let var_name = field.ident.clone();
+            // prefixed, so that fields cannot shadow `data`.
+            let var_name = format!("f_{}", field.ident);
             let var = variable_fn(i, field);
 
             let stmt: ElementSpec = match field.modifier {
@@ -283,6 +431,10 @@ impl Processor {
 
         let mut body = Elements::new();
 
+        if !decode_match.is_empty() {
+            body.push(decode_match);
+        }
+
         if !assign.is_empty() {
             body.push(assign.join(ElementSpec::Spacing));
         }
@@ -294,15 +446,23 @@ impl Processor {
         Ok(decode)
     }
 
+    /// Check if integers of the given size are represented as BigInt.
+    ///
+    /// Numbers can only represent integers of up to 53 bits exactly.
+    fn is_bigint(&self, size: &Option<usize>) -> bool {
+        self.options.bigint && size.map(|s| s > 32).unwrap_or(false)
+    }
+
     fn is_native(&self, ty: &Type) -> bool {
         match *ty {
-            Type::Signed(_) |
-            Type::Unsigned(_) => true,
+            Type::Signed(ref size) |
+            Type::Unsigned(ref size) => !self.is_bigint(size),
             Type::Float | Type::Double => true,
             Type::String => true,
             Type::Any => true,
             Type::Boolean => true,
             Type::Array(ref inner) => self.is_native(inner),
+            Type::Map(_, ref value) => self.is_native(value),
             _ => false,
         }
     }
@@ -315,6 +475,18 @@ impl Processor {
         }
     }
 
+    /// Name of the class instantiated through the given custom type.
+    ///
+    /// Sub types are declared next to their interface, so only the last part is used.
+    fn instance_name(&self, pos: &Pos, package: &Package, custom: &Custom) -> Result<Name> {
+        let custom = Custom {
+            prefix: custom.prefix.clone(),
+            parts: custom.parts.iter().last().into_iter().cloned().collect(),
+        };
+
+        self.name(pos, package, &custom)
+    }
+
     fn name(&self, pos: &Pos, package: &Package, custom: &Custom) -> Result<Name> {
         if let Some(ref used) = custom.prefix {
             let package = self.env.lookup_used(pos, package, used)?;
@@ -327,6 +499,13 @@ impl Processor {
         }
     }
 
+    /// Build a copy of the object `values`, with every value `v` replaced by `inner`.
+    fn map_values(&self, values: Statement, inner: Statement) -> Statement {
+        let entries = stmt!["Object.entries(", values, ")"];
+        let entry = "function([k, v]) { return [k, ";
+        stmt!["Object.fromEntries(", entries, ".map(", entry, inner, "]; }))"]
+    }
+
     fn encode<S>(&self, package: &Package, ty: &Type, value_stmt: S) -> Result<Statement>
         where S: Into<Statement>
     {
@@ -338,19 +517,31 @@ impl Processor {
         }
 
         let value_stmt = match *ty {
+            Type::Signed(ref size) |
+            Type::Unsigned(ref size) if self.is_bigint(size) => {
+                stmt!["String(", value_stmt, ")"]
+            }
             Type::Signed(_) |
             Type::Unsigned(_) => value_stmt,
             Type::Float | Type::Double => value_stmt,
             Type::String => value_stmt,
             Type::Any => value_stmt,
             Type::Boolean => value_stmt,
+            Type::Bytes => {
+                let chars = stmt!["Array.from(", value_stmt,
+                                  ", function(b) { return String.fromCharCode(b); })"];
+                stmt!["btoa(", chars, ".join(\"\"))"]
+            }
             Type::Custom(ref _custom) => stmt![value_stmt, ".encode()"],
             Type::Array(ref inner) => {
                 let v = stmt!["v"];
                 let inner = self.encode(package, inner, &v)?;
                 stmt![value_stmt, ".map(function(", &v, ") { return ", inner, "; })"]
             }
-            _ => value_stmt,
+            Type::Map(_, ref value) => {
+                let inner = self.encode(package, value, stmt!["v"])?;
+                self.map_values(value_stmt, inner)
+            }
         };
 
         Ok(value_stmt)
@@ -367,21 +558,32 @@ impl Processor {
         }
 
         let value_stmt = match *ty {
+            Type::Signed(ref size) |
+            Type::Unsigned(ref size) if self.is_bigint(size) => {
+                stmt!["BigInt(", value_stmt, ")"]
+            }
             Type::Signed(_) |
             Type::Unsigned(_) => value_stmt,
             Type::Float | Type::Double => value_stmt,
             Type::String => value_stmt,
             Type::Any => value_stmt,
             Type::Boolean => value_stmt,
+            Type::Bytes => {
+                stmt!["Uint8Array.from(atob(", value_stmt,
+                      "), function(c) { return c.charCodeAt(0); })"]
+            }
             Type::Custom(ref custom) => {
                 let name = self.name(pos, package, custom)?;
                 stmt![name, ".decode(", value_stmt, ")"]
             }
             Type::Array(ref inner) => {
                 let inner = self.decode(pos, package, inner, stmt!["v"])?;
-                stmt![value_stmt, ".map(function(v) { ", inner, "; })"]
+                stmt![value_stmt, ".map(function(v) { return ", inner, "; })"]
+            }
+            Type::Map(_, ref value) => {
+                let inner = self.decode(pos, package, value, stmt!["v"])?;
+                self.map_values(value_stmt, inner)
             }
-            _ => value_stmt,
         };
 
         Ok(value_stmt)
@@ -398,12 +600,23 @@ impl Processor {
             .unwrap_or_else(|| package.clone())
     }
 
+    /// Prefix the given element with a JSDoc comment, if there is one.
+    fn jsdoc<E>(&self, comment: &Vec<String>, element: E) -> Elements
+        where E: Into<ElementSpec>
+    {
+        let mut elements = Elements::new();
+        elements.push(block_comment(comment));
+        elements.push(element);
+        elements
+    }
+
     fn build_constructor(&self, fields: &Vec<Token<JsField>>) -> ConstructorSpec {
         let mut ctor = ConstructorSpec::new();
         let mut assignments = Elements::new();
 
         for field in fields {
             ctor.push_argument(stmt![&field.ident]);
+            assignments.push(block_comment(&field.comment));
             assignments.push(stmt!["this.", &field.ident, " = ", &field.ident, ";"]);
         }
 
@@ -423,6 +636,7 @@ impl Processor {
 
         for field in fields {
             ctor.push_argument(stmt![&field.ident]);
+            assignments.push(block_comment(&field.comment));
             assignments.push(stmt!["this.", &field.ident, " = ", &field.ident, ";"]);
         }
 
@@ -444,6 +658,7 @@ impl Processor {
                         ty: f.ty,
                         name: field.name().to_owned(),
                         ident: ident,
+                        comment: f.comment,
                     }
                 }));
         }
@@ -457,7 +672,8 @@ impl Processor {
             }
         }
 
-        let decode = self.decode_method(package, &fields, &class, field_index)?;
+        let decode =
+            self.decode_method(package, &fields, &class, Some(&body.match_decl), field_index)?;
         class.push(decode);
 
         let encode = self.encode_tuple_method(package, &fields)?;
@@ -467,7 +683,7 @@ impl Processor {
             class.push(code.inner.lines);
         }
 
-        Ok(class.into())
+        Ok(self.jsdoc(&body.comment, class).into())
     }
 
     fn literal_value(&self, pos: &Pos, value: &Value, ty: &Type) -> Result<Variable> {
@@ -552,6 +768,7 @@ impl Processor {
                         ty: f.ty,
                         name: field.name().to_owned(),
                         ident: ident,
+                        comment: f.comment,
                     }
                 }));
         }
@@ -577,6 +794,7 @@ impl Processor {
             let arguments = js![new &body.name, value_arguments];
             let member = stmt![&class.name, ".", &value.name];
 
+            values.push(block_comment(&value.comment));
             values.push(js![= &member, arguments]);
             members.push(member);
         }
@@ -588,7 +806,7 @@ impl Processor {
         let mut elements = Elements::new();
 
         // class declaration
-        elements.push(&class);
+        elements.push(self.jsdoc(&body.comment, &class));
 
         // enum literal values
         elements.push(values);
@@ -629,17 +847,18 @@ impl Processor {
             }
         }
 
-        let decode = self.decode_method(package, &fields, &class, field_ident)?;
+        let decode =
+            self.decode_method(package, &fields, &class, Some(&body.match_decl), field_name)?;
         class.push(decode);
 
-        let encode = self.encode_method(package, &fields, "{}", |_| {})?;
+        let encode = self.encode_method(package, &fields, "{}", None, |_| {})?;
         class.push(encode);
 
         for code in body.codes.for_context(JS_CONTEXT) {
             class.push(code.inner.lines);
         }
 
-        Ok(class.into())
+        Ok(self.jsdoc(&body.comment, class).into())
     }
 
     fn process_interface(&self, package: &Package, body: &InterfaceBody) -> Result<ElementSpec> {
@@ -647,7 +866,7 @@ impl Processor {
 
         let mut interface_spec = ClassSpec::new(&body.name);
 
-        interface_spec.push(self.interface_decode_method(body)?);
+        interface_spec.push(self.interface_decode_method(package, body)?);
 
         let interface_fields = self.convert_fields(&body.fields);
 
@@ -655,7 +874,7 @@ impl Processor {
             interface_spec.push(code.inner.lines);
         }
 
-        classes.push(interface_spec);
+        classes.push(self.jsdoc(&body.comment, interface_spec));
 
         for (_, ref sub_type) in &body.sub_types {
             let mut class = ClassSpec::new(&sub_type.name);
@@ -673,15 +892,24 @@ impl Processor {
                 }
             }
 
-            let decode = self.decode_method(package, &fields, &class, field_ident)?;
+            let decode = self.decode_method(package, &fields, &class, None, field_name)?;
 
             class.push(decode);
 
-            let type_stmt = stmt!["data[", &self.type_var, "] = ", &class.name, ".TYPE;"];
+            let type_name = stmt![&class.name, ".TYPE"];
 
-            let encode = self.encode_method(package, &fields, "{}", move |elements| {
-                    elements.push(type_stmt);
-                })?;
+            let encode = match body.type_info {
+                TypeInfo::TypeField(ref type_field) => {
+                    let type_stmt = stmt!["data[", string(type_field), "] = ", type_name, ";"];
+
+                    self.encode_method(package, &fields, "{}", None, move |elements| {
+                            elements.push(type_stmt);
+                        })?
+                }
+                TypeInfo::ObjectKeys => {
+                    self.encode_method(package, &fields, "{}", Some(type_name), |_| {})?
+                }
+            };
 
             class.push(encode);
 
@@ -689,8 +917,8 @@ impl Processor {
                 class.push(code.inner.lines);
             }
 
-            classes.push(&class);
-            classes.push(stmt![&class.name, ".TYPE", " = ", string(sub_type.name.clone()), ";"]);
+            classes.push(self.jsdoc(&sub_type.comment, &class));
+            classes.push(stmt![&class.name, ".TYPE", " = ", string(sub_type.name()), ";"]);
         }
 
         Ok(classes.join(ElementSpec::Spacing).into())
@@ -698,6 +926,7 @@ impl Processor {
 
     fn populate_files(&self) -> Result<HashMap<&Package, FileSpec>> {
         let mut files = HashMap::new();
+        let mut exports: HashMap<&Package, Vec<String>> = HashMap::new();
 
         // Process all types discovered so far.
         for (&(ref package, _), decl) in &self.env.types {
@@ -708,6 +937,14 @@ impl Processor {
                 Decl::Enum(ref body) => self.process_enum(package, body)?,
             };
 
+            let names = exports.entry(package).or_insert_with(Vec::new);
+            names.push(decl.name().to_owned());
+
+            // sub types are declared as classes next to their interface.
+            if let Decl::Interface(ref body) = decl.inner {
+                names.extend(body.sub_types.values().map(|s| s.name.to_owned()));
+            }
+
             match files.entry(package) {
                 Entry::Vacant(entry) => {
                     let mut file_spec = FileSpec::new();
@@ -721,50 +958,39 @@ impl Processor {
             }
         }
 
+        for (package, names) in exports {
+            if let Some(file_spec) = files.get_mut(package) {
+                file_spec.push(stmt!["export { ", names.join(", "), " };"]);
+            }
+        }
+
         Ok(files)
     }
 
-    fn setup_module_path(&self, root_dir: &PathBuf, package: &Package) -> Result<PathBuf> {
+    fn module_path(&self, package: &Package) -> PathBuf {
         let package = self.package(package);
-
-        let mut full_path = root_dir.to_owned();
-        let mut iter = package.parts.iter().peekable();
-
-        while let Some(part) = iter.next() {
-            full_path = full_path.join(part);
-        }
-
-        if let Some(parent) = full_path.parent() {
-            if !parent.is_dir() {
-                fs::create_dir_all(parent)?;
-            }
-        }
+        let mut full_path = package.parts.iter().fold(PathBuf::new(), |path, part| path.join(part));
 
         // path to final file
         full_path.set_extension(EXT);
-        Ok(full_path)
+        full_path
     }
 
     fn write_files(&self, files: HashMap<&Package, FileSpec>) -> Result<()> {
-        let root_dir = &self.options.parent.out_path;
-
         for (package, file_spec) in files {
-            let full_path = self.setup_module_path(root_dir, package)?;
+            let full_path = self.module_path(package);
 
             debug!("+module: {}", full_path.display());
-
-            let out = file_spec.format();
-            let mut f = File::create(full_path)?;
-            let bytes = out.into_bytes();
-
-            f.write_all(&bytes)?;
-            f.flush()?;
+            self.options.parent.output.write(&full_path, file_spec.format().as_bytes())?;
         }
 
         Ok(())
     }
 
-    fn interface_decode_method(&self, interface: &InterfaceBody) -> Result<MethodSpec> {
+    fn interface_decode_method(&self,
+                               package: &Package,
+                               interface: &InterfaceBody)
+                               -> Result<MethodSpec> {
         let mut decode = MethodSpec::with_static("decode");
 
         let data = stmt!["data"];
@@ -773,15 +999,42 @@ impl Processor {
 
         let mut body = Elements::new();
 
+        let decode_match =
+            self.decode_match(package, &interface.name, &interface.match_decl, &data)?;
+
+        if !decode_match.is_empty() {
+            body.push(decode_match);
+        }
+
         let type_field = Variable::Literal("f_type".to_owned());
 
-        body.push(stmt!["const ", &type_field, " = ", &data, "[", &self.type_var, "]"]);
+        // the data that the sub type is decoded from.
+        let sub_type_data = match interface.type_info {
+            TypeInfo::TypeField(ref name) => {
+                body.push(stmt!["const ", &type_field, " = ", &data, "[", string(name), "]"]);
+                data.clone()
+            }
+            TypeInfo::ObjectKeys => {
+                let keys = stmt!["keys"];
+                let sub_type_data = stmt!["f_data"];
+
+                let mut elements = Elements::new();
+                elements.push(js![const &keys, "Object.keys(", &data, ");"]);
+                elements.push(js![if stmt![&keys, ".length !== 1"],
+                                  js![throw string("expected exactly one key")]]);
+                elements.push(js![const &type_field, &keys, "[0];"]);
+                elements.push(js![const &sub_type_data, &data, "[", &type_field, "];"]);
+                body.push(elements);
+
+                sub_type_data
+            }
+        };
 
         for (_, ref sub_type) in &interface.sub_types {
             for name in &sub_type.names {
                 let type_name: Variable = Name::local(&sub_type.name).into();
                 let cond = stmt![&type_field, " === ", string(&name.inner)];
-                body.push(js![if cond, js![return type_name, ".decode(", &data, ")"]]);
+                body.push(js![if cond, js![return type_name, ".decode(", &sub_type_data, ")"]]);
             }
         }
 
@@ -0,0 +1,368 @@
+use super::errors::*;
+
+/// Simplified JSON model, used to render example payloads and schemas, and to validate payloads.
+#[derive(Clone)]
+pub enum Json {
+    Null,
+    Boolean(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Get the value of the given key, if this is an object which has it.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        if let Json::Object(ref entries) = *self {
+            return entries.iter().find(|e| e.0 == key).map(|e| &e.1);
+        }
+
+        None
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        if let Json::String(ref string) = *self {
+            return Some(string);
+        }
+
+        None
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        if let Json::Number(ref number) = *self {
+            return number.parse().ok();
+        }
+
+        None
+    }
+
+    /// Pretty-print the value at the given indentation level.
+    pub fn format(&self, level: usize, out: &mut String) {
+        let indent = "  ".repeat(level + 1);
+        let end = "  ".repeat(level);
+
+        match *self {
+            Json::Null => out.push_str("null"),
+            Json::Boolean(ref boolean) => out.push_str(&boolean.to_string()),
+            Json::Number(ref number) => out.push_str(number),
+            Json::String(ref string) => out.push_str(&json_string(string)),
+            Json::Array(ref values) => {
+                if values.is_empty() {
+                    out.push_str("[]");
+                    return;
+                }
+
+                out.push_str("[\n");
+
+                for (i, value) in values.iter().enumerate() {
+                    out.push_str(&indent);
+                    value.format(level + 1, out);
+                    out.push_str(if i + 1 < values.len() { ",\n" } else { "\n" });
+                }
+
+                out.push_str(&end);
+                out.push_str("]");
+            }
+            Json::Object(ref entries) => {
+                if entries.is_empty() {
+                    out.push_str("{}");
+                    return;
+                }
+
+                out.push_str("{\n");
+
+                for (i, &(ref key, ref value)) in entries.iter().enumerate() {
+                    out.push_str(&indent);
+                    out.push_str(&json_string(key));
+                    out.push_str(": ");
+                    value.format(level + 1, out);
+                    out.push_str(if i + 1 < entries.len() { ",\n" } else { "\n" });
+                }
+
+                out.push_str(&end);
+                out.push_str("}");
+            }
+        }
+    }
+}
+
+/// Encode the given string as a JSON string literal.
+pub fn json_string(input: &str) -> String {
+    let mut out = String::from("\"");
+
+    for c in input.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+/// Parser for JSON documents.
+///
+/// Numbers are kept in their textual form, so that they can be checked without loss of precision.
+struct Parser<'a> {
+    chars: ::std::iter::Peekable<::std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> Error {
+        format!("{}:{}: {}", self.line, self.column, message).into()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+
+        if let Some('\n') = c {
+            self.line += 1;
+            self.column = 1;
+        } else if c.is_some() {
+            self.column += 1;
+        }
+
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error(&format!("expected `{}`", expected))),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json> {
+        for expected in keyword.chars() {
+            self.expect(expected)?;
+        }
+
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json> {
+        self.skip_whitespace();
+
+        let value = match self.chars.peek().cloned() {
+            Some('n') => self.keyword("null", Json::Null)?,
+            Some('t') => self.keyword("true", Json::Boolean(true))?,
+            Some('f') => self.keyword("false", Json::Boolean(false))?,
+            Some('"') => Json::String(self.string()?),
+            Some('[') => self.array()?,
+            Some('{') => self.object()?,
+            Some(c) if c == '-' || c.is_digit(10) => self.number()?,
+            Some(_) => return Err(self.error("unexpected character")),
+            None => return Err(self.error("unexpected end of input")),
+        };
+
+        self.skip_whitespace();
+        Ok(value)
+    }
+
+    fn digits(&mut self, out: &mut String) -> Result<()> {
+        let start = out.len();
+
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_digit(10) {
+                break;
+            }
+
+            out.push(c);
+            self.next();
+        }
+
+        if out.len() == start {
+            return Err(self.error("expected digit"));
+        }
+
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<Json> {
+        let mut out = String::new();
+
+        if let Some(&'-') = self.chars.peek() {
+            out.push('-');
+            self.next();
+        }
+
+        self.digits(&mut out)?;
+
+        if let Some(&'.') = self.chars.peek() {
+            out.push('.');
+            self.next();
+            self.digits(&mut out)?;
+        }
+
+        if let Some(&c) = self.chars.peek() {
+            if c == 'e' || c == 'E' {
+                out.push(c);
+                self.next();
+
+                if let Some(&c) = self.chars.peek() {
+                    if c == '+' || c == '-' {
+                        out.push(c);
+                        self.next();
+                    }
+                }
+
+                self.digits(&mut out)?;
+            }
+        }
+
+        Ok(Json::Number(out))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.expect('"')?;
+
+        let mut out = String::new();
+
+        loop {
+            match self.next() {
+                Some('"') => break,
+                Some('\\') => {
+                    let c = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+
+                    out.push(c);
+                }
+                Some(c) => out.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+
+        Ok(out)
+    }
+
+    fn hex(&mut self) -> Result<u32> {
+        let mut code = 0u32;
+
+        for _ in 0..4 {
+            let digit = self.next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("invalid unicode escape"))?;
+
+            code = code * 16 + digit;
+        }
+
+        Ok(code)
+    }
+
+    fn unicode_escape(&mut self) -> Result<char> {
+        let mut code = self.hex()?;
+
+        // surrogate pair
+        if code >= 0xd800 && code < 0xdc00 {
+            self.expect('\\')?;
+            self.expect('u')?;
+            let low = self.hex()?;
+
+            if low < 0xdc00 || low >= 0xe000 {
+                return Err(self.error("invalid surrogate pair"));
+            }
+
+            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+        }
+
+        ::std::char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn array(&mut self) -> Result<Json> {
+        self.expect('[')?;
+        self.skip_whitespace();
+
+        let mut values = Vec::new();
+
+        if let Some(&']') = self.chars.peek() {
+            self.next();
+            return Ok(Json::Array(values));
+        }
+
+        loop {
+            values.push(self.value()?);
+
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => break,
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+
+        Ok(Json::Array(values))
+    }
+
+    fn object(&mut self) -> Result<Json> {
+        self.expect('{')?;
+        self.skip_whitespace();
+
+        let mut entries = Vec::new();
+
+        if let Some(&'}') = self.chars.peek() {
+            self.next();
+            return Ok(Json::Object(entries));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+
+        Ok(Json::Object(entries))
+    }
+}
+
+/// Parse the given input as a single JSON document.
+pub fn parse(input: &str) -> Result<Json> {
+    let mut parser = Parser {
+        chars: input.chars().peekable(),
+        line: 1,
+        column: 1,
+    };
+
+    let value = parser.value()?;
+
+    if parser.chars.peek().is_some() {
+        return Err(parser.error("trailing characters"));
+    }
+
+    Ok(value)
+}
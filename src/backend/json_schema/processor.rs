@@ -0,0 +1,430 @@
+use backend::*;
+use backend::errors::*;
+use backend::json::Json;
+use backend::models as m;
+use options::Options;
+use std::path::PathBuf;
+
+const EXT: &str = "json";
+const SCHEMA: &str = "http://json-schema.org/draft-07/schema#";
+
+pub trait Listeners {
+    fn configure(&self, _processor: &mut ProcessorOptions) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A vector of listeners is a valid listener.
+impl Listeners for Vec<Box<Listeners>> {
+    fn configure(&self, processor: &mut ProcessorOptions) -> Result<()> {
+        for listeners in self {
+            listeners.configure(processor)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ProcessorOptions {
+    parent: Options,
+}
+
+impl ProcessorOptions {
+    pub fn new(options: Options) -> ProcessorOptions {
+        ProcessorOptions { parent: options }
+    }
+}
+
+fn value_json(value: &m::Value) -> Json {
+    match *value {
+        m::Value::String(ref string) => Json::String(string.to_owned()),
+        m::Value::Number(ref number) => Json::Number(number.to_string()),
+        m::Value::Boolean(ref boolean) => Json::Boolean(*boolean),
+        _ => Json::Null,
+    }
+}
+
+/// Build the bounds of an integer with the given size, if they fit in 64 bits.
+fn integer_bounds(signed: bool, size: &Option<usize>) -> Vec<(String, Json)> {
+    let size = match *size {
+        Some(size) if size > 0 && size <= 64 => size,
+        _ => return vec![],
+    };
+
+    let (minimum, maximum) = if signed {
+        let max = (1u64 << (size - 1)) - 1;
+        (format!("-{}", max + 1), max.to_string())
+    } else {
+        let max = if size == 64 { u64::max_value() } else { (1u64 << size) - 1 };
+        ("0".to_owned(), max.to_string())
+    };
+
+    vec![("minimum".to_owned(), Json::Number(minimum)),
+         ("maximum".to_owned(), Json::Number(maximum))]
+}
+
+/// Build the schema of an integer with the given size.
+///
+/// Integers of more than 32 bits might not fit in a number, so they can also be encoded as
+/// strings.
+fn integer_schema(signed: bool, size: &Option<usize>) -> Vec<(String, Json)> {
+    let mut entries = vec![("type".to_owned(), Json::String("integer".to_owned()))];
+    entries.extend(integer_bounds(signed, size));
+
+    if !size.map(|s| s > 32).unwrap_or(false) {
+        return entries;
+    }
+
+    let pattern = if signed { "^-?[0-9]+$" } else { "^[0-9]+$" };
+
+    let string = vec![("type".to_owned(), Json::String("string".to_owned())),
+                      ("pattern".to_owned(), Json::String(pattern.to_owned()))];
+
+    vec![("anyOf".to_owned(), Json::Array(vec![Json::Object(entries), Json::Object(string)]))]
+}
+
+/// Build a schema for a discriminator, which matches any of the given names.
+fn names_schema(names: Vec<String>) -> Json {
+    if names.len() == 1 {
+        return Json::Object(vec![("const".to_owned(), Json::String(names[0].clone()))]);
+    }
+
+    let names = names.into_iter().map(Json::String).collect();
+    Json::Object(vec![("enum".to_owned(), Json::Array(names))])
+}
+
+pub struct Processor {
+    options: ProcessorOptions,
+    env: Environment,
+    package_prefix: Option<m::Package>,
+}
+
+impl Processor {
+    pub fn new(options: ProcessorOptions,
+               env: Environment,
+               package_prefix: Option<m::Package>)
+               -> Processor {
+        Processor {
+            options: options,
+            env: env,
+            package_prefix: package_prefix,
+        }
+    }
+
+    /// Build the schema package of a given package.
+    ///
+    /// This includes the prefixed configured in `self.options`, if specified.
+    fn package(&self, package: &m::Package) -> m::Package {
+        self.package_prefix
+            .clone()
+            .map(|prefix| prefix.join(package))
+            .unwrap_or_else(|| package.clone())
+    }
+
+    /// Build a reference to the schema of the given declaration, relative to the directory of the
+    /// schema of `package`.
+    fn schema_ref(&self, package: &m::Package, target: &m::Package, name: &str) -> String {
+        let mut parts: Vec<String> = Vec::new();
+
+        if target != package {
+            for _ in &self.package(package).parts {
+                parts.push("..".to_owned());
+            }
+
+            parts.extend(self.package(target).parts);
+        }
+
+        parts.push(format!("{}.{}", name, EXT));
+        parts.join("/")
+    }
+
+    fn type_schema(&self, pos: &m::Pos, package: &m::Package, ty: &m::Type) -> Result<Json> {
+        let entries = match *ty {
+            m::Type::Double | m::Type::Float => {
+                vec![("type".to_owned(), Json::String("number".to_owned()))]
+            }
+            m::Type::Signed(ref size) => integer_schema(true, size),
+            m::Type::Unsigned(ref size) => integer_schema(false, size),
+            m::Type::Boolean => vec![("type".to_owned(), Json::String("boolean".to_owned()))],
+            m::Type::String => vec![("type".to_owned(), Json::String("string".to_owned()))],
+            m::Type::Bytes => {
+                vec![("type".to_owned(), Json::String("string".to_owned())),
+                     ("contentEncoding".to_owned(), Json::String("base64".to_owned()))]
+            }
+            m::Type::Any => vec![],
+            m::Type::Array(ref inner) => {
+                vec![("type".to_owned(), Json::String("array".to_owned())),
+                     ("items".to_owned(), self.type_schema(pos, package, inner)?)]
+            }
+            m::Type::Map(_, ref value) => {
+                vec![("type".to_owned(), Json::String("object".to_owned())),
+                     ("additionalProperties".to_owned(), self.type_schema(pos, package, value)?)]
+            }
+            m::Type::Custom(ref custom) => {
+                let target = if let Some(ref prefix) = custom.prefix {
+                    self.env.lookup_used(pos, package, prefix)?
+                } else {
+                    package
+                };
+
+                let key = (target.clone(), custom.parts.clone());
+
+                if !self.env.types.contains_key(&key) {
+                    return Err(Error::pos(format!("no such type: {}", custom.parts.join(".")),
+                                          pos.clone()));
+                }
+
+                let reference = self.schema_ref(package, target, &custom.parts.join("."));
+                vec![("$ref".to_owned(), Json::String(reference))]
+            }
+        };
+
+        Ok(Json::Object(entries))
+    }
+
+    /// Build the properties and the names of the required properties of the given fields.
+    fn fields_schema(&self,
+                     package: &m::Package,
+                     fields: &[&m::Token<m::Field>])
+                     -> Result<(Vec<(String, Json)>, Vec<Json>)> {
+        let mut properties = Vec::new();
+        let mut required = Vec::new();
+
+        for field in fields {
+            let mut schema = self.type_schema(&field.pos, package, &field.ty)?;
+
+            if let Json::Object(ref mut entries) = schema {
+                entries.extend(self.description(&field.comment));
+            }
+
+            properties.push((field.name().to_owned(), schema));
+
+            if !field.is_optional() {
+                required.push(Json::String(field.name().to_owned()));
+            }
+        }
+
+        Ok((properties, required))
+    }
+
+    fn object_schema(&self,
+                     properties: Vec<(String, Json)>,
+                     required: Vec<Json>)
+                     -> Vec<(String, Json)> {
+        let mut entries = vec![("type".to_owned(), Json::String("object".to_owned())),
+                               ("properties".to_owned(), Json::Object(properties))];
+
+        if !required.is_empty() {
+            entries.push(("required".to_owned(), Json::Array(required)));
+        }
+
+        entries
+    }
+
+    fn description(&self, comment: &Vec<String>) -> Vec<(String, Json)> {
+        if comment.is_empty() {
+            return vec![];
+        }
+
+        vec![("description".to_owned(), Json::String(comment.join("\n")))]
+    }
+
+    fn type_body(&self, package: &m::Package, body: &m::TypeBody) -> Result<Vec<(String, Json)>> {
+        let fields: Vec<_> = body.fields.iter().collect();
+        let (properties, required) = self.fields_schema(package, &fields)?;
+        Ok(self.object_schema(properties, required))
+    }
+
+    fn tuple_body(&self,
+                  package: &m::Package,
+                  body: &m::TupleBody)
+                  -> Result<Vec<(String, Json)>> {
+        let mut items = Vec::new();
+
+        for field in &body.fields {
+            items.push(self.type_schema(&field.pos, package, &field.ty)?);
+        }
+
+        let length = Json::Number(items.len().to_string());
+
+        Ok(vec![("type".to_owned(), Json::String("array".to_owned())),
+                ("items".to_owned(), Json::Array(items)),
+                ("minItems".to_owned(), length.clone()),
+                ("maxItems".to_owned(), length)])
+    }
+
+    fn enum_body(&self, body: &m::EnumBody) -> Result<Vec<(String, Json)>> {
+        if let Some(ref s) = body.serialized_as {
+            let index = body.fields
+                .iter()
+                .position(|f| f.name == s.inner)
+                .ok_or_else(|| Error::pos(format!("no field named: {}", s.inner), s.pos.clone()))?;
+
+            let mut values = Vec::new();
+
+            for value in &body.values {
+                let argument = value.arguments
+                    .get(index)
+                    .ok_or_else(|| Error::pos("missing argument".to_owned(), value.pos.clone()))?;
+
+                values.push(value_json(&argument.inner));
+            }
+
+            return Ok(vec![("enum".to_owned(), Json::Array(values))]);
+        }
+
+        let values = body.values.iter().map(|v| Json::String(v.name.to_owned())).collect();
+
+        Ok(vec![("type".to_owned(), Json::String("string".to_owned())),
+                ("enum".to_owned(), Json::Array(values))])
+    }
+
+    fn sub_type_schema(&self,
+                       package: &m::Package,
+                       interface: &m::InterfaceBody,
+                       sub_type: &m::SubType)
+                       -> Result<Json> {
+        let fields: Vec<_> = interface.fields.iter().chain(sub_type.fields.iter()).collect();
+        let (mut properties, mut required) = self.fields_schema(package, &fields)?;
+
+        let mut names: Vec<String> = sub_type.names.iter().map(|n| n.inner.to_owned()).collect();
+
+        if names.is_empty() {
+            names.push(sub_type.name.to_owned());
+        }
+
+        let mut entries = vec![("title".to_owned(), Json::String(sub_type.name.to_owned()))];
+        entries.extend(self.description(&sub_type.comment));
+
+        match interface.type_info {
+            m::TypeInfo::TypeField(ref type_field) => {
+                properties.insert(0, (type_field.to_owned(), names_schema(names)));
+                required.insert(0, Json::String(type_field.to_owned()));
+                entries.extend(self.object_schema(properties, required));
+            }
+            m::TypeInfo::ObjectKeys => {
+                let inner = Json::Object(self.object_schema(properties, required));
+
+                let mut outer = Vec::new();
+
+                for name in &names {
+                    outer.push((name.to_owned(), inner.clone()));
+                }
+
+                let names = names.into_iter().map(Json::String).collect();
+
+                entries.push(("type".to_owned(), Json::String("object".to_owned())));
+                entries.push(("properties".to_owned(), Json::Object(outer)));
+                entries.push(("propertyNames".to_owned(),
+                              Json::Object(vec![("enum".to_owned(), Json::Array(names))])));
+                entries.push(("minProperties".to_owned(), Json::Number("1".to_owned())));
+                entries.push(("maxProperties".to_owned(), Json::Number("1".to_owned())));
+            }
+        }
+
+        Ok(Json::Object(entries))
+    }
+
+    fn interface_body(&self,
+                      package: &m::Package,
+                      body: &m::InterfaceBody)
+                      -> Result<Vec<(String, Json)>> {
+        let mut sub_types = Vec::new();
+
+        for sub_type in body.sub_types.values() {
+            sub_types.push(self.sub_type_schema(package, body, sub_type)?);
+        }
+
+        Ok(vec![("oneOf".to_owned(), Json::Array(sub_types))])
+    }
+
+    /// Build the schemas of the values that are decoded through a match declaration, in addition
+    /// to the regular encoding of the declaration.
+    fn match_schemas(&self,
+                     package: &m::Package,
+                     name: &str,
+                     match_decl: &m::MatchDecl)
+                     -> Result<Vec<Json>> {
+        let mut schemas = Vec::new();
+
+        for &(ref value, _) in &match_decl.by_value {
+            schemas.push(Json::Object(vec![("const".to_owned(), value_json(&value.inner))]));
+        }
+
+        for &(_, ref member) in &match_decl.by_type {
+            if member.is_self_object(name) {
+                continue;
+            }
+
+            if let m::MatchCondition::Type(ref variable) = member.condition.inner {
+                schemas.push(self.type_schema(&member.condition.pos, package, &variable.ty)?);
+            }
+        }
+
+        Ok(schemas)
+    }
+
+    fn decl_schema(&self, package: &m::Package, decl: &m::Decl) -> Result<Json> {
+        let (comment, match_decl, body) = match *decl {
+            m::Decl::Type(ref body) => {
+                (&body.comment, &body.match_decl, self.type_body(package, body)?)
+            }
+            m::Decl::Tuple(ref body) => {
+                (&body.comment, &body.match_decl, self.tuple_body(package, body)?)
+            }
+            m::Decl::Enum(ref body) => (&body.comment, &body.match_decl, self.enum_body(body)?),
+            m::Decl::Interface(ref body) => {
+                (&body.comment, &body.match_decl, self.interface_body(package, body)?)
+            }
+        };
+
+        let mut entries = vec![("$schema".to_owned(), Json::String(SCHEMA.to_owned())),
+                               ("title".to_owned(), Json::String(decl.name().to_owned()))];
+
+        entries.extend(self.description(comment));
+
+        let mut alternatives = self.match_schemas(package, decl.name(), match_decl)?;
+
+        if alternatives.is_empty() {
+            entries.extend(body);
+        } else {
+            // matched values are tried before the regular encoding, which may also accept them.
+            alternatives.push(Json::Object(body));
+            entries.push(("anyOf".to_owned(), Json::Array(alternatives)));
+        }
+
+        Ok(Json::Object(entries))
+    }
+
+    fn write_file(&self, package: &m::Package, name: &str, schema: Json) -> Result<()> {
+        let mut full_path = PathBuf::new();
+
+        for part in &self.package(package).parts {
+            full_path.push(part);
+        }
+
+        full_path.push(format!("{}.{}", name, EXT));
+        debug!("+schema: {}", full_path.display());
+
+        let mut content = String::new();
+        schema.format(0, &mut content);
+        content.push('\n');
+
+        self.options.parent.output.write(&full_path, content.as_bytes())
+    }
+}
+
+impl Backend for Processor {
+    fn process(&self) -> Result<()> {
+        for (&(ref package, _), decl) in &self.env.types {
+            let schema = self.decl_schema(package, decl)?;
+            self.write_file(package, decl.name(), schema)?;
+        }
+
+        Ok(())
+    }
+
+    fn verify(&self) -> Result<Vec<Error>> {
+        Ok(vec![])
+    }
+}
@@ -20,15 +20,35 @@ impl<T> Merge for Token<T>
 
 impl Merge for SubType {
     fn merge(&mut self, source: SubType) -> Result<()> {
+        self.comment.extend(source.comment);
         self.fields.merge(source.fields)?;
         self.codes.merge(source.codes)?;
         self.names.extend(source.names);
+
+        if source.number.is_some() {
+            self.number = source.number;
+        }
+
         Ok(())
     }
 }
 
 impl Merge for InterfaceBody {
     fn merge(&mut self, source: InterfaceBody) -> Result<()> {
+        if let Some(source_pos) = source.type_info_pos {
+            if let Some(ref dest_pos) = self.type_info_pos {
+                if self.type_info != source.type_info {
+                    return Err(Error::decl_merge("conflicting type_info".to_owned(),
+                                                 source_pos,
+                                                 dest_pos.clone()));
+                }
+            } else {
+                self.type_info = source.type_info;
+                self.type_info_pos = Some(source_pos);
+            }
+        }
+
+        self.comment.extend(source.comment);
         self.fields.merge(source.fields)?;
         self.codes.merge(source.codes)?;
         self.sub_types.merge(source.sub_types)?;
@@ -38,6 +58,7 @@ impl Merge for InterfaceBody {
 
 impl Merge for TypeBody {
     fn merge(&mut self, source: TypeBody) -> Result<()> {
+        self.comment.extend(source.comment);
         self.fields.merge(source.fields)?;
         self.codes.merge(source.codes)?;
         Ok(())
@@ -46,6 +67,7 @@ impl Merge for TypeBody {
 
 impl Merge for TupleBody {
     fn merge(&mut self, source: TupleBody) -> Result<()> {
+        self.comment.extend(source.comment);
         self.fields.merge(source.fields)?;
         self.codes.merge(source.codes)?;
         Ok(())
@@ -54,6 +76,7 @@ impl Merge for TupleBody {
 
 impl Merge for EnumBody {
     fn merge(&mut self, source: EnumBody) -> Result<()> {
+        self.comment.extend(source.comment);
         self.codes.merge(source.codes)?;
         Ok(())
     }
@@ -147,3 +170,50 @@ impl<K, T> Merge for BTreeMap<K, T>
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use parser;
+    use std::path::{Path, PathBuf};
+    use super::*;
+    use super::super::into_model::IntoModel;
+
+    fn decl(content: &str) -> Token<Decl> {
+        let path = Path::new("test.reproto");
+        let file = parser::parse_string(path, content).unwrap();
+        let decl = file.decls.into_iter().next().unwrap();
+        let pos = (PathBuf::from(path), decl.pos.0, decl.pos.1);
+        decl.into_model(&pos).unwrap()
+    }
+
+    fn type_info(decl: &Token<Decl>) -> TypeInfo {
+        match decl.inner {
+            Decl::Interface(ref body) => body.type_info.clone(),
+            _ => panic!("expected interface"),
+        }
+    }
+
+    #[test]
+    fn test_merge_type_info() {
+        let mut dest = decl("package a; interface I { A {} }");
+        dest.merge(decl("package a; interface I { type_info object_keys; }")).unwrap();
+        assert_eq!(TypeInfo::ObjectKeys, type_info(&dest));
+
+        // the default does not override what was given explicitly.
+        dest.merge(decl("package a; interface I { B {} }")).unwrap();
+        assert_eq!(TypeInfo::ObjectKeys, type_info(&dest));
+    }
+
+    #[test]
+    fn test_merge_conflicting_type_info() {
+        let mut dest = decl("package a; interface I { type_info object_keys; }");
+
+        match dest.merge(decl("package a; interface I { type_field_name \"kind\"; }")) {
+            Err(Error(ErrorKind::DeclMerge(ref message, _, _), _)) => {
+                assert_eq!("conflicting type_info", message)
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected error"),
+        }
+    }
+}
@@ -2,13 +2,25 @@ mod into_model;
 mod merge;
 mod options;
 
+pub mod compat;
+pub mod doc;
+pub mod doc_comment;
 pub mod environment;
 pub mod errors;
 pub mod for_context;
 pub mod java;
+#[macro_use]
 pub mod js;
+pub mod json;
+pub mod json_schema;
 pub mod models;
+pub mod output;
+pub mod protobuf;
 pub mod python;
+pub mod rust;
+pub mod samples;
+pub mod typescript;
+pub mod validate;
 
 pub use self::environment::Environment;
 use options::Options;
@@ -25,6 +37,11 @@ pub fn resolve(backend: &str, options: Options, env: Environment) -> Result<Box<
         "java" => Box::new(java::resolve(options, env)?),
         "python" => Box::new(python::resolve(options, env)?),
         "js" => Box::new(js::resolve(options, env)?),
+        "rust" => Box::new(rust::resolve(options, env)?),
+        "doc" => Box::new(doc::resolve(options, env)?),
+        "json-schema" => Box::new(json_schema::resolve(options, env)?),
+        "typescript" => Box::new(typescript::resolve(options, env)?),
+        "protobuf" => Box::new(protobuf::resolve(options, env)?),
         _ => return Err(format!("Unknown backend type: {}", backend).into()),
     };
 
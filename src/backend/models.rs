@@ -109,10 +109,13 @@ impl Modifiers {
 
 #[derive(Debug, Clone)]
 pub struct Field {
+    pub comment: Vec<String>,
     pub modifier: Modifier,
     pub name: String,
     pub ty: Type,
     pub field_as: Option<Token<String>>,
+    /// Number assigned through the `field_number` option, used by binary formats.
+    pub number: Option<Token<u32>>,
 }
 
 impl Field {
@@ -200,9 +203,12 @@ impl BodyLike for SubType {
 #[derive(Debug, Clone)]
 pub struct SubType {
     pub name: String,
+    pub comment: Vec<String>,
     pub fields: Vec<Token<Field>>,
     pub codes: Vec<Token<Code>>,
     pub names: Vec<Token<String>>,
+    /// Number assigned through the `field_number` option of its interface.
+    pub number: Option<Token<u32>>,
 }
 
 impl SubType {
@@ -215,18 +221,32 @@ impl SubType {
     }
 }
 
+/// How the sub type of an interface is indicated when serialized.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeInfo {
+    /// Sub types are serialized as objects, with the given field containing its name.
+    TypeField(String),
+    /// Sub types are serialized as objects with a single key, where the key is its name.
+    ObjectKeys,
+}
+
 #[derive(Debug, Clone)]
 pub struct InterfaceBody {
     pub name: String,
+    pub comment: Vec<String>,
     pub fields: Vec<Token<Field>>,
     pub codes: Vec<Token<Code>>,
     pub match_decl: MatchDecl,
     pub sub_types: BTreeMap<String, Token<SubType>>,
+    pub type_info: TypeInfo,
+    /// Position of the options which gave the type info, if it is not the default.
+    pub type_info_pos: Option<Pos>,
 }
 
 #[derive(Debug, Clone)]
 pub struct TypeBody {
     pub name: String,
+    pub comment: Vec<String>,
     pub fields: Vec<Token<Field>>,
     pub codes: Vec<Token<Code>>,
     pub match_decl: MatchDecl,
@@ -236,19 +256,22 @@ pub struct TypeBody {
 
 impl TypeBody {
     pub fn verify(&self) -> Result<()> {
+        let mut errors = Vec::new();
+
         for reserved in &self.reserved {
-            if let Some(field) = self.fields.iter().find(|f| f.name == reserved.inner) {
-                return Err(Error::reserved_field(field.pos.clone(), reserved.pos.clone()));
+            for field in self.fields.iter().filter(|f| f.name == reserved.inner) {
+                errors.push(Error::reserved_field(field.pos.clone(), reserved.pos.clone()));
             }
         }
 
-        Ok(())
+        from_errors(errors)
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct TupleBody {
     pub name: String,
+    pub comment: Vec<String>,
     pub fields: Vec<Token<Field>>,
     pub codes: Vec<Token<Code>>,
     pub match_decl: MatchDecl,
@@ -257,6 +280,7 @@ pub struct TupleBody {
 #[derive(Debug, Clone)]
 pub struct EnumValue {
     pub name: String,
+    pub comment: Vec<String>,
     pub arguments: Vec<Token<Value>>,
     pub ordinal: u32,
 }
@@ -264,6 +288,7 @@ pub struct EnumValue {
 #[derive(Debug, Clone)]
 pub struct EnumBody {
     pub name: String,
+    pub comment: Vec<String>,
     pub values: Vec<Token<EnumValue>>,
     pub fields: Vec<Token<Field>>,
     pub codes: Vec<Token<Code>>,
@@ -325,6 +350,25 @@ pub struct MatchMember {
     pub value: Token<Value>,
 }
 
+impl MatchMember {
+    /// Check if this member decodes the declaration itself from an object, like `foo: Foo => foo`.
+    ///
+    /// These members are covered by the regular object decoding of the declaration.
+    pub fn is_self_object(&self, name: &str) -> bool {
+        if let MatchCondition::Type(ref variable) = self.condition.inner {
+            if let Type::Custom(ref custom) = variable.ty {
+                if custom.prefix.is_none() && custom.parts == vec![name.to_owned()] {
+                    if let Value::Identifier(ref identifier) = self.value.inner {
+                        return *identifier == variable.name;
+                    }
+                }
+            }
+        }
+
+        false
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MatchVariable {
     pub name: String,
@@ -75,7 +75,7 @@ impl<'a> Options<'a> {
         Ok(None)
     }
 
-    pub fn _find_one_string(&self, name: &str) -> Result<Option<Token<String>>> {
+    pub fn find_one_string(&self, name: &str) -> Result<Option<Token<String>>> {
         if let Some(t) = self.find_one(name)? {
             if let Value::String(ref string) = t.inner {
                 return Ok(Some(Token::new(string.clone(), t.pos.clone())));
@@ -99,6 +99,54 @@ impl<'a> Options<'a> {
         Ok(None)
     }
 
+    /// Find all pairs of an identifier and a number matching the given name, as in
+    /// `<name> <identifier>, <number>;`.
+    ///
+    /// The identifier may also be the name of a sub type. The number must be a positive integer.
+    pub fn find_all_numbered(&self, name: &str) -> Result<Vec<(Token<String>, Token<u32>)>> {
+        let max = u32::max_value() as f64;
+        let mut out = Vec::new();
+
+        for option in self.options.iter().filter(|o| o.name.as_str() == name) {
+            let (first, second) = match option.values.as_slice() {
+                &[ref first, ref second] => (first, second),
+                _ => {
+                    return Err(Error::pos(format!("{}: expected <identifier>, <number>", name),
+                                          option.pos.clone()));
+                }
+            };
+
+            let identifier = match first.inner {
+                Value::Identifier(ref identifier) => {
+                    Token::new(identifier.clone(), first.pos.clone())
+                }
+                // names of sub types parse as constants.
+                Value::Constant(ref constant)
+                    if constant.prefix.is_none() && constant.parts.len() == 1 => {
+                    Token::new(constant.parts[0].clone(), first.pos.clone())
+                }
+                _ => {
+                    return Err(Error::pos(format!("{}: expected identifier", name),
+                                          first.pos.clone()));
+                }
+            };
+
+            let number = match second.inner {
+                Value::Number(n) if n >= 1.0 && n.fract() == 0.0 && n <= max => {
+                    Token::new(n as u32, second.pos.clone())
+                }
+                _ => {
+                    return Err(Error::pos(format!("{}: expected positive integer", name),
+                                          second.pos.clone()));
+                }
+            };
+
+            out.push((identifier, number));
+        }
+
+        Ok(out)
+    }
+
     /// Find all identifiers matching the given name.
     ///
     /// This enforces that all found values are identifiers, otherwise the lookup will cause an error.
@@ -0,0 +1,328 @@
+//! Destinations for the files generated by backends.
+
+use super::errors::*;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+/// Receives the files generated by a backend.
+///
+/// All paths are relative to the root of the output.
+pub trait Output {
+    /// Write a file, replacing it if it already exists.
+    fn write(&self, path: &Path, content: &[u8]) -> Result<()>;
+
+    /// Write a file, unless it already exists.
+    fn write_missing(&self, path: &Path, content: &[u8]) -> Result<()>;
+}
+
+/// Name of the manifest which lists every file generated into a directory.
+pub const MANIFEST: &str = ".reproto-manifest";
+
+/// Writes files into a directory.
+///
+/// Files whose content did not change are left untouched, so that their modification time is
+/// preserved. Clones share the record of files written, which is used to maintain the manifest.
+#[derive(Clone)]
+pub struct FileSystemOutput {
+    root: PathBuf,
+    written: Rc<RefCell<BTreeSet<PathBuf>>>,
+}
+
+impl FileSystemOutput {
+    pub fn new<P>(root: P) -> FileSystemOutput
+        where P: AsRef<Path>
+    {
+        FileSystemOutput {
+            root: root.as_ref().to_owned(),
+            written: Rc::new(RefCell::new(BTreeSet::new())),
+        }
+    }
+
+    /// Write the file, unless it already has the given content.
+    fn write_changed(&self, full_path: &Path, content: &[u8]) -> Result<()> {
+        if full_path.is_file() {
+            let mut current = Vec::new();
+            File::open(full_path)?.read_to_end(&mut current)?;
+
+            if current == content {
+                debug!("=unchanged: {}", full_path.display());
+                return Ok(());
+            }
+        }
+
+        if let Some(parent) = full_path.parent() {
+            if !parent.is_dir() {
+                debug!("+dir: {}", parent.display());
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let mut f = File::create(full_path)?;
+        f.write_all(content)?;
+        f.flush()?;
+        Ok(())
+    }
+
+    /// Read the files listed in the manifest of a previous run.
+    fn read_manifest(&self) -> Result<Vec<PathBuf>> {
+        let path = self.root.join(MANIFEST);
+
+        if !path.is_file() {
+            return Ok(vec![]);
+        }
+
+        let mut content = String::new();
+        File::open(&path)?.read_to_string(&mut content)?;
+
+        let mut files = Vec::new();
+
+        for line in content.lines().filter(|l| !l.is_empty()) {
+            let file = PathBuf::from(line);
+
+            // never touch anything outside of the output directory.
+            if !file.components().all(|c| if let Component::Normal(_) = c { true } else { false }) {
+                return Err(format!("{}: bad path in manifest: {}", path.display(), line).into());
+            }
+
+            files.push(file);
+        }
+
+        Ok(files)
+    }
+
+    /// Remove files generated by a previous run which were not written by this one, together
+    /// with any directories left empty.
+    pub fn clean(&self) -> Result<()> {
+        let written = self.written.borrow();
+
+        for file in self.read_manifest()? {
+            if written.contains(&file) {
+                continue;
+            }
+
+            let full_path = self.root.join(&file);
+
+            if !full_path.is_file() {
+                continue;
+            }
+
+            info!("-removed: {}", full_path.display());
+            fs::remove_file(&full_path)?;
+
+            let mut parent = full_path.parent();
+
+            while let Some(dir) = parent {
+                if dir == self.root || fs::read_dir(dir)?.next().is_some() {
+                    break;
+                }
+
+                debug!("-dir: {}", dir.display());
+                fs::remove_dir(dir)?;
+                parent = dir.parent();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the manifest, listing every file written so far.
+    ///
+    /// Files listed by the manifest of a previous run which still exist are kept, so that they are
+    /// removed by a later clean if they are no longer generated.
+    pub fn write_manifest(&self) -> Result<()> {
+        let mut files = self.written.borrow().clone();
+
+        for file in self.read_manifest()? {
+            if self.root.join(&file).is_file() {
+                files.insert(file);
+            }
+        }
+
+        let mut content = String::new();
+
+        for file in &files {
+            let parts: Vec<_> = file.components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+
+            content.push_str(&parts.join("/"));
+            content.push('\n');
+        }
+
+        self.write_changed(&self.root.join(MANIFEST), content.as_bytes())
+    }
+}
+
+impl Output for FileSystemOutput {
+    fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        self.written.borrow_mut().insert(path.to_owned());
+        self.write_changed(&self.root.join(path), content)
+    }
+
+    fn write_missing(&self, path: &Path, content: &[u8]) -> Result<()> {
+        let full_path = self.root.join(path);
+
+        // still part of the generated files, even if left alone.
+        self.written.borrow_mut().insert(path.to_owned());
+
+        if full_path.is_file() {
+            return Ok(());
+        }
+
+        self.write_changed(&full_path, content)
+    }
+}
+
+/// Keeps files in memory.
+///
+/// Clones share the same files, so that a clone can be handed to a backend and the files
+/// inspected afterwards.
+#[derive(Clone, Default)]
+pub struct MemoryOutput {
+    files: Rc<RefCell<BTreeMap<PathBuf, Vec<u8>>>>,
+}
+
+impl MemoryOutput {
+    pub fn new() -> MemoryOutput {
+        MemoryOutput::default()
+    }
+
+    /// All files written so far.
+    pub fn files(&self) -> BTreeMap<PathBuf, Vec<u8>> {
+        self.files.borrow().clone()
+    }
+}
+
+impl Output for MemoryOutput {
+    fn write(&self, path: &Path, content: &[u8]) -> Result<()> {
+        self.files.borrow_mut().insert(path.to_owned(), content.to_owned());
+        Ok(())
+    }
+
+    fn write_missing(&self, path: &Path, content: &[u8]) -> Result<()> {
+        self.files.borrow_mut().entry(path.to_owned()).or_insert_with(|| content.to_owned());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("reproto-output-{}-{}", name, process::id()));
+
+        if dir.is_dir() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn modified(path: &Path) -> SystemTime {
+        fs::metadata(path).unwrap().modified().unwrap()
+    }
+
+    #[test]
+    fn test_write_changed() {
+        let dir = temp_dir("write-changed");
+        let output = FileSystemOutput::new(&dir);
+        let path = dir.join("a").join("b.txt");
+
+        output.write(Path::new("a/b.txt"), b"hello").unwrap();
+        assert_eq!("hello", fs::read_to_string(&path).unwrap());
+
+        let old = UNIX_EPOCH + Duration::from_secs(1000);
+        File::open(&path).unwrap().set_modified(old).unwrap();
+
+        output.write(Path::new("a/b.txt"), b"hello").unwrap();
+        assert_eq!(old, modified(&path));
+
+        output.write(Path::new("a/b.txt"), b"world").unwrap();
+        assert_eq!("world", fs::read_to_string(&path).unwrap());
+        assert!(modified(&path) != old);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean() {
+        let dir = temp_dir("clean");
+
+        let first = FileSystemOutput::new(&dir);
+        first.write(Path::new("keep.txt"), b"keep").unwrap();
+        first.write(Path::new("stale/nested/gone.txt"), b"gone").unwrap();
+        first.write(Path::new("shared/gone.txt"), b"gone").unwrap();
+        first.write(Path::new("shared/kept.txt"), b"kept").unwrap();
+        first.write_manifest().unwrap();
+
+        let second = FileSystemOutput::new(&dir);
+        second.write(Path::new("keep.txt"), b"keep").unwrap();
+        second.write(Path::new("shared/kept.txt"), b"kept").unwrap();
+        second.clean().unwrap();
+        second.write_manifest().unwrap();
+
+        assert!(dir.join("keep.txt").is_file());
+        assert!(dir.join("shared/kept.txt").is_file());
+        assert!(!dir.join("shared/gone.txt").exists());
+        assert!(!dir.join("stale").exists());
+        assert_eq!(
+            "keep.txt\nshared/kept.txt\n",
+            fs::read_to_string(dir.join(MANIFEST)).unwrap()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_after_compile_without_clean() {
+        let dir = temp_dir("clean-later");
+
+        let first = FileSystemOutput::new(&dir);
+        first.write(Path::new("A.java"), b"a").unwrap();
+        first.write(Path::new("B.java"), b"b").unwrap();
+        first.write_manifest().unwrap();
+
+        // B was removed from the specification, but the compile does not clean.
+        let second = FileSystemOutput::new(&dir);
+        second.write(Path::new("A.java"), b"a").unwrap();
+        second.write_manifest().unwrap();
+
+        assert!(dir.join("B.java").is_file());
+        assert_eq!("A.java\nB.java\n", fs::read_to_string(dir.join(MANIFEST)).unwrap());
+
+        let third = FileSystemOutput::new(&dir);
+        third.write(Path::new("A.java"), b"a").unwrap();
+        third.clean().unwrap();
+        third.write_manifest().unwrap();
+
+        assert!(dir.join("A.java").is_file());
+        assert!(!dir.join("B.java").exists());
+        assert_eq!("A.java\n", fs::read_to_string(dir.join(MANIFEST)).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_manifest_outside() {
+        let dir = temp_dir("manifest-outside");
+
+        for line in &["../outside.txt", "/etc/passwd", "a/../../b"] {
+            fs::write(dir.join(MANIFEST), format!("{}\n", line)).unwrap();
+
+            let output = FileSystemOutput::new(&dir);
+            assert!(output.read_manifest().is_err(), "accepted: {}", line);
+            assert!(output.clean().is_err(), "cleaned: {}", line);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,556 @@
+use backend::*;
+use backend::errors::*;
+use backend::models as m;
+use codeviz::common::ElementFormat;
+use codeviz::js::{ElementSpec, Elements, Statement, Variable};
+use naming::{self, FromNaming};
+use options::Options;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+const EXT: &str = "proto";
+const INDENT: &str = "  ";
+const SYNTAX: &str = "syntax = \"proto3\";";
+/// Name of the oneof which holds the sub type of an interface.
+const ONEOF: &str = "value";
+/// Name of the field of the messages that wrap nested collections.
+const WRAPPED: &str = "value";
+const ANY_TYPE: &str = "google.protobuf.Value";
+const ANY_IMPORT: &str = "google/protobuf/struct.proto";
+/// Largest field number permitted by protobuf.
+const MAX_NUMBER: u32 = 536870911;
+/// Field numbers reserved for the implementation of protobuf.
+const RESERVED_NUMBERS: (u32, u32) = (19000, 19999);
+
+pub trait Listeners {
+    fn configure(&self, _processor: &mut ProcessorOptions) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A vector of listeners is a valid listener.
+impl Listeners for Vec<Box<Listeners>> {
+    fn configure(&self, processor: &mut ProcessorOptions) -> Result<()> {
+        for listeners in self {
+            listeners.configure(processor)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ProcessorOptions {
+    parent: Options,
+}
+
+impl ProcessorOptions {
+    pub fn new(options: Options) -> ProcessorOptions {
+        ProcessorOptions { parent: options }
+    }
+}
+
+/// A proto file being built for a package.
+struct File {
+    imports: BTreeSet<String>,
+    elements: Elements,
+}
+
+impl File {
+    fn new() -> File {
+        File {
+            imports: BTreeSet::new(),
+            elements: Elements::new(),
+        }
+    }
+}
+
+pub fn is_reserved(number: u32) -> bool {
+    number >= RESERVED_NUMBERS.0 && number <= RESERVED_NUMBERS.1
+}
+
+/// Convert a camel cased name into upper snake case, keeping acronyms together.
+fn upper_snake(name: &str) -> String {
+    let mut out = String::new();
+    let mut previous: Option<char> = None;
+
+    for c in name.chars() {
+        if c.is_uppercase() && previous.map(|p| p.is_lowercase()).unwrap_or(false) {
+            out.push('_');
+        }
+
+        out.extend(c.to_uppercase());
+        previous = Some(c);
+    }
+
+    out
+}
+
+fn is_collection(ty: &m::Type) -> bool {
+    match *ty {
+        m::Type::Array(_) | m::Type::Map(_, _) => true,
+        _ => false,
+    }
+}
+
+pub struct Processor {
+    options: ProcessorOptions,
+    env: Environment,
+    package_prefix: Option<m::Package>,
+    snake_to_upper_camel: Box<naming::Naming>,
+    camel_to_lower_snake: Box<naming::Naming>,
+}
+
+impl Processor {
+    pub fn new(options: ProcessorOptions,
+               env: Environment,
+               package_prefix: Option<m::Package>)
+               -> Processor {
+        Processor {
+            options: options,
+            env: env,
+            package_prefix: package_prefix,
+            snake_to_upper_camel: naming::SnakeCase::new().to_upper_camel(),
+            camel_to_lower_snake: naming::CamelCase::new().to_lower_snake(),
+        }
+    }
+
+    /// Build the protobuf package of a given package.
+    ///
+    /// This includes the prefixed configured in `self.options`, if specified.
+    fn package(&self, package: &m::Package) -> m::Package {
+        self.package_prefix
+            .clone()
+            .map(|prefix| prefix.join(package))
+            .unwrap_or_else(|| package.clone())
+    }
+
+    /// Path of the proto file of the given package, which is also how it is imported.
+    fn file_path(&self, package: &m::Package) -> PathBuf {
+        let package = self.package(package);
+        let mut full_path = package.parts.iter().fold(PathBuf::new(), |path, part| path.join(part));
+        full_path.set_extension(EXT);
+        full_path
+    }
+
+    fn comment(&self, comment: &Vec<String>) -> Elements {
+        let mut elements = Elements::new();
+
+        for line in comment {
+            if line.is_empty() {
+                elements.push("//");
+            } else {
+                elements.push(stmt!["// ", line]);
+            }
+        }
+
+        elements
+    }
+
+    /// Build the name of the given custom type, relative to `package`.
+    fn convert_custom(&self,
+                      pos: &m::Pos,
+                      package: &m::Package,
+                      custom: &m::Custom,
+                      imports: &mut BTreeSet<String>)
+                      -> Result<String> {
+        let target = if let Some(ref prefix) = custom.prefix {
+            self.env.lookup_used(pos, package, prefix)?
+        } else {
+            package
+        };
+
+        let key = (target.clone(), custom.parts.clone());
+        let name = custom.parts.join(".");
+
+        if !self.env.types.contains_key(&key) {
+            return Err(Error::pos(format!("no such type: {}", name), pos.clone()));
+        }
+
+        if target == package {
+            return Ok(name);
+        }
+
+        let path = self.file_path(target);
+        let parts: Vec<_> = path.components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+
+        imports.insert(parts.join("/"));
+        Ok(format!("{}.{}", self.package(target).parts.join("."), name))
+    }
+
+    /// Closest scalar type for an integer of the given size.
+    ///
+    /// There are no scalars above 64 bits, so such integers are transferred as decimal strings.
+    fn integer(&self, signed: bool, size: Option<usize>) -> &'static str {
+        match (signed, size.unwrap_or(32)) {
+            (true, s) if s <= 32 => "int32",
+            (true, s) if s <= 64 => "int64",
+            (false, s) if s <= 32 => "uint32",
+            (false, s) if s <= 64 => "uint64",
+            _ => "string",
+        }
+    }
+
+    /// Convert a type which is not a collection.
+    fn convert_type(&self,
+                    pos: &m::Pos,
+                    package: &m::Package,
+                    ty: &m::Type,
+                    imports: &mut BTreeSet<String>)
+                    -> Result<String> {
+        let ty = match *ty {
+            m::Type::Double => "double",
+            m::Type::Float => "float",
+            m::Type::Signed(size) => self.integer(true, size),
+            m::Type::Unsigned(size) => self.integer(false, size),
+            m::Type::Boolean => "bool",
+            m::Type::String => "string",
+            m::Type::Bytes => "bytes",
+            m::Type::Any => {
+                imports.insert(ANY_IMPORT.to_owned());
+                ANY_TYPE
+            }
+            m::Type::Custom(ref custom) => {
+                return self.convert_custom(pos, package, custom, imports);
+            }
+            m::Type::Array(_) | m::Type::Map(_, _) => {
+                return Err(Error::pos("expected a type which is not a collection".to_owned(),
+                                      pos.clone()));
+            }
+        };
+
+        Ok(ty.to_owned())
+    }
+
+    /// Convert a type which is contained in a collection.
+    ///
+    /// Collections can't be nested in protobuf, so nested collections are wrapped in a message
+    /// named `name`, which is added to `nested`.
+    fn convert_element(&self,
+                       pos: &m::Pos,
+                       package: &m::Package,
+                       ty: &m::Type,
+                       name: &str,
+                       imports: &mut BTreeSet<String>,
+                       nested: &mut Vec<Elements>)
+                       -> Result<String> {
+        if !is_collection(ty) {
+            return self.convert_type(pos, package, ty, imports);
+        }
+
+        let inner = format!("{}Value", name);
+        let ty = self.field_type(pos, package, ty, &inner, imports, nested)?;
+
+        let mut message = Elements::new();
+        message.push(stmt!["message ", name, " {"]);
+        message.push_nested(stmt![ty, " ", WRAPPED, " = 1;"]);
+        message.push("}");
+        nested.push(message);
+
+        Ok(name.to_owned())
+    }
+
+    /// Build the type of a field, including the `repeated` label of arrays.
+    fn field_type(&self,
+                  pos: &m::Pos,
+                  package: &m::Package,
+                  ty: &m::Type,
+                  name: &str,
+                  imports: &mut BTreeSet<String>,
+                  nested: &mut Vec<Elements>)
+                  -> Result<Statement> {
+        let ty = match *ty {
+            m::Type::Array(ref inner) => {
+                let inner = self.convert_element(pos, package, inner, name, imports, nested)?;
+                stmt!["repeated ", inner]
+            }
+            m::Type::Map(ref key, ref value) => {
+                let key = match **key {
+                    m::Type::String | m::Type::Signed(_) | m::Type::Unsigned(_) => {
+                        self.convert_type(pos, package, key, imports)?
+                    }
+                    _ => {
+                        return Err(Error::pos("map keys must be strings or integers".to_owned(),
+                                              pos.clone()));
+                    }
+                };
+
+                let value = self.convert_element(pos, package, value, name, imports, nested)?;
+                stmt!["map<", key, ", ", value, ">"]
+            }
+            _ => stmt![self.convert_type(pos, package, ty, imports)?],
+        };
+
+        Ok(ty)
+    }
+
+    /// Assign numbers to a sequence of items, keeping the explicit ones.
+    ///
+    /// Items without a number are given the lowest free numbers, in order.
+    fn assign_numbers(&self, explicit: Vec<(Option<&m::Token<u32>>, &m::Pos)>) -> Result<Vec<u32>> {
+        let mut used = BTreeSet::new();
+
+        for &(number, _) in &explicit {
+            if let Some(number) = number {
+                if **number > MAX_NUMBER || is_reserved(**number) {
+                    return Err(Error::pos(format!("{}: not a valid protobuf field number",
+                                                  **number),
+                                          number.pos.clone()));
+                }
+
+                if !used.insert(**number) {
+                    return Err(Error::pos(format!("field number {} is already used", **number),
+                                          number.pos.clone()));
+                }
+            }
+        }
+
+        let mut next = 1;
+        let mut numbers = Vec::new();
+
+        for (number, pos) in explicit {
+            if let Some(number) = number {
+                numbers.push(**number);
+                continue;
+            }
+
+            while used.contains(&next) || is_reserved(next) {
+                next += 1;
+            }
+
+            if next > MAX_NUMBER {
+                return Err(Error::pos("ran out of field numbers".to_owned(), pos.clone()));
+            }
+
+            used.insert(next);
+            numbers.push(next);
+        }
+
+        Ok(numbers)
+    }
+
+    /// Build a message with the given fields.
+    fn build_message(&self,
+                     package: &m::Package,
+                     name: &str,
+                     comment: &Vec<String>,
+                     fields: Vec<&m::Token<m::Field>>,
+                     imports: &mut BTreeSet<String>)
+                     -> Result<Elements> {
+        let numbers = self.assign_numbers(fields.iter()
+                .map(|f| (f.number.as_ref(), &f.pos))
+                .collect())?;
+
+        let mut declarations = Elements::new();
+        let mut nested = Vec::new();
+
+        for (field, number) in fields.into_iter().zip(numbers) {
+            let wrapper = format!("{}Value", self.snake_to_upper_camel.convert(&field.name));
+
+            let ty = self.field_type(&field.pos,
+                                     package,
+                                     &field.ty,
+                                     &wrapper,
+                                     imports,
+                                     &mut nested)?;
+
+            let mut declaration = Statement::new();
+
+            if field.is_optional() && !is_collection(&field.ty) {
+                declaration.push("optional ");
+            }
+
+            declaration.push(stmt![ty, " ", &field.name, " = ", number.to_string()]);
+
+            if field.name() != field.name {
+                let json_name = Variable::String(field.name().to_owned());
+                declaration.push(stmt![" [json_name = ", json_name, "]"]);
+            }
+
+            declaration.push(";");
+
+            declarations.push(self.comment(&field.comment));
+            declarations.push(declaration);
+        }
+
+        let mut body = Elements::new();
+
+        if !declarations.is_empty() {
+            body.push(declarations);
+        }
+
+        for message in nested {
+            body.push(message);
+        }
+
+        let mut message = self.comment(comment);
+        message.push(stmt!["message ", name, " {"]);
+        message.push_nested(body.join(ElementSpec::Spacing));
+        message.push("}");
+
+        Ok(message)
+    }
+
+    fn process_type(&self,
+                    package: &m::Package,
+                    body: &m::TypeBody,
+                    imports: &mut BTreeSet<String>)
+                    -> Result<Elements> {
+        let fields = body.fields.iter().collect();
+        self.build_message(package, &body.name, &body.comment, fields, imports)
+    }
+
+    /// Tuples are messages whose fields are numbered by their position.
+    fn process_tuple(&self,
+                     package: &m::Package,
+                     body: &m::TupleBody,
+                     imports: &mut BTreeSet<String>)
+                     -> Result<Elements> {
+        let fields = body.fields.iter().collect();
+        self.build_message(package, &body.name, &body.comment, fields, imports)
+    }
+
+    fn process_enum(&self, body: &m::EnumBody) -> Result<Elements> {
+        let prefix = upper_snake(&body.name);
+
+        let mut values: Vec<_> = body.values.iter().collect();
+        values.sort_by_key(|v| v.ordinal);
+
+        let mut elements = Elements::new();
+
+        // the first value of an enum must be zero, and is its default.
+        if values.first().map(|v| v.ordinal != 0).unwrap_or(true) {
+            elements.push(stmt![&prefix, "_UNSPECIFIED = 0;"]);
+        }
+
+        for value in values {
+            let mut element = self.comment(&value.comment);
+            element.push(stmt![&prefix,
+                               "_",
+                               value.name.to_uppercase(),
+                               " = ",
+                               value.ordinal.to_string(),
+                               ";"]);
+            elements.push(element);
+        }
+
+        let mut enum_ = self.comment(&body.comment);
+        enum_.push(stmt!["enum ", &body.name, " {"]);
+        enum_.push_nested(elements);
+        enum_.push("}");
+
+        Ok(enum_)
+    }
+
+    /// Interfaces are messages with a oneof over their sub types, which are nested messages with
+    /// the fields of both the interface and the sub type.
+    fn process_interface(&self,
+                         package: &m::Package,
+                         body: &m::InterfaceBody,
+                         imports: &mut BTreeSet<String>)
+                         -> Result<Elements> {
+        let sub_types: Vec<_> = body.sub_types.values().collect();
+
+        let numbers = self.assign_numbers(sub_types.iter()
+                .map(|s| (s.number.as_ref(), &s.pos))
+                .collect())?;
+
+        let mut oneof = Elements::new();
+
+        for (sub_type, number) in sub_types.iter().zip(numbers) {
+            let name = self.camel_to_lower_snake.convert(&sub_type.name);
+            oneof.push(stmt![&sub_type.name, " ", name, " = ", number.to_string(), ";"]);
+        }
+
+        let mut elements = Elements::new();
+
+        let mut oneof_decl = Elements::new();
+        oneof_decl.push(stmt!["oneof ", ONEOF, " {"]);
+        oneof_decl.push_nested(oneof);
+        oneof_decl.push("}");
+        elements.push(oneof_decl);
+
+        for sub_type in sub_types {
+            let fields = body.fields.iter().chain(sub_type.fields.iter()).collect();
+
+            elements.push(self.build_message(package,
+                                             &sub_type.name,
+                                             &sub_type.comment,
+                                             fields,
+                                             imports)?);
+        }
+
+        let mut message = self.comment(&body.comment);
+        message.push(stmt!["message ", &body.name, " {"]);
+        message.push_nested(elements.join(ElementSpec::Spacing));
+        message.push("}");
+
+        Ok(message)
+    }
+
+    fn populate_files(&self) -> Result<BTreeMap<&m::Package, File>> {
+        let mut files = BTreeMap::new();
+
+        for (&(ref package, _), decl) in &self.env.types {
+            let file = files.entry(package).or_insert_with(File::new);
+
+            let elements = match decl.inner {
+                m::Decl::Interface(ref body) => {
+                    self.process_interface(package, body, &mut file.imports)?
+                }
+                m::Decl::Type(ref body) => self.process_type(package, body, &mut file.imports)?,
+                m::Decl::Tuple(ref body) => self.process_tuple(package, body, &mut file.imports)?,
+                m::Decl::Enum(ref body) => self.process_enum(body)?,
+            };
+
+            file.elements.push(elements);
+        }
+
+        Ok(files)
+    }
+
+    fn write_files(&self, files: BTreeMap<&m::Package, File>) -> Result<()> {
+        for (package, file) in files {
+            let full_path = self.file_path(package);
+
+            let mut out = Elements::new();
+            out.push(SYNTAX);
+            out.push(stmt!["package ", self.package(package).parts.join("."), ";"]);
+
+            if !file.imports.is_empty() {
+                let mut imports = Elements::new();
+
+                for import in file.imports {
+                    imports.push(stmt!["import ", Variable::String(import), ";"]);
+                }
+
+                out.push(imports);
+            }
+
+            out.push(file.elements.join(ElementSpec::Spacing));
+
+            debug!("+proto: {}", full_path.display());
+
+            let elements: ElementSpec = out.join(ElementSpec::Spacing).into();
+
+            let mut content = String::new();
+            elements.format("", INDENT, &mut content);
+
+            self.options.parent.output.write(&full_path, content.end().as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Backend for Processor {
+    fn process(&self) -> Result<()> {
+        let files = self.populate_files()?;
+        self.write_files(files)
+    }
+
+    fn verify(&self) -> Result<Vec<Error>> {
+        Ok(vec![])
+    }
+}
@@ -1,13 +1,17 @@
 pub mod processor;
+pub mod strict;
 
 use backend::*;
 use backend::models as m;
 use options::Options;
 
 fn setup_module(module: &str) -> Result<Box<processor::Listeners>> {
-    let _module: Box<processor::Listeners> = match module {
+    let module: Box<processor::Listeners> = match module {
+        "strict" => Box::new(strict::Module::new()),
         _ => return Err(format!("No such module: {}", module).into()),
     };
+
+    Ok(module)
 }
 
 pub fn resolve(options: Options, env: Environment) -> Result<processor::Processor> {
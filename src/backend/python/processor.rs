@@ -7,15 +7,13 @@ use naming::{self, FromNaming};
 use options::Options;
 use std::collections::HashMap;
 use std::collections::hash_map::Entry;
-use std::fs::File;
-use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-const TYPE: &str = "type";
 const INIT_PY: &str = "__init__.py";
 const EXT: &str = "py";
 const PYTHON_CONTEXT: &str = "python";
+const STRICT_MODULE: &str = "reproto_strict";
+const STRICT_PY: &str = include_str!("reproto_strict.py");
 
 pub trait Listeners {
     fn configure(&self, _processor: &mut ProcessorOptions) -> Result<()> {
@@ -39,24 +37,45 @@ pub struct Field {
     pub modifier: m::Modifier,
     pub ty: m::Type,
     pub name: String,
+    /// Key of the field in JSON, which differs from its name if it is renamed.
+    pub json_name: String,
     pub ident: String,
+    pub comment: Vec<String>,
 }
 
 impl Field {
-    pub fn new(modifier: m::Modifier, ty: m::Type, name: String, ident: String) -> Field {
+    pub fn new(modifier: m::Modifier,
+               ty: m::Type,
+               name: String,
+               json_name: String,
+               ident: String,
+               comment: Vec<String>)
+               -> Field {
         Field {
             modifier: modifier,
             ty: ty,
             name: name,
+            json_name: json_name,
             ident: ident,
+            comment: comment,
         }
     }
 }
 
+/// How the data that a class is decoded from is structured.
+enum DataShape {
+    /// An object, where fields are looked up by key.
+    Object,
+    /// An array with the given number of elements, where fields are looked up by index.
+    Array(usize),
+}
+
 pub struct ProcessorOptions {
     parent: Options,
     pub build_getters: bool,
     pub build_constructor: bool,
+    /// Deeply verify the types of all values when decoding and encoding.
+    pub strict: bool,
 }
 
 impl ProcessorOptions {
@@ -65,6 +84,7 @@ impl ProcessorOptions {
             parent: options,
             build_getters: true,
             build_constructor: true,
+            strict: false,
         }
     }
 }
@@ -80,7 +100,8 @@ pub struct Processor {
     dict: BuiltInName,
     enum_enum: ImportedName,
     enum_auto: ImportedName,
-    type_var: Variable,
+    b64encode: ImportedName,
+    b64decode: ImportedName,
 }
 
 impl Processor {
@@ -100,7 +121,8 @@ impl Processor {
             dict: Name::built_in("dict"),
             enum_enum: Name::imported("enum", "Enum"),
             enum_auto: Name::imported("enum", "auto"),
-            type_var: Variable::String(TYPE.to_owned()),
+            b64encode: Name::imported("base64", "b64encode"),
+            b64decode: Name::imported("base64", "b64decode"),
         }
     }
 
@@ -118,20 +140,75 @@ impl Processor {
     }
 
     /// Build a function that raises an exception if the given value `stmt` is None.
-    fn raise_if_none(&self, stmt: &Statement, field: &Field) -> Elements {
+    ///
+    /// `segment` is the key or index that the value is encoded at.
+    fn raise_if_none(&self, stmt: &Statement, field: &Field, segment: Variable) -> Elements {
         let mut raise_if_none = Elements::new();
-        let required_error = Variable::String(format!("{}: is a required field", field.name));
 
         raise_if_none.push(stmt!["if ", &stmt, " is None:"]);
-        raise_if_none.push_nested(stmt!["raise Exception(", required_error, ")"]);
+
+        if self.options.strict {
+            let required_error = Variable::String("missing required field".to_owned());
+            raise_if_none.push_nested(stmt!["raise ",
+                                            self.strict("StrictError"),
+                                            "([",
+                                            segment,
+                                            "], ",
+                                            required_error,
+                                            ")"]);
+        } else {
+            let required_error = Variable::String(format!("{}: is a required field", field.name));
+            raise_if_none.push_nested(stmt!["raise Exception(", required_error, ")"]);
+        }
 
         raise_if_none
     }
 
+    /// Name of a helper in the runtime module used by strict decoding and encoding.
+    fn strict(&self, name: &str) -> ImportedName {
+        Name::imported(STRICT_MODULE, name)
+    }
+
+    /// Apply `check`, which operates on `v`, to `value`.
+    ///
+    /// Errors raised by `check` have `segment` prepended to their path.
+    fn strict_at(&self, segment: Variable, check: Statement, value: Statement) -> Statement {
+        stmt![self.strict("at"), "(", segment, ", lambda v: ", check, ", ", value, ")"]
+    }
+
+    /// Build the verification of a primitive value, or `None` if the type is not primitive.
+    fn strict_primitive(&self, ty: &m::Type, value: &Statement) -> Option<Statement> {
+        let check = match *ty {
+            m::Type::Signed(Some(size)) if size > 0 => {
+                let minimum = format!("-(2 ** {})", size - 1);
+                let maximum = format!("2 ** {} - 1", size - 1);
+                stmt![self.strict("integer"), "(", value, ", ", minimum, ", ", maximum, ")"]
+            }
+            m::Type::Signed(_) => stmt![self.strict("integer"), "(", value, ")"],
+            m::Type::Unsigned(Some(size)) if size > 0 => {
+                let maximum = format!("2 ** {} - 1", size);
+                stmt![self.strict("integer"), "(", value, ", 0, ", maximum, ")"]
+            }
+            m::Type::Unsigned(_) => stmt![self.strict("integer"), "(", value, ", 0)"],
+            m::Type::Float | m::Type::Double => stmt![self.strict("number"), "(", value, ")"],
+            m::Type::String => stmt![self.strict("string"), "(", value, ")"],
+            m::Type::Boolean => stmt![self.strict("boolean"), "(", value, ")"],
+            m::Type::Bytes => stmt![self.strict("base64_bytes"), "(", value, ")"],
+            m::Type::Any => value.clone(),
+            _ => return None,
+        };
+
+        Some(check)
+    }
+
+    /// Build the encode method.
+    ///
+    /// If `wrap_key` is set, the encoded data is wrapped in an object with it as its only key.
     fn encode_method<E>(&self,
                         package: &m::Package,
                         fields: &Vec<m::Token<Field>>,
                         builder: &BuiltInName,
+                        wrap_key: Option<Variable>,
                         extra: E)
                         -> Result<MethodSpec>
         where E: FnOnce(&mut Elements) -> ()
@@ -146,9 +223,15 @@ impl Processor {
         extra(&mut encode_body);
 
         for field in fields {
-            let var_string = Variable::String(field.ident.to_owned());
+            let var_string = Variable::String(field.json_name.to_owned());
             let field_stmt = stmt!["self.", &field.ident];
-            let value_stmt = self.encode(package, &field.ty, &field_stmt)?;
+
+            let value_stmt = if self.options.strict {
+                let check = self.encode(package, &field.ty, stmt!["v"])?;
+                self.strict_at(var_string.clone(), check, field_stmt.clone())
+            } else {
+                self.encode(package, &field.ty, &field_stmt)?
+            };
 
             match field.modifier {
                 m::Modifier::Optional => {
@@ -163,7 +246,7 @@ impl Processor {
                     encode_body.push(check_if_none);
                 }
                 _ => {
-                    encode_body.push(self.raise_if_none(&field_stmt, field));
+                    encode_body.push(self.raise_if_none(&field_stmt, field, var_string.clone()));
 
                     let stmt = stmt!["data[", var_string, "] = ", value_stmt];
 
@@ -172,7 +255,11 @@ impl Processor {
             }
         }
 
-        encode_body.push(stmt!["return data"]);
+        if let Some(wrap_key) = wrap_key {
+            encode_body.push(stmt!["return {", wrap_key, ": data}"]);
+        } else {
+            encode_body.push(stmt!["return data"]);
+        }
 
         encode.push(encode_body.join(ElementSpec::Spacing));
         Ok(encode)
@@ -189,10 +276,17 @@ impl Processor {
 
         let mut encode_body = Elements::new();
 
-        for field in fields {
+        for (i, field) in fields.iter().enumerate() {
+            let index = Variable::Literal(i.to_string());
             let stmt = stmt!["self.", &field.ident];
-            encode_body.push(self.raise_if_none(&stmt, field));
-            values.push(self.encode(package, &field.ty, stmt)?);
+            encode_body.push(self.raise_if_none(&stmt, field, index.clone()));
+
+            if self.options.strict {
+                let check = self.encode(package, &field.ty, stmt!["v"])?;
+                values.push(self.strict_at(index, check, stmt));
+            } else {
+                values.push(self.encode(package, &field.ty, stmt)?);
+            }
         }
 
         encode_body.push(stmt!["return (", values.join(", "), ")"]);
@@ -200,18 +294,18 @@ impl Processor {
         Ok(encode)
     }
 
-    fn encode_enum_method(&self, field: &Field) -> Result<MethodSpec> {
+    fn encode_enum_method(&self, ident: &str) -> Result<MethodSpec> {
         let mut encode = MethodSpec::new("encode");
         encode.push_argument(stmt!["self"]);
 
         let mut encode_body = Elements::new();
 
-        encode_body.push(stmt!["return self.", &field.ident]);
+        encode_body.push(stmt!["return self.", ident]);
         encode.push(encode_body.join(ElementSpec::Spacing));
         Ok(encode)
     }
 
-    fn decode_enum_method(&self, field: &Field) -> Result<MethodSpec> {
+    fn decode_enum_method(&self, ident: &str) -> Result<MethodSpec> {
         let mut decode = MethodSpec::new("decode");
 
         let cls = stmt!["cls"];
@@ -226,7 +320,7 @@ impl Processor {
         let value = stmt!["value"];
 
         let mut check = Elements::new();
-        check.push(stmt!["if ", &value, ".", &field.ident, " == ", data, ":"]);
+        check.push(stmt!["if ", &value, ".", ident, " == ", data, ":"]);
         check.push_nested(stmt!["return ", &value]);
 
         let mut member_loop = Elements::new();
@@ -235,7 +329,12 @@ impl Processor {
         member_loop.push_nested(check);
 
         let mismatch = Variable::String("data does not match enum".to_owned());
-        let raise = stmt!["raise Exception(", mismatch, ")"];
+
+        let raise = if self.options.strict {
+            stmt!["raise ", self.strict("StrictError"), "([], ", mismatch, ")"]
+        } else {
+            stmt!["raise Exception(", mismatch, ")"]
+        };
 
         decode_body.push(member_loop);
         decode_body.push(raise);
@@ -268,10 +367,153 @@ impl Processor {
         check.into()
     }
 
+    /// Build the check that `data` is equal to the given match value.
+    fn match_value_check(&self, data: &Statement, value: &m::Token<m::Value>) -> Result<Statement> {
+        let check = match value.inner {
+            m::Value::String(ref string) => {
+                stmt![data, " == ", Variable::String(string.to_owned())]
+            }
+            m::Value::Number(ref number) => {
+                stmt!["not isinstance(", data, ", bool) and ", data, " == ", number.to_string()]
+            }
+            m::Value::Boolean(ref boolean) => {
+                stmt![data, " is ", if *boolean { "True" } else { "False" }]
+            }
+            _ => {
+                return Err(Error::pos("unsupported match value".to_owned(), value.pos.clone()));
+            }
+        };
+
+        Ok(check)
+    }
+
+    /// Build the check that `data` is of the given match kind.
+    fn match_type_check(&self, data: &Statement, kind: &m::MatchKind) -> Statement {
+        match *kind {
+            m::MatchKind::Any => stmt!["True"],
+            m::MatchKind::Object => stmt!["isinstance(", data, ", ", &self.dict, ")"],
+            m::MatchKind::Array => stmt!["isinstance(", data, ", list)"],
+            m::MatchKind::String => stmt!["isinstance(", data, ", str)"],
+            m::MatchKind::Boolean => stmt!["isinstance(", data, ", bool)"],
+            m::MatchKind::Number => {
+                stmt!["isinstance(", data, ", (int, float)) and not isinstance(", data, ", bool)"]
+            }
+        }
+    }
+
+    /// Convert an argument to an instance built by a match member.
+    fn match_argument(&self,
+                      value: &m::Token<m::Value>,
+                      variable: Option<&m::MatchVariable>)
+                      -> Result<Statement> {
+        let argument = match value.inner {
+            m::Value::Identifier(ref identifier) => {
+                if !variable.map(|v| v.name == *identifier).unwrap_or(false) {
+                    return Err(Error::pos(format!("no such variable: {}", identifier),
+                                          value.pos.clone()));
+                }
+
+                stmt![identifier]
+            }
+            m::Value::String(ref string) => stmt![Variable::String(string.to_owned())],
+            m::Value::Number(ref number) => stmt![number.to_string()],
+            m::Value::Boolean(ref boolean) => stmt![if *boolean { "True" } else { "False" }],
+            _ => {
+                return Err(Error::pos("unsupported argument".to_owned(), value.pos.clone()));
+            }
+        };
+
+        Ok(argument)
+    }
+
+    /// Build the value that a match member evaluates to.
+    fn match_instance(&self,
+                      package: &m::Package,
+                      value: &m::Token<m::Value>,
+                      variable: Option<&m::MatchVariable>)
+                      -> Result<Statement> {
+        let instance = match value.inner {
+            m::Value::Instance(ref instance) => instance,
+            _ => return self.match_argument(value, variable),
+        };
+
+        let fields = self.env.lookup_instance_fields(&instance.pos, package, &instance.ty)?;
+
+        for init in &instance.arguments {
+            if !fields.iter().any(|f| f.name == *init.name) {
+                return Err(Error::pos(format!("no such field: {}", *init.name),
+                                      init.pos.clone()));
+            }
+        }
+
+        let mut arguments = Statement::new();
+
+        for field in fields {
+            if let Some(init) = instance.arguments.iter().find(|a| *a.name == field.name) {
+                arguments.push(self.match_argument(&init.value, variable)?);
+                continue;
+            }
+
+            if !field.is_optional() {
+                return Err(Error::pos(format!("missing required field: {}", field.name),
+                                      instance.pos.clone()));
+            }
+
+            arguments.push("None");
+        }
+
+        let name = self.instance_name(&instance.pos, package, &instance.ty)?;
+        Ok(stmt![name, "(", arguments.join(", "), ")"])
+    }
+
+    /// Build the decoding of all members of a match declaration.
+    ///
+    /// Value matches are tried first, followed by type matches.
+    fn decode_match(&self,
+                    package: &m::Package,
+                    name: &str,
+                    match_decl: &m::MatchDecl,
+                    data: &Statement)
+                    -> Result<Elements> {
+        let mut elements = Elements::new();
+
+        for &(ref value, ref member) in &match_decl.by_value {
+            let mut check = Elements::new();
+            check.push(stmt!["if ", self.match_value_check(data, value)?, ":"]);
+            check.push_nested(stmt!["return ", self.match_instance(package, &member.value, None)?]);
+            elements.push(check);
+        }
+
+        for &(ref kind, ref member) in &match_decl.by_type {
+            if member.is_self_object(name) {
+                continue;
+            }
+
+            if let m::MatchCondition::Type(ref variable) = member.condition.inner {
+                let pos = &member.condition.pos;
+                let decoded = self.decode(pos, package, &variable.ty, data)?;
+                let instance = self.match_instance(package, &member.value, Some(variable))?;
+
+                let mut body = Elements::new();
+                body.push(stmt![&variable.name, " = ", decoded]);
+                body.push(stmt!["return ", instance]);
+
+                let mut check = Elements::new();
+                check.push(stmt!["if ", self.match_type_check(data, kind), ":"]);
+                check.push_nested(body);
+                elements.push(check);
+            }
+        }
+
+        Ok(elements.join(ElementSpec::Spacing))
+    }
+
     fn decode_method<F>(&self,
                         package: &m::Package,
                         fields: &Vec<m::Token<Field>>,
                         class: &ClassSpec,
+                        match_decl: Option<&m::MatchDecl>,
+                        shape: DataShape,
                         variable_fn: F)
                         -> Result<MethodSpec>
         where F: Fn(usize, &Field) -> Variable
@@ -282,6 +524,25 @@ impl Processor {
 
         let mut decode_body = Elements::new();
 
+        if let Some(match_decl) = match_decl {
+            let decode_match = self.decode_match(package, &class.name, match_decl, &stmt!["data"])?;
+
+            if !decode_match.is_empty() {
+                decode_body.push(decode_match);
+            }
+        }
+
+        if self.options.strict {
+            let check = match shape {
+                DataShape::Object => stmt![self.strict("obj"), "(data)"],
+                DataShape::Array(length) => {
+                    stmt![self.strict("sequence"), "(data, ", length.to_string(), ")"]
+                }
+            };
+
+            decode_body.push(check);
+        }
+
         let mut arguments = Statement::new();
 
         for (i, field) in fields.iter().enumerate() {
@@ -290,12 +551,31 @@ impl Processor {
 
             let stmt = match field.modifier {
                 m::Modifier::Optional => {
-                    let var_stmt = self.decode(&field.pos, package, &field.ty, &var_name)?;
+                    let var_stmt = if self.options.strict {
+                        let check = self.decode(&field.pos, package, &field.ty, stmt!["v"])?;
+                        self.strict_at(var.clone(), check, stmt![&var_name])
+                    } else {
+                        self.decode(&field.pos, package, &field.ty, &var_name)?
+                    };
+
                     self.optional_check(&var_name, &var, &var_stmt)
                 }
                 _ => {
-                    let var_stmt = stmt!["data[", &var, "]"];
-                    let var_stmt = self.decode(&field.pos, package, &field.ty, var_stmt)?;
+                    let var_stmt = if self.options.strict {
+                        let value = match shape {
+                            DataShape::Object => {
+                                stmt![self.strict("required"), "(data, ", &var, ")"]
+                            }
+                            DataShape::Array(_) => stmt!["data[", &var, "]"],
+                        };
+
+                        let check = self.decode(&field.pos, package, &field.ty, stmt!["v"])?;
+                        self.strict_at(var.clone(), check, value)
+                    } else {
+                        let var_stmt = stmt!["data[", &var, "]"];
+                        self.decode(&field.pos, package, &field.ty, var_stmt)?
+                    };
+
                     stmt![&var_name, " = ", &var_stmt].into()
                 }
             };
@@ -321,6 +601,7 @@ impl Processor {
             m::Type::Any => true,
             m::Type::Boolean => true,
             m::Type::Array(ref inner) => self.is_native(inner),
+            m::Type::Map(_, ref value) => self.is_native(value),
             _ => false,
         }
     }
@@ -333,6 +614,22 @@ impl Processor {
         }
     }
 
+    /// Name of the class instantiated through the given custom type.
+    ///
+    /// Sub types are declared next to their interface, so only the last part is used.
+    fn instance_name(&self,
+                     pos: &m::Pos,
+                     package: &m::Package,
+                     custom: &m::Custom)
+                     -> Result<Name> {
+        let custom = m::Custom {
+            prefix: custom.prefix.clone(),
+            parts: custom.parts.iter().last().into_iter().cloned().collect(),
+        };
+
+        self.used_name(pos, package, &custom)
+    }
+
     fn used_name(&self, pos: &m::Pos, package: &m::Package, custom: &m::Custom) -> Result<Name> {
         if let Some(ref used) = custom.prefix {
             let package = self.env.lookup_used(pos, package, used)?;
@@ -350,8 +647,16 @@ impl Processor {
     {
         let value_stmt = value_stmt.into();
 
-        // TODO: do not skip conversion if strict type checking is enabled
-        if self.is_native(ty) {
+        if self.options.strict {
+            // verified as bytes to encode, instead of as their encoded form.
+            if let m::Type::Bytes = *ty {
+                return Ok(stmt![self.strict("bytes_base64"), "(", value_stmt, ")"]);
+            }
+
+            if let Some(check) = self.strict_primitive(ty, &value_stmt) {
+                return Ok(check);
+            }
+        } else if self.is_native(ty) {
             return Ok(value_stmt);
         }
 
@@ -362,13 +667,27 @@ impl Processor {
             m::Type::String => value_stmt,
             m::Type::Any => value_stmt,
             m::Type::Boolean => value_stmt,
+            m::Type::Bytes => stmt![&self.b64encode, "(", value_stmt, ").decode(\"ascii\")"],
             m::Type::Custom(ref _custom) => stmt![value_stmt, ".encode()"],
             m::Type::Array(ref inner) => {
                 let v = stmt!["v"];
                 let inner = self.encode(package, inner, v)?;
-                stmt!["map(lambda v: ", inner, ", ", value_stmt, ")"]
+
+                if self.options.strict {
+                    stmt![self.strict("array"), "(", value_stmt, ", lambda v: ", inner, ")"]
+                } else {
+                    stmt!["[", inner, " for v in ", value_stmt, "]"]
+                }
+            }
+            m::Type::Map(_, ref value) => {
+                let inner = self.encode(package, value, stmt!["v"])?;
+
+                if self.options.strict {
+                    stmt![self.strict("mapping"), "(", value_stmt, ", lambda v: ", inner, ")"]
+                } else {
+                    stmt!["{k: ", inner, " for k, v in ", value_stmt, ".items()}"]
+                }
             }
-            _ => value_stmt,
         };
 
         Ok(value_stmt)
@@ -384,8 +703,11 @@ impl Processor {
     {
         let value_stmt = value_stmt.into();
 
-        // TODO: do not skip conversion if strict type checking is enabled
-        if self.is_native(ty) {
+        if self.options.strict {
+            if let Some(check) = self.strict_primitive(ty, &value_stmt) {
+                return Ok(check);
+            }
+        } else if self.is_native(ty) {
             return Ok(value_stmt);
         }
 
@@ -396,15 +718,29 @@ impl Processor {
             m::Type::String => value_stmt,
             m::Type::Any => value_stmt,
             m::Type::Boolean => value_stmt,
+            m::Type::Bytes => stmt![&self.b64decode, "(", value_stmt, ")"],
             m::Type::Custom(ref custom) => {
                 let name = self.used_name(pos, package, custom)?;
                 stmt![name, ".decode(", value_stmt, ")"]
             }
             m::Type::Array(ref inner) => {
                 let inner = self.decode(pos, package, inner, stmt!["v"])?;
-                stmt!["map(lambda v: ", inner, ", ", value_stmt, ")"]
+
+                if self.options.strict {
+                    stmt![self.strict("array"), "(", value_stmt, ", lambda v: ", inner, ")"]
+                } else {
+                    stmt!["[", inner, " for v in ", value_stmt, "]"]
+                }
+            }
+            m::Type::Map(_, ref value) => {
+                let inner = self.decode(pos, package, value, stmt!["v"])?;
+
+                if self.options.strict {
+                    stmt![self.strict("mapping"), "(", value_stmt, ", lambda v: ", inner, ")"]
+                } else {
+                    stmt!["{k: ", inner, " for k, v in ", value_stmt, ".items()}"]
+                }
             }
-            _ => value_stmt,
         };
 
         Ok(value_stmt)
@@ -421,6 +757,79 @@ impl Processor {
             .unwrap_or_else(|| package.clone())
     }
 
+    /// Build a docstring out of the given lines.
+    ///
+    /// Returns no elements if there is nothing to document.
+    fn docstring(&self, lines: Vec<String>) -> Elements {
+        let mut lines: Vec<String> = lines.into_iter()
+            .map(|l| l.replace("\\", "\\\\").replace("\"\"\"", "\\\"\\\"\\\""))
+            .collect();
+
+        let mut docstring = Elements::new();
+
+        if lines.is_empty() {
+            return docstring;
+        }
+
+        if lines.len() == 1 {
+            docstring.push(format!("\"\"\"{}\"\"\"", lines[0]).as_str());
+            return docstring;
+        }
+
+        lines[0] = format!("\"\"\"{}", lines[0]);
+        lines.push("\"\"\"".to_owned());
+
+        for line in lines {
+            // avoid trailing whitespace on empty lines
+            if line.is_empty() {
+                docstring.push(ElementSpec::Spacing);
+            } else {
+                docstring.push(line.as_str());
+            }
+        }
+
+        docstring
+    }
+
+    /// Add a docstring to the class, which also documents its fields as instance variables.
+    ///
+    /// This must be called before anything else is pushed to the class.
+    fn push_class_docstring(&self,
+                            class: &mut ClassSpec,
+                            comment: &Vec<String>,
+                            fields: &Vec<m::Token<Field>>) {
+        let mut lines = comment.clone();
+        let mut ivars = Vec::new();
+
+        for field in fields {
+            let mut it = field.comment.iter();
+
+            if let Some(first) = it.next() {
+                ivars.push(format!(":ivar {}: {}", field.ident, first));
+
+                for line in it {
+                    if line.is_empty() {
+                        ivars.push(String::new());
+                    } else {
+                        ivars.push(format!("    {}", line));
+                    }
+                }
+            }
+        }
+
+        if !lines.is_empty() && !ivars.is_empty() {
+            lines.push(String::new());
+        }
+
+        lines.extend(ivars);
+
+        let docstring = self.docstring(lines);
+
+        if !docstring.is_empty() {
+            class.push(docstring);
+        }
+    }
+
     fn build_constructor(&self, fields: &Vec<m::Token<Field>>) -> MethodSpec {
         let mut constructor = MethodSpec::new("__init__");
         constructor.push_argument(stmt!["self"]);
@@ -439,11 +848,15 @@ impl Processor {
 
         for field in &body.fields {
             let ident = self.ident(&field.name);
+            let json_name = field.name().to_owned();
 
             fields.push(field.clone()
-                .map_inner(|f| Field::new(m::Modifier::Required, f.ty, f.name, ident)));
+                .map_inner(|f| {
+                    Field::new(m::Modifier::Required, f.ty, f.name, json_name, ident, f.comment)
+                }));
         }
 
+        self.push_class_docstring(&mut class, &body.comment, &fields);
         class.push(self.build_constructor(&fields));
 
         // TODO: make configurable
@@ -457,7 +870,7 @@ impl Processor {
             class.push(code.inner.lines);
         }
 
-        self.tuple_added(package, &fields, &mut class)?;
+        self.tuple_added(package, &fields, &body.match_decl, &mut class)?;
         Ok(class)
     }
 
@@ -494,6 +907,7 @@ impl Processor {
 
         for field in &body.fields {
             let ident = self.ident(&field.name);
+            let json_name = field.name().to_owned();
 
             // reserved fields
             let ident = match ident.as_str() {
@@ -503,10 +917,13 @@ impl Processor {
             };
 
             fields.push(field.clone()
-                .map_inner(|f| Field::new(m::Modifier::Required, f.ty, f.name, ident)));
+                .map_inner(|f| {
+                    Field::new(m::Modifier::Required, f.ty, f.name, json_name, ident, f.comment)
+                }));
         }
 
         class.extends(&self.enum_enum);
+        self.push_class_docstring(&mut class, &body.comment, &fields);
 
         let mut values = Elements::new();
 
@@ -524,11 +941,16 @@ impl Processor {
             };
 
             values.push(stmt![&value.name, " = ", arguments]);
+            values.push(self.docstring(value.comment.clone()));
         }
 
         class.push(values);
 
-        class.push(self.build_constructor(&fields));
+        // values without arguments are initialized with `enum.auto()`, which would not match a
+        // constructor.
+        if !fields.is_empty() {
+            class.push(self.build_constructor(&fields));
+        }
 
         // TODO: make configurable
         if false {
@@ -567,10 +989,15 @@ impl Processor {
 
         for field in &body.fields {
             let ident = self.ident(&field.name);
+            let json_name = field.name().to_owned();
 
-            fields.push(field.clone().map_inner(|f| Field::new(f.modifier, f.ty, f.name, ident)));
+            fields.push(field.clone().map_inner(|f| {
+                Field::new(f.modifier, f.ty, f.name, json_name, ident, f.comment)
+            }));
         }
 
+        self.push_class_docstring(&mut class, &body.comment, &fields);
+
         let constructor = self.build_constructor(&fields);
         class.push(&constructor);
 
@@ -584,11 +1011,13 @@ impl Processor {
         let decode = self.decode_method(package,
                            &fields,
                            &class,
-                           |_, field| Variable::String(field.ident.to_owned()))?;
+                           Some(&body.match_decl),
+                           DataShape::Object,
+                           |_, field| Variable::String(field.json_name.to_owned()))?;
 
         class.push(decode);
 
-        let encode = self.encode_method(package, &fields, &self.dict, |_| {})?;
+        let encode = self.encode_method(package, &fields, &self.dict, None, |_| {})?;
 
         class.push(encode);
 
@@ -607,18 +1036,20 @@ impl Processor {
 
         let mut interface_spec = ClassSpec::new(&body.name);
 
-        interface_spec.push(self.interface_decode_method(body)?);
-
         let mut interface_fields = Vec::new();
 
         for field in &body.fields {
             let ident = self.ident(&field.name);
+            let json_name = field.name().to_owned();
 
             interface_fields.push(field.clone().map_inner(|f| {
-                    Field::new(f.modifier, f.ty, f.name, ident)
+                    Field::new(f.modifier, f.ty, f.name, json_name, ident, f.comment)
                 }));
         }
 
+        self.push_class_docstring(&mut interface_spec, &body.comment, &interface_fields);
+        interface_spec.push(self.interface_decode_method(package, body)?);
+
         for code in body.codes.for_context(PYTHON_CONTEXT) {
             interface_spec.push(code.inner.lines);
         }
@@ -629,18 +1060,20 @@ impl Processor {
             let mut class = ClassSpec::new(&sub_type.name);
             class.extends(Name::local(&body.name));
 
-            class.push(stmt!["TYPE = ", Variable::String(sub_type.name())]);
-
             let mut fields = interface_fields.clone();
 
             for field in &sub_type.fields {
                 let ident = self.ident(&field.name);
+                let json_name = field.name().to_owned();
 
                 fields.push(field.clone().map_inner(|f| {
-                    Field::new(f.modifier, f.ty, f.name, ident)
+                    Field::new(f.modifier, f.ty, f.name, json_name, ident, f.comment)
                 }));
             }
 
+            self.push_class_docstring(&mut class, &sub_type.comment, &fields);
+            class.push(stmt!["TYPE = ", Variable::String(sub_type.name())]);
+
             let constructor = self.build_constructor(&fields);
             class.push(&constructor);
 
@@ -654,16 +1087,27 @@ impl Processor {
             let decode = self.decode_method(package,
                                &fields,
                                &class,
-                               |_, field| Variable::String(field.ident.to_owned()))?;
+                               None,
+                               DataShape::Object,
+                               |_, field| Variable::String(field.json_name.to_owned()))?;
 
             class.push(decode);
 
-            let type_stmt =
-                stmt!["data[", &self.type_var, "] = ", Variable::String(sub_type.name())];
+            let type_name = Variable::String(sub_type.name());
 
-            let encode = self.encode_method(package, &fields, &self.dict, move |elements| {
-                    elements.push(type_stmt);
-                })?;
+            let encode = match body.type_info {
+                m::TypeInfo::TypeField(ref type_field) => {
+                    let type_field = Variable::String(type_field.to_owned());
+                    let type_stmt = stmt!["data[", type_field, "] = ", type_name];
+
+                    self.encode_method(package, &fields, &self.dict, None, move |elements| {
+                            elements.push(type_stmt);
+                        })?
+                }
+                m::TypeInfo::ObjectKeys => {
+                    self.encode_method(package, &fields, &self.dict, Some(type_name), |_| {})?
+                }
+            };
 
             class.push(encode);
 
@@ -712,10 +1156,11 @@ impl Processor {
         Ok(files)
     }
 
-    fn setup_module_path(&self, root_dir: &PathBuf, package: &m::Package) -> Result<PathBuf> {
+    fn setup_module_path(&self, package: &m::Package) -> Result<PathBuf> {
         let package = self.package(package);
+        let output = &self.options.parent.output;
 
-        let mut full_path = root_dir.to_owned();
+        let mut full_path = PathBuf::new();
         let mut iter = package.parts.iter().peekable();
 
         while let Some(part) = iter.next() {
@@ -727,15 +1172,8 @@ impl Processor {
 
             let init_path = full_path.join(INIT_PY);
 
-            if !init_path.is_file() {
-                if !full_path.is_dir() {
-                    debug!("+dir: {}", full_path.display());
-                    fs::create_dir_all(&full_path)?;
-                }
-
-                debug!("+init: {}", init_path.display());
-                File::create(init_path)?;
-            }
+            debug!("+init: {}", init_path.display());
+            output.write_missing(&init_path, &[])?;
         }
 
         // path to final file
@@ -744,19 +1182,19 @@ impl Processor {
     }
 
     fn write_files(&self, files: HashMap<&m::Package, FileSpec>) -> Result<()> {
-        let root_dir = &self.options.parent.out_path;
+        let output = &self.options.parent.output;
 
         for (package, file_spec) in files {
-            let full_path = self.setup_module_path(root_dir, package)?;
+            let full_path = self.setup_module_path(package)?;
 
             debug!("+module: {}", full_path.display());
+            output.write(&full_path, file_spec.format().as_bytes())?;
+        }
 
-            let out = file_spec.format();
-            let mut f = File::create(full_path)?;
-            let bytes = out.into_bytes();
-
-            f.write_all(&bytes)?;
-            f.flush()?;
+        if self.options.strict {
+            let full_path = Path::new(STRICT_MODULE).with_extension(EXT);
+            debug!("+module: {}", full_path.display());
+            output.write(&full_path, STRICT_PY.as_bytes())?;
         }
 
         Ok(())
@@ -765,12 +1203,15 @@ impl Processor {
     fn tuple_added(&self,
                    package: &m::Package,
                    fields: &Vec<m::Token<Field>>,
+                   match_decl: &m::MatchDecl,
                    class: &mut ClassSpec)
                    -> Result<()> {
 
         let decode = self.decode_method(package,
                            fields,
                            class,
+                           Some(match_decl),
+                           DataShape::Array(fields.len()),
                            |i, _| Variable::Literal(i.to_string()))?;
 
         let encode = self.encode_tuple_method(package, fields)?;
@@ -787,26 +1228,79 @@ impl Processor {
                   -> Result<()> {
         if let Some(ref s) = *serialized_as {
             if let Some((_, ref field)) = self.find_field(fields, &s.inner) {
-                class.push(self.encode_enum_method(field)?);
-                class.push(self.decode_enum_method(field)?);
+                class.push(self.encode_enum_method(&field.ident)?);
+                class.push(self.decode_enum_method(&field.ident)?);
             } else {
                 return Err(Error::pos(format!("no field named: {}", s.inner), s.pos.clone()));
             }
+        } else {
+            // enums are serialized by name unless told otherwise.
+            class.push(self.encode_enum_method("name")?);
+            class.push(self.decode_enum_method("name")?);
         }
 
         Ok(())
     }
 
-    fn interface_decode_method(&self, interface: &m::InterfaceBody) -> Result<MethodSpec> {
+    fn interface_decode_method(&self,
+                               package: &m::Package,
+                               interface: &m::InterfaceBody)
+                               -> Result<MethodSpec> {
         let mut decode = MethodSpec::new("decode");
         decode.push_decorator(&self.staticmethod);
         decode.push_argument(stmt!["data"]);
 
         let mut decode_body = Elements::new();
 
+        let decode_match = self.decode_match(package,
+                          &interface.name,
+                          &interface.match_decl,
+                          &stmt!["data"])?;
+
+        if !decode_match.is_empty() {
+            decode_body.push(decode_match);
+        }
+
         let type_field = Variable::Literal("f_type".to_owned());
 
-        decode_body.push(stmt![&type_field, " = data[", &self.type_var, "]"]);
+        if self.options.strict {
+            decode_body.push(stmt![self.strict("obj"), "(data)"]);
+        }
+
+        // the data that the sub type is decoded from.
+        let sub_type_data = match interface.type_info {
+            m::TypeInfo::TypeField(ref name) => {
+                let name = Variable::String(name.to_owned());
+
+                if self.options.strict {
+                    let check = stmt![self.strict("string"), "(v)"];
+                    let value = stmt![self.strict("required"), "(data, ", &name, ")"];
+                    let value = self.strict_at(name, check, value);
+                    decode_body.push(stmt![&type_field, " = ", value]);
+                } else {
+                    decode_body.push(stmt![&type_field, " = data[", name, "]"]);
+                }
+
+                stmt!["data"]
+            }
+            m::TypeInfo::ObjectKeys => {
+                let sub_type_data = stmt!["f_data"];
+                let one_key = Variable::String("expected exactly one key".to_owned());
+
+                let raise = if self.options.strict {
+                    stmt!["raise ", self.strict("StrictError"), "([], ", one_key, ")"]
+                } else {
+                    stmt!["raise Exception(", one_key, ")"]
+                };
+
+                let mut keys = Elements::new();
+                keys.push(stmt!["if len(data) != 1:"]);
+                keys.push_nested(raise);
+                keys.push(stmt![&type_field, ", ", &sub_type_data, " = next(iter(data.items()))"]);
+                decode_body.push(keys);
+                sub_type_data
+            }
+        };
 
         for (_, ref sub_type) in &interface.sub_types {
             for name in &sub_type.names {
@@ -819,17 +1313,45 @@ impl Processor {
                                  " == ",
                                  Variable::String(name.inner.to_owned()),
                                  ":"]);
-                check.push_nested(stmt!["return ", type_name, ".decode(data)"]);
+                let decoded = match interface.type_info {
+                    m::TypeInfo::ObjectKeys if self.options.strict => {
+                        self.strict_at(type_field.clone(),
+                                       stmt![type_name, ".decode(v)"],
+                                       sub_type_data.clone())
+                    }
+                    _ => stmt![type_name, ".decode(", &sub_type_data, ")"],
+                };
+
+                check.push_nested(stmt!["return ", decoded]);
 
                 decode_body.push(check);
             }
         }
 
-        decode_body.push(stmt!["raise Exception(",
-                               Variable::String("bad type".to_owned()),
-                               " + ",
-                               &type_field,
-                               ")"]);
+        if self.options.strict {
+            let path = match interface.type_info {
+                m::TypeInfo::TypeField(ref name) => {
+                    stmt!["[", Variable::String(name.to_owned()), "]"]
+                }
+                m::TypeInfo::ObjectKeys => stmt!["[", &type_field, "]"],
+            };
+
+            decode_body.push(stmt!["raise ",
+                                   self.strict("StrictError"),
+                                   "(",
+                                   path,
+                                   ", ",
+                                   Variable::String("bad type: ".to_owned()),
+                                   " + ",
+                                   &type_field,
+                                   ")"]);
+        } else {
+            decode_body.push(stmt!["raise Exception(",
+                                   Variable::String("bad type".to_owned()),
+                                   " + ",
+                                   &type_field,
+                                   ")"]);
+        }
 
         decode.push(decode_body.join(ElementSpec::Spacing));
 
@@ -0,0 +1,120 @@
+import base64
+import binascii
+
+
+class StrictError(Exception):
+    """Raised when data does not match its declaration.
+
+    :ivar path: Path to the offending value, as a list of keys and indexes.
+    :ivar message: Description of what is wrong with the value.
+    """
+
+    def __init__(self, path, message):
+        Exception.__init__(self, "{}: {}".format(format_path(path), message))
+        self.path = path
+        self.message = message
+
+
+def format_path(path):
+    return "".join("/" + str(segment) for segment in path) or "/"
+
+
+def expected(kind, value):
+    return StrictError([], "expected {}, but was {}".format(kind, type(value).__name__))
+
+
+def at(segment, fn, value):
+    """Apply fn to value, prefixing the path of any raised error with the given segment."""
+    try:
+        return fn(value)
+    except StrictError as e:
+        raise StrictError([segment] + e.path, e.message)
+
+
+def required(data, key):
+    if key not in data:
+        raise StrictError([key], "missing required field")
+
+    return data[key]
+
+
+def obj(value):
+    if not isinstance(value, dict):
+        raise expected("object", value)
+
+    return value
+
+
+def sequence(value, length):
+    if not isinstance(value, (list, tuple)):
+        raise expected("array", value)
+
+    if len(value) != length:
+        raise StrictError([], "expected {} element(s), but was {}".format(length, len(value)))
+
+    return value
+
+
+def string(value):
+    if not isinstance(value, str):
+        raise expected("string", value)
+
+    return value
+
+
+def boolean(value):
+    if not isinstance(value, bool):
+        raise expected("boolean", value)
+
+    return value
+
+
+def number(value):
+    if not isinstance(value, (int, float)) or isinstance(value, bool):
+        raise expected("number", value)
+
+    return value
+
+
+def integer(value, minimum=None, maximum=None):
+    if not isinstance(value, int) or isinstance(value, bool):
+        raise expected("integer", value)
+
+    if minimum is not None and value < minimum:
+        raise StrictError([], "{} is smaller than {}".format(value, minimum))
+
+    if maximum is not None and value > maximum:
+        raise StrictError([], "{} is larger than {}".format(value, maximum))
+
+    return value
+
+
+def base64_bytes(value):
+    if not isinstance(value, str):
+        raise expected("string", value)
+
+    try:
+        return base64.b64decode(value, validate=True)
+    except (binascii.Error, ValueError):
+        raise StrictError([], "expected base64 encoded bytes")
+
+
+def bytes_base64(value):
+    if not isinstance(value, (bytes, bytearray)):
+        raise expected("bytes", value)
+
+    return base64.b64encode(value).decode("ascii")
+
+
+def array(value, fn):
+    if not isinstance(value, (list, tuple)):
+        raise expected("array", value)
+
+    return [at(index, fn, v) for index, v in enumerate(value)]
+
+
+def mapping(value, fn):
+    if not isinstance(value, dict):
+        raise expected("object", value)
+
+    return dict((key, at(key, fn, v)) for key, v in value.items())
@@ -0,0 +1,18 @@
+use backend::*;
+use super::processor::*;
+
+pub struct Module {
+}
+
+impl Module {
+    pub fn new() -> Module {
+        Module {}
+    }
+}
+
+impl Listeners for Module {
+    fn configure(&self, options: &mut ProcessorOptions) -> Result<()> {
+        options.strict = true;
+        Ok(())
+    }
+}
@@ -0,0 +1,693 @@
+use backend::*;
+use backend::errors::*;
+use backend::for_context::ForContext;
+use backend::models as m;
+use codeviz::common::ElementFormat;
+use codeviz::js::{ElementSpec, Elements, Statement, Variable};
+use naming::{self, FromNaming};
+use options::Options;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+const MOD_RS: &str = "mod.rs";
+const EXT: &str = "rs";
+const RUST_CONTEXT: &str = "rust";
+const INDENT: &str = "    ";
+const DERIVE: &str = "#[derive(Serialize, Deserialize, Debug, Clone)]";
+
+/// Words which are reserved in rust, and can't be used as identifiers.
+const KEYWORDS: &[&str] = &["abstract", "alignof", "as", "become", "box", "break", "const",
+                            "continue", "crate", "do", "else", "enum", "extern", "false",
+                            "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro",
+                            "match", "mod", "move", "mut", "offsetof", "override", "priv",
+                            "proc", "pub", "pure", "ref", "return", "self", "sizeof", "static",
+                            "struct", "super", "trait", "true", "type", "typeof", "unsafe",
+                            "unsized", "use", "virtual", "where", "while", "yield"];
+
+pub trait Listeners {
+    fn configure(&self, _processor: &mut ProcessorOptions) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A vector of listeners is a valid listener.
+impl Listeners for Vec<Box<Listeners>> {
+    fn configure(&self, processor: &mut ProcessorOptions) -> Result<()> {
+        for listeners in self {
+            listeners.configure(processor)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ProcessorOptions {
+    parent: Options,
+}
+
+impl ProcessorOptions {
+    pub fn new(options: Options) -> ProcessorOptions {
+        ProcessorOptions { parent: options }
+    }
+}
+
+/// A module in the generated module tree.
+///
+/// Modules with children are written as `<name>/mod.rs`, all others as `<name>.rs`.
+struct Module {
+    elements: Elements,
+    children: BTreeSet<String>,
+}
+
+impl Module {
+    fn new() -> Module {
+        Module {
+            elements: Elements::new(),
+            children: BTreeSet::new(),
+        }
+    }
+}
+
+pub struct Processor {
+    options: ProcessorOptions,
+    env: Environment,
+    package_prefix: Option<m::Package>,
+    to_upper_camel: Box<naming::Naming>,
+}
+
+impl Processor {
+    pub fn new(options: ProcessorOptions,
+               env: Environment,
+               package_prefix: Option<m::Package>)
+               -> Processor {
+        Processor {
+            options: options,
+            env: env,
+            package_prefix: package_prefix,
+            to_upper_camel: naming::SnakeCase::new().to_upper_camel(),
+        }
+    }
+
+    /// Build the rust package of a given package.
+    ///
+    /// This includes the prefixed configured in `self.options`, if specified.
+    fn package(&self, package: &m::Package) -> m::Package {
+        self.package_prefix
+            .clone()
+            .map(|prefix| prefix.join(package))
+            .unwrap_or_else(|| package.clone())
+    }
+
+    fn ident(&self, name: &str) -> String {
+        let ident = if let Some(ref id_converter) = self.options.parent.id_converter {
+            id_converter.convert(name)
+        } else {
+            name.to_owned()
+        };
+
+        if KEYWORDS.contains(&ident.as_str()) {
+            return format!("{}_", ident);
+        }
+
+        ident
+    }
+
+    /// Build documentation comments out of the given lines.
+    fn doc_comment(&self, comment: &Vec<String>) -> Elements {
+        let mut elements = Elements::new();
+
+        for line in comment {
+            if line.is_empty() {
+                elements.push("///");
+            } else {
+                elements.push(stmt!["/// ", line]);
+            }
+        }
+
+        elements
+    }
+
+    /// Build the path to the given custom type, relative to the module of `package`.
+    fn convert_custom(&self,
+                      pos: &m::Pos,
+                      package: &m::Package,
+                      custom: &m::Custom)
+                      -> Result<Statement> {
+        let target = if let Some(ref prefix) = custom.prefix {
+            self.env.lookup_used(pos, package, prefix)?
+        } else {
+            package
+        };
+
+        let key = (target.clone(), custom.parts.clone());
+
+        if !self.env.types.contains_key(&key) {
+            return Err(Error::pos(format!("no such type: {}", custom.parts.join(".")),
+                                  pos.clone()));
+        }
+
+        let name = custom.parts.join("");
+
+        if target == package {
+            return Ok(stmt![name]);
+        }
+
+        let mut path: Vec<String> = self.package(package)
+            .parts
+            .iter()
+            .map(|_| "super".to_owned())
+            .collect();
+
+        path.extend(self.package(target).parts);
+        path.push(name);
+
+        Ok(stmt![path.join("::")])
+    }
+
+    /// Size of the smallest rust integer which can hold an integer of the given size.
+    fn integer_size(&self, pos: &m::Pos, size: Option<usize>) -> Result<String> {
+        let size = match size.unwrap_or(32) {
+            s if s <= 32 => 32,
+            s if s <= 64 => 64,
+            s if s <= 128 => 128,
+            s => {
+                return Err(Error::pos(format!("integers of size {} are not supported", s),
+                                      pos.clone()));
+            }
+        };
+
+        Ok(size.to_string())
+    }
+
+    fn convert_type(&self, pos: &m::Pos, package: &m::Package, ty: &m::Type) -> Result<Statement> {
+        let ty = match *ty {
+            m::Type::String => stmt!["String"],
+            m::Type::Signed(size) => stmt!["i", self.integer_size(pos, size)?],
+            m::Type::Unsigned(size) => stmt!["u", self.integer_size(pos, size)?],
+            m::Type::Float => stmt!["f32"],
+            m::Type::Double => stmt!["f64"],
+            m::Type::Boolean => stmt!["bool"],
+            // serde has no base64 support, so bytes are kept in their encoded form.
+            m::Type::Bytes => stmt!["String"],
+            m::Type::Array(ref inner) => {
+                let inner = self.convert_type(pos, package, inner)?;
+                stmt!["Vec<", inner, ">"]
+            }
+            m::Type::Map(ref key, ref value) => {
+                let key = self.convert_type(pos, package, key)?;
+                let value = self.convert_type(pos, package, value)?;
+                stmt!["::std::collections::BTreeMap<", key, ", ", value, ">"]
+            }
+            m::Type::Any => stmt!["::serde_json::Value"],
+            m::Type::Custom(ref custom) => self.convert_custom(pos, package, custom)?,
+        };
+
+        Ok(ty)
+    }
+
+    /// Build a single field.
+    ///
+    /// Fields of struct variants can't have a visibility, which is controlled by `visibility`.
+    fn build_field(&self,
+                   package: &m::Package,
+                   field: &m::Token<m::Field>,
+                   visibility: &str)
+                   -> Result<Elements> {
+        let ident = self.ident(&field.name);
+        let ty = self.convert_type(&field.pos, package, &field.ty)?;
+
+        let mut elements = self.doc_comment(&field.comment);
+
+        if ident != field.name() {
+            let name = Variable::String(field.name().to_owned());
+            elements.push(stmt!["#[serde(rename = ", name, ")]"]);
+        }
+
+        let ty = if field.is_optional() {
+            let skip = Variable::String("Option::is_none".to_owned());
+            elements.push(stmt!["#[serde(default, skip_serializing_if = ", skip, ")]"]);
+            stmt!["Option<", ty, ">"]
+        } else {
+            ty
+        };
+
+        elements.push(stmt![visibility, ident, ": ", ty, ","]);
+        Ok(elements)
+    }
+
+    fn build_fields(&self,
+                    package: &m::Package,
+                    fields: &Vec<m::Token<m::Field>>,
+                    visibility: &str)
+                    -> Result<Elements> {
+        let mut elements = Elements::new();
+
+        for field in fields {
+            elements.push(self.build_field(package, field, visibility)?);
+        }
+
+        Ok(elements)
+    }
+
+    /// Build an impl block containing all the given code.
+    fn build_impl(&self, name: &str, codes: &Vec<m::Token<m::Code>>) -> Option<Elements> {
+        let mut body = Elements::new();
+
+        for code in codes.for_context(RUST_CONTEXT) {
+            body.push(code.inner.lines);
+        }
+
+        if body.is_empty() {
+            return None;
+        }
+
+        let mut elements = Elements::new();
+        elements.push(stmt!["impl ", name, " {"]);
+        elements.push_nested(body.join(ElementSpec::Spacing));
+        elements.push("}");
+        Some(elements)
+    }
+
+    fn process_type(&self, package: &m::Package, body: &m::TypeBody) -> Result<Elements> {
+        let mut struct_ = self.doc_comment(&body.comment);
+        struct_.push(DERIVE);
+        struct_.push(stmt!["pub struct ", &body.name, " {"]);
+        struct_.push_nested(self.build_fields(package, &body.fields, "pub ")?);
+        struct_.push("}");
+
+        let mut elements = Elements::new();
+        elements.push(struct_);
+
+        if let Some(impl_) = self.build_impl(&body.name, &body.codes) {
+            elements.push(impl_);
+        }
+
+        Ok(elements.join(ElementSpec::Spacing))
+    }
+
+    fn process_tuple(&self, package: &m::Package, body: &m::TupleBody) -> Result<Elements> {
+        let mut arguments = Statement::new();
+
+        for field in &body.fields {
+            let ty = self.convert_type(&field.pos, package, &field.ty)?;
+            arguments.push(stmt!["pub ", ty]);
+        }
+
+        let mut struct_ = self.doc_comment(&body.comment);
+        struct_.push(DERIVE);
+        struct_.push(stmt!["pub struct ", &body.name, "(", arguments.join(", "), ");"]);
+
+        let mut elements = Elements::new();
+        elements.push(struct_);
+
+        if let Some(impl_) = self.build_impl(&body.name, &body.codes) {
+            elements.push(impl_);
+        }
+
+        Ok(elements.join(ElementSpec::Spacing))
+    }
+
+    /// Convert a literal value for a field of an enum.
+    ///
+    /// Returns the type that the accessor returns, and the value.
+    fn enum_literal(&self, value: &m::Token<m::Value>, ty: &m::Type) -> Result<Statement> {
+        let suffix = match *ty {
+            m::Type::String => {
+                if let m::Value::String(ref string) = value.inner {
+                    return Ok(stmt![Variable::String(string.to_owned())]);
+                }
+
+                None
+            }
+            m::Type::Boolean => {
+                if let m::Value::Boolean(ref boolean) = value.inner {
+                    return Ok(stmt![boolean.to_string()]);
+                }
+
+                None
+            }
+            m::Type::Signed(ref size) => {
+                Some(if size.map(|s| s <= 32usize).unwrap_or(true) { "i32" } else { "i64" })
+            }
+            m::Type::Unsigned(ref size) => {
+                Some(if size.map(|s| s <= 32usize).unwrap_or(true) { "u32" } else { "u64" })
+            }
+            m::Type::Float => Some("f32"),
+            m::Type::Double => Some("f64"),
+            _ => None,
+        };
+
+        if let (Some(suffix), &m::Value::Number(ref number)) = (suffix, &value.inner) {
+            if suffix.starts_with("f") {
+                return Ok(stmt![format!("{}{}", number, suffix)]);
+            }
+
+            return Ok(stmt![format!("{}{}", number.floor(), suffix)]);
+        }
+
+        Err(Error::pos(format!("{} cannot be applied to expected type {}", value.inner, ty),
+                       value.pos.clone()))
+    }
+
+    fn enum_field_type(&self, field: &m::Token<m::Field>) -> Result<Statement> {
+        let ty = match field.ty {
+            m::Type::String => stmt!["&'static str"],
+            m::Type::Boolean |
+            m::Type::Signed(_) |
+            m::Type::Unsigned(_) |
+            m::Type::Float |
+            m::Type::Double => self.convert_type(&field.pos, &m::Package::new(vec![]), &field.ty)?,
+            ref ty => {
+                return Err(Error::pos(format!("unsupported type for enum field: {}", ty),
+                                      field.pos.clone()));
+            }
+        };
+
+        Ok(ty)
+    }
+
+    fn process_enum(&self, _package: &m::Package, body: &m::EnumBody) -> Result<Elements> {
+        let serialized_as = if let Some(ref s) = body.serialized_as {
+            let index = body.fields.iter().position(|f| f.name == s.inner);
+
+            let index = index.ok_or_else(|| {
+                    Error::pos(format!("no field named: {}", s.inner), s.pos.clone())
+                })?;
+
+            Some(index)
+        } else {
+            None
+        };
+
+        // only strings can be serialized through renamed variants, other fields are serialized
+        // through implementations of their own.
+        let serialized_field = match serialized_as {
+            Some(index) if body.fields[index].ty != m::Type::String => Some(&body.fields[index]),
+            _ => None,
+        };
+
+        let mut variants = Elements::new();
+        let mut accessors: Vec<Elements> = body.fields.iter().map(|_| Elements::new()).collect();
+
+        for value in &body.values {
+            let variant = self.to_upper_camel.convert(&value.name);
+
+            let serialized = match serialized_as {
+                Some(_) if serialized_field.is_some() => variant.clone(),
+                Some(index) => {
+                    match value.arguments.get(index).map(|a| &a.inner) {
+                        Some(&m::Value::String(ref string)) => string.to_owned(),
+                        _ => {
+                            return Err(Error::pos("expected string argument".to_owned(),
+                                                  value.pos.clone()));
+                        }
+                    }
+                }
+                None => value.name.to_owned(),
+            };
+
+            let mut element = self.doc_comment(&value.comment);
+
+            if serialized != variant {
+                let serialized = Variable::String(serialized);
+                element.push(stmt!["#[serde(rename = ", serialized, ")]"]);
+            }
+
+            element.push(stmt![&variant, ","]);
+            variants.push(element);
+
+            for ((argument, field), accessor) in value.arguments
+                .iter()
+                .zip(body.fields.iter())
+                .zip(accessors.iter_mut()) {
+                let literal = self.enum_literal(argument, &field.ty)?;
+                accessor.push(stmt![&body.name, "::", &variant, " => ", literal, ","]);
+            }
+        }
+
+        let mut enum_ = self.doc_comment(&body.comment);
+
+        if serialized_field.is_some() {
+            enum_.push(stmt!["#[derive(Debug, Clone, Copy, PartialEq, Eq)]"]);
+        } else {
+            enum_.push(stmt!["#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]"]);
+        }
+
+        enum_.push(stmt!["pub enum ", &body.name, " {"]);
+        enum_.push_nested(variants);
+        enum_.push("}");
+
+        let mut impl_body = Elements::new();
+
+        for (field, accessor) in body.fields.iter().zip(accessors.into_iter()) {
+            let ty = self.enum_field_type(field)?;
+
+            let mut match_ = Elements::new();
+            match_.push("match *self {");
+            match_.push_nested(accessor);
+            match_.push("}");
+
+            let mut method = self.doc_comment(&field.comment);
+            method.push(stmt!["pub fn ", self.ident(&field.name), "(&self) -> ", ty, " {"]);
+            method.push_nested(match_);
+            method.push("}");
+
+            impl_body.push(method);
+        }
+
+        for code in body.codes.for_context(RUST_CONTEXT) {
+            impl_body.push(code.inner.lines);
+        }
+
+        let mut elements = Elements::new();
+        elements.push(enum_);
+
+        if !impl_body.is_empty() {
+            let mut impl_ = Elements::new();
+            impl_.push(stmt!["impl ", &body.name, " {"]);
+            impl_.push_nested(impl_body.join(ElementSpec::Spacing));
+            impl_.push("}");
+            elements.push(impl_);
+        }
+
+        if let Some(field) = serialized_field {
+            elements.push(self.enum_serialize(body, field)?);
+            elements.push(self.enum_deserialize(body, field)?);
+        }
+
+        Ok(elements.join(ElementSpec::Spacing))
+    }
+
+    /// Build an implementation of `Serialize` for an enum, which serializes the given field.
+    fn enum_serialize(&self, body: &m::EnumBody, field: &m::Token<m::Field>) -> Result<Elements> {
+        let accessor = self.ident(&field.name);
+
+        let mut serialize = Elements::new();
+        serialize.push("fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>");
+        serialize.push_nested("where S: ::serde::Serializer");
+        serialize.push("{");
+        serialize.push_nested(stmt!["::serde::Serialize::serialize(&self.",
+                                    accessor,
+                                    "(), serializer)"]);
+        serialize.push("}");
+
+        let mut impl_ = Elements::new();
+        impl_.push(stmt!["impl ::serde::Serialize for ", &body.name, " {"]);
+        impl_.push_nested(serialize);
+        impl_.push("}");
+        Ok(impl_)
+    }
+
+    /// Build an implementation of `Deserialize` for an enum, which picks the value whose given
+    /// field is equal to the deserialized value.
+    fn enum_deserialize(&self, body: &m::EnumBody, field: &m::Token<m::Field>) -> Result<Elements> {
+        let ty = self.enum_field_type(field)?;
+        let accessor = self.ident(&field.name);
+
+        let values: Vec<_> = body.values
+            .iter()
+            .map(|v| format!("{}::{}", body.name, self.to_upper_camel.convert(&v.name)))
+            .collect();
+
+        let mut check = Elements::new();
+        check.push(stmt!["if value.", &accessor, "() == input {"]);
+        check.push_nested("return Ok(*value);");
+        check.push("}");
+
+        let mut find = Elements::new();
+        find.push(stmt!["for value in &[", values.join(", "), "] {"]);
+        find.push_nested(check);
+        find.push("}");
+
+        let message = Variable::String(format!("not a valid value for {}: {{}}", body.name));
+
+        let mut deserialize_body = Elements::new();
+        deserialize_body.push(stmt!["let input: ",
+                                    ty,
+                                    " = ::serde::Deserialize::deserialize(deserializer)?;"]);
+        deserialize_body.push(find);
+        deserialize_body.push(stmt!["Err(::serde::de::Error::custom(format!(",
+                                    message,
+                                    ", input)))"]);
+
+        let mut deserialize = Elements::new();
+        deserialize.push(stmt!["fn deserialize<D>(deserializer: D) -> Result<",
+                               &body.name,
+                               ", D::Error>"]);
+        deserialize.push_nested("where D: ::serde::Deserializer<'de>");
+        deserialize.push("{");
+        deserialize.push_nested(deserialize_body.join(ElementSpec::Spacing));
+        deserialize.push("}");
+
+        let mut impl_ = Elements::new();
+        impl_.push(stmt!["impl<'de> ::serde::Deserialize<'de> for ", &body.name, " {"]);
+        impl_.push_nested(deserialize);
+        impl_.push("}");
+        Ok(impl_)
+    }
+
+    fn process_interface(&self,
+                         package: &m::Package,
+                         body: &m::InterfaceBody)
+                         -> Result<Elements> {
+        let mut variants = Elements::new();
+
+        for (_, ref sub_type) in &body.sub_types {
+            let mut variant = self.doc_comment(&sub_type.comment);
+
+            let mut names = sub_type.names.iter().map(|n| n.inner.to_owned());
+            let name = names.next().unwrap_or_else(|| sub_type.name.to_owned());
+
+            if name != sub_type.name {
+                variant.push(stmt!["#[serde(rename = ", Variable::String(name), ")]"]);
+            }
+
+            for alias in names {
+                variant.push(stmt!["#[serde(alias = ", Variable::String(alias), ")]"]);
+            }
+
+            let mut fields = self.build_fields(package, &body.fields, "")?;
+            fields.push(self.build_fields(package, &sub_type.fields, "")?);
+
+            if body.fields.is_empty() && sub_type.fields.is_empty() {
+                variant.push(stmt![&sub_type.name, " {},"]);
+            } else {
+                variant.push(stmt![&sub_type.name, " {"]);
+                variant.push_nested(fields);
+                variant.push("},");
+            }
+
+            variants.push(variant);
+        }
+
+        let mut enum_ = self.doc_comment(&body.comment);
+        enum_.push(DERIVE);
+
+        if let m::TypeInfo::TypeField(ref type_field) = body.type_info {
+            let type_field = Variable::String(type_field.to_owned());
+            enum_.push(stmt!["#[serde(tag = ", type_field, ")]"]);
+        }
+
+        enum_.push(stmt!["pub enum ", &body.name, " {"]);
+        enum_.push_nested(variants.join(ElementSpec::Spacing));
+        enum_.push("}");
+
+        let mut elements = Elements::new();
+        elements.push(enum_);
+
+        let codes = body.codes
+            .iter()
+            .chain(body.sub_types.values().flat_map(|s| s.codes.iter()))
+            .cloned()
+            .collect();
+
+        if let Some(impl_) = self.build_impl(&body.name, &codes) {
+            elements.push(impl_);
+        }
+
+        Ok(elements.join(ElementSpec::Spacing))
+    }
+
+    /// Build the tree of modules, keyed by their path.
+    ///
+    /// All parents of a module are also present in the tree, and the root module has an empty path.
+    fn populate_modules(&self) -> Result<BTreeMap<Vec<String>, Module>> {
+        let mut modules = BTreeMap::new();
+        modules.insert(Vec::new(), Module::new());
+
+        // Process all types discovered so far.
+        for (&(ref package, _), decl) in &self.env.types {
+            let elements = match decl.inner {
+                m::Decl::Interface(ref body) => self.process_interface(package, body)?,
+                m::Decl::Type(ref body) => self.process_type(package, body)?,
+                m::Decl::Tuple(ref body) => self.process_tuple(package, body)?,
+                m::Decl::Enum(ref body) => self.process_enum(package, body)?,
+            };
+
+            let path = self.package(package).parts;
+
+            for i in 0..path.len() {
+                let parent = modules.entry(path[..i].to_vec()).or_insert_with(Module::new);
+                parent.children.insert(path[i].clone());
+            }
+
+            modules.entry(path).or_insert_with(Module::new).elements.push(elements);
+        }
+
+        Ok(modules)
+    }
+
+    fn module_path(&self, path: &Vec<String>, module: &Module) -> PathBuf {
+        let full_path = path.iter().fold(PathBuf::new(), |current, next| current.join(next));
+
+        if path.is_empty() || !module.children.is_empty() {
+            return full_path.join(MOD_RS);
+        }
+
+        full_path.with_extension(EXT)
+    }
+
+    fn write_modules(&self, modules: BTreeMap<Vec<String>, Module>) -> Result<()> {
+        for (path, module) in modules {
+            let full_path = self.module_path(&path, &module);
+
+            let mut out = Elements::new();
+
+            if !module.children.is_empty() {
+                let mut children = Elements::new();
+
+                for child in &module.children {
+                    children.push(stmt!["pub mod ", child, ";"]);
+                }
+
+                out.push(children);
+            }
+
+            if !module.elements.is_empty() {
+                out.push(module.elements.join(ElementSpec::Spacing));
+            }
+
+            debug!("+module: {}", full_path.display());
+
+            let elements: ElementSpec = out.join(ElementSpec::Spacing).into();
+
+            let mut content = String::new();
+            elements.format("", INDENT, &mut content);
+
+            self.options.parent.output.write(&full_path, content.end().as_bytes())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Backend for Processor {
+    fn process(&self) -> Result<()> {
+        let modules = self.populate_modules()?;
+        self.write_modules(modules)
+    }
+
+    fn verify(&self) -> Result<Vec<Error>> {
+        Ok(vec![])
+    }
+}
@@ -0,0 +1,384 @@
+//! Generation of random payloads which conform to their declarations.
+
+use super::environment::Environment;
+use super::errors::*;
+use super::json::Json;
+use super::models::*;
+
+/// Nesting after which optional fields are left out, and arrays and maps are left empty.
+const MAX_DEPTH: usize = 6;
+/// Nesting after which generation gives up, since required fields keep recursing.
+const LIMIT_DEPTH: usize = 64;
+
+const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789 _-";
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(input: &[u8]) -> String {
+    let mut out = String::new();
+
+    for chunk in input.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as usize) << 16) | ((b[1] as usize) << 8) | b[2] as usize;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i)) & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+fn value_json(value: &Value) -> Json {
+    match *value {
+        Value::String(ref string) => Json::String(string.to_owned()),
+        Value::Number(ref number) => Json::Number(number.to_string()),
+        Value::Boolean(ref boolean) => Json::Boolean(*boolean),
+        _ => Json::Null,
+    }
+}
+
+/// A small pseudo-random number generator (xorshift64*), so that a seed always produces the same
+/// samples regardless of platform.
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        let state = seed ^ 0x9e37_79b9_7f4a_7c15;
+
+        // the state must never be zero, which only happens for one seed.
+        Random { state: if state == 0 { 1 } else { state } }
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Random number in the range `[0, n)`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        if n == 0 { 0 } else { self.next() % n }
+    }
+
+    /// Pick a random element of the given slice.
+    pub fn pick<'a, T>(&mut self, values: &'a [T]) -> Option<&'a T> {
+        let index = self.below(values.len() as u64) as usize;
+        values.get(index)
+    }
+
+    /// Succeeds with a probability of one in `n`.
+    pub fn one_in(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+}
+
+/// Generates random values for the declarations of an environment.
+pub struct Generator<'a> {
+    env: &'a Environment,
+    random: Random,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(env: &'a Environment, seed: u64) -> Generator<'a> {
+        Generator {
+            env: env,
+            random: Random::new(seed),
+        }
+    }
+
+    /// Generate a value for the declaration with the given name in `package`.
+    pub fn generate(&mut self, package: &Package, name: &str) -> Result<Json> {
+        let key = (package.clone(), vec![name.to_owned()]);
+
+        let decl = self.env
+            .types
+            .get(&key)
+            .ok_or_else(|| format!("no such type: {}.{}", package, name))?;
+
+        self.decl(package, decl, 0)
+    }
+
+    /// Generate an integer in the range of the given size, favoring the bounds of the range.
+    fn integer(&mut self, signed: bool, size: &Option<usize>) -> Json {
+        let size = match *size {
+            Some(size) if size > 0 && size < 64 => size,
+            _ => 64,
+        };
+
+        let span = if size == 64 { u64::max_value() } else { (1u64 << size) - 1 };
+
+        let offset = match self.random.below(8) {
+            0 => 0,
+            1 => span,
+            _ if size == 64 => self.random.next(),
+            _ => self.random.below(span + 1),
+        };
+
+        let number = if signed {
+            let min = -(1i128 << (size - 1));
+            (min + offset as i128).to_string()
+        } else {
+            offset.to_string()
+        };
+
+        Json::Number(number)
+    }
+
+    fn string(&mut self) -> String {
+        let len = self.random.below(12);
+        let mut out = String::new();
+
+        for _ in 0..len {
+            out.push(*self.random.pick(ALPHABET).expect("alphabet") as char);
+        }
+
+        out
+    }
+
+    /// Number of elements to generate in an array or a map.
+    fn len(&mut self, depth: usize) -> u64 {
+        if depth >= MAX_DEPTH { 0 } else { self.random.below(4) }
+    }
+
+    fn map_key(&mut self, key: &Type) -> String {
+        match *key {
+            Type::Signed(ref size) | Type::Unsigned(ref size) => {
+                let signed = if let Type::Signed(_) = *key { true } else { false };
+
+                match self.integer(signed, size) {
+                    Json::Number(number) => number,
+                    _ => "0".to_owned(),
+                }
+            }
+            _ => self.string(),
+        }
+    }
+
+    fn ty(&mut self, pos: &Pos, package: &Package, ty: &Type, depth: usize) -> Result<Json> {
+        if depth > LIMIT_DEPTH {
+            return Err(Error::pos("cannot generate a finite value for recursive type".to_owned(),
+                                  pos.clone()));
+        }
+
+        let value = match *ty {
+            Type::Double | Type::Float => {
+                let value = (self.random.below(2_000_000) as f64 - 1_000_000f64) / 1000f64;
+                Json::Number(value.to_string())
+            }
+            Type::Signed(ref size) => self.integer(true, size),
+            Type::Unsigned(ref size) => self.integer(false, size),
+            Type::Boolean => Json::Boolean(self.random.one_in(2)),
+            Type::String => Json::String(self.string()),
+            Type::Bytes => {
+                let len = self.random.below(16);
+                let bytes: Vec<u8> = (0..len).map(|_| self.random.next() as u8).collect();
+                Json::String(base64(&bytes))
+            }
+            Type::Any => {
+                match self.random.below(4) {
+                    0 => Json::Null,
+                    1 => Json::Boolean(self.random.one_in(2)),
+                    2 => self.integer(true, &Some(32)),
+                    _ => Json::String(self.string()),
+                }
+            }
+            Type::Array(ref inner) => {
+                let mut values = Vec::new();
+
+                for _ in 0..self.len(depth) {
+                    values.push(self.ty(pos, package, inner, depth + 1)?);
+                }
+
+                Json::Array(values)
+            }
+            Type::Map(ref key, ref inner) => {
+                let mut entries: Vec<(String, Json)> = Vec::new();
+
+                for _ in 0..self.len(depth) {
+                    let name = self.map_key(key);
+
+                    if entries.iter().any(|e| e.0 == name) {
+                        continue;
+                    }
+
+                    let value = self.ty(pos, package, inner, depth + 1)?;
+                    entries.push((name, value));
+                }
+
+                Json::Object(entries)
+            }
+            Type::Custom(ref custom) => {
+                let (target, decl) = self.env.lookup_decl(pos, package, custom)?;
+                self.decl(target, decl, depth + 1)?
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn fields(&mut self,
+              package: &Package,
+              fields: &[&Token<Field>],
+              depth: usize)
+              -> Result<Vec<(String, Json)>> {
+        let mut entries = Vec::new();
+
+        for field in fields {
+            if field.is_optional() && (depth >= MAX_DEPTH || self.random.one_in(2)) {
+                continue;
+            }
+
+            let value = self.ty(&field.pos, package, &field.ty, depth)?;
+            entries.push((field.name().to_owned(), value));
+        }
+
+        Ok(entries)
+    }
+
+    fn enum_value(&mut self, body: &EnumBody) -> Result<Json> {
+        let value = match self.random.pick(&body.values) {
+            Some(value) => value,
+            None => return Err(format!("enum {} has no values", body.name).into()),
+        };
+
+        if let Some(ref s) = body.serialized_as {
+            let index = body.fields
+                .iter()
+                .position(|f| f.name == s.inner)
+                .ok_or_else(|| Error::pos(format!("no field named: {}", s.inner), s.pos.clone()))?;
+
+            if let Some(argument) = value.arguments.get(index) {
+                return Ok(value_json(&argument.inner));
+            }
+        }
+
+        Ok(Json::String(value.name.to_owned()))
+    }
+
+    fn interface(&mut self,
+                 package: &Package,
+                 body: &InterfaceBody,
+                 depth: usize)
+                 -> Result<Json> {
+        let sub_types: Vec<_> = body.sub_types.values().collect();
+
+        let sub_type = match self.random.pick(&sub_types) {
+            Some(sub_type) => *sub_type,
+            None => return Err(format!("interface {} has no sub types", body.name).into()),
+        };
+
+        // any of the names of a sub type identifies it.
+        let name = match self.random.pick(&sub_type.names) {
+            Some(name) => name.inner.to_owned(),
+            None => sub_type.name.to_owned(),
+        };
+
+        let fields: Vec<_> = body.fields.iter().chain(sub_type.fields.iter()).collect();
+        let mut entries = self.fields(package, &fields, depth)?;
+
+        let value = match body.type_info {
+            TypeInfo::TypeField(ref type_field) => {
+                entries.insert(0, (type_field.to_owned(), Json::String(name)));
+                Json::Object(entries)
+            }
+            TypeInfo::ObjectKeys => Json::Object(vec![(name, Json::Object(entries))]),
+        };
+
+        Ok(value)
+    }
+
+    fn decl(&mut self, package: &Package, decl: &Decl, depth: usize) -> Result<Json> {
+        let value = match *decl {
+            Decl::Type(ref body) => {
+                let fields: Vec<_> = body.fields.iter().collect();
+                Json::Object(self.fields(package, &fields, depth)?)
+            }
+            Decl::Tuple(ref body) => {
+                let mut values = Vec::new();
+
+                // every element of a tuple is required, in the order of its fields.
+                for field in &body.fields {
+                    values.push(self.ty(&field.pos, package, &field.ty, depth)?);
+                }
+
+                Json::Array(values)
+            }
+            Decl::Enum(ref body) => self.enum_value(body)?,
+            Decl::Interface(ref body) => self.interface(package, body, depth)?,
+        };
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+    use super::super::validate::Validator;
+
+    fn environment() -> Environment {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("snapshots")
+            .join("proto");
+
+        let mut env = Environment::new(vec![root]);
+        env.import(&Package::new(vec!["snapshot".to_owned(), "v1".to_owned()])).unwrap();
+        env.verify().unwrap();
+        env
+    }
+
+    fn format(value: &Json) -> String {
+        let mut out = String::new();
+        value.format(0, &mut out);
+        out
+    }
+
+    #[test]
+    fn test_samples_are_valid() {
+        let env = environment();
+
+        for seed in 0..50 {
+            let mut generator = Generator::new(&env, seed);
+
+            for &(ref package, ref parts) in env.types.keys() {
+                let name = parts.join(".");
+                let value = generator.generate(package, &name).unwrap();
+                let violations = Validator::new(&env).validate(package, &name, &value).unwrap();
+
+                assert!(violations.is_empty(),
+                        "{}.{} (seed {}): {:?}\n{}",
+                        package,
+                        name,
+                        seed,
+                        violations,
+                        format(&value));
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_seed() {
+        let env = environment();
+        let package = Package::new(vec!["snapshot".to_owned(), "v1".to_owned()]);
+
+        let generate = |seed| {
+            let mut generator = Generator::new(&env, seed);
+            let values: Vec<_> = (0..10)
+                .map(|_| format(&generator.generate(&package, "Entry").unwrap()))
+                .collect();
+            values
+        };
+
+        assert_eq!(generate(42), generate(42));
+        assert!(generate(42) != generate(43));
+    }
+}
@@ -0,0 +1,1183 @@
+use backend::*;
+use backend::doc_comment::block_comment;
+use backend::errors::*;
+use backend::for_context::ForContext;
+use backend::js::models::*;
+use backend::js::utils::*;
+use codeviz::common::ElementFormat;
+use codeviz::js::*;
+use options::Options;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::collections::hash_map::Entry;
+use std::path::PathBuf;
+
+const EXT: &str = "ts";
+const TS_CONTEXT: &str = "typescript";
+const RUNTIME_MODULE: &str = "reproto";
+const RUNTIME_TS: &str = include_str!("reproto.ts");
+
+/// Name of the property that discriminates sub types of interfaces serialized as object keys.
+const DEFAULT_DISCRIMINATOR: &str = "type";
+
+fn field_name(_i: usize, field: &JsField) -> Variable {
+    string(&field.name)
+}
+
+fn field_index(i: usize, _field: &JsField) -> Variable {
+    Variable::Literal(i.to_string())
+}
+
+/// Build a block, like a class or a method, opened by the given statement.
+fn block<S>(open: S, body: Elements) -> Elements
+    where S: Into<Statement>
+{
+    let mut el = Elements::new();
+    el.push(stmt![open.into(), " {"]);
+    el.push_nested(body.join(ElementSpec::Spacing));
+    el.push("}");
+    el
+}
+
+/// Build the name of a property, which is quoted unless it is a valid identifier.
+fn property(name: &str) -> Variable {
+    let mut chars = name.chars();
+
+    let valid = chars.next().map(|c| c.is_alphabetic() || c == '_' || c == '$').unwrap_or(false) &&
+                chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+
+    if valid {
+        Variable::Literal(name.to_owned())
+    } else {
+        string(name)
+    }
+}
+
+pub trait Listeners {
+    fn configure(&self, _processor: &mut ProcessorOptions) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A vector of listeners is a valid listener.
+impl Listeners for Vec<Box<Listeners>> {
+    fn configure(&self, processor: &mut ProcessorOptions) -> Result<()> {
+        for listeners in self {
+            listeners.configure(processor)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ProcessorOptions {
+    parent: Options,
+    /// Represent integers which might not fit in a number as bigint, encoded as strings.
+    pub bigint: bool,
+}
+
+impl ProcessorOptions {
+    pub fn new(options: Options) -> ProcessorOptions {
+        ProcessorOptions {
+            parent: options,
+            bigint: false,
+        }
+    }
+}
+
+pub struct Processor {
+    options: ProcessorOptions,
+    env: Environment,
+    package_prefix: Option<Package>,
+    map_values: Name,
+    encode_bytes: Name,
+    decode_bytes: Name,
+}
+
+impl Processor {
+    pub fn new(options: ProcessorOptions,
+               env: Environment,
+               package_prefix: Option<Package>)
+               -> Processor {
+        Processor {
+            options: options,
+            env: env,
+            package_prefix: package_prefix,
+            map_values: Name::imported(RUNTIME_MODULE, "mapValues").into(),
+            encode_bytes: Name::imported(RUNTIME_MODULE, "encodeBytes").into(),
+            decode_bytes: Name::imported(RUNTIME_MODULE, "decodeBytes").into(),
+        }
+    }
+
+    fn find_field<'a>(&self,
+                      fields: &'a Vec<Token<JsField>>,
+                      name: &str)
+                      -> Option<(usize, &'a Token<JsField>)> {
+        for (i, field) in fields.iter().enumerate() {
+            if field.name == name {
+                return Some((i, field));
+            }
+        }
+
+        None
+    }
+
+    /// Build a function that throws an exception if the given value `stmt` is not defined.
+    fn throw_if_null<S>(&self, stmt: S, field: &JsField) -> Elements
+        where S: Into<Statement>
+    {
+        let required_error = string(format!("{}: is a required field", field.name));
+        js![if is_not_defined(stmt), js![throw required_error]]
+    }
+
+    fn convert_fields(&self, fields: &Vec<Token<Field>>) -> Vec<Token<JsField>> {
+        fields.iter()
+            .map(|f| {
+                let ident = self.field_ident(&f);
+
+                f.clone().map_inner(|o| {
+                    JsField {
+                        modifier: o.modifier,
+                        ty: o.ty,
+                        name: f.name().to_owned(),
+                        ident: ident,
+                        comment: o.comment,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Convert fields which are all required, like the fields of tuples and enums.
+    fn convert_required_fields(&self, fields: &Vec<Token<Field>>) -> Vec<Token<JsField>> {
+        self.convert_fields(fields)
+            .into_iter()
+            .map(|f| {
+                f.map_inner(|o| JsField { modifier: Modifier::Required, ..o })
+            })
+            .collect()
+    }
+
+    /// Build the TypeScript type corresponding to the given type.
+    fn field_type(&self, pos: &Pos, package: &Package, ty: &Type) -> Result<Statement> {
+        let ty = match *ty {
+            Type::Signed(ref size) |
+            Type::Unsigned(ref size) if self.is_bigint(size) => stmt!["bigint"],
+            Type::Signed(_) |
+            Type::Unsigned(_) => stmt!["number"],
+            Type::Float | Type::Double => stmt!["number"],
+            Type::String => stmt!["string"],
+            Type::Bytes => stmt!["Uint8Array"],
+            Type::Any => stmt!["any"],
+            Type::Boolean => stmt!["boolean"],
+            Type::Custom(ref custom) => stmt![self.name(pos, package, custom)?],
+            Type::Array(ref inner) => stmt![self.field_type(pos, package, inner)?, "[]"],
+            Type::Map(_, ref value) => {
+                stmt!["{[key: string]: ", self.field_type(pos, package, value)?, "}"]
+            }
+        };
+
+        Ok(ty)
+    }
+
+    /// Build the field declarations of a class.
+    fn field_declarations(&self,
+                          package: &Package,
+                          fields: &Vec<Token<JsField>>)
+                          -> Result<Elements> {
+        let mut declarations = Elements::new();
+
+        for field in fields {
+            let ty = self.field_type(&field.pos, package, &field.ty)?;
+
+            let declaration = match field.modifier {
+                Modifier::Optional => stmt![&field.ident, "?: ", ty, ";"],
+                _ => stmt![&field.ident, ": ", ty, ";"],
+            };
+
+            declarations.push(block_comment(&field.comment));
+            declarations.push(declaration);
+        }
+
+        Ok(declarations)
+    }
+
+    /// Build the encode method.
+    ///
+    /// If `wrap_key` is set, the encoded data is wrapped in an object with it as its only key.
+    fn encode_method<E>(&self,
+                        package: &Package,
+                        fields: &Vec<Token<JsField>>,
+                        wrap_key: Option<Statement>,
+                        extra: E)
+                        -> Result<Elements>
+        where E: FnOnce(&mut Elements) -> ()
+    {
+        let mut body = Elements::new();
+        let data = stmt!["data"];
+
+        body.push(stmt!["const ", &data, ": any = {};"]);
+
+        extra(&mut body);
+
+        let mut assign = Elements::new();
+
+        for field in fields {
+            let var_string = string(field.name.to_owned());
+            let field_stmt = stmt!["this.", &field.ident];
+            let value_stmt = self.encode(&field.pos, package, &field.ty, &field_stmt)?;
+
+            match field.modifier {
+                Modifier::Optional => {
+                    let stmt = js![if is_defined(field_stmt),
+                                      stmt![&data, "[", var_string, "] = ", value_stmt, ";"]];
+                    assign.push(stmt);
+                }
+                _ => {
+                    assign.push(self.throw_if_null(field_stmt, field));
+                    let stmt = stmt![&data, "[", var_string, "] = ", value_stmt, ";"];
+                    assign.push(stmt);
+                }
+            }
+        }
+
+        if !assign.is_empty() {
+            body.push(assign.join(ElementSpec::Spacing));
+        }
+
+        if let Some(wrap_key) = wrap_key {
+            body.push(js![return "{[", wrap_key, "]: ", data, "}"]);
+        } else {
+            body.push(js![return data]);
+        }
+
+        Ok(block(stmt!["encode(): any"], body))
+    }
+
+    fn encode_tuple_method(&self,
+                           package: &Package,
+                           fields: &Vec<Token<JsField>>)
+                           -> Result<Elements> {
+        let mut values = Statement::new();
+        let mut body = Elements::new();
+
+        for field in fields {
+            let stmt = stmt!["this.", &field.ident];
+            body.push(self.throw_if_null(&stmt, field));
+            values.push(self.encode(&field.pos, package, &field.ty, stmt)?);
+        }
+
+        body.push(js![@return [ values ]]);
+        Ok(block(stmt!["encode(): any"], body))
+    }
+
+    /// Build the check that `data` is equal to the given match value.
+    fn match_value_check(&self, data: &Statement, value: &Token<Value>) -> Result<Statement> {
+        let value = match value.inner {
+            Value::String(ref string) => stmt![Variable::String(string.to_owned())],
+            Value::Number(ref number) => stmt![number.to_string()],
+            Value::Boolean(ref boolean) => stmt![boolean.to_string()],
+            _ => return Err(Error::pos("unsupported match value".to_owned(), value.pos.clone())),
+        };
+
+        Ok(stmt![data, " === ", value])
+    }
+
+    /// Build the check that `data` is of the given match kind.
+    fn match_type_check(&self, data: &Statement, kind: &MatchKind) -> Statement {
+        match *kind {
+            MatchKind::Any => stmt!["true"],
+            MatchKind::Object => {
+                stmt!["typeof ",
+                      data,
+                      " === \"object\" && ",
+                      data,
+                      " !== null && !Array.isArray(",
+                      data,
+                      ")"]
+            }
+            MatchKind::Array => stmt!["Array.isArray(", data, ")"],
+            MatchKind::String => stmt!["typeof ", data, " === \"string\""],
+            MatchKind::Boolean => stmt!["typeof ", data, " === \"boolean\""],
+            MatchKind::Number => stmt!["typeof ", data, " === \"number\""],
+        }
+    }
+
+    /// Convert an argument to an instance built by a match member.
+    fn match_argument(&self,
+                      value: &Token<Value>,
+                      variable: Option<&MatchVariable>)
+                      -> Result<Statement> {
+        let argument = match value.inner {
+            Value::Identifier(ref identifier) => {
+                if !variable.map(|v| v.name == *identifier).unwrap_or(false) {
+                    return Err(Error::pos(format!("no such variable: {}", identifier),
+                                          value.pos.clone()));
+                }
+
+                stmt![identifier]
+            }
+            Value::String(ref string) => stmt![Variable::String(string.to_owned())],
+            Value::Number(ref number) => stmt![number.to_string()],
+            Value::Boolean(ref boolean) => stmt![boolean.to_string()],
+            _ => return Err(Error::pos("unsupported argument".to_owned(), value.pos.clone())),
+        };
+
+        Ok(argument)
+    }
+
+    /// Build the value that a match member evaluates to.
+    fn match_instance(&self,
+                      package: &Package,
+                      value: &Token<Value>,
+                      variable: Option<&MatchVariable>)
+                      -> Result<Statement> {
+        let instance = match value.inner {
+            Value::Instance(ref instance) => instance,
+            _ => return self.match_argument(value, variable),
+        };
+
+        let fields = self.env.lookup_instance_fields(&instance.pos, package, &instance.ty)?;
+
+        for init in &instance.arguments {
+            if !fields.iter().any(|f| f.name == *init.name) {
+                return Err(Error::pos(format!("no such field: {}", *init.name),
+                                      init.pos.clone()));
+            }
+        }
+
+        let mut arguments = Statement::new();
+
+        for field in fields {
+            if let Some(init) = instance.arguments.iter().find(|a| *a.name == field.name) {
+                arguments.push(self.match_argument(&init.value, variable)?);
+                continue;
+            }
+
+            if !field.is_optional() {
+                return Err(Error::pos(format!("missing required field: {}", field.name),
+                                      instance.pos.clone()));
+            }
+
+            arguments.push("undefined");
+        }
+
+        let name = self.name(&instance.pos, package, &instance.ty)?;
+        Ok(js![new name, arguments])
+    }
+
+    /// Build the decoding of all members of a match declaration.
+    ///
+    /// Value matches are tried first, followed by type matches.
+    fn decode_match(&self,
+                    package: &Package,
+                    name: &str,
+                    match_decl: &MatchDecl,
+                    data: &Statement)
+                    -> Result<Elements> {
+        let mut elements = Elements::new();
+
+        for &(ref value, ref member) in &match_decl.by_value {
+            let cond = self.match_value_check(data, value)?;
+            let instance = self.match_instance(package, &member.value, None)?;
+            elements.push(js![if cond, js![return instance]]);
+        }
+
+        for &(ref kind, ref member) in &match_decl.by_type {
+            if member.is_self_object(name) {
+                continue;
+            }
+
+            if let MatchCondition::Type(ref variable) = member.condition.inner {
+                let pos = &member.condition.pos;
+                let decoded = self.decode(pos, package, &variable.ty, data)?;
+                let instance = self.match_instance(package, &member.value, Some(variable))?;
+
+                let mut body = Elements::new();
+                body.push(stmt!["const ", &variable.name, " = ", decoded, ";"]);
+                body.push(js![return instance]);
+
+                elements.push(js![if self.match_type_check(data, kind), body]);
+            }
+        }
+
+        Ok(elements.join(ElementSpec::Spacing))
+    }
+
+    fn decode_method<F>(&self,
+                        package: &Package,
+                        name: &str,
+                        fields: &Vec<Token<JsField>>,
+                        match_decl: Option<&MatchDecl>,
+                        variable_fn: F)
+                        -> Result<Elements>
+        where F: Fn(usize, &JsField) -> Variable
+    {
+        let data = stmt!["data"];
+
+        let mut decode_match = Elements::new();
+
+        if let Some(match_decl) = match_decl {
+            decode_match = self.decode_match(package, name, match_decl, &data)?;
+        }
+
+        let mut arguments = Statement::new();
+        let mut assign = Elements::new();
+
+        for (i, field) in fields.iter().enumerate() {
+            // prefixed, so that fields cannot shadow `data`.
+            let var_name = format!("f_{}", field.ident);
+            let var = variable_fn(i, field);
+
+            let stmt: ElementSpec = match field.modifier {
+                Modifier::Optional => {
+                    let var_stmt = self.decode(&field.pos, package, &field.ty, &var_name)?;
+
+                    let mut check = Elements::new();
+
+                    check.push(stmt!["let ", &var_name, " = ", &data, "[", &var, "];"]);
+                    check.push(ElementSpec::Spacing);
+                    check.push(js![if is_defined(stmt![&var_name]),
+                                      stmt![&var_name, " = ", var_stmt, ";"],
+                                      stmt![&var_name, " = undefined", ";"]]);
+
+                    check.into()
+                }
+                _ => {
+                    let var_stmt = stmt![&data, "[", &var, "]"];
+                    let var_stmt = self.decode(&field.pos, package, &field.ty, var_stmt)?;
+                    stmt!["const ", &var_name, " = ", &var_stmt, ";"].into()
+                }
+            };
+
+            assign.push(stmt);
+            arguments.push(var_name);
+        }
+
+        let mut body = Elements::new();
+
+        if !decode_match.is_empty() {
+            body.push(decode_match);
+        }
+
+        if !assign.is_empty() {
+            body.push(assign.join(ElementSpec::Spacing));
+        }
+
+        body.push(js![@return new name, arguments]);
+
+        Ok(block(stmt!["static decode(", &data, ": any): ", name], body))
+    }
+
+    /// Check if integers of the given size are represented as bigint.
+    ///
+    /// Numbers can only represent integers of up to 53 bits exactly.
+    fn is_bigint(&self, size: &Option<usize>) -> bool {
+        self.options.bigint && size.map(|s| s > 32).unwrap_or(false)
+    }
+
+    fn is_native(&self, ty: &Type) -> bool {
+        match *ty {
+            Type::Signed(ref size) |
+            Type::Unsigned(ref size) => !self.is_bigint(size),
+            Type::Float | Type::Double => true,
+            Type::String => true,
+            Type::Any => true,
+            Type::Boolean => true,
+            Type::Array(ref inner) => self.is_native(inner),
+            Type::Map(_, ref value) => self.is_native(value),
+            _ => false,
+        }
+    }
+
+    /// Check if the given custom type refers to an enum.
+    ///
+    /// Enums are declared as TypeScript enums, whose values are already in their serialized form.
+    fn is_enum(&self, pos: &Pos, package: &Package, custom: &Custom) -> Result<bool> {
+        // sub types are referenced through their interface.
+        if custom.parts.len() != 1 {
+            return Ok(false);
+        }
+
+        let (_, decl) = self.env.lookup_decl(pos, package, custom)?;
+
+        Ok(match *decl {
+            Decl::Enum(_) => true,
+            _ => false,
+        })
+    }
+
+    fn field_ident(&self, field: &Field) -> String {
+        if let Some(ref id_converter) = self.options.parent.id_converter {
+            id_converter.convert(&field.name)
+        } else {
+            field.name.to_owned()
+        }
+    }
+
+    /// Name of the given custom type.
+    ///
+    /// Sub types are declared in a namespace named after their interface, so all parts are used.
+    fn name(&self, pos: &Pos, package: &Package, custom: &Custom) -> Result<Name> {
+        if let Some(ref used) = custom.prefix {
+            let package = self.env.lookup_used(pos, package, used)?;
+            let package = self.package(package);
+            let package = package.parts.join(".");
+            Ok(Name::imported_alias(&package, &custom.parts.join("."), used).into())
+        } else {
+            Ok(Name::local(&custom.parts.join(".")).into())
+        }
+    }
+
+    /// Check if values of the given type are already in their encoded form.
+    fn is_encoded(&self, pos: &Pos, package: &Package, ty: &Type) -> Result<bool> {
+        let encoded = match *ty {
+            Type::Signed(ref size) |
+            Type::Unsigned(ref size) => !self.is_bigint(size),
+            Type::Bytes => false,
+            Type::Custom(ref custom) => self.is_enum(pos, package, custom)?,
+            Type::Array(ref inner) => self.is_encoded(pos, package, inner)?,
+            Type::Map(_, ref value) => self.is_encoded(pos, package, value)?,
+            _ => true,
+        };
+
+        Ok(encoded)
+    }
+
+    fn encode<S>(&self, pos: &Pos, package: &Package, ty: &Type, value_stmt: S) -> Result<Statement>
+        where S: Into<Statement>
+    {
+        let value_stmt = value_stmt.into();
+
+        if self.is_encoded(pos, package, ty)? {
+            return Ok(value_stmt);
+        }
+
+        let value_stmt = match *ty {
+            Type::Signed(_) |
+            Type::Unsigned(_) => stmt!["String(", value_stmt, ")"],
+            Type::Bytes => stmt![&self.encode_bytes, "(", value_stmt, ")"],
+            Type::Custom(_) => stmt![value_stmt, ".encode()"],
+            Type::Array(ref inner) => {
+                let v = stmt!["v"];
+                let inner = self.encode(pos, package, inner, &v)?;
+                stmt![value_stmt, ".map((", &v, ") => ", inner, ")"]
+            }
+            Type::Map(_, ref value) => {
+                let v = stmt!["v"];
+                let inner = self.encode(pos, package, value, &v)?;
+                stmt![&self.map_values, "(", value_stmt, ", (", &v, ") => ", inner, ")"]
+            }
+            _ => value_stmt,
+        };
+
+        Ok(value_stmt)
+    }
+
+    fn decode<S>(&self, pos: &Pos, package: &Package, ty: &Type, value_stmt: S) -> Result<Statement>
+        where S: Into<Statement>
+    {
+        let value_stmt = value_stmt.into();
+
+        if self.is_native(ty) {
+            return Ok(value_stmt);
+        }
+
+        let value_stmt = match *ty {
+            Type::Signed(_) |
+            Type::Unsigned(_) => stmt!["BigInt(", value_stmt, ")"],
+            Type::Bytes => stmt![&self.decode_bytes, "(", value_stmt, ")"],
+            Type::Custom(ref custom) => {
+                let name = self.name(pos, package, custom)?;
+                stmt![name, ".decode(", value_stmt, ")"]
+            }
+            Type::Array(ref inner) => {
+                let v = stmt!["v"];
+                let inner = self.decode(pos, package, inner, &v)?;
+                stmt![value_stmt, ".map((", &v, ": any) => ", inner, ")"]
+            }
+            Type::Map(_, ref value) => {
+                let v = stmt!["v"];
+                let inner = self.decode(pos, package, value, &v)?;
+                stmt![&self.map_values, "(", value_stmt, ", (", &v, ": any) => ", inner, ")"]
+            }
+            _ => value_stmt,
+        };
+
+        Ok(value_stmt)
+    }
+
+    /// Build the package of a given package.
+    ///
+    /// This includes the prefixed configured in `self.options`, if specified.
+    fn package(&self, package: &Package) -> Package {
+        self.package_prefix
+            .clone()
+            .map(|prefix| prefix.join(package))
+            .unwrap_or_else(|| package.clone())
+    }
+
+    /// Prefix the given element with a TSDoc comment, if there is one.
+    fn tsdoc<E>(&self, comment: &Vec<String>, element: E) -> Elements
+        where E: Into<ElementSpec>
+    {
+        let mut elements = Elements::new();
+        elements.push(block_comment(comment));
+        elements.push(element);
+        elements
+    }
+
+    fn build_constructor(&self,
+                         package: &Package,
+                         fields: &Vec<Token<JsField>>)
+                         -> Result<Elements> {
+        let mut arguments = Statement::new();
+        let mut assignments = Elements::new();
+
+        for field in fields {
+            let ty = self.field_type(&field.pos, package, &field.ty)?;
+
+            match field.modifier {
+                Modifier::Optional => arguments.push(stmt![&field.ident, ": ", ty, " | undefined"]),
+                _ => arguments.push(stmt![&field.ident, ": ", ty]),
+            }
+
+            assignments.push(stmt!["this.", &field.ident, " = ", &field.ident, ";"]);
+        }
+
+        let mut body = Elements::new();
+        body.push(assignments);
+        Ok(block(stmt!["constructor(", arguments.join(", "), ")"], body))
+    }
+
+    /// Build a class with the given fields.
+    ///
+    /// The discriminator, if specified, is a property with a string literal type which is used to
+    /// tell the sub types of an interface apart.
+    fn build_class(&self,
+                   package: &Package,
+                   name: &str,
+                   fields: &Vec<Token<JsField>>,
+                   discriminator: Option<(&str, &str)>,
+                   elements: Elements)
+                   -> Result<Elements> {
+        let mut body = Elements::new();
+        let mut declarations = Elements::new();
+
+        if let Some((key, value)) = discriminator {
+            if let Some(field) = fields.iter().find(|f| f.ident == key || f.name == key) {
+                return Err(Error::pos(format!("field conflicts with type discriminator: {}",
+                                              key),
+                                      field.pos.clone()));
+            }
+
+            let value = string(value);
+            declarations.push(stmt!["readonly ", property(key), ": ", &value, " = ", &value, ";"]);
+        }
+
+        declarations.push(self.field_declarations(package, fields)?);
+
+        body.push(declarations);
+        body.push(self.build_constructor(package, fields)?);
+        body.push(elements.join(ElementSpec::Spacing));
+
+        Ok(block(stmt!["export class ", name], body))
+    }
+
+    fn process_tuple(&self, package: &Package, body: &TupleBody) -> Result<ElementSpec> {
+        let fields = self.convert_required_fields(&body.fields);
+
+        let mut elements = Elements::new();
+
+        elements.push(self.decode_method(package,
+                                         &body.name,
+                                         &fields,
+                                         Some(&body.match_decl),
+                                         field_index)?);
+
+        elements.push(self.encode_tuple_method(package, &fields)?);
+
+        for code in body.codes.for_context(TS_CONTEXT) {
+            elements.push(code.inner.lines);
+        }
+
+        let class = self.build_class(package, &body.name, &fields, None, elements)?;
+        Ok(self.tsdoc(&body.comment, class).into())
+    }
+
+    fn literal_value(&self, pos: &Pos, value: &Value, ty: &Type) -> Result<Variable> {
+        match *ty {
+            Type::Double |
+            Type::Float |
+            Type::Signed(_) |
+            Type::Unsigned(_) |
+            Type::Boolean => {
+                if let Value::Boolean(ref boolean) = *value {
+                    return Ok(Variable::Literal(boolean.to_string()));
+                }
+
+                if let Value::Number(ref number) = *value {
+                    return Ok(Variable::Literal(number.to_string()));
+                }
+            }
+            Type::String => {
+                if let Value::String(ref s) = *value {
+                    return Ok(string(s));
+                }
+            }
+            _ => {}
+        }
+
+        Err(Error::pos(format!("{} cannot be applied to expected type {}", value, ty),
+                       pos.clone()))
+    }
+
+    /// Build the serialized value of every enum value.
+    ///
+    /// Enums serialized as a string field or by name become string enums, everything else becomes
+    /// numeric enums.
+    fn enum_serialized_values(&self,
+                              body: &EnumBody,
+                              fields: &Vec<Token<JsField>>)
+                              -> Result<Vec<Variable>> {
+        let mut values = Vec::new();
+
+        // lookup serialized_as if specified.
+        if let Some(ref s) = body.serialized_as {
+            let (index, field) = match self.find_field(fields, &s.inner) {
+                Some(found) => found,
+                None => {
+                    return Err(Error::pos(format!("no field named: {}", s.inner), s.pos.clone()))
+                }
+            };
+
+            match field.ty {
+                Type::String | Type::Signed(_) | Type::Unsigned(_) | Type::Float |
+                Type::Double => {}
+                ref ty => {
+                    return Err(Error::pos(format!("enum cannot be serialized as type {}", ty),
+                                          s.pos.clone()));
+                }
+            }
+
+            for value in &body.values {
+                let argument = value.arguments
+                    .get(index)
+                    .ok_or_else(|| Error::pos(format!("missing argument: {}", field.name),
+                                              value.pos.clone()))?;
+
+                values.push(self.literal_value(&argument.pos, argument, &field.ty)?);
+            }
+
+            return Ok(values);
+        }
+
+        for value in &body.values {
+            if body.serialized_as_name {
+                values.push(string(&value.name));
+            } else {
+                values.push(Variable::Literal(value.ordinal.to_string()));
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Build the functions accessing the fields of enum values.
+    fn enum_field_functions(&self,
+                            package: &Package,
+                            body: &EnumBody,
+                            fields: &Vec<Token<JsField>>)
+                            -> Result<Elements> {
+        let mut functions = Elements::new();
+        let value = stmt!["value"];
+
+        for (index, field) in fields.iter().enumerate() {
+            let ty = self.field_type(&field.pos, package, &field.ty)?;
+
+            let mut cases = Elements::new();
+
+            for enum_value in &body.values {
+                let argument = enum_value.arguments
+                    .get(index)
+                    .ok_or_else(|| Error::pos(format!("missing argument: {}", field.name),
+                                              enum_value.pos.clone()))?;
+
+                let argument = self.literal_value(&argument.pos, argument, &field.ty)?;
+
+                cases.push(stmt!["case ", &body.name, ".", &enum_value.name, ":"]);
+                cases.push_nested(js![return argument]);
+            }
+
+            let mut switch = Elements::new();
+            switch.push(cases);
+
+            let mut function_body = Elements::new();
+            function_body.push(block(stmt!["switch (", &value, ")"], switch));
+
+            let signature = stmt!["export function ",
+                                  &field.ident,
+                                  "(",
+                                  &value,
+                                  ": ",
+                                  &body.name,
+                                  "): ",
+                                  ty];
+
+            functions.push(self.tsdoc(&field.comment, block(signature, function_body)));
+        }
+
+        Ok(functions.join(ElementSpec::Spacing))
+    }
+
+    fn process_enum(&self, package: &Package, body: &EnumBody) -> Result<ElementSpec> {
+        let fields = self.convert_required_fields(&body.fields);
+
+        let serialized = self.enum_serialized_values(body, &fields)?;
+
+        let mut members = Elements::new();
+        let mut values = Statement::new();
+
+        for (value, serialized) in body.values.iter().zip(serialized.into_iter()) {
+            members.push(block_comment(&value.comment));
+            members.push(stmt![&value.name, " = ", serialized, ","]);
+            values.push(stmt![&body.name, ".", &value.name]);
+        }
+
+        let mut enum_body = Elements::new();
+        enum_body.push(members);
+
+        let data = stmt!["data"];
+        let i = stmt!["i"];
+        let l = stmt!["l"];
+        let member = stmt!["member"];
+        let all = stmt!["values"];
+
+        let mut loop_body = Elements::new();
+        loop_body.push(js![const &member, &all, "[", &i, "];"]);
+        loop_body.push(js![if stmt![&member, " === ", &data], js![return &member]]);
+
+        let loop_init = stmt!["let ", &i, " = 0, ", &l, " = ", &all, ".length"];
+
+        let mut decode_body = Elements::new();
+        decode_body.push(js![for loop_init; stmt![&i, " < ", &l]; stmt![&i, "++"], loop_body]);
+        decode_body.push(js![throw string("no matching value")]);
+
+        let mut namespace = Elements::new();
+        namespace.push(stmt!["const ", &all, ": ", &body.name, "[] = ", js!([values]), ";"]);
+        namespace.push(block(stmt!["export function decode(", &data, ": any): ", &body.name],
+                             decode_body));
+        let functions = self.enum_field_functions(package, body, &fields)?;
+
+        if !functions.is_empty() {
+            namespace.push(functions);
+        }
+
+        for code in body.codes.for_context(TS_CONTEXT) {
+            namespace.push(code.inner.lines);
+        }
+
+        let mut elements = Elements::new();
+        elements.push(self.tsdoc(&body.comment, block(stmt!["export enum ", &body.name], enum_body)));
+        elements.push(block(stmt!["export namespace ", &body.name], namespace));
+
+        Ok(elements.join(ElementSpec::Spacing).into())
+    }
+
+    fn process_type(&self, package: &Package, body: &TypeBody) -> Result<ElementSpec> {
+        let fields = self.convert_fields(&body.fields);
+
+        let mut elements = Elements::new();
+
+        elements.push(self.decode_method(package,
+                                         &body.name,
+                                         &fields,
+                                         Some(&body.match_decl),
+                                         field_name)?);
+
+        elements.push(self.encode_method(package, &fields, None, |_| {})?);
+
+        for code in body.codes.for_context(TS_CONTEXT) {
+            elements.push(code.inner.lines);
+        }
+
+        let class = self.build_class(package, &body.name, &fields, None, elements)?;
+        Ok(self.tsdoc(&body.comment, class).into())
+    }
+
+    fn process_interface(&self, package: &Package, body: &InterfaceBody) -> Result<ElementSpec> {
+        let interface_fields = self.convert_fields(&body.fields);
+
+        let discriminator = match body.type_info {
+            TypeInfo::TypeField(ref type_field) => type_field.as_str(),
+            TypeInfo::ObjectKeys => DEFAULT_DISCRIMINATOR,
+        };
+
+        let mut namespace = Elements::new();
+        let mut union = Statement::new();
+
+        namespace.push(self.interface_decode_method(package, body)?);
+
+        for code in body.codes.for_context(TS_CONTEXT) {
+            namespace.push(code.inner.lines);
+        }
+
+        for (_, ref sub_type) in &body.sub_types {
+            let mut fields = interface_fields.clone();
+            fields.extend(self.convert_fields(&sub_type.fields));
+
+            let mut elements = Elements::new();
+
+            elements.push(self.decode_method(package, &sub_type.name, &fields, None, field_name)?);
+
+            let type_name = stmt!["this.", property(discriminator)];
+
+            let encode = match body.type_info {
+                TypeInfo::TypeField(ref type_field) => {
+                    let type_stmt = stmt!["data[", string(type_field), "] = ", type_name, ";"];
+
+                    self.encode_method(package, &fields, None, move |elements| {
+                            elements.push(type_stmt);
+                        })?
+                }
+                TypeInfo::ObjectKeys => {
+                    self.encode_method(package, &fields, Some(type_name), |_| {})?
+                }
+            };
+
+            elements.push(encode);
+
+            for code in sub_type.codes.for_context(TS_CONTEXT) {
+                elements.push(code.inner.lines);
+            }
+
+            let class = self.build_class(package,
+                                         &sub_type.name,
+                                         &fields,
+                                         Some((discriminator, &sub_type.name())),
+                                         elements)?;
+
+            namespace.push(self.tsdoc(&sub_type.comment, class));
+            union.push(stmt![&body.name, ".", &sub_type.name]);
+        }
+
+        let union = if union.parts.is_empty() {
+            stmt!["never"]
+        } else {
+            union.join(" | ")
+        };
+
+        let mut elements = Elements::new();
+
+        elements.push(self.tsdoc(&body.comment,
+                                 stmt!["export type ", &body.name, " = ", union, ";"]));
+        elements.push(block(stmt!["export namespace ", &body.name], namespace));
+
+        Ok(elements.join(ElementSpec::Spacing).into())
+    }
+
+    fn interface_decode_method(&self,
+                               package: &Package,
+                               interface: &InterfaceBody)
+                               -> Result<Elements> {
+        let data = stmt!["data"];
+
+        let mut body = Elements::new();
+
+        let decode_match =
+            self.decode_match(package, &interface.name, &interface.match_decl, &data)?;
+
+        if !decode_match.is_empty() {
+            body.push(decode_match);
+        }
+
+        let type_field = Variable::Literal("f_type".to_owned());
+
+        // the data that the sub type is decoded from.
+        let sub_type_data = match interface.type_info {
+            TypeInfo::TypeField(ref name) => {
+                body.push(stmt!["const ", &type_field, " = ", &data, "[", string(name), "];"]);
+                data.clone()
+            }
+            TypeInfo::ObjectKeys => {
+                let keys = stmt!["keys"];
+                let sub_type_data = stmt!["f_data"];
+
+                let mut elements = Elements::new();
+                elements.push(js![const &keys, "Object.keys(", &data, ");"]);
+                elements.push(js![if stmt![&keys, ".length !== 1"],
+                                  js![throw string("expected exactly one key")]]);
+                elements.push(js![const &type_field, &keys, "[0];"]);
+                elements.push(js![const &sub_type_data, &data, "[", &type_field, "];"]);
+                body.push(elements);
+
+                sub_type_data
+            }
+        };
+
+        for (_, ref sub_type) in &interface.sub_types {
+            // sub types without explicit names are serialized using their own name.
+            let names: Vec<String> = if sub_type.names.is_empty() {
+                vec![sub_type.name()]
+            } else {
+                sub_type.names.iter().map(|n| n.inner.clone()).collect()
+            };
+
+            for name in names {
+                let type_name: Variable = Name::local(&sub_type.name).into();
+                let cond = stmt![&type_field, " === ", string(name)];
+                body.push(js![if cond, js![return type_name, ".decode(", &sub_type_data, ")"]]);
+            }
+        }
+
+        body.push(js![throw string("bad type")]);
+
+        let signature = stmt!["export function decode(", &data, ": any): ", &interface.name];
+        Ok(block(signature, body))
+    }
+
+    fn populate_files(&self) -> Result<HashMap<&Package, Elements>> {
+        let mut files = HashMap::new();
+
+        // Process all types discovered so far.
+        for (&(ref package, _), decl) in &self.env.types {
+            let spec = match decl.inner {
+                Decl::Interface(ref body) => self.process_interface(package, body)?,
+                Decl::Type(ref body) => self.process_type(package, body)?,
+                Decl::Tuple(ref body) => self.process_tuple(package, body)?,
+                Decl::Enum(ref body) => self.process_enum(package, body)?,
+            };
+
+            match files.entry(package) {
+                Entry::Vacant(entry) => {
+                    let mut elements = Elements::new();
+                    elements.push(spec);
+                    entry.insert(elements);
+                }
+                Entry::Occupied(entry) => {
+                    let elements = entry.into_mut();
+                    elements.push(spec);
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Build the path of a module, relative to the module of the given package.
+    fn relative_module(&self, package: &Package, module: &str) -> String {
+        let from: Vec<&str> = package.parts.iter().map(String::as_str).collect();
+        let from = &from[..from.len().saturating_sub(1)];
+
+        let to: Vec<&str> = module.split(".").collect();
+
+        let common = from.iter()
+            .zip(to[..to.len() - 1].iter())
+            .take_while(|&(a, b)| a == b)
+            .count();
+
+        let mut path = String::new();
+
+        if common == from.len() {
+            path.push_str("./");
+        }
+
+        for _ in common..from.len() {
+            path.push_str("../");
+        }
+
+        path.push_str(&to[common..].join("/"));
+        path
+    }
+
+    /// Build the import statements for all the given imported names.
+    fn imports(&self, package: &Package, imported: BTreeSet<ImportedName>) -> Elements {
+        // specific imports from modules.
+        let mut collected: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        // * imported modules, and their alias.
+        let mut wildcard: BTreeSet<(String, String)> = BTreeSet::new();
+
+        for import in imported {
+            if let Some(alias) = import.alias {
+                wildcard.insert((import.module, alias));
+            } else {
+                collected.entry(import.module).or_insert_with(Vec::new).push(import.name);
+            }
+        }
+
+        let mut out = Elements::new();
+
+        for (module, names) in collected {
+            let path = string(self.relative_module(package, &module));
+            out.push(stmt!["import {", names.join(", "), "} from ", path, ";"]);
+        }
+
+        for (module, alias) in wildcard {
+            let path = string(self.relative_module(package, &module));
+            out.push(stmt!["import * as ", alias, " from ", path, ";"]);
+        }
+
+        out
+    }
+
+    fn module_path(&self, package: &Package) -> PathBuf {
+        let mut full_path = package.parts.iter().fold(PathBuf::new(), |path, part| path.join(part));
+
+        // path to final file
+        full_path.set_extension(EXT);
+        full_path
+    }
+
+    fn write_file(&self, full_path: PathBuf, out: String) -> Result<()> {
+        debug!("+module: {}", full_path.display());
+        self.options.parent.output.write(&full_path, out.as_bytes())
+    }
+
+    fn write_files(&self, files: HashMap<&Package, Elements>) -> Result<()> {
+        let mut uses_runtime = false;
+
+        for (package, elements) in files {
+            let package = self.package(package);
+            let full_path = self.module_path(&package);
+
+            let mut imported: BTreeSet<ImportedName> = BTreeSet::new();
+            elements.imports(&mut imported);
+
+            uses_runtime = uses_runtime || imported.iter().any(|i| i.module == RUNTIME_MODULE);
+
+            let imports = self.imports(&package, imported);
+
+            let mut out = Elements::new();
+
+            if !imports.is_empty() {
+                out.push(imports);
+            }
+
+            out.push(elements.join(ElementSpec::Spacing));
+
+            let elements: ElementSpec = out.join(ElementSpec::Spacing).into();
+
+            let mut formatted = String::new();
+            elements.format("", "  ", &mut formatted);
+            self.write_file(full_path, formatted.end())?;
+        }
+
+        if uses_runtime {
+            let runtime = Package::new(vec![RUNTIME_MODULE.to_owned()]);
+            let full_path = self.module_path(&runtime);
+            self.write_file(full_path, RUNTIME_TS.to_owned())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Backend for Processor {
+    fn process(&self) -> Result<()> {
+        let files = self.populate_files()?;
+        self.write_files(files)
+    }
+
+    fn verify(&self) -> Result<Vec<Error>> {
+        Ok(vec![])
+    }
+}
@@ -0,0 +1,27 @@
+/**
+ * Build a new object by applying the given function to every value of an object used as a map.
+ */
+export function mapValues<T, U>(values: {[key: string]: T},
+                                f: (value: T) => U): {[key: string]: U} {
+  const out: {[key: string]: U} = {};
+
+  for (const key of Object.keys(values)) {
+    out[key] = f(values[key]);
+  }
+
+  return out;
+}
+
+/**
+ * Encode bytes as a base64 string.
+ */
+export function encodeBytes(bytes: Uint8Array): string {
+  return btoa(Array.from(bytes, (b) => String.fromCharCode(b)).join(""));
+}
+
+/**
+ * Decode a base64 string into bytes.
+ */
+export function decodeBytes(data: string): Uint8Array {
+  return Uint8Array.from(atob(data), (c) => c.charCodeAt(0));
+}
@@ -0,0 +1,662 @@
+use super::environment::Environment;
+use super::errors::*;
+use super::json::Json;
+use super::models::*;
+
+/// A single way in which a payload does not conform to its declaration.
+#[derive(Debug)]
+pub struct Violation {
+    /// JSON pointer to the offending value.
+    pub path: String,
+    pub message: String,
+}
+
+/// Escape a single reference token of a JSON pointer.
+fn pointer_token(token: &str) -> String {
+    token.replace("~", "~0").replace("/", "~1")
+}
+
+fn json_kind(value: &Json) -> &'static str {
+    match *value {
+        Json::Null => "null",
+        Json::Boolean(_) => "boolean",
+        Json::Number(_) => "number",
+        Json::String(_) => "string",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+    }
+}
+
+fn is_integer(number: &str) -> bool {
+    !number.contains(|c| c == '.' || c == 'e' || c == 'E')
+}
+
+/// Check if the string is an integer in its decimal form.
+fn is_decimal(input: &str) -> bool {
+    let digits = input.trim_left_matches('-');
+    !digits.is_empty() && digits.chars().all(|c| c.is_digit(10)) && input.len() - digits.len() <= 1
+}
+
+/// Integers of more than 32 bits might not fit in a number, so they can also be encoded as
+/// strings.
+fn is_wide(size: &Option<usize>) -> bool {
+    size.map(|s| s > 32).unwrap_or(false)
+}
+
+fn is_base64(input: &str) -> bool {
+    if input.len() % 4 != 0 {
+        return false;
+    }
+
+    let data = input.trim_right_matches('=');
+
+    if input.len() - data.len() > 2 {
+        return false;
+    }
+
+    data.chars().all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '/')
+}
+
+/// Test if the given JSON value matches the constant value of an enum argument.
+fn value_matches(value: &Json, expected: &Value) -> bool {
+    match (value, expected) {
+        (&Json::String(ref value), &Value::String(ref expected)) => value == expected,
+        (&Json::Boolean(ref value), &Value::Boolean(ref expected)) => value == expected,
+        (&Json::Number(ref value), &Value::Number(ref expected)) => {
+            value.parse::<f64>().map(|v| v == *expected).unwrap_or(false)
+        }
+        _ => false,
+    }
+}
+
+/// Test if the given JSON value is of the kind that a match member applies to.
+fn kind_matches(kind: &MatchKind, value: &Json) -> bool {
+    match (kind, value) {
+        (&MatchKind::Any, _) => true,
+        (&MatchKind::Object, &Json::Object(_)) => true,
+        (&MatchKind::Array, &Json::Array(_)) => true,
+        (&MatchKind::String, &Json::String(_)) => true,
+        (&MatchKind::Boolean, &Json::Boolean(_)) => true,
+        (&MatchKind::Number, &Json::Number(_)) => true,
+        _ => false,
+    }
+}
+
+/// Validates JSON payloads against the declarations of an environment.
+pub struct Validator<'a> {
+    env: &'a Environment,
+    violations: Vec<Violation>,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(env: &'a Environment) -> Validator<'a> {
+        Validator {
+            env: env,
+            violations: Vec::new(),
+        }
+    }
+
+    /// Validate the given value against the declaration with the given name in `package`.
+    ///
+    /// Returns one violation for every part of the value that does not conform.
+    pub fn validate(mut self,
+                    package: &Package,
+                    name: &str,
+                    value: &Json)
+                    -> Result<Vec<Violation>> {
+        let key = (package.clone(), vec![name.to_owned()]);
+
+        let decl = self.env
+            .types
+            .get(&key)
+            .ok_or_else(|| format!("no such type: {}.{}", package, name))?;
+
+        self.decl(&mut String::new(), package, decl, value)?;
+        Ok(self.violations)
+    }
+
+    fn violation(&mut self, path: &str, message: String) {
+        self.violations.push(Violation {
+            path: path.to_owned(),
+            message: message,
+        });
+    }
+
+    fn expected(&mut self, path: &str, expected: &str, value: &Json) {
+        self.violation(path, format!("expected {}, but was {}", expected, json_kind(value)));
+    }
+
+    /// Run the given validation with `token` appended to the current path.
+    fn nested<F>(&mut self, path: &mut String, token: &str, f: F) -> Result<()>
+        where F: FnOnce(&mut Self, &mut String) -> Result<()>
+    {
+        let len = path.len();
+        path.push('/');
+        path.push_str(&pointer_token(token));
+        let result = f(self, path);
+        path.truncate(len);
+        result
+    }
+
+    fn integer(&mut self, path: &str, signed: bool, size: &Option<usize>, value: &Json) {
+        let number = match *value {
+            Json::Number(ref number) if is_integer(number) => number,
+            Json::String(ref number) if is_wide(size) && is_decimal(number) => number,
+            _ => {
+                self.expected(path, "integer", value);
+                return;
+            }
+        };
+
+        if !signed && number.starts_with('-') {
+            self.violation(path, format!("expected unsigned integer, but was {}", number));
+            return;
+        }
+
+        let size = match *size {
+            Some(size) if size > 0 && size <= 64 => size,
+            _ => return,
+        };
+
+        let in_range = if signed {
+            let max = ((1u64 << (size - 1)) - 1) as i64;
+            number.parse::<i64>().map(|n| n >= -max - 1 && n <= max).unwrap_or(false)
+        } else {
+            let max = if size == 64 { u64::max_value() } else { (1u64 << size) - 1 };
+            number.parse::<u64>().map(|n| n <= max).unwrap_or(false)
+        };
+
+        if !in_range {
+            let kind = if signed { "signed" } else { "unsigned" };
+            self.violation(path, format!("{} does not fit in {}/{}", number, kind, size));
+        }
+    }
+
+    /// Map keys are always strings in JSON, integer keys are encoded in their decimal form.
+    fn map_key(&mut self, path: &str, key: &Type, name: &str) {
+        let (signed, size) = match *key {
+            Type::Signed(ref size) => (true, size),
+            Type::Unsigned(ref size) => (false, size),
+            _ => return,
+        };
+
+        if !is_decimal(name) {
+            self.violation(path, format!("expected integer key, but was `{}`", name));
+            return;
+        }
+
+        self.integer(path, signed, size, &Json::Number(name.to_owned()));
+    }
+
+    fn ty(&mut self,
+          path: &mut String,
+          pos: &Pos,
+          package: &Package,
+          ty: &Type,
+          value: &Json)
+          -> Result<()> {
+        match *ty {
+            Type::Double | Type::Float => {
+                if let Json::Number(_) = *value {
+                } else {
+                    self.expected(path, "number", value);
+                }
+            }
+            Type::Signed(ref size) => self.integer(path, true, size, value),
+            Type::Unsigned(ref size) => self.integer(path, false, size, value),
+            Type::Boolean => {
+                if let Json::Boolean(_) = *value {
+                } else {
+                    self.expected(path, "boolean", value);
+                }
+            }
+            Type::String => {
+                if let Json::String(_) = *value {
+                } else {
+                    self.expected(path, "string", value);
+                }
+            }
+            Type::Bytes => {
+                match *value {
+                    Json::String(ref string) if is_base64(string) => {}
+                    Json::String(_) => {
+                        self.violation(path, "expected base64 encoded bytes".to_owned());
+                    }
+                    _ => self.expected(path, "string", value),
+                }
+            }
+            Type::Any => {}
+            Type::Array(ref inner) => {
+                if let Json::Array(ref values) = *value {
+                    for (index, value) in values.iter().enumerate() {
+                        self.nested(path,
+                                    &index.to_string(),
+                                    |v, path| v.ty(path, pos, package, inner, value))?;
+                    }
+                } else {
+                    self.expected(path, "array", value);
+                }
+            }
+            Type::Map(ref key, ref inner) => {
+                if let Json::Object(ref entries) = *value {
+                    for &(ref name, ref value) in entries {
+                        self.nested(path, name, |v, path| {
+                                v.map_key(path, key, name);
+                                v.ty(path, pos, package, inner, value)
+                            })?;
+                    }
+                } else {
+                    self.expected(path, "object", value);
+                }
+            }
+            Type::Custom(ref custom) => {
+                let target = if let Some(ref prefix) = custom.prefix {
+                    self.env.lookup_used(pos, package, prefix)?
+                } else {
+                    package
+                };
+
+                let key = (target.clone(), custom.parts.clone());
+
+                let decl = self.env
+                    .types
+                    .get(&key)
+                    .ok_or_else(|| {
+                        Error::pos(format!("no such type: {}", custom.parts.join(".")),
+                                   pos.clone())
+                    })?;
+
+                self.decl(path, target, decl, value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fields(&mut self,
+              path: &mut String,
+              package: &Package,
+              fields: &[&Token<Field>],
+              entries: &Vec<(String, Json)>)
+              -> Result<()> {
+        for field in fields {
+            let value = entries.iter().find(|e| e.0 == field.name()).map(|e| &e.1);
+
+            match value {
+                None | Some(&Json::Null) if field.is_optional() => {}
+                None => {
+                    self.violation(path, format!("missing required field `{}`", field.name()));
+                }
+                Some(value) => {
+                    self.nested(path,
+                                field.name(),
+                                |v, path| v.ty(path, &field.pos, package, &field.ty, value))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn enum_value(&mut self, path: &str, body: &EnumBody, value: &Json) -> Result<()> {
+        if let Some(ref s) = body.serialized_as {
+            let index = body.fields
+                .iter()
+                .position(|f| f.name == s.inner)
+                .ok_or_else(|| Error::pos(format!("no field named: {}", s.inner), s.pos.clone()))?;
+
+            let found = body.values
+                .iter()
+                .filter_map(|v| v.arguments.get(index))
+                .any(|argument| value_matches(value, &argument.inner));
+
+            if !found {
+                self.violation(path, format!("not a valid value for enum {}", body.name));
+            }
+
+            return Ok(());
+        }
+
+        match *value {
+            Json::String(ref name) => {
+                if !body.values.iter().any(|v| v.name == *name) {
+                    self.violation(path,
+                                   format!("`{}` is not a value of enum {}", name, body.name));
+                }
+            }
+            _ => self.expected(path, "string", value),
+        }
+
+        Ok(())
+    }
+
+    fn sub_type_by_name<'b>(&self,
+                            body: &'b InterfaceBody,
+                            name: &str)
+                            -> Option<&'b Token<SubType>> {
+        body.sub_types.values().find(|sub_type| {
+            if sub_type.names.is_empty() {
+                sub_type.name == name
+            } else {
+                sub_type.names.iter().any(|n| n.inner == name)
+            }
+        })
+    }
+
+    fn interface(&mut self,
+                 path: &mut String,
+                 package: &Package,
+                 body: &InterfaceBody,
+                 value: &Json)
+                 -> Result<()> {
+        let entries = match *value {
+            Json::Object(ref entries) => entries,
+            _ => {
+                self.expected(path, "object", value);
+                return Ok(());
+            }
+        };
+
+        match body.type_info {
+            TypeInfo::TypeField(ref type_field) => {
+                let name = match entries.iter().find(|e| e.0 == *type_field).map(|e| &e.1) {
+                    Some(&Json::String(ref name)) => name,
+                    Some(value) => {
+                        return self.nested(path, type_field, |v, path| {
+                            v.expected(path, "string", value);
+                            Ok(())
+                        });
+                    }
+                    None => {
+                        self.violation(path, format!("missing type field `{}`", type_field));
+                        return Ok(());
+                    }
+                };
+
+                match self.sub_type_by_name(body, name) {
+                    Some(sub_type) => {
+                        let fields: Vec<_> =
+                            body.fields.iter().chain(sub_type.fields.iter()).collect();
+                        self.fields(path, package, &fields, entries)?;
+                    }
+                    None => {
+                        self.nested(path, type_field, |v, path| {
+                                v.violation(path,
+                                            format!("`{}` is not a sub type of {}",
+                                                    name,
+                                                    body.name));
+                                Ok(())
+                            })?;
+                    }
+                }
+            }
+            TypeInfo::ObjectKeys => {
+                if entries.len() != 1 {
+                    self.violation(path,
+                                   format!("expected exactly one key, but was {}", entries.len()));
+                    return Ok(());
+                }
+
+                let (ref name, ref value) = entries[0];
+
+                match self.sub_type_by_name(body, name) {
+                    Some(sub_type) => {
+                        self.nested(path, name, |v, path| {
+                                match *value {
+                                    Json::Object(ref entries) => {
+                                        let fields: Vec<_> = body.fields
+                                            .iter()
+                                            .chain(sub_type.fields.iter())
+                                            .collect();
+                                        v.fields(path, package, &fields, entries)
+                                    }
+                                    _ => {
+                                        v.expected(path, "object", value);
+                                        Ok(())
+                                    }
+                                }
+                            })?;
+                    }
+                    None => {
+                        self.violation(path,
+                                       format!("`{}` is not a sub type of {}", name, body.name));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate the value through the members of a match declaration, in the order that they are
+    /// tried by decoders.
+    ///
+    /// Returns `true` if any member applies to the value.
+    fn match_decl(&mut self,
+                  path: &mut String,
+                  package: &Package,
+                  name: &str,
+                  match_decl: &MatchDecl,
+                  value: &Json)
+                  -> Result<bool> {
+        if match_decl.by_value.iter().any(|&(ref v, _)| value_matches(value, &v.inner)) {
+            return Ok(true);
+        }
+
+        for &(ref kind, ref member) in &match_decl.by_type {
+            if member.is_self_object(name) || !kind_matches(kind, value) {
+                continue;
+            }
+
+            if let MatchCondition::Type(ref variable) = member.condition.inner {
+                self.ty(path, &member.condition.pos, package, &variable.ty, value)?;
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn decl(&mut self,
+            path: &mut String,
+            package: &Package,
+            decl: &Decl,
+            value: &Json)
+            -> Result<()> {
+        let match_decl = match *decl {
+            Decl::Type(ref body) => &body.match_decl,
+            Decl::Tuple(ref body) => &body.match_decl,
+            Decl::Enum(ref body) => &body.match_decl,
+            Decl::Interface(ref body) => &body.match_decl,
+        };
+
+        if self.match_decl(path, package, decl.name(), match_decl, value)? {
+            return Ok(());
+        }
+
+        match *decl {
+            Decl::Type(ref body) => {
+                if let Json::Object(ref entries) = *value {
+                    let fields: Vec<_> = body.fields.iter().collect();
+                    self.fields(path, package, &fields, entries)?;
+                } else {
+                    self.expected(path, "object", value);
+                }
+            }
+            Decl::Tuple(ref body) => {
+                if let Json::Array(ref values) = *value {
+                    if values.len() != body.fields.len() {
+                        self.violation(path,
+                                       format!("expected {} element(s), but was {}",
+                                               body.fields.len(),
+                                               values.len()));
+                    } else {
+                        for (index, (field, value)) in
+                            body.fields.iter().zip(values.iter()).enumerate() {
+                            self.nested(path, &index.to_string(), |v, path| {
+                                    v.ty(path, &field.pos, package, &field.ty, value)
+                                })?;
+                        }
+                    }
+                } else {
+                    self.expected(path, "array", value);
+                }
+            }
+            Decl::Enum(ref body) => self.enum_value(path, body, value)?,
+            Decl::Interface(ref body) => self.interface(path, package, body, value)?,
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+    use super::super::json;
+
+    const SPEC: &str = "package a;
+
+type T {
+  a: string;
+  b?: unsigned/8;
+  c?: signed/16;
+  d?: unsigned/64;
+}
+
+tuple P {
+  x: double;
+  y: double;
+}
+
+enum E {
+  A(\"a\");
+  B(\"b\");
+
+  serialized_as v;
+
+  v: string;
+}
+
+interface I {
+  match {
+    n: double => I.Circle(radius: n);
+  }
+
+  Circle {
+    name \"circle\";
+
+    radius: double;
+  }
+}
+
+interface K {
+  type_info object_keys;
+
+  Square {
+    side: double;
+  }
+}
+";
+
+    fn validate(name: &str, value: &str) -> Vec<String> {
+        let mut env = Environment::new(vec![PathBuf::from("test")]);
+        env.add_source(PathBuf::from("test").join("a.reproto"), SPEC.to_owned());
+
+        let package = Package::new(vec!["a".to_owned()]);
+        env.import(&package).unwrap();
+        env.verify().unwrap();
+
+        let value = json::parse(value).unwrap();
+
+        Validator::new(&env)
+            .validate(&package, name, &value)
+            .unwrap()
+            .into_iter()
+            .map(|v| format!("{}: {}", v.path, v.message))
+            .collect()
+    }
+
+    fn assert_valid(name: &str, value: &str) {
+        assert_eq!(Vec::<String>::new(), validate(name, value), "{}", value);
+    }
+
+    #[test]
+    fn test_fields() {
+        assert_valid("T", r#"{"a": "x"}"#);
+        assert_valid("T", r#"{"a": "x", "b": null}"#);
+
+        assert_eq!(vec![": missing required field `a`"], validate("T", r#"{"b": 1}"#));
+        assert_eq!(vec!["/a: expected string, but was number"], validate("T", r#"{"a": 1}"#));
+        assert_eq!(vec![": expected object, but was array"], validate("T", "[]"));
+    }
+
+    #[test]
+    fn test_integer_sizes() {
+        assert_valid("T", r#"{"a": "x", "b": 255, "c": -32768}"#);
+
+        assert_eq!(vec!["/b: 256 does not fit in unsigned/8",
+                        "/c: 32768 does not fit in signed/16"],
+                   validate("T", r#"{"a": "x", "b": 256, "c": 32768}"#));
+
+        assert_eq!(vec!["/b: expected unsigned integer, but was -1",
+                        "/c: expected integer, but was number"],
+                   validate("T", r#"{"a": "x", "b": -1, "c": 1.5}"#));
+    }
+
+    #[test]
+    fn test_wide_integer_strings() {
+        assert_valid("T", r#"{"a": "x", "d": 18446744073709551615}"#);
+        assert_valid("T", r#"{"a": "x", "d": "18446744073709551615"}"#);
+
+        assert_eq!(vec!["/d: 18446744073709551616 does not fit in unsigned/64"],
+                   validate("T", r#"{"a": "x", "d": "18446744073709551616"}"#));
+        assert_eq!(vec!["/d: expected integer, but was string"],
+                   validate("T", r#"{"a": "x", "d": "1.5"}"#));
+        assert_eq!(vec!["/c: expected integer, but was string"],
+                   validate("T", r#"{"a": "x", "c": "1"}"#));
+    }
+
+    #[test]
+    fn test_tuple() {
+        assert_valid("P", "[1, 2.5]");
+
+        assert_eq!(vec![": expected 2 element(s), but was 3"], validate("P", "[1, 2, 3]"));
+        assert_eq!(vec!["/1: expected number, but was string"], validate("P", r#"[1, "2"]"#));
+    }
+
+    #[test]
+    fn test_enum_serialized_as() {
+        assert_valid("E", r#""a""#);
+
+        assert_eq!(vec![": not a valid value for enum E"], validate("E", r#""A""#));
+    }
+
+    #[test]
+    fn test_type_field() {
+        assert_valid("I", r#"{"type": "circle", "radius": 1}"#);
+
+        assert_eq!(vec![": missing type field `type`"], validate("I", r#"{"radius": 1}"#));
+        assert_eq!(vec!["/type: `Circle` is not a sub type of I"],
+                   validate("I", r#"{"type": "Circle"}"#));
+        assert_eq!(vec![": missing required field `radius`"],
+                   validate("I", r#"{"type": "circle"}"#));
+    }
+
+    #[test]
+    fn test_object_keys() {
+        assert_valid("K", r#"{"Square": {"side": 2}}"#);
+
+        assert_eq!(vec![": expected exactly one key, but was 0"], validate("K", "{}"));
+        assert_eq!(vec!["/Square/side: expected number, but was string"],
+                   validate("K", r#"{"Square": {"side": "2"}}"#));
+    }
+
+    #[test]
+    fn test_match() {
+        assert_valid("I", "3.5");
+
+        assert_eq!(vec![": expected object, but was string"], validate("I", r#""x""#));
+    }
+}
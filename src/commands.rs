@@ -1,11 +1,26 @@
+use backend::compat::Compat;
 use backend::environment::Environment;
+use backend::samples::Generator;
+use backend::validate::Validator;
 use backend::models as m;
+use backend::output::{FileSystemOutput, Output};
 use backend;
 use clap::{Arg, App, SubCommand, ArgMatches};
 use errors::*;
+use import;
+use language_server::Server;
 use naming;
 use options::Options;
-use std::path::Path;
+use parser::{self, ast, format};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Milliseconds between each check for changes when watching.
+const WATCH_INTERVAL: u64 = 500;
 
 fn parse_id_converter(input: &str) -> Result<Box<naming::Naming>> {
     let mut parts = input.split(":");
@@ -85,21 +100,24 @@ pub fn compile_options<'a, 'b>(name: &str) -> App<'a, 'b> {
             .number_of_values(1))
 }
 
-fn setup_compiler<'a>
-    (matches: &'a ArgMatches)
-     -> Result<(Vec<&'a Path>, Vec<m::Package>, Environment, Options, &'a str)> {
-    let paths: Vec<::std::path::PathBuf> = matches.values_of("path")
+fn out_path<'a>(matches: &'a ArgMatches) -> Result<&'a Path> {
+    let out_path = matches.value_of("out").ok_or("--out <dir> is required")?;
+    Ok(Path::new(out_path))
+}
+
+fn setup_environment(matches: &ArgMatches) -> Environment {
+    let paths: Vec<PathBuf> = matches.values_of("path")
         .into_iter()
         .flat_map(|it| it)
         .map(Path::new)
         .map(ToOwned::to_owned)
         .collect();
 
-    let backend = matches.value_of("backend").ok_or("--backend <backend> is required")?;
-    let out_path = matches.value_of("out").ok_or("--out <dir> is required")?;
-
-    let out_path = Path::new(&out_path);
+    Environment::new(paths)
+}
 
+fn setup_compiler<'a>(matches: &'a ArgMatches, output: Box<Output>) -> Result<(Options, &'a str)> {
+    let backend = matches.value_of("backend").ok_or("--backend <backend> is required")?;
     let package_prefix = matches.value_of("package-prefix").map(ToOwned::to_owned);
 
     let modules =
@@ -112,50 +130,51 @@ fn setup_compiler<'a>
     };
 
     let options = Options {
-        out_path: out_path.to_path_buf(),
+        output: output,
         package_prefix: package_prefix,
         id_converter: id_converter,
         modules: modules,
     };
 
-    let env = Environment::new(paths);
+    Ok((options, backend))
+}
 
-    let files: Vec<&Path> = matches.values_of("file")
-        .into_iter()
-        .flat_map(|it| it)
-        .map(Path::new)
-        .collect();
+/// Import the files and packages to compile into the environment, collecting every error.
+fn load(matches: &ArgMatches, env: &mut Environment) -> Vec<backend::errors::Error> {
+    let files = matches.values_of("file").into_iter().flat_map(|it| it).map(Path::new);
 
-    let packages: Vec<m::Package> = matches.values_of("package")
+    let packages = matches.values_of("package")
         .into_iter()
         .flat_map(|it| it)
-        .map(|s| m::Package::new(s.split(".").map(ToOwned::to_owned).collect()))
-        .collect();
-
-    Ok((files, packages, env, options, backend))
-}
-
-fn do_compile(matches: &ArgMatches) -> Result<Box<backend::Backend>> {
-    let (files, packages, mut env, options, backend) = setup_compiler(matches)?;
+        .map(|s| m::Package::new(s.split(".").map(ToOwned::to_owned).collect()));
 
     let mut failed: Vec<backend::errors::Error> = Vec::new();
 
     for file in files {
         if let Err(e) = env.import_file(file, None) {
-            failed.push(e);
+            failed.extend(e.into_errors());
         }
     }
 
     for package in packages {
         if let Err(e) = env.import(&package) {
-            failed.push(e);
+            failed.extend(e.into_errors());
         }
     }
 
     if let Err(e) = env.verify() {
-        failed.push(e);
+        failed.extend(e.into_errors());
     }
 
+    failed
+}
+
+fn do_compile(matches: &ArgMatches,
+              output: Box<Output>,
+              env: Environment,
+              mut failed: Vec<backend::errors::Error>)
+              -> Result<Box<backend::Backend>> {
+    let (options, backend) = setup_compiler(matches, output)?;
     let backend = backend::resolve(&backend, options, env);
 
     match backend {
@@ -173,14 +192,166 @@ fn do_compile(matches: &ArgMatches) -> Result<Box<backend::Backend>> {
     }
 }
 
-pub fn compile(matches: &ArgMatches) -> Result<()> {
-    let backend = do_compile(matches)?;
+/// Compile the loaded environment into the output directory.
+fn compile_env(matches: &ArgMatches,
+               env: Environment,
+               failed: Vec<backend::errors::Error>)
+               -> Result<()> {
+    let output = FileSystemOutput::new(out_path(matches)?);
+    let backend = do_compile(matches, Box::new(output.clone()), env, failed)?;
     backend.process()?;
+
+    if matches.is_present("clean") {
+        output.clean()?;
+    }
+
+    output.write_manifest()?;
     Ok(())
 }
 
+pub fn compile(matches: &ArgMatches) -> Result<()> {
+    let mut env = setup_environment(matches);
+    let failed = load(matches, &mut env);
+    compile_env(matches, env, failed)
+}
+
+/// Keeps track of the specifications that a watched compile depends on.
+struct Watcher {
+    /// Files which are compiled, whether they belong to an imported package or not.
+    files: Vec<PathBuf>,
+    /// Paths and files which are looked through for specifications.
+    roots: Vec<PathBuf>,
+    /// Modification time and parsed content of every specification found so far.
+    cache: HashMap<PathBuf, (SystemTime, Option<ast::File>)>,
+    /// Packages imported by the last compile, or `None` if it failed.
+    imported: Option<HashSet<m::Package>>,
+    /// If the compile has to run regardless of what changed, like before the first compile.
+    stale: bool,
+}
+
+impl Watcher {
+    fn new(files: Vec<PathBuf>, paths: Vec<PathBuf>) -> Watcher {
+        let roots = paths.into_iter().chain(files.iter().cloned()).collect();
+
+        Watcher {
+            files: files,
+            roots: roots,
+            cache: HashMap::new(),
+            imported: None,
+            stale: true,
+        }
+    }
+
+    /// Look for specifications which were added, changed, or removed since the last poll.
+    ///
+    /// Returns `true` if the compile has to run again, which is when a changed file belongs to a
+    /// package that was imported, or if the last compile failed.
+    fn poll(&mut self) -> Result<bool> {
+        let mut found = Vec::new();
+
+        for root in self.roots.iter().filter(|r| r.exists()) {
+            find_specifications(root, &mut found)?;
+        }
+
+        // changed files, and the package they belong to if known.
+        let mut changed: Vec<(PathBuf, Option<m::Package>)> = Vec::new();
+
+        let removed: Vec<PathBuf> =
+            self.cache.keys().filter(|path| !found.contains(path)).cloned().collect();
+
+        for path in removed {
+            if let Some((_, file)) = self.cache.remove(&path) {
+                changed.push((path, file.map(|f| f.package.inner)));
+            }
+        }
+
+        for path in found {
+            let modified = match fs::metadata(&path).and_then(|m| m.modified()) {
+                Ok(modified) => modified,
+                // removed while looking for it, noticed next time around.
+                Err(_) => continue,
+            };
+
+            if self.cache.get(&path).map(|c| c.0 == modified).unwrap_or(false) {
+                continue;
+            }
+
+            debug!("changed: {}", path.display());
+
+            // files which fail to parse are parsed again by the compile, which reports why.
+            let file = parser::parse_file(&path).ok();
+            changed.push((path.clone(), file.as_ref().map(|f| f.package.inner.clone())));
+            self.cache.insert(path, (modified, file));
+        }
+
+        let affected = changed.iter().any(|&(ref path, ref package)| {
+            match (self.imported.as_ref(), package.as_ref()) {
+                (Some(imported), Some(package)) => {
+                    imported.contains(package) || self.files.contains(path)
+                }
+                _ => true,
+            }
+        });
+
+        Ok(self.stale || affected)
+    }
+
+    /// Add the parsed content of every specification to the environment, so that only the files
+    /// which changed since they were last compiled are parsed again.
+    fn add_parsed(&self, env: &mut Environment) {
+        for (path, &(_, ref file)) in &self.cache {
+            if let Some(ref file) = *file {
+                env.add_parsed(path.clone(), file.clone());
+            }
+        }
+    }
+
+    /// Record the packages imported by a compile, or `None` if it failed.
+    fn compiled(&mut self, imported: Option<HashSet<m::Package>>) {
+        self.imported = imported;
+        self.stale = false;
+    }
+}
+
+/// Compile, and compile again whenever a specification that the compile depends on changes.
+///
+/// Files are polled for changes, and only those which changed are parsed again. Errors are passed
+/// to `report` instead of ending the watch.
+pub fn watch(matches: &ArgMatches, report: &Fn(&Error)) -> Result<()> {
+    let files = matches.values_of("file").into_iter().flat_map(|it| it).map(PathBuf::from);
+    let paths = matches.values_of("path").into_iter().flat_map(|it| it).map(PathBuf::from);
+
+    let mut watcher = Watcher::new(files.collect(), paths.collect());
+
+    loop {
+        if watcher.poll()? {
+            let mut env = setup_environment(matches);
+            watcher.add_parsed(&mut env);
+
+            let failed = load(matches, &mut env);
+            let packages = env.imported().into_iter().cloned().collect();
+
+            match compile_env(matches, env, failed) {
+                Ok(()) => {
+                    info!("compiled, watching for changes");
+                    watcher.compiled(Some(packages));
+                }
+                Err(e) => {
+                    report(&e);
+                    watcher.compiled(None);
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_millis(WATCH_INTERVAL));
+    }
+}
+
 pub fn verify(matches: &ArgMatches) -> Result<()> {
-    let backend = do_compile(matches)?;
+    let output = FileSystemOutput::new(out_path(matches)?);
+    let mut env = setup_environment(matches);
+    let failed = load(matches, &mut env);
+    let backend = do_compile(matches, Box::new(output), env, failed)?;
 
     let errors = backend.verify()?;
 
@@ -191,9 +362,593 @@ pub fn verify(matches: &ArgMatches) -> Result<()> {
     Err(ErrorKind::BackendErrors(errors).into())
 }
 
+pub fn check_options<'a, 'b>(name: &str) -> App<'a, 'b> {
+    SubCommand::with_name(name)
+        .arg(Arg::with_name("path")
+            .long("path")
+            .short("p")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Paths to look for new definitions."))
+        .arg(Arg::with_name("old-path")
+            .long("old-path")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Paths to look for old definitions."))
+        .arg(Arg::with_name("package")
+            .long("package")
+            .help("Packages to check")
+            .takes_value(true)
+            .required(true)
+            .multiple(true)
+            .number_of_values(1))
+}
+
+fn load_environment(paths: Vec<::std::path::PathBuf>,
+                    packages: &Vec<m::Package>,
+                    failed: &mut Vec<backend::errors::Error>)
+                    -> Environment {
+    let mut env = Environment::new(paths);
+
+    for package in packages {
+        if let Err(e) = env.import(package) {
+            failed.extend(e.into_errors());
+        }
+    }
+
+    if let Err(e) = env.verify() {
+        failed.extend(e.into_errors());
+    }
+
+    env
+}
+
+/// Check that the new declarations are backwards compatible with the old ones.
+pub fn check(matches: &ArgMatches) -> Result<()> {
+    let paths = |name| -> Vec<::std::path::PathBuf> {
+        matches.values_of(name)
+            .into_iter()
+            .flat_map(|it| it)
+            .map(Path::new)
+            .map(ToOwned::to_owned)
+            .collect()
+    };
+
+    let packages: Vec<m::Package> = matches.values_of("package")
+        .into_iter()
+        .flat_map(|it| it)
+        .map(|s| m::Package::new(s.split(".").map(ToOwned::to_owned).collect()))
+        .collect();
+
+    let mut failed: Vec<backend::errors::Error> = Vec::new();
+
+    let old = load_environment(paths("old-path"), &packages, &mut failed);
+    let new = load_environment(paths("path"), &packages, &mut failed);
+
+    if !failed.is_empty() {
+        return Err(failed.into());
+    }
+
+    let errors = Compat::new(&old, &new).check();
+
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    Err(ErrorKind::BackendErrors(errors).into())
+}
+
+pub fn validate_options<'a, 'b>(name: &str) -> App<'a, 'b> {
+    SubCommand::with_name(name)
+        .arg(Arg::with_name("path")
+            .long("path")
+            .short("p")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Paths to look for definitions."))
+        .arg(Arg::with_name("type")
+            .help("Fully qualified type to validate against, like `heroic.v1.Query`")
+            .required(true)
+            .index(1))
+        .arg(Arg::with_name("json")
+            .help("JSON files to validate")
+            .required(true)
+            .multiple(true)
+            .index(2))
+}
+
+/// Parse a fully qualified type, like `heroic.v1.Query`, into its package and name.
+fn parse_type(ty: &str) -> Result<(m::Package, String)> {
+    let mut parts: Vec<String> = ty.split(".").map(ToOwned::to_owned).collect();
+    let name = parts.pop().ok_or("<type> is required")?;
+
+    if parts.is_empty() {
+        return Err(format!("type must be fully qualified, like `foo.v1.Type`: {}", ty).into());
+    }
+
+    Ok((m::Package::new(parts), name))
+}
+
+/// Validate JSON payloads against a declaration, printing every violation found.
+pub fn validate(matches: &ArgMatches) -> Result<()> {
+    let paths: Vec<::std::path::PathBuf> = matches.values_of("path")
+        .into_iter()
+        .flat_map(|it| it)
+        .map(Path::new)
+        .map(ToOwned::to_owned)
+        .collect();
+
+    let ty = matches.value_of("type").ok_or("<type> is required")?;
+    let (package, name) = parse_type(ty)?;
+
+    let mut env = Environment::new(paths);
+    env.import(&package)?;
+    env.verify()?;
+
+    let mut violations = 0;
+
+    for file in matches.values_of("json").into_iter().flat_map(|it| it) {
+        let mut content = String::new();
+        File::open(file)?.read_to_string(&mut content)?;
+
+        let value = backend::json::parse(&content)
+            .chain_err(|| format!("failed to parse JSON: {}", file))?;
+
+        for violation in Validator::new(&env).validate(&package, &name, &value)? {
+            println!("{}#{}: {}", file, violation.path, violation.message);
+            violations += 1;
+        }
+    }
+
+    if violations > 0 {
+        return Err(format!("encountered {} violation(s)", violations).into());
+    }
+
+    Ok(())
+}
+
+pub fn generate_samples_options<'a, 'b>(name: &str) -> App<'a, 'b> {
+    SubCommand::with_name(name)
+        .arg(Arg::with_name("path")
+            .long("path")
+            .short("p")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Paths to look for definitions."))
+        .arg(Arg::with_name("count")
+            .long("count")
+            .short("n")
+            .takes_value(true)
+            .default_value("1")
+            .help("Number of samples to generate."))
+        .arg(Arg::with_name("seed")
+            .long("seed")
+            .takes_value(true)
+            .default_value("0")
+            .help("Seed to generate samples from, the same seed always gives the same samples."))
+        .arg(Arg::with_name("out")
+            .long("out")
+            .short("o")
+            .takes_value(true)
+            .help("Directory to write samples to, as <Type>-<n>.json, instead of stdout."))
+        .arg(Arg::with_name("type")
+            .help("Fully qualified type to generate samples of, like `heroic.v1.Query`")
+            .required(true)
+            .index(1))
+}
+
+/// Generate random JSON payloads which conform to a declaration.
+pub fn generate_samples(matches: &ArgMatches) -> Result<()> {
+    let paths: Vec<::std::path::PathBuf> = matches.values_of("path")
+        .into_iter()
+        .flat_map(|it| it)
+        .map(Path::new)
+        .map(ToOwned::to_owned)
+        .collect();
+
+    let ty = matches.value_of("type").ok_or("<type> is required")?;
+    let (package, name) = parse_type(ty)?;
+
+    let count: usize = matches.value_of("count")
+        .unwrap_or("1")
+        .parse()
+        .chain_err(|| "--count must be a positive number")?;
+
+    let seed: u64 = matches.value_of("seed")
+        .unwrap_or("0")
+        .parse()
+        .chain_err(|| "--seed must be a positive number")?;
+
+    let out = matches.value_of("out").map(Path::new);
+
+    let mut env = Environment::new(paths);
+    env.import(&package)?;
+    env.verify()?;
+
+    let mut generator = Generator::new(&env, seed);
+
+    for index in 0..count {
+        let mut content = String::new();
+        generator.generate(&package, &name)?.format(0, &mut content);
+
+        if let Some(out) = out {
+            if !out.is_dir() {
+                ::std::fs::create_dir_all(out)?;
+            }
+
+            let path = out.join(format!("{}-{}.json", name, index));
+            debug!("+file: {}", path.display());
+            let mut f = File::create(&path)?;
+            writeln!(f, "{}", content)?;
+        } else {
+            println!("{}", content);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn fmt_options<'a, 'b>(name: &str) -> App<'a, 'b> {
+    SubCommand::with_name(name)
+        .arg(Arg::with_name("check")
+            .long("check")
+            .help("Only check that files are formatted, without modifying them."))
+        .arg(Arg::with_name("files")
+            .help("Files to format, directories are searched for .reproto files")
+            .required(true)
+            .multiple(true))
+}
+
+/// Find all specifications in the given path, if it is a directory.
+fn find_specifications(path: &Path, out: &mut Vec<::std::path::PathBuf>) -> Result<()> {
+    if !path.is_dir() {
+        out.push(path.to_owned());
+        return Ok(());
+    }
+
+    let mut entries = Vec::new();
+
+    for entry in ::std::fs::read_dir(path)? {
+        entries.push(entry?.path());
+    }
+
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() || entry.extension().map(|e| e == "reproto").unwrap_or(false) {
+            find_specifications(&entry, out)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Format specifications in their canonical layout.
+pub fn fmt(matches: &ArgMatches) -> Result<()> {
+    let check = matches.is_present("check");
+
+    let mut files = Vec::new();
+
+    for path in matches.values_of("files").into_iter().flat_map(|it| it) {
+        find_specifications(Path::new(path), &mut files)?;
+    }
+
+    let mut unformatted = 0;
+
+    for file in files {
+        let mut content = String::new();
+        File::open(&file)?.read_to_string(&mut content)?;
+
+        let formatted = format::format(&file, &content)?;
+
+        if formatted == content {
+            continue;
+        }
+
+        if check {
+            println!("{}: not formatted", file.display());
+            unformatted += 1;
+            continue;
+        }
+
+        info!("Formatting: {}", file.display());
+        File::create(&file)?.write_all(formatted.as_bytes())?;
+    }
+
+    if unformatted > 0 {
+        return Err(format!("{} file(s) are not formatted", unformatted).into());
+    }
+
+    Ok(())
+}
+
+pub fn import_options<'a, 'b>(name: &str) -> App<'a, 'b> {
+    SubCommand::with_name(name)
+        .arg(Arg::with_name("format")
+            .long("format")
+            .short("f")
+            .takes_value(true)
+            .possible_values(&["json-schema", "protobuf"])
+            .help("Format to import, defaults to the one given by the file extensions."))
+        .arg(Arg::with_name("package")
+            .long("package")
+            .takes_value(true)
+            .help("Package to import into, prefixed to the packages of the imported files."))
+        .arg(Arg::with_name("out")
+            .long("out")
+            .short("o")
+            .takes_value(true)
+            .required(true)
+            .help("Directory to write the .reproto files to."))
+        .arg(Arg::with_name("files")
+            .help("Files to import, directories are searched for files of the given format")
+            .required(true)
+            .multiple(true))
+}
+
+/// Find the format of the files to import by their extension.
+fn import_format(files: &[PathBuf]) -> Result<&'static str> {
+    let mut formats = HashSet::new();
+
+    for file in files {
+        let format = match file.extension().and_then(|e| e.to_str()) {
+            Some("json") => "json-schema",
+            Some("proto") => "protobuf",
+            _ if file.is_dir() => continue,
+            _ => return Err(format!("unknown format, use --format: {}", file.display()).into()),
+        };
+
+        formats.insert(format);
+    }
+
+    match formats.into_iter().collect::<Vec<_>>().as_slice() {
+        &[format] => Ok(format),
+        &[] => Err("no format to import directories as, use --format".into()),
+        _ => Err("files of more than one format can't be imported at once".into()),
+    }
+}
+
+/// Import JSON Schema documents or proto3 files as .reproto specifications.
+pub fn import(matches: &ArgMatches) -> Result<()> {
+    let files: Vec<PathBuf> = matches.values_of("files")
+        .into_iter()
+        .flat_map(|it| it)
+        .map(PathBuf::from)
+        .collect();
+
+    let prefix = matches.value_of("package")
+        .map(|p| m::Package::new(p.split(".").map(ToOwned::to_owned).collect()));
+
+    let format = match matches.value_of("format") {
+        Some(format) => format,
+        None => import_format(&files)?,
+    };
+
+    let modules = match format {
+        "json-schema" => import::json_schema::import(&files, prefix.as_ref())?,
+        "protobuf" => import::protobuf::import(&files, prefix.as_ref())?,
+        _ => return Err(format!("unsupported format: {}", format).into()),
+    };
+
+    let sources = import::render(modules);
+    let out = out_path(matches)?;
+    let output = FileSystemOutput::new(out);
+
+    for source in &sources {
+        info!("Writing: {}", out.join(&source.path).display());
+        output.write(&source.path, source.content.as_bytes())?;
+    }
+
+    import::verify(out, &sources)
+}
+
+pub fn language_server_options<'a, 'b>(name: &str) -> App<'a, 'b> {
+    SubCommand::with_name(name).arg(Arg::with_name("path")
+        .long("path")
+        .short("p")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("Paths to look for definitions, defaults to the root of the workspace."))
+}
+
+/// Run a language server, speaking the language server protocol over stdin and stdout.
+pub fn language_server(matches: &ArgMatches) -> Result<()> {
+    let mut paths = Vec::new();
+
+    for path in matches.values_of("path").into_iter().flat_map(|it| it) {
+        // documents are identified by absolute paths.
+        paths.push(Path::new(path).canonicalize()?);
+    }
+
+    let stdin = ::std::io::stdin();
+    let stdout = ::std::io::stdout();
+
+    let mut server = Server::new(paths);
+    server.run(&mut stdin.lock(), &mut stdout.lock())
+}
+
 pub fn commands<'a, 'b>() -> Vec<App<'a, 'b>> {
     let mut commands = Vec::new();
-    commands.push(compile_options("compile").about("Compile .reproto declarations"));
+    commands.push(compile_options("compile")
+        .about("Compile .reproto declarations")
+        .arg(Arg::with_name("clean")
+            .long("clean")
+            .help("Remove files generated by a previous compile which are no longer generated"))
+        .arg(Arg::with_name("watch")
+            .long("watch")
+            .help("Compile again whenever the specifications change")));
     commands.push(compile_options("verify").about("Verify .reproto declarations"));
+    commands.push(check_options("check")
+        .about("Check .reproto declarations for changes that break compatibility"));
+    commands.push(validate_options("validate")
+        .about("Validate JSON payloads against .reproto declarations"));
+    commands.push(generate_samples_options("generate-samples")
+        .about("Generate random JSON payloads which conform to a declaration"));
+    commands.push(fmt_options("fmt").about("Format .reproto files"));
+    commands.push(import_options("import")
+        .about("Import JSON Schema documents or proto3 files as .reproto files"));
+    commands.push(language_server_options("language-server")
+        .about("Run a language server for .reproto files over stdio"));
     commands
 }
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+    use std::time::UNIX_EPOCH;
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("reproto-watch-{}-{}", name, process::id()));
+
+        if dir.is_dir() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Write the file, with a modification time which is `seconds` after the epoch.
+    fn write(path: &Path, content: &str, seconds: u64) {
+        let mut f = File::create(path).unwrap();
+        f.write_all(content.as_bytes()).unwrap();
+        f.set_modified(UNIX_EPOCH + Duration::from_secs(seconds)).unwrap();
+    }
+
+    fn packages(names: &[&str]) -> Option<HashSet<m::Package>> {
+        Some(names.iter().map(|n| m::Package::new(vec![n.to_string()])).collect())
+    }
+
+    #[test]
+    fn test_watch_first_poll() {
+        let dir = temp_dir("first-poll");
+        let mut watcher = Watcher::new(vec![], vec![dir.clone()]);
+
+        assert!(watcher.poll().unwrap());
+        watcher.compiled(packages(&[]));
+        assert!(!watcher.poll().unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_imported_packages() {
+        let dir = temp_dir("imported-packages");
+        let a = dir.join("a.reproto");
+        let b = dir.join("b.reproto");
+
+        write(&a, "package a;", 1);
+        write(&b, "package b;", 1);
+
+        let mut watcher = Watcher::new(vec![], vec![dir.clone()]);
+        assert!(watcher.poll().unwrap());
+        watcher.compiled(packages(&["a"]));
+
+        // b is not used by the compile.
+        write(&b, "package b; type B {}", 2);
+        assert!(!watcher.poll().unwrap());
+
+        write(&a, "package a; type A {}", 2);
+        assert!(watcher.poll().unwrap());
+        watcher.compiled(packages(&["a"]));
+
+        // a file moved into an imported package.
+        write(&b, "package a; type B {}", 3);
+        assert!(watcher.poll().unwrap());
+        watcher.compiled(packages(&["a"]));
+
+        fs::remove_file(&b).unwrap();
+        assert!(watcher.poll().unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_after_failure() {
+        let dir = temp_dir("after-failure");
+        let a = dir.join("a.reproto");
+        let b = dir.join("b.reproto");
+
+        write(&a, "package a;", 1);
+        write(&b, "package b;", 1);
+
+        let mut watcher = Watcher::new(vec![], vec![dir.clone()]);
+        assert!(watcher.poll().unwrap());
+        watcher.compiled(None);
+        assert!(!watcher.poll().unwrap());
+
+        // any change could fix the compile.
+        write(&b, "package b; type B {}", 2);
+        assert!(watcher.poll().unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_unparsable_file() {
+        let dir = temp_dir("unparsable-file");
+        let a = dir.join("a.reproto");
+
+        write(&a, "package a;", 1);
+
+        let mut watcher = Watcher::new(vec![], vec![dir.clone()]);
+        assert!(watcher.poll().unwrap());
+        watcher.compiled(packages(&["b"]));
+
+        // the package of the file is unknown.
+        write(&a, "package", 2);
+        assert!(watcher.poll().unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_files() {
+        let dir = temp_dir("files");
+        let a = dir.join("a.reproto");
+
+        write(&a, "package a;", 1);
+
+        let mut watcher = Watcher::new(vec![a.clone()], vec![]);
+        assert!(watcher.poll().unwrap());
+        watcher.compiled(packages(&[]));
+
+        write(&a, "package a; type A {}", 2);
+        assert!(watcher.poll().unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_watch_reuses_parsed_files() {
+        let dir = temp_dir("reuses-parsed-files");
+        let a = dir.join("a.reproto");
+        let package = m::Package::new(vec!["a".to_owned()]);
+
+        write(&a, "package a; type A {}", 1);
+
+        let mut watcher = Watcher::new(vec![], vec![dir.clone()]);
+        assert!(watcher.poll().unwrap());
+        watcher.compiled(packages(&["a"]));
+
+        // unchanged according to its modification time, so it is not parsed again.
+        write(&a, "package a; type B {}", 1);
+        assert!(!watcher.poll().unwrap());
+
+        let mut env = Environment::new(vec![dir.clone()]);
+        watcher.add_parsed(&mut env);
+        env.import(&package).unwrap();
+
+        let names: Vec<_> = env.types.keys().map(|&(_, ref name)| name.join(".")).collect();
+        assert_eq!(vec!["A"], names);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
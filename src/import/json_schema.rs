@@ -0,0 +1,731 @@
+//! Import of JSON Schema documents.
+//!
+//! Every document is declared after its `title`, or its file name, together with all of its
+//! `definitions` (or `$defs`). Objects become types, arrays of a fixed list of items become tuples,
+//! string enums become enums, and unions of objects which are told apart by a constant property
+//! become interfaces. Other schemas can't be declared, and are inlined where they are referenced.
+//!
+//! An `anyOf` with a single alternative which can be declared, like the schemas of declarations
+//! with a `match`, is declared as that alternative. An `anyOf` of an integer and a string with a
+//! pattern, like the schemas of integers of more than 32 bits, is imported as the integer.
+
+use backend::json::{self, Json};
+use backend::models::{Package, Type};
+use errors::*;
+use parser::ast::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use super::{EnumVariant, Module, Modules, Variant, enumeration, find_files, interface, option,
+            type_name};
+
+const EXT: &str = "json";
+/// Keywords of the schemas which are declared together with a document.
+const DEFINITIONS: &[&str] = &["definitions", "$defs"];
+/// Keywords of unions.
+const ALTERNATIVES: &[&str] = &["oneOf", "anyOf"];
+/// Property that sub types are preferably told apart by.
+const TYPE_FIELD: &str = "type";
+
+struct Document {
+    path: PathBuf,
+    package: Package,
+    name: String,
+    schema: Json,
+}
+
+/// A schema, identified by the document that it is in, and its JSON pointer in that document.
+type Key = (usize, String);
+
+/// Sub types of a union, and the property that they are told apart by.
+struct Union {
+    field: String,
+    alternatives: Vec<(Key, Json)>,
+}
+
+fn escape(part: &str) -> String {
+    part.replace("~", "~0").replace("/", "~1")
+}
+
+fn unescape(part: &str) -> String {
+    part.replace("~1", "/").replace("~0", "~")
+}
+
+/// Find the schema at the given JSON pointer.
+fn at<'a>(schema: &'a Json, pointer: &str) -> Option<&'a Json> {
+    let mut current = schema;
+
+    for part in pointer.split('/').skip(1) {
+        let part = unescape(part);
+
+        current = match *current {
+            Json::Object(_) => current.get(&part)?,
+            Json::Array(ref values) => values.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+
+    Some(current)
+}
+
+fn description(schema: &Json) -> Vec<String> {
+    schema.get("description").and_then(Json::as_str).map(super::comment).unwrap_or_default()
+}
+
+fn strings(value: &Json) -> Option<Vec<String>> {
+    if let Json::Array(ref values) = *value {
+        return values.iter().map(|v| v.as_str().map(ToOwned::to_owned)).collect();
+    }
+
+    None
+}
+
+fn string_enum(schema: &Json) -> Option<Vec<String>> {
+    schema.get("enum").and_then(strings)
+}
+
+/// The names that a discriminating property can have.
+fn discriminator(schema: &Json) -> Option<Vec<String>> {
+    match schema.get("const").and_then(Json::as_str) {
+        Some(name) => Some(vec![name.to_owned()]),
+        None => string_enum(schema),
+    }
+}
+
+/// The items of a tuple.
+fn tuple_items(schema: &Json) -> Option<&Vec<Json>> {
+    match schema.get("prefixItems").or_else(|| schema.get("items")) {
+        Some(&Json::Array(ref items)) => Some(items),
+        _ => None,
+    }
+}
+
+fn alternatives(schema: &Json) -> Option<(&'static str, &Vec<Json>)> {
+    for keyword in ALTERNATIVES {
+        if let Some(&Json::Array(ref alternatives)) = schema.get(keyword) {
+            return Some((keyword, alternatives));
+        }
+    }
+
+    None
+}
+
+fn is_null(schema: &Json) -> bool {
+    schema.get("type").and_then(Json::as_str) == Some("null")
+}
+
+/// The integer of alternatives which also accept it in its decimal form, as integers which might
+/// not fit in a number are.
+fn wide_integer(alternatives: &Vec<Json>) -> Option<&Json> {
+    let of_type = |name| {
+        alternatives.iter().find(|a| a.get("type").and_then(Json::as_str) == Some(name))
+    };
+
+    match (alternatives.len(), of_type("integer"), of_type("string")) {
+        (2, Some(integer), Some(string)) if string.get("pattern").is_some() => Some(integer),
+        _ => None,
+    }
+}
+
+fn number(schema: &Json, key: &str) -> Option<f64> {
+    match schema.get(key) {
+        Some(&Json::Number(ref number)) => number.parse().ok(),
+        _ => None,
+    }
+}
+
+/// The smallest integer which covers the bounds of the schema.
+fn integer(schema: &Json) -> Type {
+    let minimum = number(schema, "minimum");
+    let maximum = number(schema, "maximum");
+
+    match (minimum, maximum) {
+        (Some(min), max) if min >= 0.0 => {
+            let size = match max {
+                Some(max) if max <= u32::max_value() as f64 => Some(32),
+                Some(max) if max <= u64::max_value() as f64 => Some(64),
+                _ => None,
+            };
+
+            Type::Unsigned(size)
+        }
+        (Some(min), Some(max)) if min >= i32::min_value() as f64 &&
+                                  max <= i32::max_value() as f64 => Type::Signed(Some(32)),
+        (Some(min), Some(max)) if min >= i64::min_value() as f64 &&
+                                  max <= i64::max_value() as f64 => Type::Signed(Some(64)),
+        _ => Type::Signed(None),
+    }
+}
+
+fn load(path: &Path, prefix: Option<&Package>, parts: Vec<String>) -> Result<Document> {
+    let mut content = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut content))
+        .chain_err(|| format!("failed to read: {}", path.display()))?;
+
+    let schema = json::parse(&content)
+        .chain_err(|| format!("failed to parse JSON: {}", path.display()))?;
+
+    let mut package = prefix.map(|p| p.parts.clone()).unwrap_or_default();
+    package.extend(parts);
+
+    if package.is_empty() {
+        return Err(format!("{}: no package to import into, use --package", path.display()).into());
+    }
+
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = schema.get("title").and_then(Json::as_str).map(ToOwned::to_owned).unwrap_or(stem);
+
+    Ok(Document {
+        path: path.canonicalize()?,
+        package: super::package(&package)?,
+        name: type_name(&name).chain_err(|| format!("{}: bad title", path.display()))?,
+        schema: schema,
+    })
+}
+
+struct Importer {
+    documents: Vec<Document>,
+    /// Names of the schemas which are declared.
+    names: HashMap<Key, String>,
+    /// Schemas which have been named, but not declared yet.
+    queue: Vec<Key>,
+    /// Schemas which are being inlined, to detect references to themselves.
+    inlining: Vec<Key>,
+    modules: Modules,
+}
+
+impl Importer {
+    fn schema(&self, key: &Key) -> Json {
+        at(&self.documents[key.0].schema, &key.1).cloned().expect("schema to exist")
+    }
+
+    fn register(&mut self, key: Key, name: String) -> Result<()> {
+        let package = &self.documents[key.0].package;
+
+        let taken = self.names
+            .iter()
+            .any(|(k, n)| *n == name && self.documents[k.0].package == *package);
+
+        if taken {
+            return Err(format!("{} is declared more than once in {}, give the schemas distinct \
+                                titles",
+                               name,
+                               package)
+                .into());
+        }
+
+        self.names.insert(key.clone(), name);
+        self.queue.push(key);
+        Ok(())
+    }
+
+    /// Resolve a `$ref` in the given document.
+    fn reference(&self, doc: usize, reference: &str) -> Result<Key> {
+        let (file, fragment) = match reference.find('#') {
+            Some(index) => (&reference[..index], &reference[index + 1..]),
+            None => (reference, ""),
+        };
+
+        let target = if file.is_empty() {
+            doc
+        } else {
+            let dir = self.documents[doc].path.parent().unwrap_or(Path::new("."));
+
+            let path = dir.join(file)
+                .canonicalize()
+                .chain_err(|| format!("$ref to a missing document: {}", reference))?;
+
+            self.documents
+                .iter()
+                .position(|d| d.path == path)
+                .ok_or_else(|| format!("$ref to a document which is not imported: {}", reference))?
+        };
+
+        if !fragment.is_empty() && !fragment.starts_with('/') {
+            return Err(format!("$ref is not a JSON pointer: {}", reference).into());
+        }
+
+        if at(&self.documents[target].schema, fragment).is_none() {
+            return Err(format!("$ref to a schema which does not exist: {}", reference).into());
+        }
+
+        Ok((target, fragment.to_owned()))
+    }
+
+    fn union(&self, key: &Key, schema: &Json) -> Result<Option<Union>> {
+        let (keyword, alternatives) = match alternatives(schema) {
+            Some(alternatives) => alternatives,
+            None => return Ok(None),
+        };
+
+        let mut resolved = Vec::new();
+
+        for (index, alternative) in alternatives.iter().enumerate() {
+            let alternative_key = match alternative.get("$ref").and_then(Json::as_str) {
+                Some(reference) => self.reference(key.0, reference)?,
+                None => (key.0, format!("{}/{}/{}", key.1, keyword, index)),
+            };
+
+            let alternative = self.schema(&alternative_key);
+
+            if alternative.get("properties").is_none() {
+                return Ok(None);
+            }
+
+            resolved.push((alternative_key, alternative));
+        }
+
+        let tagged = |field: &str| {
+            resolved.iter().all(|&(_, ref alternative)| {
+                alternative.get("properties")
+                    .and_then(|p| p.get(field))
+                    .and_then(discriminator)
+                    .is_some()
+            })
+        };
+
+        let field = if tagged(TYPE_FIELD) {
+            TYPE_FIELD.to_owned()
+        } else {
+            let first = match resolved.first().and_then(|r| r.1.get("properties")) {
+                Some(&Json::Object(ref properties)) => properties,
+                _ => return Ok(None),
+            };
+
+            match first.iter().map(|p| &p.0).find(|p| tagged(p)) {
+                Some(field) => field.clone(),
+                None => return Ok(None),
+            }
+        };
+
+        Ok(Some(Union {
+            field: field,
+            alternatives: resolved,
+        }))
+    }
+
+    /// Find the only declarable alternative of a schema which also permits other values, like
+    /// the schemas of declarations with a `match`.
+    fn matched(&self, key: &Key, schema: &Json) -> Result<Option<(Key, Json)>> {
+        let alternatives = match schema.get("anyOf") {
+            Some(&Json::Array(ref alternatives)) if alternatives.len() > 1 => alternatives,
+            _ => return Ok(None),
+        };
+
+        if self.union(key, schema)?.is_some() {
+            return Ok(None);
+        }
+
+        let mut declared = None;
+
+        for (index, alternative) in alternatives.iter().enumerate() {
+            if is_null(alternative) {
+                return Ok(None);
+            }
+
+            // references are values which are matched.
+            if alternative.get("$ref").is_some() {
+                continue;
+            }
+
+            let alternative_key = (key.0, format!("{}/anyOf/{}", key.1, index));
+
+            if self.declarable(&alternative_key, alternative)? {
+                if declared.is_some() {
+                    return Ok(None);
+                }
+
+                declared = Some((alternative_key, alternative.clone()));
+            }
+        }
+
+        Ok(declared)
+    }
+
+    fn declarable(&self, key: &Key, schema: &Json) -> Result<bool> {
+        if schema.get("properties").is_some() || string_enum(schema).is_some() ||
+           tuple_items(schema).is_some() {
+            return Ok(true);
+        }
+
+        if self.union(key, schema)?.is_some() {
+            return Ok(true);
+        }
+
+        Ok(self.matched(key, schema)?.is_some())
+    }
+
+    /// Refer to a declared schema from the given package.
+    fn custom(&mut self, from: &Package, key: &Key) -> Type {
+        let name = self.names[key].clone();
+        let package = self.documents[key.0].package.clone();
+        self.modules.get(from).custom(&package, vec![name])
+    }
+
+    /// Convert a referenced schema, declaring it if necessary.
+    fn refer(&mut self, from: &Package, target: Key, hint: &str) -> Result<(Type, bool)> {
+        if self.names.contains_key(&target) {
+            return Ok((self.custom(from, &target), false));
+        }
+
+        let schema = self.schema(&target);
+
+        if self.declarable(&target, &schema)? {
+            let name = match schema.get("title").and_then(Json::as_str) {
+                Some(title) => title.to_owned(),
+                None => target.1.rsplit('/').next().map(unescape).unwrap_or_default(),
+            };
+
+            self.register(target.clone(), type_name(&name)?)?;
+            return Ok((self.custom(from, &target), false));
+        }
+
+        if self.inlining.contains(&target) {
+            return Err(format!("schema refers to itself, but can't be declared: {}#{}",
+                               self.documents[target.0].path.display(),
+                               target.1)
+                .into());
+        }
+
+        self.inlining.push(target.clone());
+        let result = self.convert(from, &target, &schema, hint);
+        self.inlining.pop();
+        result
+    }
+
+    /// Convert a schema into a type, and if it permits null.
+    ///
+    /// Schemas which can be declared, but are not, are named after `hint`.
+    fn convert(&mut self,
+               from: &Package,
+               key: &Key,
+               schema: &Json,
+               hint: &str)
+               -> Result<(Type, bool)> {
+        if let Json::Boolean(_) = *schema {
+            return Ok((Type::Any, false));
+        }
+
+        if let Some(reference) = schema.get("$ref").and_then(Json::as_str) {
+            let target = self.reference(key.0, reference)?;
+            return self.refer(from, target, hint);
+        }
+
+        if self.declarable(key, schema)? {
+            if !self.names.contains_key(key) {
+                let name = match schema.get("title").and_then(Json::as_str) {
+                    Some(title) => type_name(title)?,
+                    None => hint.to_owned(),
+                };
+
+                self.register(key.clone(), name)?;
+            }
+
+            return Ok((self.custom(from, key), false));
+        }
+
+        if let Some((keyword, alternatives)) = alternatives(schema) {
+            if let Some(integer_schema) = wide_integer(alternatives) {
+                return Ok((integer(integer_schema), false));
+            }
+
+            let mut values = alternatives.iter().enumerate().filter(|&(_, a)| !is_null(a));
+
+            // only nullable schemas can be represented, other unions permit anything.
+            return match (values.next(), values.next()) {
+                (Some((index, value)), None) if alternatives.len() == 2 => {
+                    let key = (key.0, format!("{}/{}/{}", key.1, keyword, index));
+                    let (ty, _) = self.convert(from, &key, value, hint)?;
+                    Ok((ty, true))
+                }
+                _ => Ok((Type::Any, false)),
+            };
+        }
+
+        if let Some(constant) = schema.get("const") {
+            let ty = if constant.as_str().is_some() { Type::String } else { Type::Any };
+            return Ok((ty, false));
+        }
+
+        let (name, nullable) = match schema.get("type") {
+            Some(&Json::String(ref name)) => (Some(name.clone()), false),
+            Some(&Json::Array(ref names)) => {
+                let names: Vec<_> = names.iter()
+                    .filter_map(Json::as_str)
+                    .filter(|n| *n != "null")
+                    .collect();
+
+                let nullable = names.len() + 1 == strings(schema.get("type").unwrap())
+                    .map(|n| n.len())
+                    .unwrap_or(0);
+
+                match names.as_slice() {
+                    &[name] => (Some(name.to_owned()), nullable),
+                    _ => (None, nullable),
+                }
+            }
+            _ => (None, false),
+        };
+
+        let ty = match name.as_ref().map(String::as_str) {
+            Some("string") => {
+                match schema.get("contentEncoding").and_then(Json::as_str) {
+                    Some("base64") => Type::Bytes,
+                    _ => Type::String,
+                }
+            }
+            Some("integer") => integer(schema),
+            Some("number") => {
+                match schema.get("format").and_then(Json::as_str) {
+                    Some("float") => Type::Float,
+                    _ => Type::Double,
+                }
+            }
+            Some("boolean") => Type::Boolean,
+            Some("array") => {
+                let inner = match schema.get("items") {
+                    Some(items) => {
+                        let key = (key.0, format!("{}/items", key.1));
+                        self.convert(from, &key, items, &format!("{}Item", hint))?.0
+                    }
+                    None => Type::Any,
+                };
+
+                Type::Array(Box::new(inner))
+            }
+            Some("object") => {
+                let value = match schema.get("additionalProperties") {
+                    Some(value @ &Json::Object(_)) => {
+                        let key = (key.0, format!("{}/additionalProperties", key.1));
+                        self.convert(from, &key, value, &format!("{}Value", hint))?.0
+                    }
+                    _ => Type::Any,
+                };
+
+                Type::Map(Box::new(Type::String), Box::new(value))
+            }
+            _ => Type::Any,
+        };
+
+        Ok((ty, nullable))
+    }
+
+    /// Convert the properties of an object, except the one that tells sub types apart.
+    fn fields(&mut self,
+              from: &Package,
+              key: &Key,
+              schema: &Json,
+              hint: &str,
+              skip: Option<&str>)
+              -> Result<Vec<Member>> {
+        let required = schema.get("required").and_then(strings).unwrap_or_default();
+        let mut members = Vec::new();
+
+        if let Some(&Json::Object(ref properties)) = schema.get("properties") {
+            for &(ref name, ref property) in properties {
+                if Some(name.as_str()) == skip {
+                    continue;
+                }
+
+                let key = (key.0, format!("{}/properties/{}", key.1, escape(name)));
+                let hint = format!("{}{}", hint, type_name(name)?);
+                let (ty, nullable) = self.convert(from, &key, property, &hint)?;
+                let optional = nullable || !required.contains(name);
+                let field = super::field(name, ty, optional, description(property))?;
+                members.push(Member::Field(field));
+            }
+        }
+
+        Ok(members)
+    }
+
+    fn declare(&mut self, key: &Key) -> Result<Decl> {
+        let schema = self.schema(key);
+        let name = self.names[key].clone();
+        let from = self.documents[key.0].package.clone();
+        let comment = description(&schema);
+
+        let (key, schema) = match self.matched(key, &schema)? {
+            Some(matched) => {
+                warn!("{}: only the declared alternative of `anyOf` is imported, add a `match` \
+                       for the other values",
+                      name);
+                matched
+            }
+            None => (key.clone(), schema),
+        };
+
+        let key = &key;
+
+        if let Some(values) = string_enum(&schema) {
+            let variants = values.into_iter()
+                .map(|value| {
+                    EnumVariant {
+                        serialized: value,
+                        name: None,
+                        comment: vec![],
+                        ordinal: None,
+                    }
+                })
+                .collect();
+
+            return enumeration(name, comment, variants);
+        }
+
+        if let Some(union) = self.union(key, &schema)? {
+            let Union { field, alternatives } = union;
+            let mut variants = Vec::new();
+
+            for (alternative_key, alternative) in alternatives {
+                let names = alternative.get("properties")
+                    .and_then(|p| p.get(&field))
+                    .and_then(discriminator)
+                    .unwrap_or_default();
+
+                let sub_name = match alternative.get("title").and_then(Json::as_str) {
+                    Some(title) => type_name(title)?,
+                    None => type_name(names.first().map(String::as_str).unwrap_or_default())?,
+                };
+
+                let hint = format!("{}{}", name, sub_name);
+
+                let members = self.fields(&from,
+                            &alternative_key,
+                            &alternative,
+                            &hint,
+                            Some(&field))?;
+
+                variants.push(Variant {
+                    name: sub_name,
+                    comment: description(&alternative),
+                    names: names,
+                    members: members,
+                });
+            }
+
+            let mut members = Vec::new();
+
+            if field != TYPE_FIELD {
+                members.push(option("type_field_name", vec![Value::String(field)]));
+            }
+
+            return Ok(interface(name, comment, members, variants));
+        }
+
+        if let Some(items) = tuple_items(&schema).cloned() {
+            let mut members = Vec::new();
+
+            for (index, item) in items.iter().enumerate() {
+                let field_name = match item.get("title").and_then(Json::as_str) {
+                    Some(title) => super::field_name(title)?,
+                    None => format!("item_{}", index),
+                };
+
+                let key = (key.0, format!("{}/items/{}", key.1, index));
+                let hint = match item.get("title").and_then(Json::as_str) {
+                    Some(title) => format!("{}{}", name, type_name(title)?),
+                    None => format!("{}Item", name),
+                };
+
+                let (ty, _) = self.convert(&from, &key, item, &hint)?;
+
+                members.push(Member::Field(super::field(&field_name,
+                                                         ty,
+                                                         false,
+                                                         description(item))?));
+            }
+
+            return Ok(Decl::Tuple(TupleBody {
+                name: name,
+                comment: comment,
+                members: members.into_iter().map(super::token).collect(),
+            }));
+        }
+
+        let members = self.fields(&from, key, &schema, &name, None)?;
+
+        Ok(Decl::Type(TypeBody {
+            name: name,
+            comment: comment,
+            members: members.into_iter().map(super::token).collect(),
+        }))
+    }
+}
+
+/// Import JSON Schema documents, and every document in the given directories.
+///
+/// Documents are imported into the package given by `prefix`, followed by their directory
+/// relative to the directory they were found in.
+pub fn import(inputs: &[PathBuf], prefix: Option<&Package>) -> Result<Vec<Module>> {
+    let mut documents = Vec::new();
+
+    for input in inputs {
+        if !input.is_dir() {
+            documents.push(load(input, prefix, vec![])?);
+            continue;
+        }
+
+        let mut files = Vec::new();
+        find_files(input, EXT, &mut files)?;
+
+        for file in files {
+            let parts = file.parent()
+                .and_then(|p| p.strip_prefix(input).ok())
+                .map(|p| {
+                    p.components()
+                        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            documents.push(load(&file, prefix, parts)?);
+        }
+    }
+
+    let mut importer = Importer {
+        documents: documents,
+        names: HashMap::new(),
+        queue: Vec::new(),
+        inlining: Vec::new(),
+        modules: Modules::new(),
+    };
+
+    for doc in 0..importer.documents.len() {
+        let root = (doc, String::new());
+        let schema = importer.documents[doc].schema.clone();
+
+        if importer.declarable(&root, &schema)? {
+            let name = importer.documents[doc].name.clone();
+            importer.register(root, name)?;
+        }
+
+        for keyword in DEFINITIONS {
+            if let Some(&Json::Object(ref definitions)) = schema.get(keyword) {
+                for &(ref name, ref definition) in definitions {
+                    let key = (doc, format!("/{}/{}", keyword, escape(name)));
+
+                    if importer.declarable(&key, definition)? &&
+                       !importer.names.contains_key(&key) {
+                        importer.register(key, type_name(name)?)?;
+                    }
+                }
+            }
+        }
+    }
+
+    while let Some(key) = importer.queue.pop() {
+        let decl = importer.declare(&key)
+            .chain_err(|| {
+                format!("failed to import {}#{}",
+                        importer.documents[key.0].path.display(),
+                        key.1)
+            })?;
+
+        let package = importer.documents[key.0].package.clone();
+        importer.modules.get(&package).decls.push(decl);
+    }
+
+    Ok(importer.modules.into_vec())
+}
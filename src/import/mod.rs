@@ -0,0 +1,525 @@
+//! Import of declarations from other schema languages.
+//!
+//! Importers translate documents into `ast` trees, with one `Module` for every package, which are
+//! formatted into `.reproto` source. The source is parsed again and verified by an `Environment`,
+//! so that anything which could not be expressed is caught before it is written.
+
+pub mod json_schema;
+pub mod protobuf;
+
+use backend::environment::Environment;
+use backend::models::{Custom, Modifier, Package, Type};
+use backend::protobuf::processor::is_reserved;
+use errors::*;
+use parser::ast::*;
+use parser::format;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const EXT: &str = "reproto";
+/// Name of the field that enums are serialized as, if their values can't be used as names.
+const ENUM_FIELD: &str = "json_name";
+
+/// Declarations imported into a single package.
+pub struct Module {
+    pub package: Package,
+    /// Aliases of the other packages that declarations refer to.
+    uses: BTreeMap<Package, String>,
+    pub decls: Vec<Decl>,
+}
+
+impl Module {
+    pub fn new(package: Package) -> Module {
+        Module {
+            package: package,
+            uses: BTreeMap::new(),
+            decls: Vec::new(),
+        }
+    }
+
+    /// Refer to the declaration with the given name in the given package, using the package if
+    /// it is not this one.
+    pub fn custom(&mut self, package: &Package, parts: Vec<String>) -> Type {
+        if *package == self.package {
+            return Type::Custom(Custom {
+                prefix: None,
+                parts: parts,
+            });
+        }
+
+        if !self.uses.contains_key(package) {
+            let base = package.parts
+                .last()
+                .and_then(|p| field_name(p).ok())
+                .unwrap_or_else(|| "other".to_owned());
+
+            let mut alias = base.clone();
+            let mut index = 1;
+
+            while self.uses.values().any(|a| *a == alias) {
+                index += 1;
+                alias = format!("{}_{}", base, index);
+            }
+
+            self.uses.insert(package.clone(), alias);
+        }
+
+        Type::Custom(Custom {
+            prefix: self.uses.get(package).cloned(),
+            parts: parts,
+        })
+    }
+
+    /// Path of the module, relative to the root of the output.
+    pub fn path(&self) -> PathBuf {
+        let mut path = self.package.parts.iter().fold(PathBuf::new(), |path, part| path.join(part));
+        path.set_extension(EXT);
+        path
+    }
+
+    /// Build the file of the module, with declarations sorted by name.
+    pub fn into_file(self) -> File {
+        let uses = self.uses
+            .into_iter()
+            .map(|(package, alias)| {
+                let alias = if package.parts.last() == Some(&alias) { None } else { Some(alias) };
+
+                token(UseDecl {
+                    package: token(package),
+                    alias: alias,
+                })
+            })
+            .collect();
+
+        let mut decls = self.decls;
+        decls.sort_by_key(Decl::name);
+
+        File {
+            package: token(self.package),
+            uses: uses,
+            decls: decls.into_iter().map(token).collect(),
+        }
+    }
+}
+
+/// Modules by package, merging everything imported into the same package.
+pub struct Modules {
+    modules: BTreeMap<Package, Module>,
+}
+
+impl Modules {
+    pub fn new() -> Modules {
+        Modules { modules: BTreeMap::new() }
+    }
+
+    pub fn get(&mut self, package: &Package) -> &mut Module {
+        self.modules.entry(package.clone()).or_insert_with(|| Module::new(package.clone()))
+    }
+
+    pub fn into_vec(self) -> Vec<Module> {
+        self.modules.into_iter().map(|(_, module)| module).collect()
+    }
+}
+
+/// A sub type of an imported interface.
+pub struct Variant {
+    pub name: String,
+    pub comment: Vec<String>,
+    /// Names that the sub type is serialized as, the first one is used when encoding.
+    pub names: Vec<String>,
+    pub members: Vec<Member>,
+}
+
+/// A value of an imported enum.
+pub struct EnumVariant {
+    /// Name that the value is serialized as.
+    pub serialized: String,
+    /// Name to derive the name of the value from, if not the serialized one.
+    pub name: Option<String>,
+    pub comment: Vec<String>,
+    pub ordinal: Option<u32>,
+}
+
+/// Tokens of built trees have no position.
+fn token<T>(inner: T) -> Token<T> {
+    Token::new(inner, (0, 0))
+}
+
+/// Split a name into words, at anything which is not alphanumeric and where the case changes.
+fn words(name: &str) -> Vec<String> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut words = Vec::new();
+    let mut current = String::new();
+
+    for (i, c) in chars.iter().cloned().enumerate() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(current);
+                current = String::new();
+            }
+
+            continue;
+        }
+
+        if c.is_uppercase() && !current.is_empty() {
+            let previous = chars[i - 1];
+            let next_lower = chars.get(i + 1).map(|n| n.is_lowercase()).unwrap_or(false);
+
+            // split `HTTPServer` into `HTTP` and `Server`.
+            if previous.is_lowercase() || previous.is_numeric() ||
+               (previous.is_uppercase() && next_lower) {
+                words.push(current);
+                current = String::new();
+            }
+        }
+
+        current.push(c);
+    }
+
+    if !current.is_empty() {
+        words.push(current);
+    }
+
+    words
+}
+
+fn is_type_name(name: &str) -> bool {
+    name.chars().next().map(|c| c.is_ascii_uppercase()).unwrap_or(false) &&
+    name.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+fn is_field_name(name: &str) -> bool {
+    name.chars().next().map(|c| c.is_ascii_lowercase()).unwrap_or(false) &&
+    name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Convert a name into the name of a declaration, unless it already is a valid one.
+///
+/// Only letters are permitted in the names of declarations.
+pub fn type_name(name: &str) -> Result<String> {
+    if is_type_name(name) {
+        return Ok(name.to_owned());
+    }
+
+    let mut out = String::new();
+
+    for word in words(name) {
+        let mut chars = word.chars();
+
+        if let Some(first) = chars.next() {
+            out.extend(first.to_uppercase());
+            out.extend(chars.flat_map(|c| c.to_lowercase()));
+        }
+    }
+
+    if !is_type_name(&out) {
+        return Err(format!("`{}` can't be converted into a type name, which can only contain \
+                            letters",
+                           name)
+            .into());
+    }
+
+    Ok(out)
+}
+
+/// Convert a name into the name of a field, or a part of a package, unless it already is a valid
+/// one.
+pub fn field_name(name: &str) -> Result<String> {
+    if is_field_name(name) {
+        return Ok(name.to_owned());
+    }
+
+    let words: Vec<_> = words(name).into_iter().map(|w| w.to_lowercase()).collect();
+    let out = words.join("_");
+
+    if !is_field_name(&out) {
+        return Err(format!("`{}` can't be converted into a field name", name).into());
+    }
+
+    Ok(out)
+}
+
+/// Convert every part of a package into a valid name.
+pub fn package(parts: &[String]) -> Result<Package> {
+    let parts: Result<Vec<_>> = parts.iter().map(|p| field_name(p)).collect();
+    Ok(Package::new(parts?))
+}
+
+/// Split documentation into lines.
+pub fn comment(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = text.lines().map(|l| l.trim_right().to_owned()).collect();
+
+    while lines.last().map(|l| l.is_empty()).unwrap_or(false) {
+        lines.pop();
+    }
+
+    lines
+}
+
+/// Build a field which is serialized with the given name, renaming it if the name is not a valid
+/// identifier.
+pub fn field(name: &str, ty: Type, optional: bool, comment: Vec<String>) -> Result<Field> {
+    Ok(renamed_field(field_name(name)?, name, ty, optional, comment))
+}
+
+/// Build a field which is serialized with a name other than its own, if they differ.
+pub fn renamed_field(name: String,
+                     serialized: &str,
+                     ty: Type,
+                     optional: bool,
+                     comment: Vec<String>)
+                     -> Field {
+    let field_as = if name != serialized {
+        Some(token(Value::String(serialized.to_owned())))
+    } else {
+        None
+    };
+
+    Field {
+        comment: comment,
+        modifier: if optional { Modifier::Optional } else { Modifier::Required },
+        name: name,
+        ty: ty,
+        field_as: field_as,
+    }
+}
+
+pub fn option(name: &str, values: Vec<Value>) -> Member {
+    Member::Option(token(OptionDecl {
+        name: name.to_owned(),
+        values: values.into_iter().map(token).collect(),
+    }))
+}
+
+/// Find which numbers have to be given explicitly, for all numbers to be the same as those that
+/// the protobuf backend assigns.
+///
+/// Numbers which are not where they would be if they were assigned in order of declaration are
+/// given explicitly, and if that is not enough, all of them are.
+pub fn explicit_numbers(numbers: &[u32]) -> Vec<bool> {
+    let explicit: Vec<bool> = numbers.iter()
+        .enumerate()
+        .map(|(i, n)| *n as usize != i + 1)
+        .collect();
+    let mut used: BTreeSet<u32> = numbers.iter()
+        .zip(&explicit)
+        .filter(|&(_, e)| *e)
+        .map(|(n, _)| *n)
+        .collect();
+
+    let mut next = 1;
+
+    for (number, explicit) in numbers.iter().zip(&explicit) {
+        if *explicit {
+            continue;
+        }
+
+        while used.contains(&next) || is_reserved(next) {
+            next += 1;
+        }
+
+        if next != *number {
+            return vec![true; numbers.len()];
+        }
+
+        used.insert(next);
+    }
+
+    explicit
+}
+
+/// Build the members of a declaration, with the `field_number` options that its fields need.
+pub fn numbered_fields(fields: Vec<(Field, u32)>) -> Vec<Member> {
+    let numbers: Vec<_> = fields.iter().map(|&(_, number)| number).collect();
+    let mut members = Vec::new();
+
+    for (&(ref field, number), explicit) in fields.iter().zip(explicit_numbers(&numbers)) {
+        if explicit {
+            let values = vec![Value::Identifier(field.name.clone()),
+                              Value::Number(number as f64)];
+            members.push(option("field_number", values));
+        }
+    }
+
+    members.extend(fields.into_iter().map(|(field, _)| Member::Field(field)));
+    members
+}
+
+fn same_field(a: &Field, b: &Field) -> bool {
+    a.name == b.name && a.modifier == b.modifier && a.ty == b.ty && a.field_as == b.field_as
+}
+
+fn fields(members: &Vec<Member>) -> Vec<&Field> {
+    members.iter()
+        .filter_map(|m| if let Member::Field(ref field) = *m { Some(field) } else { None })
+        .collect()
+}
+
+/// Build an interface out of its sub types.
+///
+/// Fields which all sub types start with are moved to the interface, unless fields are numbered
+/// explicitly, since that would change their numbers.
+pub fn interface(name: String,
+                 comment: Vec<String>,
+                 mut members: Vec<Member>,
+                 variants: Vec<Variant>)
+                 -> Decl {
+    let numbered = variants.iter()
+        .flat_map(|v| v.members.iter())
+        .any(|m| if let Member::Option(ref o) = *m { o.name == "field_number" } else { false });
+
+    let mut common = 0;
+
+    if !numbered && !variants.is_empty() {
+        let first = fields(&variants[0].members);
+
+        while common < first.len() &&
+              variants.iter().all(|v| {
+            fields(&v.members).get(common).map(|f| same_field(f, first[common])).unwrap_or(false)
+        }) {
+            common += 1;
+        }
+
+        members.extend(first[..common].iter().map(|f| Member::Field((*f).clone())));
+    }
+
+    let mut sub_types = Vec::new();
+
+    for variant in variants {
+        let names = variant.names.into_iter().map(Value::String).collect();
+        let mut sub_members = vec![option("name", names)];
+        let mut skipped = 0;
+
+        for member in variant.members {
+            if let Member::Field(_) = member {
+                if skipped < common {
+                    skipped += 1;
+                    continue;
+                }
+            }
+
+            sub_members.push(member);
+        }
+
+        sub_types.push(token(SubType {
+            name: variant.name,
+            comment: variant.comment,
+            members: sub_members.into_iter().map(token).collect(),
+        }));
+    }
+
+    Decl::Interface(InterfaceBody {
+        name: name,
+        comment: comment,
+        members: members.into_iter().map(token).collect(),
+        sub_types: sub_types,
+    })
+}
+
+/// Build an enum out of its values.
+///
+/// If the serialized values can't all be used as names, the enum is serialized as a string field
+/// instead.
+pub fn enumeration(name: String, comment: Vec<String>, variants: Vec<EnumVariant>) -> Result<Decl> {
+    let by_name = variants.iter().all(|v| v.name.is_none() && is_type_name(&v.serialized));
+
+    let mut values = Vec::new();
+    let mut names = Vec::new();
+
+    for variant in variants {
+        let value_name = type_name(variant.name.as_ref().unwrap_or(&variant.serialized))
+            .chain_err(|| format!("bad value in enum {}", name))?;
+
+        if names.contains(&value_name) {
+            return Err(format!("enum {}: more than one value is named {}", name, value_name)
+                .into());
+        }
+
+        names.push(value_name.clone());
+
+        let arguments = if by_name {
+            vec![]
+        } else {
+            vec![token(Value::String(variant.serialized))]
+        };
+
+        values.push(token(EnumValue {
+            name: value_name,
+            comment: variant.comment,
+            arguments: arguments,
+            ordinal: variant.ordinal.map(|o| token(Value::Number(o as f64))),
+        }));
+    }
+
+    let mut members = Vec::new();
+
+    if !by_name {
+        members.push(option("serialized_as", vec![Value::Identifier(ENUM_FIELD.to_owned())]));
+        members.push(Member::Field(field(ENUM_FIELD, Type::String, false, vec![])?));
+    }
+
+    Ok(Decl::Enum(EnumBody {
+        name: name,
+        comment: comment,
+        values: values,
+        members: members.into_iter().map(token).collect(),
+    }))
+}
+
+/// Find all files with the given extension in the given directory.
+pub fn find_files(path: &Path, extension: &str, out: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries = Vec::new();
+
+    for entry in fs::read_dir(path)? {
+        entries.push(entry?.path());
+    }
+
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            find_files(&entry, extension, out)?;
+        } else if entry.extension().map(|e| e == extension).unwrap_or(false) {
+            out.push(entry);
+        }
+    }
+
+    Ok(())
+}
+
+/// The formatted source of a module.
+pub struct Source {
+    pub package: Package,
+    /// Path of the source, relative to the root of the output.
+    pub path: PathBuf,
+    pub content: String,
+}
+
+pub fn render(modules: Vec<Module>) -> Vec<Source> {
+    modules.into_iter()
+        .map(|module| {
+            Source {
+                package: module.package.clone(),
+                path: module.path(),
+                content: format::format_file(&module.into_file()),
+            }
+        })
+        .collect()
+}
+
+/// Parse and verify the sources, as if they were written to the given root.
+pub fn verify(root: &Path, sources: &[Source]) -> Result<()> {
+    let mut env = Environment::new(vec![root.to_owned()]);
+
+    for source in sources {
+        env.add_source(root.join(&source.path), source.content.clone());
+    }
+
+    for source in sources {
+        env.import(&source.package)?;
+    }
+
+    env.verify()?;
+    Ok(())
+}
@@ -0,0 +1,1060 @@
+//! Import of proto3 files.
+//!
+//! Messages become types, and enums become enums serialized as the names of their values. A
+//! message which only holds a `oneof` of messages nested in it becomes an interface, whose sub
+//! types are serialized as objects with a single key, which is how protobuf serializes such a
+//! message to JSON. Other nested declarations are moved to the top of their package, prefixed with
+//! the names of the messages they were nested in.
+//!
+//! Protobuf leaves out fields which have their default value when serializing to JSON, so every
+//! imported field is optional.
+
+use backend::models::{Package, Type};
+use errors::*;
+use parser::ast::*;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use super::{EnumVariant, Module, Modules, Variant, enumeration, explicit_numbers, field_name,
+            find_files, interface, numbered_fields, option, renamed_field, type_name, words};
+
+const EXT: &str = "proto";
+/// Package of the well-known types, which are never imported.
+const WELL_KNOWN: &str = "google.protobuf.";
+const WELL_KNOWN_PATH: &str = "google/protobuf/";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Identifier,
+    Number,
+    String,
+    Symbol,
+}
+
+#[derive(Debug, Clone)]
+struct Tok {
+    kind: Kind,
+    text: String,
+    /// Comments on the lines right before the token.
+    comment: Vec<String>,
+    line: usize,
+}
+
+#[derive(Debug, Clone)]
+enum FieldType {
+    Named(String),
+    Map(String, String),
+}
+
+#[derive(Debug, Clone)]
+struct ProtoField {
+    name: String,
+    comment: Vec<String>,
+    repeated: bool,
+    ty: FieldType,
+    number: u32,
+    json_name: Option<String>,
+    oneof: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ProtoEnumValue {
+    name: String,
+    comment: Vec<String>,
+    number: i64,
+}
+
+#[derive(Debug, Clone)]
+struct ProtoEnum {
+    name: String,
+    comment: Vec<String>,
+    values: Vec<ProtoEnumValue>,
+}
+
+#[derive(Debug, Clone)]
+struct Message {
+    name: String,
+    comment: Vec<String>,
+    fields: Vec<ProtoField>,
+    oneofs: Vec<String>,
+    messages: Vec<Message>,
+    enums: Vec<ProtoEnum>,
+}
+
+struct ProtoFile {
+    path: PathBuf,
+    package: Vec<String>,
+    imports: Vec<String>,
+    messages: Vec<Message>,
+    enums: Vec<ProtoEnum>,
+}
+
+/// Collect the text of a comment, without its markers.
+fn comment_lines(text: &str, out: &mut Vec<String>) {
+    for line in text.lines() {
+        let line = line.trim();
+        let line = line.trim_left_matches('*');
+        let line = if line.starts_with(' ') { &line[1..] } else { line };
+        out.push(line.trim_right().to_owned());
+    }
+}
+
+fn tokenize(path: &Path, content: &str) -> Result<Vec<Tok>> {
+    let chars: Vec<char> = content.chars().collect();
+    let mut tokens: Vec<Tok> = Vec::new();
+    let mut comment = Vec::new();
+    let mut line = 1;
+    // newlines since the last token or comment, to tell trailing and detached comments apart.
+    let mut newlines = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if c == '\n' {
+            line += 1;
+            newlines += 1;
+            i += 1;
+            continue;
+        }
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c == '/' && (chars.get(i + 1) == Some(&'/') || chars.get(i + 1) == Some(&'*')) {
+            let block = chars[i + 1] == '*';
+            let start = i + 2;
+            let mut end = start;
+
+            if block {
+                while end + 1 < chars.len() && !(chars[end] == '*' && chars[end + 1] == '/') {
+                    end += 1;
+                }
+            } else {
+                while end < chars.len() && chars[end] != '\n' {
+                    end += 1;
+                }
+            }
+
+            let text: String = chars[start..end.min(chars.len())].iter().collect();
+
+            // comments after a token on the same line, or separated by a blank line, are not
+            // documentation.
+            if newlines > 1 {
+                comment.clear();
+            }
+
+            if newlines > 0 || tokens.is_empty() {
+                comment_lines(text.trim_left_matches('*'), &mut comment);
+            }
+
+            line += text.matches('\n').count();
+            newlines = 0;
+            i = if block { end + 2 } else { end };
+            continue;
+        }
+
+        let start = i;
+
+        let kind = if c.is_alphabetic() || c == '_' ||
+                      (c == '.' && chars.get(i + 1).map(|n| n.is_alphabetic()).unwrap_or(false)) {
+            while i < chars.len() &&
+                  (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+
+            Kind::Identifier
+        } else if c.is_ascii_digit() ||
+                  (c == '-' && chars.get(i + 1).map(|n| n.is_ascii_digit()).unwrap_or(false)) {
+            i += 1;
+
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+
+            Kind::Number
+        } else if c == '"' || c == '\'' {
+            i += 1;
+
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+
+                i += 1;
+            }
+
+            if i >= chars.len() {
+                return Err(format!("{}:{}: unterminated string", path.display(), line).into());
+            }
+
+            i += 1;
+            Kind::String
+        } else {
+            i += 1;
+            Kind::Symbol
+        };
+
+        let text: String = chars[start..i].iter().collect();
+
+        let text = if kind == Kind::String {
+            unquote(&text)
+        } else {
+            text
+        };
+
+        let comment = if newlines > 1 {
+            comment.clear();
+            vec![]
+        } else {
+            comment.drain(..).collect()
+        };
+
+        tokens.push(Tok {
+            kind: kind,
+            text: text,
+            comment: comment,
+            line: line,
+        });
+
+        newlines = 0;
+    }
+
+    Ok(tokens)
+}
+
+/// Decode a string literal, including its quotes.
+fn unquote(literal: &str) -> String {
+    let mut out = String::new();
+    let mut chars = literal[1..literal.len() - 1].chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+
+    out
+}
+
+struct Parser<'a> {
+    path: &'a Path,
+    tokens: Vec<Tok>,
+    index: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: String) -> Error {
+        let line = self.tokens
+            .get(self.index)
+            .or_else(|| self.tokens.last())
+            .map(|t| t.line)
+            .unwrap_or(1);
+
+        format!("{}:{}: {}", self.path.display(), line, message).into()
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.index).map(|t| t.text.as_str())
+    }
+
+    fn next(&mut self) -> Result<Tok> {
+        match self.tokens.get(self.index).cloned() {
+            Some(token) => {
+                self.index += 1;
+                Ok(token)
+            }
+            None => Err(self.error("unexpected end of file".to_owned())),
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Tok> {
+        if self.peek() != Some(text) {
+            let found = self.peek().unwrap_or("end of file").to_owned();
+            return Err(self.error(format!("expected `{}`, but found `{}`", text, found)));
+        }
+
+        self.next()
+    }
+
+    fn kind(&mut self, kind: Kind) -> Result<Tok> {
+        match self.tokens.get(self.index) {
+            Some(token) if token.kind == kind => {}
+            _ => {
+                let found = self.peek().unwrap_or("end of file").to_owned();
+                return Err(self.error(format!("expected {:?}, but found `{}`", kind, found)));
+            }
+        }
+
+        self.next()
+    }
+
+    fn number(&mut self) -> Result<i64> {
+        let token = self.kind(Kind::Number)?;
+
+        let number = if token.text.starts_with("0x") || token.text.starts_with("0X") {
+            i64::from_str_radix(&token.text[2..], 16).ok()
+        } else {
+            token.text.parse().ok()
+        };
+
+        number.ok_or_else(|| self.error(format!("bad number: {}", token.text)))
+    }
+
+    /// Skip a statement, or a block.
+    fn skip(&mut self) -> Result<()> {
+        loop {
+            let token = self.next()?;
+
+            if token.text == ";" {
+                return Ok(());
+            }
+
+            if token.text == "{" {
+                let mut depth = 1;
+
+                while depth > 0 {
+                    match self.next()?.text.as_str() {
+                        "{" => depth += 1,
+                        "}" => depth -= 1,
+                        _ => {}
+                    }
+                }
+
+                return Ok(());
+            }
+        }
+    }
+
+    fn file(&mut self) -> Result<ProtoFile> {
+        let mut file = ProtoFile {
+            path: self.path.to_owned(),
+            package: vec![],
+            imports: vec![],
+            messages: vec![],
+            enums: vec![],
+        };
+
+        while let Some(keyword) = self.peek().map(ToOwned::to_owned) {
+            match keyword.as_str() {
+                "syntax" => {
+                    self.next()?;
+                    self.expect("=")?;
+                    let syntax = self.kind(Kind::String)?;
+
+                    if syntax.text != "proto3" {
+                        return Err(self.error(format!("only proto3 is supported, not {}",
+                                                      syntax.text)));
+                    }
+
+                    self.expect(";")?;
+                }
+                "package" => {
+                    self.next()?;
+                    let package = self.kind(Kind::Identifier)?;
+                    file.package = package.text.split('.').map(ToOwned::to_owned).collect();
+                    self.expect(";")?;
+                }
+                "import" => {
+                    self.next()?;
+
+                    if self.peek() == Some("public") || self.peek() == Some("weak") {
+                        self.next()?;
+                    }
+
+                    file.imports.push(self.kind(Kind::String)?.text);
+                    self.expect(";")?;
+                }
+                "message" => file.messages.push(self.message()?),
+                "enum" => file.enums.push(self.enumeration()?),
+                "option" => self.skip()?,
+                "service" | "extend" => {
+                    warn!("{}: skipping {}", self.path.display(), keyword);
+                    self.skip()?;
+                }
+                ";" => {
+                    self.next()?;
+                }
+                _ => return Err(self.error(format!("unexpected `{}`", keyword))),
+            }
+        }
+
+        Ok(file)
+    }
+
+    fn message(&mut self) -> Result<Message> {
+        let comment = self.expect("message")?.comment;
+        let name = self.kind(Kind::Identifier)?.text;
+        self.expect("{")?;
+
+        let mut message = Message {
+            name: name,
+            comment: comment,
+            fields: vec![],
+            oneofs: vec![],
+            messages: vec![],
+            enums: vec![],
+        };
+
+        while let Some(keyword) = self.peek().map(ToOwned::to_owned) {
+            match keyword.as_str() {
+                "}" => {
+                    self.next()?;
+                    return Ok(message);
+                }
+                "message" => message.messages.push(self.message()?),
+                "enum" => message.enums.push(self.enumeration()?),
+                "oneof" => {
+                    self.next()?;
+                    let oneof = self.kind(Kind::Identifier)?.text;
+                    self.expect("{")?;
+
+                    while self.peek() != Some("}") {
+                        if self.peek() == Some("option") {
+                            self.skip()?;
+                            continue;
+                        }
+
+                        let mut field = self.field()?;
+                        field.oneof = Some(oneof.clone());
+                        message.fields.push(field);
+                    }
+
+                    self.next()?;
+                    message.oneofs.push(oneof);
+                }
+                "option" | "reserved" | "extensions" | "extend" => self.skip()?,
+                ";" => {
+                    self.next()?;
+                }
+                _ => message.fields.push(self.field()?),
+            }
+        }
+
+        Err(self.error(format!("message {} is not closed", message.name)))
+    }
+
+    fn field(&mut self) -> Result<ProtoField> {
+        let comment = self.tokens.get(self.index).map(|t| t.comment.clone()).unwrap_or_default();
+        let mut repeated = false;
+
+        match self.peek() {
+            Some("repeated") => {
+                repeated = true;
+                self.next()?;
+            }
+            Some("optional") | Some("required") => {
+                self.next()?;
+            }
+            _ => {}
+        }
+
+        let ty = if self.peek() == Some("map") {
+            self.next()?;
+            self.expect("<")?;
+            let key = self.kind(Kind::Identifier)?.text;
+            self.expect(",")?;
+            let value = self.kind(Kind::Identifier)?.text;
+            self.expect(">")?;
+            FieldType::Map(key, value)
+        } else {
+            FieldType::Named(self.kind(Kind::Identifier)?.text)
+        };
+
+        let name = self.kind(Kind::Identifier)?.text;
+        self.expect("=")?;
+        let number = self.number()?;
+
+        if number < 1 || number > u32::max_value() as i64 {
+            return Err(self.error(format!("bad field number: {}", number)));
+        }
+
+        let mut json_name = None;
+
+        if self.peek() == Some("[") {
+            self.next()?;
+
+            while self.peek() != Some("]") {
+                let option = self.next()?;
+
+                if option.text == "json_name" {
+                    self.expect("=")?;
+                    json_name = Some(self.kind(Kind::String)?.text);
+                }
+            }
+
+            self.next()?;
+        }
+
+        self.expect(";")?;
+
+        Ok(ProtoField {
+            name: name,
+            comment: comment,
+            repeated: repeated,
+            ty: ty,
+            number: number as u32,
+            json_name: json_name,
+            oneof: None,
+        })
+    }
+
+    fn enumeration(&mut self) -> Result<ProtoEnum> {
+        let comment = self.expect("enum")?.comment;
+        let name = self.kind(Kind::Identifier)?.text;
+        self.expect("{")?;
+
+        let mut values = Vec::new();
+
+        loop {
+            match self.peek() {
+                Some("}") => {
+                    self.next()?;
+                    break;
+                }
+                Some("option") | Some("reserved") => self.skip()?,
+                Some(";") => {
+                    self.next()?;
+                }
+                _ => {
+                    let token = self.kind(Kind::Identifier)?;
+                    self.expect("=")?;
+                    let number = self.number()?;
+
+                    if self.peek() == Some("[") {
+                        while self.next()?.text != "]" {}
+                    }
+
+                    self.expect(";")?;
+
+                    values.push(ProtoEnumValue {
+                        name: token.text,
+                        comment: token.comment,
+                        number: number,
+                    });
+                }
+            }
+        }
+
+        Ok(ProtoEnum {
+            name: name,
+            comment: comment,
+            values: values,
+        })
+    }
+}
+
+fn parse(path: &Path) -> Result<ProtoFile> {
+    let mut content = String::new();
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut content))
+        .chain_err(|| format!("failed to read: {}", path.display()))?;
+
+    let mut parser = Parser {
+        path: path,
+        tokens: tokenize(path, &content)?,
+        index: 0,
+    };
+
+    parser.file()
+}
+
+/// The name of a field in JSON, as protobuf derives it.
+fn json_name(name: &str) -> String {
+    let mut out = String::new();
+    let mut upper = false;
+
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+fn upper_snake(name: &str) -> String {
+    let words: Vec<_> = words(name).into_iter().map(|w| w.to_uppercase()).collect();
+    words.join("_")
+}
+
+fn scalar(name: &str) -> Option<Type> {
+    let ty = match name {
+        "double" => Type::Double,
+        "float" => Type::Float,
+        "int32" | "sint32" | "sfixed32" => Type::Signed(Some(32)),
+        "int64" | "sint64" | "sfixed64" => Type::Signed(Some(64)),
+        "uint32" | "fixed32" => Type::Unsigned(Some(32)),
+        "uint64" | "fixed64" => Type::Unsigned(Some(64)),
+        "bool" => Type::Boolean,
+        "string" => Type::String,
+        "bytes" => Type::Bytes,
+        _ => return None,
+    };
+
+    Some(ty)
+}
+
+/// Types with the same JSON encoding as the well-known types.
+fn well_known(name: &str) -> Option<Type> {
+    let name = name.trim_left_matches('.');
+
+    if !name.starts_with(WELL_KNOWN) {
+        return None;
+    }
+
+    let ty = match &name[WELL_KNOWN.len()..] {
+        "Any" | "Value" => Type::Any,
+        "Struct" => Type::Map(Box::new(Type::String), Box::new(Type::Any)),
+        "ListValue" => Type::Array(Box::new(Type::Any)),
+        "Timestamp" | "Duration" | "FieldMask" | "StringValue" => Type::String,
+        "DoubleValue" => Type::Double,
+        "FloatValue" => Type::Float,
+        "Int64Value" => Type::Signed(Some(64)),
+        "UInt64Value" => Type::Unsigned(Some(64)),
+        "Int32Value" => Type::Signed(Some(32)),
+        "UInt32Value" => Type::Unsigned(Some(32)),
+        "BoolValue" => Type::Boolean,
+        "BytesValue" => Type::Bytes,
+        _ => return None,
+    };
+
+    Some(ty)
+}
+
+/// The sub types of a message which only holds a `oneof` of messages nested in it.
+fn variants(message: &Message) -> Option<Vec<(&ProtoField, &Message)>> {
+    if message.oneofs.len() != 1 || message.fields.is_empty() {
+        return None;
+    }
+
+    let mut variants = Vec::new();
+
+    for field in &message.fields {
+        let nested = match field.ty {
+            FieldType::Named(ref name) if field.oneof.is_some() && !field.repeated => {
+                message.messages.iter().find(|m| m.name == *name)?
+            }
+            _ => return None,
+        };
+
+        if variants.iter().any(|&(_, m): &(&ProtoField, &Message)| m.name == nested.name) {
+            return None;
+        }
+
+        variants.push((field, nested));
+    }
+
+    Some(variants)
+}
+
+/// Where a protobuf declaration is imported.
+struct Target {
+    package: Package,
+    parts: Vec<String>,
+}
+
+struct Importer {
+    /// Imported declarations, by their fully qualified protobuf name.
+    targets: HashMap<String, Target>,
+    modules: Modules,
+}
+
+impl Importer {
+    fn register(&mut self, full_name: String, package: &Package, parts: Vec<String>) -> Result<()> {
+        let taken = self.targets.values().any(|t| t.package == *package && t.parts == parts);
+
+        if taken {
+            return Err(format!("{} is declared more than once in {}",
+                               parts.join("."),
+                               package)
+                .into());
+        }
+
+        self.targets.insert(full_name,
+                            Target {
+                                package: package.clone(),
+                                parts: parts,
+                            });
+
+        Ok(())
+    }
+
+    fn register_message(&mut self,
+                        package: &Package,
+                        scope: &str,
+                        message: &Message,
+                        parts: Vec<String>)
+                        -> Result<()> {
+        let full_name = format!("{}{}", scope, message.name);
+        let scope = format!("{}.", full_name);
+        let prefix = parts.concat();
+
+        let sub_types: Vec<&str> = variants(message)
+            .map(|v| v.into_iter().map(|(_, m)| m.name.as_str()).collect())
+            .unwrap_or_default();
+
+        for nested in &message.messages {
+            let name = type_name(&nested.name)?;
+
+            let nested_parts = if sub_types.contains(&nested.name.as_str()) {
+                vec![prefix.clone(), name]
+            } else {
+                vec![format!("{}{}", prefix, name)]
+            };
+
+            self.register_message(package, &scope, nested, nested_parts)?;
+        }
+
+        for nested in &message.enums {
+            let parts = vec![format!("{}{}", prefix, type_name(&nested.name)?)];
+            self.register(format!("{}{}", scope, nested.name), package, parts)?;
+        }
+
+        self.register(full_name, package, parts)
+    }
+
+    fn register_file(&mut self, package: &Package, file: &ProtoFile) -> Result<()> {
+        let scope = file.package.iter().map(|p| format!("{}.", p)).collect::<String>();
+
+        for message in &file.messages {
+            self.register_message(package, &scope, message, vec![type_name(&message.name)?])?;
+        }
+
+        for nested in &file.enums {
+            let parts = vec![type_name(&nested.name)?];
+            self.register(format!("{}{}", scope, nested.name), package, parts)?;
+        }
+
+        Ok(())
+    }
+
+    /// Find the declaration that a name refers to in the given scope.
+    fn lookup(&self, scope: &str, name: &str) -> Option<&Target> {
+        if name.starts_with('.') {
+            return self.targets.get(&name[1..]);
+        }
+
+        let mut scope = scope.trim_right_matches('.').to_owned();
+
+        loop {
+            let candidate = if scope.is_empty() {
+                name.to_owned()
+            } else {
+                format!("{}.{}", scope, name)
+            };
+
+            if let Some(target) = self.targets.get(&candidate) {
+                return Some(target);
+            }
+
+            if scope.is_empty() {
+                return None;
+            }
+
+            scope = match scope.rfind('.') {
+                Some(index) => scope[..index].to_owned(),
+                None => String::new(),
+            };
+        }
+    }
+
+    fn convert(&mut self, package: &Package, scope: &str, name: &str) -> Result<Type> {
+        if let Some(ty) = scalar(name) {
+            return Ok(ty);
+        }
+
+        let target = self.lookup(scope, name).map(|t| (t.package.clone(), t.parts.clone()));
+
+        if let Some((target_package, parts)) = target {
+            return Ok(self.modules.get(package).custom(&target_package, parts));
+        }
+
+        well_known(name).ok_or_else(|| format!("no such type: {}", name).into())
+    }
+
+    fn field(&mut self, package: &Package, scope: &str, field: &ProtoField) -> Result<Field> {
+        let ty = match field.ty {
+            FieldType::Named(ref name) => self.convert(package, scope, name)?,
+            FieldType::Map(ref key, ref value) => {
+                let key = scalar(key).ok_or_else(|| format!("bad type of map key: {}", key))?;
+                let value = self.convert(package, scope, value)?;
+                Type::Map(Box::new(key), Box::new(value))
+            }
+        };
+
+        let ty = if field.repeated { Type::Array(Box::new(ty)) } else { ty };
+        let serialized = field.json_name.clone().unwrap_or_else(|| json_name(&field.name));
+
+        Ok(renamed_field(field_name(&field.name)?,
+                         &serialized,
+                         ty,
+                         true,
+                         field.comment.clone()))
+    }
+
+    fn fields(&mut self,
+              package: &Package,
+              scope: &str,
+              fields: &[ProtoField])
+              -> Result<Vec<Member>> {
+        let mut numbered = Vec::new();
+
+        for field in fields {
+            numbered.push((self.field(package, scope, field)?, field.number));
+        }
+
+        Ok(numbered_fields(numbered))
+    }
+
+    fn declare_enum(&mut self, package: &Package, scope: &str, body: &ProtoEnum) -> Result<()> {
+        let name = self.targets[&format!("{}{}", scope, body.name)].parts.concat();
+        let prefix = format!("{}_", upper_snake(&body.name));
+        let mut numbers = HashSet::new();
+        let mut variants = Vec::new();
+
+        for value in &body.values {
+            if value.number < 0 {
+                return Err(format!("{}.{}: negative values are not supported",
+                                   body.name,
+                                   value.name)
+                    .into());
+            }
+
+            if !numbers.insert(value.number) {
+                warn!("{}.{}: skipping alias", body.name, value.name);
+                continue;
+            }
+
+            let stripped = if value.name.starts_with(&prefix) && value.name.len() > prefix.len() {
+                Some(value.name[prefix.len()..].to_owned())
+            } else {
+                None
+            };
+
+            variants.push(EnumVariant {
+                serialized: value.name.clone(),
+                name: stripped,
+                comment: value.comment.clone(),
+                ordinal: Some(value.number as u32),
+            });
+        }
+
+        let decl = enumeration(name, body.comment.clone(), variants)?;
+        self.modules.get(package).decls.push(decl);
+        Ok(())
+    }
+
+    fn declare_message(&mut self,
+                       package: &Package,
+                       scope: &str,
+                       message: &Message)
+                       -> Result<()> {
+        let full_name = format!("{}{}", scope, message.name);
+        let name = self.targets[&full_name].parts.concat();
+        let scope = format!("{}.", full_name);
+        let variants = variants(message);
+
+        let sub_types: Vec<&str> = variants.as_ref()
+            .map(|v| v.iter().map(|&(_, m)| m.name.as_str()).collect())
+            .unwrap_or_default();
+
+        for nested in &message.messages {
+            if sub_types.contains(&nested.name.as_str()) {
+                // nested declarations of sub types are still declared.
+                let sub_scope = format!("{}{}.", scope, nested.name);
+
+                for inner in &nested.messages {
+                    self.declare_message(package, &sub_scope, inner)?;
+                }
+
+                for inner in &nested.enums {
+                    self.declare_enum(package, &sub_scope, inner)?;
+                }
+
+                continue;
+            }
+
+            self.declare_message(package, &scope, nested)?;
+        }
+
+        for nested in &message.enums {
+            self.declare_enum(package, &scope, nested)?;
+        }
+
+        let decl = match variants {
+            Some(variants) => {
+                let object_keys = Value::Identifier("object_keys".to_owned());
+                let mut members = vec![option("type_info", vec![object_keys])];
+                let mut sub_types = Vec::new();
+                let numbers: Vec<_> = variants.iter().map(|&(field, _)| field.number).collect();
+                let explicit = explicit_numbers(&numbers);
+
+                for ((field, sub_message), explicit) in variants.into_iter().zip(explicit) {
+                    let sub_name = type_name(&sub_message.name)?;
+                    let sub_scope = format!("{}{}.", scope, sub_message.name);
+
+                    if explicit {
+                        let values = vec![Value::Constant(super::token(Constant {
+                                              prefix: None,
+                                              parts: vec![sub_name.clone()],
+                                          })),
+                                          Value::Number(field.number as f64)];
+
+                        members.push(option("field_number", values));
+                    }
+
+                    let comment = if sub_message.comment.is_empty() {
+                        field.comment.clone()
+                    } else {
+                        sub_message.comment.clone()
+                    };
+
+                    let serialized = field.json_name
+                        .clone()
+                        .unwrap_or_else(|| json_name(&field.name));
+
+                    sub_types.push(Variant {
+                        name: sub_name,
+                        comment: comment,
+                        names: vec![serialized],
+                        members: self.fields(package, &sub_scope, &sub_message.fields)?,
+                    });
+                }
+
+                interface(name, message.comment.clone(), members, sub_types)
+            }
+            None => {
+                let members = self.fields(package, &scope, &message.fields)?;
+
+                Decl::Type(TypeBody {
+                    name: name,
+                    comment: message.comment.clone(),
+                    members: members.into_iter().map(super::token).collect(),
+                })
+            }
+        };
+
+        self.modules.get(package).decls.push(decl);
+        Ok(())
+    }
+}
+
+/// Find an imported file in the given directories.
+fn resolve_import(import: &str, dirs: &[PathBuf]) -> Option<PathBuf> {
+    dirs.iter().map(|dir| dir.join(import)).find(|path| path.is_file())
+}
+
+/// Import proto3 files, and every file in the given directories.
+///
+/// The files that they import are imported as well, and are looked for in the given directories
+/// and the directories containing the given files. Packages are prefixed with `prefix`, if given.
+pub fn import(inputs: &[PathBuf], prefix: Option<&Package>) -> Result<Vec<Module>> {
+    let mut dirs = Vec::new();
+    let mut pending = Vec::new();
+
+    for input in inputs {
+        if input.is_dir() {
+            dirs.push(input.clone());
+            find_files(input, EXT, &mut pending)?;
+        } else {
+            // imports are relative to a root which is not known, so try every parent.
+            if let Some(parent) = input.parent() {
+                dirs.extend(parent.ancestors().map(ToOwned::to_owned));
+            }
+
+            pending.push(input.clone());
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut files = Vec::new();
+
+    while let Some(path) = pending.pop() {
+        if !visited.insert(path.canonicalize()?) {
+            continue;
+        }
+
+        let file = parse(&path)?;
+
+        for import in &file.imports {
+            if import.starts_with(WELL_KNOWN_PATH) {
+                continue;
+            }
+
+            match resolve_import(import, &dirs) {
+                Some(path) => pending.push(path),
+                None => {
+                    return Err(format!("{}: imported file not found: {}",
+                                       path.display(),
+                                       import)
+                        .into())
+                }
+            }
+        }
+
+        files.push(file);
+    }
+
+    let mut importer = Importer {
+        targets: HashMap::new(),
+        modules: Modules::new(),
+    };
+
+    let mut packages = Vec::new();
+
+    for file in &files {
+        let mut parts = prefix.map(|p| p.parts.clone()).unwrap_or_default();
+        parts.extend(file.package.iter().cloned());
+
+        if parts.is_empty() {
+            return Err(format!("{}: no package to import into, use --package",
+                               file.path.display())
+                .into());
+        }
+
+        let package = super::package(&parts)?;
+
+        importer.register_file(&package, file)
+            .chain_err(|| format!("failed to import {}", file.path.display()))?;
+
+        packages.push(package);
+    }
+
+    for (file, package) in files.iter().zip(packages) {
+        let scope = file.package.iter().map(|p| format!("{}.", p)).collect::<String>();
+
+        let result: Result<()> = (|| {
+            for message in &file.messages {
+                importer.declare_message(&package, &scope, message)?;
+            }
+
+            for body in &file.enums {
+                importer.declare_enum(&package, &scope, body)?;
+            }
+
+            Ok(())
+        })();
+
+        result.chain_err(|| format!("failed to import {}", file.path.display()))?;
+    }
+
+    Ok(importer.modules.into_vec())
+}
@@ -0,0 +1,969 @@
+//! A language server for `.reproto` files, speaking the language server protocol over stdio.
+//!
+//! Diagnostics, definitions, hovers, and completions are all based on an `Environment` which is
+//! built from the search paths, using the content of open documents instead of what is on disk.
+
+mod protocol;
+
+use backend::compat::type_display;
+use backend::environment::Environment;
+use backend::errors as backend_errors;
+use backend::json::Json;
+use backend::models::*;
+use errors::*;
+use parser;
+use parser::errors::ErrorKind::Syntax;
+use self::protocol::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+
+const PARSE_ERROR: &str = "-32700";
+const METHOD_NOT_FOUND: &str = "-32601";
+
+const SEVERITY_ERROR: usize = 1;
+const SEVERITY_INFORMATION: usize = 3;
+
+const COMPLETION_FIELD: usize = 5;
+const COMPLETION_CLASS: usize = 7;
+const COMPLETION_INTERFACE: usize = 8;
+const COMPLETION_MODULE: usize = 9;
+const COMPLETION_ENUM: usize = 13;
+const COMPLETION_KEYWORD: usize = 14;
+const COMPLETION_ENUM_MEMBER: usize = 20;
+
+const BUILT_IN_TYPES: &[&str] = &["any", "boolean", "bytes", "double", "float", "signed",
+                                  "string", "unsigned"];
+
+/// A diagnostic, which is reported at the start of the file if it has no position.
+struct Diagnostic {
+    path: PathBuf,
+    range: Option<(usize, usize)>,
+    severity: usize,
+    message: String,
+}
+
+/// The environment built for a document.
+struct Analysis {
+    env: Environment,
+    /// The package declared by the document, if it could be parsed.
+    package: Option<Package>,
+    errors: Vec<backend_errors::Error>,
+}
+
+/// Something a reference in a document resolves to.
+enum Target<'a> {
+    Decl(&'a Token<Decl>),
+    SubType(&'a InterfaceBody, &'a Token<SubType>),
+    EnumValue(&'a EnumBody, &'a Token<EnumValue>),
+}
+
+impl<'a> Target<'a> {
+    fn pos(&self) -> &'a Pos {
+        match *self {
+            Target::Decl(decl) => &decl.pos,
+            Target::SubType(_, sub_type) => &sub_type.pos,
+            Target::EnumValue(_, value) => &value.pos,
+        }
+    }
+}
+
+fn fields_display(fields: &Vec<Token<Field>>, indent: &str, out: &mut Vec<String>) {
+    for field in fields {
+        let modifier = if field.is_optional() { "?" } else { "" };
+
+        let field_as = field.field_as
+            .as_ref()
+            .map(|f| format!(" as \"{}\"", f.inner))
+            .unwrap_or_default();
+
+        out.push(format!("{}{}{}: {}{};",
+                         indent,
+                         field.name,
+                         modifier,
+                         type_display(&field.ty),
+                         field_as));
+    }
+}
+
+fn sub_type_display(sub_type: &SubType, indent: &str, out: &mut Vec<String>) {
+    out.push(format!("{}{} {{", indent, sub_type.name));
+    fields_display(&sub_type.fields, &format!("{}  ", indent), out);
+    out.push(format!("{}}}", indent));
+}
+
+/// Render a declaration the way it is written in a specification, without codes or options.
+fn decl_display(decl: &Decl) -> Vec<String> {
+    let mut out = Vec::new();
+    out.push(format!("{} {{", decl.display()));
+
+    match *decl {
+        Decl::Type(ref body) => fields_display(&body.fields, "  ", &mut out),
+        Decl::Tuple(ref body) => fields_display(&body.fields, "  ", &mut out),
+        Decl::Interface(ref body) => {
+            fields_display(&body.fields, "  ", &mut out);
+
+            for (_, sub_type) in &body.sub_types {
+                sub_type_display(sub_type, "  ", &mut out);
+            }
+        }
+        Decl::Enum(ref body) => {
+            for value in &body.values {
+                out.push(format!("  {};", value.name));
+            }
+
+            fields_display(&body.fields, "  ", &mut out);
+        }
+    }
+
+    out.push("}".to_owned());
+    out
+}
+
+fn decl_comment(decl: &Decl) -> &Vec<String> {
+    match *decl {
+        Decl::Type(ref body) => &body.comment,
+        Decl::Tuple(ref body) => &body.comment,
+        Decl::Interface(ref body) => &body.comment,
+        Decl::Enum(ref body) => &body.comment,
+    }
+}
+
+/// Find the reference to a type at the given offset, like `c::Date` or `Shape.Circle`.
+///
+/// Also returns the offset at which the reference starts.
+fn reference_at(content: &str, offset: usize) -> Option<(Custom, usize)> {
+    let is_reference = |c: char| c.is_alphanumeric() || c == '_' || c == ':' || c == '.';
+
+    let start = content[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_reference(c))
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or(offset);
+
+    let end = content[offset..]
+        .char_indices()
+        .find(|&(_, c)| !is_reference(c))
+        .map(|(i, _)| offset + i)
+        .unwrap_or(content.len());
+
+    parse_reference(&content[start..end]).map(|custom| (custom, start))
+}
+
+fn parse_reference(reference: &str) -> Option<Custom> {
+    let (prefix, rest) = match reference.find("::") {
+        Some(index) => (Some(reference[..index].to_owned()), &reference[index + 2..]),
+        None => (None, reference),
+    };
+
+    let parts: Vec<String> = rest.split('.').map(ToOwned::to_owned).collect();
+
+    if parts.iter().any(String::is_empty) {
+        return None;
+    }
+
+    Some(Custom {
+        prefix: prefix,
+        parts: parts,
+    })
+}
+
+/// Convert an error into diagnostics, where errors referencing other positions have an
+/// informational diagnostic for each of them.
+fn diagnostics(path: &Path, error: &backend_errors::Error, out: &mut Vec<Diagnostic>) {
+    let mut push = |pos: &Pos, severity: usize, message: &str| {
+        out.push(Diagnostic {
+            path: pos.0.clone(),
+            range: Some((pos.1, pos.2)),
+            severity: severity,
+            message: message.to_owned(),
+        });
+    };
+
+    match *error.kind() {
+        backend_errors::ErrorKind::Pos(ref m, ref p) => {
+            push(p, SEVERITY_ERROR, m);
+        }
+        backend_errors::ErrorKind::DeclMerge(ref m, ref source, ref target) |
+        backend_errors::ErrorKind::ExtendEnum(ref m, ref source, ref target) => {
+            push(source, SEVERITY_ERROR, m);
+            push(target, SEVERITY_INFORMATION, "previous declaration here");
+        }
+        backend_errors::ErrorKind::FieldConflict(ref name, ref source, ref target) => {
+            push(source, SEVERITY_ERROR, &format!("conflict in field `{}`", name));
+            push(target, SEVERITY_INFORMATION, "previous declaration here");
+        }
+        backend_errors::ErrorKind::ReservedField(ref field_pos, ref reserved_pos) => {
+            push(field_pos, SEVERITY_ERROR, "field reserved");
+            push(reserved_pos, SEVERITY_INFORMATION, "field reserved here");
+        }
+        backend_errors::ErrorKind::MatchConflict(ref source, ref target) => {
+            push(source, SEVERITY_ERROR, "conflicts with existing clause");
+            push(target, SEVERITY_INFORMATION, "existing clause here");
+        }
+        backend_errors::ErrorKind::Parser(Syntax(ref p, ref expected)) => {
+            let expected: Vec<String> = expected.iter().map(|e| format!("{:?}", e)).collect();
+            push(p,
+                 SEVERITY_ERROR,
+                 &format!("syntax error, expected one of: {}", expected.join(", ")));
+        }
+        backend_errors::ErrorKind::Errors(ref errors) => {
+            for error in errors {
+                diagnostics(path, error, out);
+            }
+        }
+        _ => {
+            out.push(Diagnostic {
+                path: path.to_owned(),
+                range: None,
+                severity: SEVERITY_ERROR,
+                message: error.to_string(),
+            });
+        }
+    }
+}
+
+fn error_response(id: Json, code: &str, message: String) -> Json {
+    let error = object(vec![("code", Json::Number(code.to_owned())), ("message", string(message))]);
+    object(vec![("jsonrpc", string("2.0")), ("id", id), ("error", error)])
+}
+
+fn completion(label: &str, kind: usize, detail: Option<String>) -> Json {
+    let mut entries = vec![("label", string(label)), ("kind", number(kind))];
+
+    if let Some(detail) = detail {
+        entries.push(("detail", string(detail)));
+    }
+
+    object(entries)
+}
+
+fn decl_completion(name: &str, decl: &Decl) -> Json {
+    let kind = match *decl {
+        Decl::Interface(_) => COMPLETION_INTERFACE,
+        Decl::Enum(_) => COMPLETION_ENUM,
+        _ => COMPLETION_CLASS,
+    };
+
+    completion(name, kind, Some(decl.display()))
+}
+
+pub struct Server {
+    /// Paths to look for declarations in.
+    paths: Vec<PathBuf>,
+    /// Content of all open documents.
+    documents: BTreeMap<PathBuf, String>,
+    /// Files which diagnostics have been published for, and which need to be cleared.
+    published: BTreeSet<PathBuf>,
+}
+
+impl Server {
+    pub fn new(paths: Vec<PathBuf>) -> Server {
+        Server {
+            paths: paths,
+            documents: BTreeMap::new(),
+            published: BTreeSet::new(),
+        }
+    }
+
+    /// Serve requests until the client asks the server to exit, or the input is closed.
+    pub fn run<R, W>(&mut self, input: &mut R, output: &mut W) -> Result<()>
+        where R: BufRead,
+              W: Write
+    {
+        while let Some(message) = read_message(input)? {
+            let message = match message {
+                Incoming::Message(message) => message,
+                Incoming::Malformed(reason) => {
+                    write_message(output, &error_response(Json::Null, PARSE_ERROR, reason))?;
+                    continue;
+                }
+            };
+
+            let method = match message.get("method").and_then(Json::as_str) {
+                Some(method) => method.to_owned(),
+                // responses to requests, which are never sent.
+                None => continue,
+            };
+
+            let params = message.get("params").cloned().unwrap_or(Json::Null);
+
+            if let Some(id) = message.get("id") {
+                let response = match self.request(&method, &params) {
+                    Some(result) => {
+                        object(vec![("jsonrpc", string("2.0")),
+                                    ("id", id.clone()),
+                                    ("result", result)])
+                    }
+                    None => {
+                        let message = format!("no such method: {}", method);
+                        error_response(id.clone(), METHOD_NOT_FOUND, message)
+                    }
+                };
+
+                write_message(output, &response)?;
+                continue;
+            }
+
+            if method == "exit" {
+                break;
+            }
+
+            for notification in self.notification(&method, &params) {
+                write_message(output, &notification)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle a request, returning `None` if the method is not supported.
+    fn request(&mut self, method: &str, params: &Json) -> Option<Json> {
+        let result = match method {
+            "initialize" => self.initialize(params),
+            "shutdown" => Json::Null,
+            "textDocument/definition" => self.definition(params).unwrap_or(Json::Null),
+            "textDocument/hover" => self.hover(params).unwrap_or(Json::Null),
+            "textDocument/completion" => Json::Array(self.completion(params).unwrap_or_default()),
+            _ => return None,
+        };
+
+        Some(result)
+    }
+
+    /// Handle a notification, returning the notifications to send in response.
+    fn notification(&mut self, method: &str, params: &Json) -> Vec<Json> {
+        let document = params.get("textDocument");
+        let path = document.and_then(|d| d.get("uri")).and_then(Json::as_str).and_then(uri_to_path);
+
+        let path = match path {
+            Some(path) => path,
+            None => return vec![],
+        };
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = document.and_then(|d| d.get("text")).and_then(Json::as_str);
+                self.documents.insert(path, text.unwrap_or_default().to_owned());
+            }
+            "textDocument/didChange" => {
+                let text = match params.get("contentChanges") {
+                    Some(&Json::Array(ref changes)) => {
+                        changes.iter().filter_map(|c| c.get("text")).filter_map(Json::as_str).last()
+                    }
+                    _ => None,
+                };
+
+                if let Some(text) = text {
+                    self.documents.insert(path, text.to_owned());
+                }
+            }
+            "textDocument/didSave" => {}
+            "textDocument/didClose" => {
+                self.documents.remove(&path);
+            }
+            _ => return vec![],
+        }
+
+        self.publish_diagnostics()
+    }
+
+    fn initialize(&mut self, params: &Json) -> Json {
+        let root = params.get("rootUri").and_then(Json::as_str).and_then(uri_to_path);
+
+        if self.paths.is_empty() {
+            self.paths.extend(root);
+        }
+
+        let capabilities = object(vec![("textDocumentSync", number(1)),
+                                       ("definitionProvider", Json::Boolean(true)),
+                                       ("hoverProvider", Json::Boolean(true)),
+                                       ("completionProvider",
+                                        object(vec![("triggerCharacters",
+                                                     Json::Array(vec![string(":"),
+                                                                      string("."),
+                                                                      string("(")]))]))]);
+
+        object(vec![("capabilities", capabilities)])
+    }
+
+    /// Build the environment for the document at the given path.
+    fn analyze(&self, path: &Path) -> Analysis {
+        let mut env = Environment::new(self.paths.clone());
+
+        for (path, content) in &self.documents {
+            env.add_source(path.clone(), content.clone());
+        }
+
+        let mut errors = Vec::new();
+        let content = self.documents.get(path).map(String::as_str).unwrap_or_default();
+
+        let package = match parser::parse_string(path, content) {
+            Ok(file) => Some(file.package.inner),
+            Err(e) => {
+                errors.push(e.into());
+                None
+            }
+        };
+
+        if let Some(ref package) = package {
+            // import through the package if possible, so that it is not imported twice when used.
+            let result = if env.candidates(package).iter().any(|c| c == path) {
+                env.import(package)
+            } else {
+                env.import_file(path, None)
+            };
+
+            if let Err(e) = result {
+                errors.extend(e.into_errors());
+            }
+
+            if let Err(e) = env.verify() {
+                errors.extend(e.into_errors());
+            }
+        }
+
+        Analysis {
+            env: env,
+            package: package,
+            errors: errors,
+        }
+    }
+
+    /// Content of the given file, preferring open documents over what is on disk.
+    fn content(&self, path: &Path) -> String {
+        if let Some(content) = self.documents.get(path) {
+            return content.clone();
+        }
+
+        let mut content = String::new();
+
+        if let Ok(mut f) = File::open(path) {
+            if f.read_to_string(&mut content).is_err() {
+                content.clear();
+            }
+        }
+
+        content
+    }
+
+    fn publish_diagnostics(&mut self) -> Vec<Json> {
+        let mut all = Vec::new();
+
+        for path in self.documents.keys() {
+            for error in self.analyze(path).errors {
+                diagnostics(path, &error, &mut all);
+            }
+        }
+
+        let mut by_path: BTreeMap<PathBuf, Vec<Json>> = BTreeMap::new();
+        let mut seen = BTreeSet::new();
+
+        for path in self.published.iter().chain(self.documents.keys()) {
+            by_path.insert(path.clone(), Vec::new());
+        }
+
+        for diagnostic in all {
+            // the same error is found through every document that imports the file.
+            let key = (diagnostic.path.clone(), diagnostic.range, diagnostic.message.clone());
+
+            if !seen.insert(key) {
+                continue;
+            }
+
+            let content = self.content(&diagnostic.path);
+            let (start, end) = diagnostic.range.unwrap_or((0, 0));
+
+            let entry = object(vec![("range", range(&content, start, end)),
+                                    ("severity", number(diagnostic.severity)),
+                                    ("source", string("reproto")),
+                                    ("message", string(diagnostic.message))]);
+
+            by_path.entry(diagnostic.path).or_insert_with(Vec::new).push(entry);
+        }
+
+        let mut notifications = Vec::new();
+        self.published.clear();
+
+        for (path, diagnostics) in by_path {
+            if !diagnostics.is_empty() {
+                self.published.insert(path.clone());
+            }
+
+            let params = object(vec![("uri", string(path_to_uri(&path))),
+                                     ("diagnostics", Json::Array(diagnostics))]);
+
+            notifications.push(object(vec![("jsonrpc", string("2.0")),
+                                           ("method", string("textDocument/publishDiagnostics")),
+                                           ("params", params)]));
+        }
+
+        notifications
+    }
+
+    /// Get the document path and byte offset that a request refers to.
+    fn document_position(&self, params: &Json) -> Option<(PathBuf, usize)> {
+        let path = params.get("textDocument")
+            .and_then(|d| d.get("uri"))
+            .and_then(Json::as_str)
+            .and_then(uri_to_path)?;
+
+        let position = params.get("position")?;
+        let line = position.get("line").and_then(Json::as_usize)?;
+        let character = position.get("character").and_then(Json::as_usize)?;
+
+        let offset = offset(self.documents.get(&path)?, line, character);
+        Some((path, offset))
+    }
+
+    /// Resolve what a reference in the given package refers to.
+    fn resolve<'a>(&self,
+                   env: &'a Environment,
+                   package: &Package,
+                   custom: &Custom)
+                   -> Option<(Package, Target<'a>)> {
+        let package = match custom.prefix {
+            Some(ref prefix) => env.used.get(&(package.clone(), prefix.clone()))?.clone(),
+            None => package.clone(),
+        };
+
+        let (first, rest) = custom.parts.split_first()?;
+        let decl = env.types.get(&(package.clone(), vec![first.clone()]))?;
+
+        let target = match (&decl.inner, rest.first(), rest.len()) {
+            (_, None, _) => Target::Decl(decl),
+            (&Decl::Interface(ref body), Some(name), 1) => {
+                Target::SubType(body, body.sub_types.get(name)?)
+            }
+            (&Decl::Enum(ref body), Some(name), 1) => {
+                Target::EnumValue(body, body.values.iter().find(|v| v.name == *name)?)
+            }
+            _ => return None,
+        };
+
+        Some((package, target))
+    }
+
+    fn definition(&self, params: &Json) -> Option<Json> {
+        let (path, offset) = self.document_position(params)?;
+        let (custom, _) = reference_at(self.documents.get(&path)?, offset)?;
+
+        let analysis = self.analyze(&path);
+        let (_, target) = self.resolve(&analysis.env, analysis.package.as_ref()?, &custom)?;
+
+        let pos = target.pos();
+        let content = self.content(&pos.0);
+
+        Some(object(vec![("uri", string(path_to_uri(&pos.0))),
+                         ("range", range(&content, pos.1, pos.1))]))
+    }
+
+    fn hover(&self, params: &Json) -> Option<Json> {
+        let (path, offset) = self.document_position(params)?;
+        let (custom, _) = reference_at(self.documents.get(&path)?, offset)?;
+
+        let analysis = self.analyze(&path);
+        let (package, target) = self.resolve(&analysis.env, analysis.package.as_ref()?, &custom)?;
+
+        let (lines, comment) = match target {
+            Target::Decl(decl) => (decl_display(decl), decl_comment(decl)),
+            Target::SubType(body, sub_type) => {
+                let mut out = Vec::new();
+                out.push(format!("interface {} {{", body.name));
+                sub_type_display(sub_type, "  ", &mut out);
+                out.push("}".to_owned());
+                (out, &sub_type.comment)
+            }
+            Target::EnumValue(body, value) => {
+                (vec![format!("{}.{}", body.name, value.name)], &value.comment)
+            }
+        };
+
+        let mut value = format!("```reproto\npackage {};\n\n{}\n```", package, lines.join("\n"));
+
+        if !comment.is_empty() {
+            value.push_str("\n\n");
+            value.push_str(&comment.join("\n"));
+        }
+
+        let contents = object(vec![("kind", string("markdown")), ("value", string(value))]);
+        Some(object(vec![("contents", contents)]))
+    }
+
+    fn completion(&self, params: &Json) -> Option<Vec<Json>> {
+        let (path, offset) = self.document_position(params)?;
+        let content = self.documents.get(&path)?;
+
+        let analysis = self.analyze(&path);
+        let package = analysis.package.as_ref()?;
+        let env = &analysis.env;
+
+        let mut items = Vec::new();
+
+        // complete the fields of an instance, like `Foo(na|`.
+        if let Some(open) = self.open_instance(&content[..offset]) {
+            let (custom, _) = reference_at(content, open)?;
+            let pos = (path.clone(), open, open);
+
+            for field in env.lookup_instance_fields(&pos, package, &custom).ok()? {
+                items.push(completion(&field.name,
+                                      COMPLETION_FIELD,
+                                      Some(type_display(&field.ty))));
+            }
+
+            return Some(items);
+        }
+
+        let word_start = content[..offset]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| c.is_alphanumeric() || c == '_' || c == ':' || c == '.')
+            .last()
+            .map(|(i, _)| i)
+            .unwrap_or(offset);
+
+        let word = &content[word_start..offset];
+
+        let (prefix, rest) = match word.find("::") {
+            Some(index) => (Some(word[..index].to_owned()), &word[index + 2..]),
+            None => (None, word),
+        };
+
+        // complete the members of a declaration, like `Shape.Cir|`.
+        if let Some(index) = rest.rfind('.') {
+            let custom = Custom {
+                prefix: prefix,
+                parts: rest[..index].split('.').map(ToOwned::to_owned).collect(),
+            };
+
+            if let Some((_, Target::Decl(decl))) = self.resolve(env, package, &custom) {
+                match decl.inner {
+                    Decl::Interface(ref body) => {
+                        for (name, sub_type) in &body.sub_types {
+                            items.push(completion(name,
+                                                  COMPLETION_CLASS,
+                                                  Some(sub_type.name())));
+                        }
+                    }
+                    Decl::Enum(ref body) => {
+                        for value in &body.values {
+                            items.push(completion(&value.name, COMPLETION_ENUM_MEMBER, None));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            return Some(items);
+        }
+
+        let target_package = match prefix {
+            Some(ref prefix) => env.used.get(&(package.clone(), prefix.clone()))?,
+            None => package,
+        };
+
+        for (&(ref p, ref parts), decl) in &env.types {
+            if p == target_package {
+                items.push(decl_completion(&parts.join("."), decl));
+            }
+        }
+
+        if prefix.is_none() {
+            for (&(ref p, ref alias), used) in &env.used {
+                if p == package {
+                    items.push(completion(&format!("{}::", alias),
+                                          COMPLETION_MODULE,
+                                          Some(used.to_string())));
+                }
+            }
+
+            for ty in BUILT_IN_TYPES {
+                items.push(completion(ty, COMPLETION_KEYWORD, None));
+            }
+        }
+
+        Some(items)
+    }
+
+    /// Find the offset of the reference an unclosed instance argument list belongs to.
+    fn open_instance(&self, before: &str) -> Option<usize> {
+        let mut depth = 0;
+
+        for (i, c) in before.char_indices().rev() {
+            match c {
+                ')' => depth += 1,
+                '(' if depth == 0 => return Some(i),
+                '(' => depth -= 1,
+                ';' | '{' | '}' => return None,
+                _ => {}
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::*;
+
+    const ROOT: &str = "/reproto-test";
+
+    const COMMON: &str = "package test.common;
+
+/// A point in time.
+type Date {
+  seconds: unsigned/64;
+}
+";
+
+    const V1: &str = "package test.v1;
+
+use test.common as c;
+
+type Entry {
+  date: c::Date;
+}
+";
+
+    fn uri(name: &str) -> String {
+        path_to_uri(&Path::new(ROOT).join("test").join(name))
+    }
+
+    fn request(id: usize, method: &str, params: Json) -> Json {
+        object(vec![("jsonrpc", string("2.0")),
+                    ("id", number(id)),
+                    ("method", string(method)),
+                    ("params", params)])
+    }
+
+    fn notification(method: &str, params: Json) -> Json {
+        object(vec![("jsonrpc", string("2.0")), ("method", string(method)), ("params", params)])
+    }
+
+    fn did_open(name: &str, text: &str) -> Json {
+        let document = object(vec![("uri", string(uri(name))), ("text", string(text))]);
+        notification("textDocument/didOpen", object(vec![("textDocument", document)]))
+    }
+
+    fn at(id: usize, method: &str, name: &str, line: usize, character: usize) -> Json {
+        let position = object(vec![("line", number(line)), ("character", number(character))]);
+        let document = object(vec![("uri", string(uri(name)))]);
+        request(id, method, object(vec![("textDocument", document), ("position", position)]))
+    }
+
+    /// Encode the given messages the way a client would send them.
+    fn encode(messages: &[Json]) -> Vec<u8> {
+        let mut input = Vec::new();
+
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+
+        input
+    }
+
+    /// Serve the given input until it is exhausted, and return every message sent back.
+    fn serve(input: Vec<u8>) -> Vec<Json> {
+        let mut server = Server::new(vec![PathBuf::from(ROOT)]);
+        let mut output = Vec::new();
+
+        server.run(&mut Cursor::new(input), &mut output).unwrap();
+
+        let mut output = Cursor::new(output);
+        let mut messages = Vec::new();
+
+        while let Some(message) = read_message(&mut output).unwrap() {
+            match message {
+                Incoming::Message(message) => messages.push(message),
+                Incoming::Malformed(reason) => panic!("malformed response: {}", reason),
+            }
+        }
+
+        messages
+    }
+
+    fn response(messages: &[Json], id: usize) -> &Json {
+        messages.iter()
+            .find(|m| m.get("id").and_then(Json::as_usize) == Some(id))
+            .unwrap_or_else(|| panic!("no response to: {}", id))
+    }
+
+    fn text(value: &Json) -> String {
+        let mut out = String::new();
+        value.format(0, &mut out);
+        out
+    }
+
+    /// Diagnostics of the last notification published for the given document.
+    fn diagnostics_for(messages: &[Json], name: &str) -> Vec<Json> {
+        let uri = uri(name);
+
+        let params = messages.iter()
+            .filter(|m| m.get("method").and_then(Json::as_str) ==
+                        Some("textDocument/publishDiagnostics"))
+            .filter_map(|m| m.get("params"))
+            .filter(|p| p.get("uri").and_then(Json::as_str) == Some(uri.as_str()))
+            .last()
+            .unwrap_or_else(|| panic!("no diagnostics for: {}", name));
+
+        match params.get("diagnostics") {
+            Some(&Json::Array(ref diagnostics)) => diagnostics.clone(),
+            _ => panic!("diagnostics are not an array"),
+        }
+    }
+
+    fn error_code(message: &Json) -> Option<String> {
+        match message.get("error").and_then(|e| e.get("code")) {
+            Some(&Json::Number(ref code)) => Some(code.clone()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let broken = "package test.v1;\n\ntype Entry {\n  date: Missing;\n}\n";
+
+        let messages = serve(encode(&[did_open("v1.reproto", broken)]));
+        let diagnostics = diagnostics_for(&messages, "v1.reproto");
+
+        assert_eq!(1, diagnostics.len());
+
+        let start = diagnostics[0].get("range").and_then(|r| r.get("start")).unwrap();
+        assert_eq!(Some(3), start.get("line").and_then(Json::as_usize));
+        assert_eq!(Some(1), diagnostics[0].get("severity").and_then(Json::as_usize));
+    }
+
+    #[test]
+    fn test_diagnostics_cleared() {
+        let broken = "package test.v1;\n\ntype Entry {\n  date: Missing;\n}\n";
+
+        let change = object(vec![("textDocument", object(vec![("uri", string(uri("v1.reproto")))])),
+                                 ("contentChanges",
+                                  Json::Array(vec![object(vec![("text", string(V1))])]))]);
+
+        let messages = serve(encode(&[did_open("common.reproto", COMMON),
+                                      did_open("v1.reproto", broken),
+                                      notification("textDocument/didChange", change)]));
+
+        assert!(diagnostics_for(&messages, "v1.reproto").is_empty());
+        assert!(diagnostics_for(&messages, "common.reproto").is_empty());
+    }
+
+    #[test]
+    fn test_definition() {
+        let messages = serve(encode(&[did_open("common.reproto", COMMON),
+                                      did_open("v1.reproto", V1),
+                                      at(1, "textDocument/definition", "v1.reproto", 5, 12)]));
+
+        let result = response(&messages, 1).get("result").unwrap();
+        assert_eq!(Some(uri("common.reproto").as_str()),
+                   result.get("uri").and_then(Json::as_str));
+
+        // declarations start at their documentation.
+        let start = result.get("range").and_then(|r| r.get("start")).unwrap();
+        assert_eq!(Some(2), start.get("line").and_then(Json::as_usize));
+    }
+
+    #[test]
+    fn test_hover() {
+        let messages = serve(encode(&[did_open("common.reproto", COMMON),
+                                      did_open("v1.reproto", V1),
+                                      at(1, "textDocument/hover", "v1.reproto", 5, 12)]));
+
+        let result = response(&messages, 1).get("result").unwrap();
+        let value = result.get("contents").and_then(|c| c.get("value")).and_then(Json::as_str);
+
+        assert_eq!(Some("```reproto\npackage test.common;\n\ntype Date {\n  seconds: \
+                         unsigned/64;\n}\n```\n\nA point in time."),
+                   value);
+    }
+
+    #[test]
+    fn test_completion() {
+        let messages = serve(encode(&[did_open("common.reproto", COMMON),
+                                      did_open("v1.reproto", V1),
+                                      at(1, "textDocument/completion", "v1.reproto", 5, 11),
+                                      at(2, "textDocument/completion", "v1.reproto", 5, 8)]));
+
+        let labels = |id| -> Vec<String> {
+            match response(&messages, id).get("result") {
+                Some(&Json::Array(ref items)) => {
+                    items.iter()
+                        .filter_map(|i| i.get("label").and_then(Json::as_str))
+                        .map(ToOwned::to_owned)
+                        .collect()
+                }
+                _ => panic!("completion is not an array"),
+            }
+        };
+
+        assert_eq!(vec!["Date".to_owned()], labels(1));
+
+        let labels = labels(2);
+        assert!(labels.contains(&"Entry".to_owned()));
+        assert!(labels.contains(&"c::".to_owned()));
+        assert!(labels.contains(&"string".to_owned()));
+    }
+
+    #[test]
+    fn test_shutdown() {
+        let messages = serve(encode(&[request(1, "shutdown", Json::Null),
+                                      notification("exit", Json::Null),
+                                      request(2, "shutdown", Json::Null)]));
+
+        assert_eq!(1, messages.len());
+        assert_eq!("null", text(response(&messages, 1).get("result").unwrap()));
+    }
+
+    #[test]
+    fn test_unknown_method() {
+        let messages = serve(encode(&[request(1, "workspace/unknown", Json::Null)]));
+        assert_eq!(Some(METHOD_NOT_FOUND.to_owned()), error_code(response(&messages, 1)));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let mut input = b"Content-Length: 9\r\n\r\n{\"bad\": }".to_vec();
+        input.extend(encode(&[request(1, "shutdown", Json::Null)]));
+
+        let messages = serve(input);
+
+        assert_eq!(2, messages.len());
+        assert_eq!(Some(PARSE_ERROR.to_owned()), error_code(&messages[0]));
+        assert_eq!("null", text(messages[0].get("id").unwrap()));
+        assert!(response(&messages, 1).get("result").is_some());
+    }
+
+    #[test]
+    fn test_bad_content_length() {
+        let mut input = b"Content-Length: many\r\n\r\n".to_vec();
+        input.extend(b"Content-Type: application/vscode-jsonrpc\r\n\r\n");
+        input.extend(encode(&[request(1, "shutdown", Json::Null)]));
+
+        let messages = serve(input);
+
+        assert_eq!(3, messages.len());
+        assert_eq!(Some(PARSE_ERROR.to_owned()), error_code(&messages[0]));
+        assert_eq!(Some(PARSE_ERROR.to_owned()), error_code(&messages[1]));
+        assert!(response(&messages, 1).get("result").is_some());
+    }
+
+    #[test]
+    fn test_read_message() {
+        let mut input = Cursor::new(encode(&[request(1, "shutdown", Json::Null)]));
+
+        match read_message(&mut input).unwrap() {
+            Some(Incoming::Message(message)) => {
+                assert_eq!(Some("shutdown"), message.get("method").and_then(Json::as_str));
+            }
+            _ => panic!("expected a message"),
+        }
+
+        assert!(read_message(&mut input).unwrap().is_none());
+    }
+}
@@ -0,0 +1,194 @@
+//! Encoding of messages in the language server protocol.
+
+use backend::json::{self, Json};
+use errors::*;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// A message read from the client.
+pub enum Incoming {
+    Message(Json),
+    /// A message which could not be read, with the reason why.
+    Malformed(String),
+}
+
+/// Read the next message, or `None` if the input has been closed.
+///
+/// A message with bad headers or content is consumed and returned as `Incoming::Malformed`, so
+/// that the following messages can still be read.
+pub fn read_message<R>(input: &mut R) -> Result<Option<Incoming>>
+    where R: BufRead
+{
+    let mut length: Option<usize> = None;
+    let mut malformed: Option<String> = None;
+
+    loop {
+        let mut line = String::new();
+
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim();
+
+        if line.is_empty() {
+            break;
+        }
+
+        let mut parts = line.splitn(2, ':');
+
+        if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                match value.trim().parse() {
+                    Ok(value) => length = Some(value),
+                    Err(_) => malformed = Some(format!("bad Content-Length header: {}", line)),
+                }
+            }
+        }
+    }
+
+    if let Some(malformed) = malformed {
+        return Ok(Some(Incoming::Malformed(malformed)));
+    }
+
+    let length = match length {
+        Some(length) => length,
+        None => return Ok(Some(Incoming::Malformed("missing Content-Length header".to_owned()))),
+    };
+
+    let mut content = vec![0u8; length];
+    input.read_exact(&mut content)?;
+
+    let content = match String::from_utf8(content) {
+        Ok(content) => content,
+        Err(_) => return Ok(Some(Incoming::Malformed("message is not valid UTF-8".to_owned()))),
+    };
+
+    match json::parse(&content) {
+        Ok(message) => Ok(Some(Incoming::Message(message))),
+        Err(e) => Ok(Some(Incoming::Malformed(format!("bad message: {}", e)))),
+    }
+}
+
+pub fn write_message<W>(output: &mut W, message: &Json) -> Result<()>
+    where W: Write
+{
+    let mut content = String::new();
+    message.format(0, &mut content);
+
+    write!(output, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    output.flush()?;
+    Ok(())
+}
+
+pub fn object(entries: Vec<(&str, Json)>) -> Json {
+    Json::Object(entries.into_iter().map(|(key, value)| (key.to_owned(), value)).collect())
+}
+
+pub fn string<S>(value: S) -> Json
+    where S: Into<String>
+{
+    Json::String(value.into())
+}
+
+pub fn number(value: usize) -> Json {
+    Json::Number(value.to_string())
+}
+
+/// Convert a `file://` URI into a path.
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    if !uri.starts_with("file://") {
+        return None;
+    }
+
+    let bytes = uri["file://".len()..].as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = ::std::str::from_utf8(&bytes[i + 1..i + 3]);
+
+            if let Some(b) = hex.ok().and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8(decoded).ok().map(PathBuf::from)
+}
+
+/// Convert a path into a `file://` URI.
+pub fn path_to_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+
+    for b in path.to_string_lossy().bytes() {
+        if (b as char).is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+
+    uri
+}
+
+/// Convert a byte offset into a zero-based line, and a character counted in UTF-16 code units.
+pub fn position(content: &str, offset: usize) -> (usize, usize) {
+    let mut line = 0;
+    let mut character = 0;
+
+    for (i, c) in content.char_indices() {
+        if i >= offset {
+            break;
+        }
+
+        if c == '\n' {
+            line += 1;
+            character = 0;
+        } else {
+            character += c.len_utf16();
+        }
+    }
+
+    (line, character)
+}
+
+/// Convert a zero-based line and UTF-16 character into a byte offset.
+pub fn offset(content: &str, line: usize, character: usize) -> usize {
+    let mut current_line = 0;
+    let mut current_character = 0;
+
+    for (i, c) in content.char_indices() {
+        if current_line == line && current_character >= character {
+            return i;
+        }
+
+        if c == '\n' {
+            if current_line == line {
+                return i;
+            }
+
+            current_line += 1;
+            current_character = 0;
+        } else {
+            current_character += c.len_utf16();
+        }
+    }
+
+    content.len()
+}
+
+pub fn range(content: &str, start: usize, end: usize) -> Json {
+    let position = |offset| {
+        let (line, character) = position(content, offset);
+        object(vec![("line", number(line)), ("character", number(character))])
+    };
+
+    object(vec![("start", position(start)), ("end", position(end))])
+}
@@ -14,6 +14,8 @@ extern crate codeviz;
 pub mod backend;
 pub mod commands;
 pub mod errors;
+pub mod import;
+pub mod language_server;
 pub mod logger;
 pub mod naming;
 pub mod options;
@@ -1,7 +1,9 @@
 use errors::*;
 use log;
 
-struct SimpleLogger;
+struct SimpleLogger {
+    stderr: bool,
+}
 
 impl log::Log for SimpleLogger {
     fn enabled(&self, metadata: &log::LogMetadata) -> bool {
@@ -10,15 +12,28 @@ impl log::Log for SimpleLogger {
 
     fn log(&self, record: &log::LogRecord) {
         if self.enabled(record.metadata()) {
-            println!("{} - {}", record.level(), record.args());
+            if self.stderr {
+                eprintln!("{} - {}", record.level(), record.args());
+            } else {
+                println!("{} - {}", record.level(), record.args());
+            }
         }
     }
 }
 
-pub fn init(level: log::LogLevelFilter) -> Result<()> {
+fn init_logger(level: log::LogLevelFilter, stderr: bool) -> Result<()> {
     log::set_logger(|max_level| {
             max_level.set(level);
-            Box::new(SimpleLogger)
+            Box::new(SimpleLogger { stderr: stderr })
         })
         .map_err(|e| e.into())
 }
+
+pub fn init(level: log::LogLevelFilter) -> Result<()> {
+    init_logger(level, false)
+}
+
+/// Initialize logging to stderr, for commands which use stdout for other purposes.
+pub fn init_stderr(level: log::LogLevelFilter) -> Result<()> {
+    init_logger(level, true)
+}
@@ -21,13 +21,18 @@ fn setup_opts<'a, 'b>() -> clap::App<'a, 'b> {
 /// Configure logging
 ///
 /// If debug (--debug) is specified, logging should be configured with LogLevelFilter::Debug.
+///
+/// The language server uses stdout for its protocol, so it logs to stderr instead.
 fn setup_logger(matches: &clap::ArgMatches) -> Result<()> {
     let level: log::LogLevelFilter = match matches.is_present("debug") {
         true => log::LogLevelFilter::Debug,
         false => log::LogLevelFilter::Info,
     };
 
-    logger::init(level)?;
+    match matches.subcommand_name() {
+        Some("language-server") => logger::init_stderr(level)?,
+        _ => logger::init(level)?,
+    }
 
     Ok(())
 }
@@ -82,12 +87,36 @@ fn handle_backend_error(e: &backend::errors::ErrorKind) -> Result<()> {
         backend::errors::ErrorKind::Parser(ref e) => {
             handle_parser_error(e)?;
         }
+        backend::errors::ErrorKind::Errors(ref errors) => {
+            handle_backend_errors(errors)?;
+        }
         _ => {}
     }
 
     Ok(())
 }
 
+/// Print every error in a collection.
+///
+/// Errors without a position are printed as messages, since they would otherwise be lost among
+/// the others.
+fn handle_backend_errors(errors: &Vec<backend::errors::Error>) -> Result<()> {
+    for e in errors {
+        match *e.kind() {
+            backend::errors::ErrorKind::Msg(_) |
+            backend::errors::ErrorKind::IO(_) |
+            backend::errors::ErrorKind::Fmt(_) => {
+                error!("{}", e);
+            }
+            ref kind => {
+                handle_backend_error(kind)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 fn handle_parser_error(e: &parser::errors::ErrorKind) -> Result<()> {
     match *e {
         parser::errors::ErrorKind::Syntax(ref p, ref expected) => {
@@ -143,8 +172,17 @@ fn entry() -> Result<()> {
 
     if let Some(matches) = matches {
         match name {
+            "compile" if matches.is_present("watch") => {
+                commands::watch(matches, &report_error)
+            }
             "compile" => commands::compile(matches),
             "verify" => commands::verify(matches),
+            "check" => commands::check(matches),
+            "validate" => commands::validate(matches),
+            "generate-samples" => commands::generate_samples(matches),
+            "fmt" => commands::fmt(matches),
+            "import" => commands::import(matches),
+            "language-server" => commands::language_server(matches),
             _ => Err(format!("No such command: {}", name).into()),
         }
     } else {
@@ -152,24 +190,37 @@ fn entry() -> Result<()> {
     }
 }
 
+/// Print the positions in the specifications that an error refers to.
+fn handle_error(e: &Error) -> Result<()> {
+    match *e.kind() {
+        ErrorKind::BackendErrors(ref errors) => {
+            handle_backend_errors(errors)?;
+        }
+        ErrorKind::BackendError(ref e) => {
+            handle_backend_error(e)?;
+        }
+        ErrorKind::Parser(ref e) => {
+            handle_parser_error(e)?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Report an error without exiting, like when watching for changes.
+fn report_error(e: &Error) {
+    if let Err(e) = handle_error(e) {
+        error!("{}", e);
+    }
+
+    error!("{}", e);
+}
+
 fn compiler_entry() -> Result<()> {
     match entry() {
         Err(e) => {
-            match *e.kind() {
-                ErrorKind::BackendErrors(ref errors) => {
-                    for e in errors {
-                        handle_backend_error(e)?;
-                    }
-                }
-                ErrorKind::BackendError(ref e) => {
-                    handle_backend_error(e)?;
-                }
-                ErrorKind::Parser(ref e) => {
-                    handle_parser_error(e)?;
-                }
-                _ => {}
-            }
-
+            handle_error(&e)?;
             Err(e)
         }
         ok => ok,
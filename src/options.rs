@@ -1,8 +1,9 @@
+use backend::output::Output;
 use naming;
-use std::path::PathBuf;
 
 pub struct Options {
-    pub out_path: PathBuf,
+    /// Where generated files are written.
+    pub output: Box<Output>,
     pub package_prefix: Option<String>,
     pub id_converter: Option<Box<naming::Naming>>,
     pub modules: Vec<String>,
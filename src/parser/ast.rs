@@ -34,14 +34,15 @@ pub enum Value {
     Constant(Token<Constant>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OptionDecl {
     pub name: String,
     pub values: Vec<Token<Value>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Field {
+    pub comment: Vec<String>,
     pub modifier: Modifier,
     pub name: String,
     pub ty: Type,
@@ -57,7 +58,7 @@ impl Field {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Member {
     Field(Field),
     Code(String, Vec<String>),
@@ -65,13 +66,13 @@ pub enum Member {
     Match(MatchDecl),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MatchVariable {
     pub name: String,
     pub ty: Type,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum MatchCondition {
     /// Match a specific value.
     Value(Token<Value>),
@@ -79,13 +80,13 @@ pub enum MatchCondition {
     Type(MatchVariable),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MatchMember {
     pub condition: Token<MatchCondition>,
     pub value: Token<Value>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MatchDecl {
     pub members: Vec<Token<MatchMember>>,
 }
@@ -118,47 +119,53 @@ impl Body for InterfaceBody {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TupleBody {
     pub name: String,
+    pub comment: Vec<String>,
     pub members: Vec<Token<Member>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InterfaceBody {
     pub name: String,
+    pub comment: Vec<String>,
     pub members: Vec<Token<Member>>,
     pub sub_types: Vec<Token<SubType>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TypeBody {
     pub name: String,
+    pub comment: Vec<String>,
     pub members: Vec<Token<Member>>,
 }
 
 /// Sub-types in interface declarations.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SubType {
     pub name: String,
+    pub comment: Vec<String>,
     pub members: Vec<Token<Member>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EnumBody {
     pub name: String,
+    pub comment: Vec<String>,
     pub values: Vec<Token<EnumValue>>,
     pub members: Vec<Token<Member>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct EnumValue {
     pub name: String,
+    pub comment: Vec<String>,
     pub arguments: Vec<Token<Value>>,
     pub ordinal: Option<Token<Value>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Decl {
     Type(TypeBody),
     Tuple(TupleBody),
@@ -186,13 +193,13 @@ impl Decl {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UseDecl {
     pub package: Token<Package>,
     pub alias: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct File {
     pub package: Token<Package>,
     pub uses: Vec<Token<UseDecl>>,
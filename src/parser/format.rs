@@ -0,0 +1,600 @@
+//! Canonical formatting of `.reproto` sources.
+//!
+//! Declarations are parsed through the grammar and emitted again with consistent indentation and
+//! spacing. Comments are not part of the syntax tree, so they are recovered from the parts of the
+//! source which are not covered by any token, and emitted before the element that follows them.
+//!
+//! Trees which were built rather than parsed, like imported declarations, can be formatted as well.
+
+use backend::compat::type_display;
+use backend::models::{Custom, Type};
+use pest::Parser;
+use std::collections::VecDeque;
+use std::path::Path;
+use super::ast::*;
+use super::errors::*;
+use super::run_parser;
+
+const INDENT: &str = "  ";
+
+/// A comment which is not a documentation comment.
+struct Comment {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+/// Find all comments in the given part of the source.
+fn find_comments(content: &str, start: usize, end: usize, out: &mut VecDeque<Comment>) {
+    let mut offset = start;
+
+    while let Some(index) = content[offset..end].find('/') {
+        let comment_start = offset + index;
+        let rest = &content[comment_start..end];
+
+        let len = if rest.starts_with("//") {
+            rest.find(|c| c == '\r' || c == '\n').unwrap_or(rest.len())
+        } else if rest.starts_with("/*") {
+            rest.find("*/").map(|i| i + 2).unwrap_or(rest.len())
+        } else {
+            offset = comment_start + 1;
+            continue;
+        };
+
+        out.push_back(Comment {
+            start: comment_start,
+            end: comment_start + len,
+            text: rest[..len].trim_right().to_owned(),
+        });
+
+        offset = comment_start + len;
+    }
+}
+
+fn custom_display(custom: &Custom) -> String {
+    type_display(&Type::Custom(custom.clone()))
+}
+
+/// Encode a string literal, escaping everything that the parser decodes as a unicode escape.
+fn string_display(string: &str) -> String {
+    let mut out = String::from("\"");
+
+    for c in string.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '"' | '\\' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+/// Kind of a member, members of different kinds are separated when formatting a built tree.
+fn member_kind(member: &Member) -> &'static str {
+    match *member {
+        Member::Field(_) => "field",
+        Member::Code(_, _) => "code",
+        Member::Option(_) => "option",
+        Member::Match(_) => "match",
+    }
+}
+
+struct Formatter<'a> {
+    content: &'a str,
+    /// Comments which have not been emitted yet, in the order they appear in the source.
+    comments: VecDeque<Comment>,
+    out: String,
+    /// Offset in the source of the last element emitted.
+    last_end: usize,
+    /// If a block was just opened, in which case no blank line should follow.
+    open: bool,
+    /// If the tree was built rather than parsed, in which case there is no source to keep the
+    /// spelling and spacing of.
+    built: bool,
+}
+
+impl<'a> Formatter<'a> {
+    /// Check if there is a blank line in the source between the last emitted element, and the
+    /// given offset.
+    fn blank_line(&self, start: usize) -> bool {
+        if self.built || start <= self.last_end {
+            return false;
+        }
+
+        self.content[self.last_end..start].matches('\n').count() > 1
+    }
+
+    fn separate(&mut self, start: usize, force: bool) {
+        if !self.open && (force || self.blank_line(start)) {
+            self.out.push('\n');
+        }
+
+        self.open = false;
+    }
+
+    fn push_line(&mut self, level: usize, line: &str) {
+        for _ in 0..level {
+            self.out.push_str(INDENT);
+        }
+
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    /// Emit all comments which appear before the given offset.
+    ///
+    /// Returns if the element following them still has to be forcibly separated.
+    fn comments(&mut self, level: usize, start: usize, force: bool) -> bool {
+        let mut force = force;
+
+        while self.comments.front().map(|c| c.start < start).unwrap_or(false) {
+            let comment = self.comments.pop_front().expect("front comment");
+            self.separate(comment.start, force);
+            force = false;
+            self.push_line(level, &comment.text);
+            self.last_end = comment.end;
+        }
+
+        force
+    }
+
+    /// Emit all comments which appear before the given offset, and separate the element that
+    /// starts there from what came before it.
+    fn leading(&mut self, level: usize, start: usize, force: bool) {
+        let force = self.comments(level, start, force);
+        self.separate(start, force);
+    }
+
+    /// Take the comment following the given offset on the same line, if there is one.
+    fn trailing(&mut self, end: usize) -> Option<Comment> {
+        let trailing = match self.comments.front() {
+            Some(comment) => {
+                comment.start >= end && !self.content[end..comment.start].contains('\n')
+            }
+            None => false,
+        };
+
+        if trailing {
+            self.comments.pop_front()
+        } else {
+            None
+        }
+    }
+
+    /// Emit a line for an element ending at the given offset, keeping a comment that follows it
+    /// on the same line.
+    fn line(&mut self, level: usize, line: &str, end: usize) {
+        let mut line = line.to_owned();
+        self.last_end = end;
+
+        if let Some(comment) = self.trailing(end) {
+            line.push(' ');
+            line.push_str(&comment.text);
+            self.last_end = comment.end;
+        }
+
+        self.push_line(level, &line);
+    }
+
+    fn open(&mut self, level: usize, header: &str) {
+        self.push_line(level, &format!("{} {{", header));
+        self.open = true;
+    }
+
+    /// Close a block ending at the given offset, emitting any comments remaining in it.
+    fn close(&mut self, level: usize, end: usize) {
+        self.comments(level + 1, end, false);
+
+        // empty blocks are closed on the same line as they are opened.
+        if self.open {
+            self.open = false;
+            self.out.pop();
+
+            let start = self.out.rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let header = self.out[start..].to_owned();
+            self.out.truncate(start);
+            self.line(0, &format!("{}}}", header), end);
+            return;
+        }
+
+        self.line(level, "}", end);
+    }
+
+    fn doc_comment(&mut self, level: usize, comment: &Vec<String>) {
+        for line in comment {
+            if line.is_empty() {
+                self.push_line(level, "///");
+            } else {
+                self.push_line(level, &format!("/// {}", line));
+            }
+        }
+    }
+
+    fn value(&self, value: &Token<Value>) -> String {
+        match value.inner {
+            Value::String(ref string) if self.built => string_display(string),
+            Value::Number(ref number) if self.built => number.to_string(),
+            // keep the original spelling of strings and numbers.
+            Value::String(_) | Value::Number(_) => {
+                self.content[value.pos.0..value.pos.1].to_owned()
+            }
+            Value::Boolean(ref boolean) => boolean.to_string(),
+            Value::Identifier(ref identifier) => identifier.to_owned(),
+            Value::Type(ref ty) => type_display(ty),
+            Value::Instance(ref instance) => {
+                let arguments: Vec<String> = instance.arguments
+                    .iter()
+                    .map(|a| format!("{}: {}", a.name.inner, self.value(&a.value)))
+                    .collect();
+
+                format!("{}({})", custom_display(&instance.ty), arguments.join(", "))
+            }
+            Value::Constant(ref constant) => {
+                custom_display(&Custom {
+                    prefix: constant.prefix.clone(),
+                    parts: constant.parts.clone(),
+                })
+            }
+        }
+    }
+
+    fn values(&self, values: &Vec<Token<Value>>) -> String {
+        values.iter().map(|v| self.value(v)).collect::<Vec<_>>().join(", ")
+    }
+
+    fn member(&mut self, level: usize, member: &Token<Member>, force: bool) {
+        self.leading(level, member.pos.0, force);
+
+        match member.inner {
+            Member::Field(ref field) => {
+                self.doc_comment(level, &field.comment);
+
+                let modifier = if field.is_optional() { "?" } else { "" };
+
+                let field_as = field.field_as
+                    .as_ref()
+                    .map(|v| format!(" as {}", self.value(v)))
+                    .unwrap_or_default();
+
+                let line = format!("{}{}: {}{};",
+                                   field.name,
+                                   modifier,
+                                   type_display(&field.ty),
+                                   field_as);
+
+                self.line(level, &line, member.pos.1);
+            }
+            Member::Code(ref context, ref lines) => {
+                self.push_line(level, &format!("{} {{{{", context));
+
+                if lines.iter().any(|l| !l.trim().is_empty()) {
+                    for line in lines {
+                        if line.trim().is_empty() {
+                            self.out.push('\n');
+                        } else {
+                            self.push_line(level + 1, line.trim_right());
+                        }
+                    }
+                }
+
+                self.line(level, "}}", member.pos.1);
+            }
+            Member::Option(ref option) => {
+                let line = format!("{} {};", option.name, self.values(&option.values));
+                self.line(level, &line, member.pos.1);
+            }
+            Member::Match(ref match_decl) => {
+                self.open(level, "match");
+
+                for m in &match_decl.members {
+                    self.leading(level + 1, m.pos.0, false);
+
+                    let condition = match m.condition.inner {
+                        MatchCondition::Value(ref value) => self.value(value),
+                        MatchCondition::Type(ref variable) => {
+                            format!("{}: {}", variable.name, type_display(&variable.ty))
+                        }
+                    };
+
+                    let line = format!("{} => {};", condition, self.value(&m.value));
+                    self.line(level + 1, &line, m.pos.1);
+                }
+
+                self.close(level, member.pos.1);
+            }
+        }
+    }
+
+    fn members(&mut self, level: usize, members: &Vec<Token<Member>>, force: bool) {
+        let mut previous = None;
+
+        for member in members {
+            let kind = member_kind(&member.inner);
+            let force = previous.map(|p| self.built && p != kind).unwrap_or(force);
+            self.member(level, member, force);
+            previous = Some(kind);
+        }
+    }
+
+    fn enum_value(&mut self, level: usize, value: &Token<EnumValue>) {
+        self.leading(level, value.pos.0, false);
+        self.doc_comment(level, &value.comment);
+
+        let mut line = value.name.clone();
+
+        if !value.arguments.is_empty() {
+            line.push_str(&format!("({})", self.values(&value.arguments)));
+        }
+
+        if let Some(ref ordinal) = value.ordinal {
+            line.push_str(&format!(" = {}", self.value(ordinal)));
+        }
+
+        line.push(';');
+        self.line(level, &line, value.pos.1);
+    }
+
+    fn sub_type(&mut self, level: usize, sub_type: &Token<SubType>, force: bool) {
+        self.leading(level, sub_type.pos.0, force);
+        self.doc_comment(level, &sub_type.comment);
+        self.open(level, &sub_type.name);
+        self.members(level + 1, &sub_type.members, false);
+        self.close(level, sub_type.pos.1);
+    }
+
+    fn decl(&mut self, decl: &Token<Decl>) {
+        self.leading(0, decl.pos.0, true);
+
+        let comment = match decl.inner {
+            Decl::Type(ref body) => &body.comment,
+            Decl::Tuple(ref body) => &body.comment,
+            Decl::Interface(ref body) => &body.comment,
+            Decl::Enum(ref body) => &body.comment,
+        };
+
+        self.doc_comment(0, comment);
+        self.open(0, &decl.display());
+
+        match decl.inner {
+            Decl::Type(ref body) => self.members(1, &body.members, false),
+            Decl::Tuple(ref body) => self.members(1, &body.members, false),
+            Decl::Interface(ref body) => {
+                self.members(1, &body.members, false);
+
+                for sub_type in &body.sub_types {
+                    self.sub_type(1, sub_type, self.built);
+                }
+            }
+            Decl::Enum(ref body) => {
+                for value in &body.values {
+                    self.enum_value(1, value);
+                }
+
+                self.members(1, &body.members, self.built);
+            }
+        }
+
+        self.close(0, decl.pos.1);
+    }
+
+    fn file(&mut self, file: &File) {
+        self.leading(0, file.package.pos.0, false);
+        let package = format!("package {};", file.package.inner);
+        self.line(0, &package, file.package.pos.1);
+
+        // comments around a use declaration move together with it when sorted.
+        let mut uses = Vec::new();
+
+        for use_decl in &file.uses {
+            let mut comments = Vec::new();
+
+            while self.comments.front().map(|c| c.start < use_decl.pos.0).unwrap_or(false) {
+                comments.extend(self.comments.pop_front());
+            }
+
+            let trailing = self.trailing(use_decl.pos.1);
+            uses.push((use_decl, comments, trailing));
+        }
+
+        uses.sort_by_key(|&(ref u, _, _)| (u.package.inner.to_string(), u.alias.clone()));
+
+        for (i, (use_decl, comments, trailing)) in uses.into_iter().enumerate() {
+            if i == 0 {
+                self.out.push('\n');
+            }
+
+            for comment in comments {
+                self.push_line(0, &comment.text);
+            }
+
+            let mut line = match use_decl.alias {
+                Some(ref alias) => format!("use {} as {};", use_decl.package.inner, alias),
+                None => format!("use {};", use_decl.package.inner),
+            };
+
+            if let Some(comment) = trailing {
+                line.push(' ');
+                line.push_str(&comment.text);
+            }
+
+            self.push_line(0, &line);
+        }
+
+        if let Some(last) = file.uses.last() {
+            self.last_end = last.pos.1;
+        }
+
+        for decl in &file.decls {
+            self.decl(decl);
+        }
+
+        let end = self.content.len();
+        self.comments(0, end, true);
+    }
+}
+
+/// Format the given content, as if it was read from the file at the given path.
+pub fn format(path: &Path, content: &str) -> Result<String> {
+    let parser = run_parser(path, content)?;
+
+    let mut comments = VecDeque::new();
+    let mut offset = 0;
+
+    {
+        let queue = parser.queue();
+
+        // comments can only be in the gaps between tokens that do not contain other tokens.
+        for (i, token) in queue.iter().enumerate() {
+            let leaf = queue.get(i + 1).map(|next| next.start >= token.end).unwrap_or(true);
+
+            if !leaf {
+                continue;
+            }
+
+            if token.start > offset {
+                find_comments(content, offset, token.start, &mut comments);
+            }
+
+            if token.end > offset {
+                offset = token.end;
+            }
+        }
+    }
+
+    find_comments(content, offset, content.len(), &mut comments);
+
+    let file = parser._file()?;
+
+    let mut formatter = Formatter {
+        content: content,
+        comments: comments,
+        out: String::new(),
+        last_end: 0,
+        open: true,
+        built: false,
+    };
+
+    formatter.file(&file);
+    Ok(formatter.out)
+}
+
+/// Format a tree which was built rather than parsed, and has no source to recover comments or
+/// spacing from.
+pub fn format_file(file: &File) -> String {
+    let mut formatter = Formatter {
+        content: "",
+        comments: VecDeque::new(),
+        out: String::new(),
+        last_end: 0,
+        open: true,
+        built: true,
+    };
+
+    formatter.file(file);
+    formatter.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "package foo.v1;
+use z.a; // trailing
+// leading
+use b.c as  x;
+type Foo { // opening
+  /// Docs
+  a :string as   \"A\";
+
+
+  b? : {string:[x::Y]};
+  java {{
+      int x;
+  }}
+}
+enum E { A(1, \"x\") = 3;
+  n: unsigned;
+}
+type Empty {
+}
+";
+
+    const OUTPUT: &str = "package foo.v1;
+
+// leading
+use b.c as x;
+use z.a; // trailing
+
+type Foo {
+  // opening
+  /// Docs
+  a: string as \"A\";
+
+  b?: {string: [x::Y]};
+  java {{
+    int x;
+  }}
+}
+
+enum E {
+  A(1, \"x\") = 3;
+  n: unsigned;
+}
+
+type Empty {}
+";
+
+    #[test]
+    fn test_format() {
+        let path = Path::new("test.reproto");
+        assert_eq!(OUTPUT, format(path, INPUT).unwrap());
+    }
+
+    #[test]
+    fn test_format_is_stable() {
+        let path = Path::new("test.reproto");
+        assert_eq!(OUTPUT, format(path, OUTPUT).unwrap());
+    }
+
+    #[test]
+    fn test_format_file() {
+        let input = "package foo.v1;
+use b.c;
+type Foo { a: string as \"A\"; option 3; java {{
+int x;
+}} }
+enum E { A(\"a\\n\"); B(\"b\"); n: string; }
+";
+
+        let expected = "package foo.v1;
+
+use b.c;
+
+type Foo {
+  a: string as \"A\";
+
+  option 3;
+
+  java {{
+    int x;
+  }}
+}
+
+enum E {
+  A(\"a\\n\");
+  B(\"b\");
+
+  n: string;
+}
+";
+
+        let path = Path::new("test.reproto");
+        let file = ::parser::parse_string(path, input).unwrap();
+        assert_eq!(expected, format_file(&file));
+    }
+}
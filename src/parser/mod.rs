@@ -1,5 +1,6 @@
 pub mod ast;
 pub mod errors;
+pub mod format;
 pub mod parser;
 
 use pest::Parser;
@@ -54,7 +55,19 @@ pub fn parse_file(path: &Path) -> Result<ast::File> {
 
     f.read_to_string(&mut content)?;
 
-    let mut parser = parser::Rdp::new(StringInput::new(&content));
+    parse_string(path, &content)
+}
+
+/// Parse the given content, as if it was read from the file at the given path.
+pub fn parse_string(path: &Path, content: &str) -> Result<ast::File> {
+    run_parser(path, content)?._file()
+}
+
+/// Run the grammar over the given content, leaving the matched tokens in the parser's queue.
+fn run_parser<'input>(path: &Path,
+                      content: &'input str)
+                      -> Result<parser::Rdp<StringInput<'input>>> {
+    let mut parser = parser::Rdp::new(StringInput::new(content));
 
     if !parser.file() {
         let pos = parser.tracked_len_pos();
@@ -76,5 +89,5 @@ pub fn parse_file(path: &Path) -> Result<ast::File> {
         }
     }
 
-    parser._file()
+    Ok(parser)
 }
@@ -100,6 +100,13 @@ fn decode_escaped_string(input: &str) -> Result<String> {
     Ok(out)
 }
 
+/// Strip the leading slashes, and a single optional space from a documentation comment.
+fn strip_doc_comment(comment: &str) -> String {
+    let comment = comment.trim_left_matches('/');
+    let comment = if comment.starts_with(' ') { &comment[1..] } else { comment };
+    comment.trim_right().to_owned()
+}
+
 /// Decode the next four characters as a unicode escape sequence.
 fn decode_unicode4(it: &mut Iterator<Item = char>) -> Result<char> {
     let mut res = 0u32;
@@ -123,29 +130,29 @@ impl_rdp! {
 
         package_decl = { package_keyword ~ package_ident ~ semi_colon }
 
-        type_decl = { type_keyword ~ type_identifier ~ left_curly ~ type_body ~ right_curly }
+        type_decl = { doc_comment* ~ type_keyword ~ type_identifier ~ left_curly ~ type_body ~ right_curly }
         type_body = _{ member* }
 
-        tuple_decl = { tuple_keyword ~ type_identifier ~ left_curly ~ tuple_body ~ right_curly }
+        tuple_decl = { doc_comment* ~ tuple_keyword ~ type_identifier ~ left_curly ~ tuple_body ~ right_curly }
         tuple_body = _{ member* }
 
-        interface_decl = { interface_keyword ~ type_identifier ~ left_curly ~ interface_body ~ right_curly }
+        interface_decl = { doc_comment* ~ interface_keyword ~ type_identifier ~ left_curly ~ interface_body ~ right_curly }
         interface_body = _{ member* ~ sub_type* }
 
-        enum_decl = { enum_keyword ~ type_identifier ~ left_curly ~ enum_body ~ right_curly }
+        enum_decl = { doc_comment* ~ enum_keyword ~ type_identifier ~ left_curly ~ enum_body ~ right_curly }
         enum_body = _{ enum_body_value* ~ member* }
         enum_body_value = { enum_value }
 
-        sub_type = { type_identifier ~ left_curly ~ sub_type_body ~ right_curly }
+        sub_type = { doc_comment* ~ type_identifier ~ left_curly ~ sub_type_body ~ right_curly }
         sub_type_body = _{ member* }
 
         member = { option_decl | match_decl | field | code_block }
-        field = { identifier ~ optional? ~ colon ~ type_spec ~ field_as? ~ semi_colon }
+        field = { doc_comment* ~ identifier ~ optional? ~ colon ~ type_spec ~ field_as? ~ semi_colon }
         field_as = { as_keyword ~ value }
         code_block = @{ identifier ~ whitespace* ~ code_start ~ code_body ~ code_end }
         code_body = { (!(["}}"]) ~ any)* }
 
-        enum_value = { type_identifier ~ enum_arguments? ~ enum_ordinal? ~ semi_colon }
+        enum_value = { doc_comment* ~ type_identifier ~ enum_arguments? ~ enum_ordinal? ~ semi_colon }
         enum_arguments = { (left_paren ~ (value ~ (comma ~ value)*) ~ right_paren) }
         enum_ordinal = { equals ~ value }
         option_decl = { identifier ~ (value ~ (comma ~ value)*) ~ semi_colon }
@@ -238,9 +245,21 @@ impl_rdp! {
 
         whitespace = _{ [" "] | ["\t"] | ["\r"] | ["\n"] }
 
+        // documentation comment, attached to the declaration, field, or value that follows it
+        doc_comment = @{ ["///"] ~ (!(["\r"] | ["\n"]) ~ any)* }
+
+        // documentation comments followed by something that they can be attached to
+        attached_doc = !@{ doc_comment+ ~ (decl_start | sub_type_start | enum_value_start | field_start) }
+        decl_start = { (type_keyword | tuple_keyword | interface_keyword | enum_keyword) ~ type_identifier }
+        sub_type_start = { type_identifier ~ left_curly }
+        enum_value_start = { type_identifier ~ (left_paren | equals | semi_colon) }
+        field_start = { identifier ~ optional? ~ colon ~ type_spec ~ (as_keyword | semi_colon) }
+
         comment = _{
-            // line comment
-            ( ["//"] ~ (!(["\r"] | ["\n"]) ~ any)* ~ (["\n"] | ["\r\n"] | ["\r"] | eoi) ) |
+            // documentation comment which is not attached to anything
+            ( !attached_doc ~ ["///"] ~ (!(["\r"] | ["\n"]) ~ any)* ~ (["\n"] | ["\r\n"] | ["\r"] | eoi) ) |
+            // line comment, which is not a documentation comment
+            ( ["//"] ~ !(["/"]) ~ (!(["\r"] | ["\n"]) ~ any)* ~ (["\n"] | ["\r\n"] | ["\r"] | eoi) ) |
             // block comment
             ( ["/*"] ~ (!(["*/"]) ~ any)* ~ ["*/"] )
         }
@@ -315,6 +334,7 @@ impl_rdp! {
         _decl(&self) -> Result<ast::Decl> {
             (
                 _: type_decl,
+                comment: _doc_comments(),
                 _: type_keyword,
                 &name: type_identifier,
                 _: left_curly,
@@ -325,6 +345,7 @@ impl_rdp! {
 
                 let body = ast::TypeBody {
                     name: name.to_owned(),
+                    comment: comment.into_iter().collect(),
                     members: members
                 };
 
@@ -333,6 +354,7 @@ impl_rdp! {
 
             (
                 _: tuple_decl,
+                comment: _doc_comments(),
                 _: tuple_keyword,
                 &name: type_identifier,
                 _: left_curly,
@@ -343,6 +365,7 @@ impl_rdp! {
 
                 let body = ast::TupleBody {
                     name: name.to_owned(),
+                    comment: comment.into_iter().collect(),
                     members: members,
                 };
 
@@ -351,6 +374,7 @@ impl_rdp! {
 
             (
                 _: interface_decl,
+                comment: _doc_comments(),
                 _: interface_keyword,
                 &name: type_identifier,
                 _: left_curly,
@@ -363,6 +387,7 @@ impl_rdp! {
 
                 let body = ast::InterfaceBody {
                     name: name.to_owned(),
+                    comment: comment.into_iter().collect(),
                     members: members,
                     sub_types: sub_types,
                 };
@@ -372,6 +397,7 @@ impl_rdp! {
 
             (
                 _: enum_decl,
+                comment: _doc_comments(),
                 _: enum_keyword,
                 &name: type_identifier,
                 _: left_curly,
@@ -384,6 +410,7 @@ impl_rdp! {
 
                 let body = ast::EnumBody {
                     name: name.to_owned(),
+                    comment: comment.into_iter().collect(),
                     values: values,
                     members: members,
                 };
@@ -405,6 +432,7 @@ impl_rdp! {
         _enum_value(&self) -> Result<ast::Token<ast::EnumValue>> {
             (
                 token: enum_value,
+                comment: _doc_comments(),
                 &name: type_identifier,
                 values: _enum_arguments(),
                 ordinal: _enum_ordinal(),
@@ -413,7 +441,12 @@ impl_rdp! {
                 let arguments = values?.into_iter().collect();
                 let ordinal = ordinal?;
                 let pos = (token.start, token.end);
-                let enum_value = ast::EnumValue { name: name.to_owned(), arguments: arguments, ordinal: ordinal };
+                let enum_value = ast::EnumValue {
+                    name: name.to_owned(),
+                    comment: comment.into_iter().collect(),
+                    arguments: arguments,
+                    ordinal: ordinal,
+                };
                 Ok(ast::Token::new(enum_value, pos))
             },
         }
@@ -575,6 +608,7 @@ impl_rdp! {
         _member(&self) -> Result<ast::Member> {
             (
                 _: field,
+                comment: _doc_comments(),
                 &name: identifier,
                 modifier: _modifier(),
                 _: colon,
@@ -583,6 +617,7 @@ impl_rdp! {
                 _: semi_colon,
             ) => {
                 let field = ast::Field {
+                    comment: comment.into_iter().collect(),
                     modifier: modifier,
                     name: name.to_owned(),
                     ty: type_spec?,
@@ -652,6 +687,7 @@ impl_rdp! {
 
         _sub_type(&self) -> Result<ast::SubType> {
             (
+                comment: _doc_comments(),
                 &name: type_identifier,
                 _: left_curly,
                 members: _member_list(),
@@ -659,7 +695,8 @@ impl_rdp! {
              ) => {
                 let name = name.to_owned();
                 let members = members?.into_iter().collect();
-                Ok(ast::SubType { name: name, members: members })
+                let comment = comment.into_iter().collect();
+                Ok(ast::SubType { name: name, comment: comment, members: members })
             },
         }
 
@@ -807,6 +844,15 @@ impl_rdp! {
             },
         }
 
+        _doc_comments(&self) -> LinkedList<String> {
+            (&comment: doc_comment, mut tail: _doc_comments()) => {
+                tail.push_front(strip_doc_comment(comment));
+                tail
+            },
+
+            () => LinkedList::new(),
+        }
+
         _modifier(&self) -> m::Modifier {
             (_: optional) => m::Modifier::Optional,
             () => m::Modifier::Required,
@@ -1000,7 +1046,10 @@ mod tests {
         let field = ast::Token::new(field, (8, 17));
 
         let instance = ast::Instance {
-            ty: m::Type::Custom(None, vec!["Foo".to_owned(), "Bar".to_owned()]),
+            ty: m::Custom {
+                prefix: None,
+                parts: vec!["Foo".to_owned(), "Bar".to_owned()],
+            },
             arguments: vec![field],
         };
 
@@ -1013,8 +1062,12 @@ mod tests {
     #[test]
     fn test_type_spec() {
         assert_type_spec_eq!(m::Type::String, "string");
-        assert_type_spec_eq!(m::Type::Custom(None, vec!["Hello".to_owned(), "World".to_owned()]),
-                             "Hello.World");
+        let custom = m::Custom {
+            prefix: None,
+            parts: vec!["Hello".to_owned(), "World".to_owned()],
+        };
+
+        assert_type_spec_eq!(m::Type::Custom(custom), "Hello.World");
     }
 
     #[test]
@@ -1038,4 +1091,117 @@ mod tests {
 
         panic!("option did not match");
     }
+
+    #[test]
+    fn test_doc_comments() {
+        let input = "/// Hello\n///\n///  World\n// ignored\nfoo: string;";
+        let mut parser = parse(input);
+
+        assert!(parser.field());
+        assert!(parser.end());
+
+        if let ast::Member::Field(field) = parser._member().unwrap() {
+            assert_eq!(vec!["Hello".to_owned(), "".to_owned(), " World".to_owned()],
+                       field.comment);
+            return;
+        }
+
+        panic!("field did not match");
+    }
+
+    /// Parse the given file, which is expected to consist of a single declaration.
+    fn parse_decl(input: &'static str) -> ast::Decl {
+        let mut parser = parse(input);
+
+        assert!(parser.file(), "did not parse: {}", input);
+        assert!(parser.end(), "not parsed until end: {}", input);
+
+        let mut file = parser._file().unwrap();
+        assert_eq!(1, file.decls.len());
+        file.decls.remove(0).inner
+    }
+
+    #[test]
+    fn test_unattached_doc_comment_before_option() {
+        let decl = parse_decl("package a;\ntype A {\n  /// reserved\n  reserved a;\n}\n");
+
+        if let ast::Decl::Type(body) = decl {
+            assert_eq!(1, body.members.len());
+            return;
+        }
+
+        panic!("type did not match");
+    }
+
+    #[test]
+    fn test_unattached_doc_comment_before_match() {
+        let decl = parse_decl("package a;\ntype A {\n  /// matched\n  match {\n    /// number\n    \
+                               n: double => A(a: n);\n  }\n  a: double;\n}\n");
+
+        if let ast::Decl::Type(body) = decl {
+            assert_eq!(2, body.members.len());
+            return;
+        }
+
+        panic!("type did not match");
+    }
+
+    #[test]
+    fn test_unattached_doc_comment_before_code_block() {
+        let decl = parse_decl("package a;\ntype A {\n  /// code\n  java {{\n  }}\n}\n");
+
+        if let ast::Decl::Type(body) = decl {
+            assert_eq!(1, body.members.len());
+            return;
+        }
+
+        panic!("type did not match");
+    }
+
+    #[test]
+    fn test_unattached_doc_comment_at_end_of_body() {
+        let decl = parse_decl("package a;\ninterface A {\n  B {\n    /// end of sub type\n  }\n  \
+                               /// end of interface\n}\n/// end of file\n");
+
+        if let ast::Decl::Interface(body) = decl {
+            assert_eq!(1, body.sub_types.len());
+            assert!(body.sub_types[0].inner.comment.is_empty());
+            return;
+        }
+
+        panic!("interface did not match");
+    }
+
+    #[test]
+    fn test_unattached_doc_comment_before_use() {
+        let mut parser = parse("package a;\n/// use\nuse b;\n/// doc\ntype A {}\n");
+
+        assert!(parser.file());
+        assert!(parser.end());
+
+        let file = parser._file().unwrap();
+        assert_eq!(1, file.uses.len());
+
+        if let ast::Decl::Type(ref body) = file.decls[0].inner {
+            assert_eq!(vec!["doc".to_owned()], body.comment);
+            return;
+        }
+
+        panic!("type did not match");
+    }
+
+    #[test]
+    fn test_attached_doc_comment_after_unattached() {
+        let decl = parse_decl("package a;\n/// type\n// plain\n/// more\nenum A {\n  /// value\n  B;\n  \
+                               /// option\n  serialized_as_name true;\n}\n");
+
+        if let ast::Decl::Enum(body) = decl {
+            assert_eq!(vec!["type".to_owned(), "more".to_owned()], body.comment);
+            assert_eq!(vec!["value".to_owned()], body.values[0].inner.comment);
+            assert_eq!(1, body.members.len());
+            return;
+        }
+
+        panic!("enum did not match");
+    }
 }
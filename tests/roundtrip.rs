@@ -0,0 +1,430 @@
+//! Round-trips shared JSON fixtures through the code generated for `examples/heroic`.
+//!
+//! Every fixture in `tests/roundtrip` is named `<Type>.<case>.json`, and is decoded into the given
+//! type of the `heroic.v1` package and encoded again by each backend. The result must match the
+//! fixture, or `<Type>.<case>.expected.json` if the encoded form differs from the input.
+//!
+//! Backends whose tooling (`python3`, `node`, `tsc`, `javac`) is not installed are skipped.
+//!
+//! Java is encoded and decoded through Jackson (2.9 or later), which is not fetched by the tests:
+//! point `REPROTO_JACKSON_CLASSPATH` at its jars to round-trip the `fasterxml` module. Otherwise,
+//! only the modules which have no dependencies are compiled.
+
+extern crate reproto;
+
+use reproto::backend::json::{self, Json};
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::slice;
+
+const PACKAGE: &str = "heroic.v1";
+
+const PYTHON_DRIVER: &str = r#"
+import importlib, json, sys
+
+module = importlib.import_module(sys.argv[1])
+args = sys.argv[2:]
+
+for ty, path in zip(args[::2], args[1::2]):
+    try:
+        with open(path) as f:
+            data = json.load(f)
+
+        print(json.dumps(getattr(module, ty).decode(data).encode()))
+    except Exception as e:
+        print(json.dumps({"error": repr(e)}))
+"#;
+
+const JS_DRIVER: &str = r#"
+import { readFileSync } from "fs";
+
+const args = process.argv.slice(2);
+
+for (let i = 0; i + 1 < args.length; i += 2) {
+  try {
+    const data = JSON.parse(readFileSync(args[i + 1], "utf8"));
+    console.log(JSON.stringify(module[args[i]].decode(data).encode()));
+  } catch (e) {
+    console.log(JSON.stringify({error: String(e)}));
+  }
+}
+"#;
+
+const TYPESCRIPT_DRIVER: &str = r#"
+declare const process: any;
+declare function require(name: string): any;
+
+const fs = require("fs");
+const args: string[] = process.argv.slice(2);
+
+for (let i = 0; i + 1 < args.length; i += 2) {
+  try {
+    const data = JSON.parse(fs.readFileSync(args[i + 1], "utf8"));
+    console.log(JSON.stringify((module as any)[args[i]].decode(data).encode()));
+  } catch (e) {
+    console.log(JSON.stringify({error: String(e)}));
+  }
+}
+"#;
+
+/// Prints every value as it is encoded by Jackson, the way the `fasterxml` module expects it to be
+/// configured.
+///
+/// `Optional` is supported here, since `jackson-datatype-jdk8` might not be on the classpath.
+const JAVA_DRIVER: &str = include_str!("roundtrip/Driver.java");
+
+/// Environment variable with the classpath of Jackson.
+const JACKSON_CLASSPATH: &str = "REPROTO_JACKSON_CLASSPATH";
+
+/// Java modules which can be compiled without any dependencies.
+const JAVA_MODULES: &[&str] = &["builder", "mutable", "constructor_properties"];
+
+struct Fixture {
+    ty: String,
+    path: PathBuf,
+    expected: Json,
+}
+
+fn root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn read(path: &Path) -> String {
+    let mut content = String::new();
+
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut content))
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+
+    content
+}
+
+fn parse(path: &Path) -> Json {
+    json::parse(&read(path)).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+fn fixtures() -> Vec<Fixture> {
+    let dir = root().join("tests").join("roundtrip");
+    let mut fixtures = Vec::new();
+
+    for entry in fs::read_dir(&dir).expect("fixtures directory") {
+        let path = entry.expect("fixture").path();
+
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+
+        if !name.ends_with(".json") || name.ends_with(".expected.json") {
+            continue;
+        }
+
+        let ty = name.split('.').next().expect("type").to_owned();
+        let expected = path.with_extension("expected.json");
+
+        let expected = if expected.is_file() {
+            parse(&expected)
+        } else {
+            parse(&path)
+        };
+
+        fixtures.push(Fixture {
+            ty: ty,
+            path: path,
+            expected: expected,
+        });
+    }
+
+    fixtures.sort_by(|a, b| a.path.cmp(&b.path));
+    fixtures
+}
+
+/// Render the value with sorted keys and normalized numbers, so that backends are free to order
+/// and format their output however they want.
+///
+/// Integers are kept as they are written, so that any loss of precision is detected.
+fn canonical(value: &Json) -> String {
+    match *value {
+        Json::Null => "null".to_owned(),
+        Json::Boolean(boolean) => boolean.to_string(),
+        Json::Number(ref number) if !number.contains(|c| c == '.' || c == 'e' || c == 'E') => {
+            number.to_owned()
+        }
+        Json::Number(ref number) => {
+            number.parse::<f64>().map(|n| n.to_string()).unwrap_or_else(|_| number.to_owned())
+        }
+        Json::String(ref string) => format!("{:?}", string),
+        Json::Array(ref values) => {
+            let values: Vec<_> = values.iter().map(canonical).collect();
+            format!("[{}]", values.join(","))
+        }
+        Json::Object(ref entries) => {
+            let mut entries: Vec<_> = entries.iter()
+                .map(|&(ref key, ref value)| format!("{:?}:{}", key, canonical(value)))
+                .collect();
+
+            entries.sort();
+            format!("{{{}}}", entries.join(","))
+        }
+    }
+}
+
+fn available(command: &str) -> bool {
+    let found = Command::new(command).arg("--version").output().is_ok();
+
+    if !found {
+        eprintln!("skipping: `{}` is not available", command);
+    }
+
+    found
+}
+
+/// A temporary directory, which is removed when dropped, even if the test failed.
+struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    fn new(name: &str) -> TempDir {
+        let name = format!("reproto-roundtrip-{}-{}", name, process::id());
+        let path = env::temp_dir().join(name);
+
+        if path.is_dir() {
+            fs::remove_dir_all(&path).expect("remove temporary directory");
+        }
+
+        fs::create_dir_all(&path).expect("create temporary directory");
+        TempDir { path: path }
+    }
+
+    fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.path.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        fs::remove_dir_all(&self.path).ok();
+    }
+}
+
+fn run(command: &mut Command) -> String {
+    let output = command.output().unwrap_or_else(|e| panic!("{:?}: {}", command, e));
+
+    if !output.status.success() {
+        panic!("{:?} failed:\n{}{}",
+               command,
+               String::from_utf8_lossy(&output.stdout),
+               String::from_utf8_lossy(&output.stderr));
+    }
+
+    String::from_utf8(output.stdout).expect("output is not UTF-8")
+}
+
+fn compile(backend: &str, modules: &[&str], out: &Path) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_reproto"));
+
+    command.args(&["compile", "-b", backend, "--package", PACKAGE]);
+    command.arg("--path").arg(root().join("examples"));
+    command.arg("-o").arg(out);
+
+    for module in modules {
+        command.args(&["-m", module]);
+    }
+
+    run(&mut command);
+}
+
+/// Pass every fixture as a `<Type> <path>` pair to the given driver, and compare what it prints.
+fn check(backend: &str, mut command: Command) {
+    let fixtures = fixtures();
+
+    for fixture in &fixtures {
+        command.arg(&fixture.ty).arg(&fixture.path);
+    }
+
+    let output = run(&mut command);
+    let lines: Vec<_> = output.lines().collect();
+
+    assert_eq!(fixtures.len(), lines.len(), "{}: unexpected output:\n{}", backend, output);
+
+    let mut failures = Vec::new();
+
+    for (fixture, line) in fixtures.iter().zip(lines) {
+        let expected = canonical(&fixture.expected);
+
+        let actual = match json::parse(line) {
+            Ok(actual) => canonical(&actual),
+            Err(e) => format!("{} (in: {})", e, line),
+        };
+
+        if expected != actual {
+            failures.push(format!("{}:\n  expected: {}\n    actual: {}",
+                                  fixture.path.display(),
+                                  expected,
+                                  actual));
+        }
+    }
+
+    if !failures.is_empty() {
+        panic!("{}: {} fixture(s) did not round-trip:\n{}",
+               backend,
+               failures.len(),
+               failures.join("\n"));
+    }
+}
+
+fn python(name: &str, modules: &[&str]) {
+    if !available("python3") {
+        return;
+    }
+
+    let out = TempDir::new(name);
+    compile("python", modules, &out.path);
+
+    let mut command = Command::new("python3");
+    command.env("PYTHONPATH", &out.path).arg("-c").arg(PYTHON_DRIVER).arg(PACKAGE);
+    check(name, command);
+}
+
+#[test]
+fn python_default() {
+    python("python", &[]);
+}
+
+/// Strict encoding and decoding verifies every value, including bytes, on the way.
+#[test]
+fn python_strict() {
+    python("python-strict", &["strict"]);
+}
+
+#[test]
+fn js() {
+    if !available("node") {
+        return;
+    }
+
+    // imports of other packages are resolved as node modules.
+    let out = TempDir::new("js");
+    let modules = out.join("node_modules");
+    compile("js", &[], &modules);
+
+    for package in fs::read_dir(&modules).expect("node modules") {
+        let package = package.expect("node module").path();
+
+        if !package.is_dir() {
+            continue;
+        }
+
+        let package_json = package.join("package.json");
+        let mut f = File::create(package_json).expect("create package.json");
+        f.write_all(b"{\"type\": \"module\"}\n").expect("write package.json");
+    }
+
+    let module = format!("{}.js", PACKAGE.replace('.', "/"));
+    let driver = out.join("driver.mjs");
+    let mut f = File::create(&driver).expect("create driver");
+    write!(f, "import * as module from {:?};\n{}", module, JS_DRIVER).expect("write driver");
+
+    let mut command = Command::new("node");
+    command.arg(&driver);
+    check("js", command);
+}
+
+#[test]
+fn typescript() {
+    if !available("tsc") || !available("node") {
+        return;
+    }
+
+    let out = TempDir::new("typescript");
+    let sources = out.join("src");
+    compile("typescript", &[], &sources);
+
+    let module = format!("./{}", PACKAGE.replace('.', "/"));
+    let driver = sources.join("driver.ts");
+    let mut f = File::create(&driver).expect("create driver");
+    write!(f, "import * as module from {:?};\n{}", module, TYPESCRIPT_DRIVER)
+        .expect("write driver");
+
+    let mut tsc = Command::new("tsc");
+    tsc.args(&["--target", "es2017", "--module", "commonjs", "--outDir"]);
+    tsc.arg(out.join("js")).arg(&driver);
+    run(&mut tsc);
+
+    let mut command = Command::new("node");
+    command.arg(out.join("js").join("driver.js"));
+    check("typescript", command);
+}
+
+/// Round-trip through the `fasterxml` module if Jackson is available.
+///
+/// The other modules do not affect encoding, so they are only checked to compile.
+#[test]
+fn java() {
+    if !available("javac") {
+        return;
+    }
+
+    let default: &[&str] = &[];
+    let modules = iter::once(default).chain(JAVA_MODULES.iter().map(slice::from_ref));
+
+    for modules in modules {
+        let name = modules.first().unwrap_or(&"default");
+        let out = TempDir::new(&format!("java-{}", name));
+        let sources = out.join("src");
+
+        compile("java", modules, &sources);
+
+        let mut command = Command::new("javac");
+        command.arg("-d").arg(out.join("classes")).arg("-sourcepath").arg(&sources);
+        command.args(java_sources(&sources));
+        run(&mut command);
+    }
+
+    let classpath = match env::var(JACKSON_CLASSPATH) {
+        Ok(classpath) => classpath,
+        Err(_) => {
+            eprintln!("skipping: `{}` is not set, fasterxml is not round-tripped",
+                      JACKSON_CLASSPATH);
+            return;
+        }
+    };
+
+    let out = TempDir::new("java-fasterxml");
+    let sources = out.join("src");
+    let classes = out.join("classes");
+
+    compile("java", &["fasterxml"], &sources);
+
+    let driver = sources.join("Driver.java");
+    let mut f = File::create(&driver).expect("create driver");
+    f.write_all(JAVA_DRIVER.as_bytes()).expect("write driver");
+
+    let mut javac = Command::new("javac");
+    javac.args(&["-nowarn", "-cp", &classpath, "-d"]).arg(&classes);
+    javac.arg("-sourcepath").arg(&sources);
+    javac.args(java_sources(&sources)).arg(&driver);
+    run(&mut javac);
+
+    let classpath = env::join_paths(env::split_paths(&classpath).chain(iter::once(classes)))
+        .expect("classpath");
+
+    let mut command = Command::new("java");
+    command.arg("-cp").arg(classpath).arg("Driver").arg(PACKAGE);
+    check("java", command);
+}
+
+/// All Java sources generated for `PACKAGE` under the given directory.
+fn java_sources(sources: &Path) -> Vec<PathBuf> {
+    let package = PACKAGE.split('.').fold(sources.to_owned(), |path, part| path.join(part));
+
+    fs::read_dir(&package)
+        .expect("java package")
+        .map(|source| source.expect("java source").path())
+        .collect()
+}
@@ -0,0 +1,160 @@
+import com.fasterxml.jackson.annotation.JsonInclude;
+import com.fasterxml.jackson.databind.BeanDescription;
+import com.fasterxml.jackson.databind.BeanProperty;
+import com.fasterxml.jackson.databind.DeserializationConfig;
+import com.fasterxml.jackson.databind.DeserializationContext;
+import com.fasterxml.jackson.databind.JavaType;
+import com.fasterxml.jackson.databind.JsonDeserializer;
+import com.fasterxml.jackson.databind.JsonSerializer;
+import com.fasterxml.jackson.databind.ObjectMapper;
+import com.fasterxml.jackson.databind.SerializationConfig;
+import com.fasterxml.jackson.databind.deser.Deserializers;
+import com.fasterxml.jackson.databind.deser.std.ReferenceTypeDeserializer;
+import com.fasterxml.jackson.databind.jsontype.TypeDeserializer;
+import com.fasterxml.jackson.databind.jsontype.TypeSerializer;
+import com.fasterxml.jackson.databind.module.SimpleModule;
+import com.fasterxml.jackson.databind.ser.Serializers;
+import com.fasterxml.jackson.databind.ser.std.ReferenceTypeSerializer;
+import com.fasterxml.jackson.databind.type.ReferenceType;
+import com.fasterxml.jackson.databind.type.TypeBindings;
+import com.fasterxml.jackson.databind.type.TypeFactory;
+import com.fasterxml.jackson.databind.type.TypeModifier;
+import com.fasterxml.jackson.databind.util.NameTransformer;
+import java.io.File;
+import java.lang.reflect.Type;
+import java.util.Collections;
+import java.util.Optional;
+
+public class Driver {
+  /* Support for Optional, as provided by jackson-datatype-jdk8 which might not be available. */
+  static class OptionalDeserializer extends ReferenceTypeDeserializer<Optional<?>> {
+    OptionalDeserializer(JavaType type, TypeDeserializer valueType, JsonDeserializer<?> value) {
+      super(type, null, valueType, value);
+    }
+
+    protected OptionalDeserializer withResolved(TypeDeserializer valueType, JsonDeserializer<?> value) {
+      return new OptionalDeserializer(_fullType, valueType, value);
+    }
+
+    public Optional<?> getNullValue(DeserializationContext context) {
+      return Optional.empty();
+    }
+
+    public Object getAbsentValue(DeserializationContext context) {
+      return Optional.empty();
+    }
+
+    public Optional<?> referenceValue(Object contents) {
+      return Optional.ofNullable(contents);
+    }
+
+    public Optional<?> updateReference(Optional<?> reference, Object contents) {
+      return Optional.ofNullable(contents);
+    }
+
+    public Object getReferenced(Optional<?> reference) {
+      return reference.orElse(null);
+    }
+  }
+
+  static class OptionalSerializer extends ReferenceTypeSerializer<Optional<?>> {
+    OptionalSerializer(ReferenceType type, TypeSerializer valueType, JsonSerializer<Object> value) {
+      super(type, true, valueType, value);
+    }
+
+    OptionalSerializer(OptionalSerializer base, BeanProperty property, TypeSerializer valueType,
+        JsonSerializer<?> value, NameTransformer unwrapper, Object suppressable,
+        boolean suppressNulls) {
+      super(base, property, valueType, value, unwrapper, suppressable, suppressNulls);
+    }
+
+    protected ReferenceTypeSerializer<Optional<?>> withResolved(BeanProperty property,
+        TypeSerializer valueType, JsonSerializer<?> value, NameTransformer unwrapper) {
+      return new OptionalSerializer(this, property, valueType, value, unwrapper,
+          _suppressableValue, _suppressNulls);
+    }
+
+    public ReferenceTypeSerializer<Optional<?>> withContentInclusion(Object suppressable,
+        boolean suppressNulls) {
+      return new OptionalSerializer(this, _property, _valueTypeSerializer, _valueSerializer,
+          _unwrapper, suppressable, suppressNulls);
+    }
+
+    protected boolean _isValuePresent(Optional<?> value) {
+      return value.isPresent();
+    }
+
+    protected Object _getReferenced(Optional<?> value) {
+      return value.get();
+    }
+
+    protected Object _getReferencedIfPresent(Optional<?> value) {
+      return value.orElse(null);
+    }
+  }
+
+  static class OptionalModule extends SimpleModule {
+    OptionalModule() {
+      super("optional");
+    }
+
+    @Override
+    public void setupModule(SetupContext context) {
+      super.setupModule(context);
+
+      context.addTypeModifier(new TypeModifier() {
+        public JavaType modifyType(JavaType type, Type jdkType, TypeBindings bindings,
+            TypeFactory factory) {
+          if (type.isReferenceType() || !type.hasRawClass(Optional.class)) {
+            return type;
+          }
+
+          return ReferenceType.upgradeFrom(type, type.containedTypeOrUnknown(0));
+        }
+      });
+
+      context.addDeserializers(new Deserializers.Base() {
+        public JsonDeserializer<?> findReferenceDeserializer(ReferenceType type,
+            DeserializationConfig config, BeanDescription description,
+            TypeDeserializer valueType, JsonDeserializer<?> value) {
+          if (!type.hasRawClass(Optional.class)) {
+            return null;
+          }
+
+          return new OptionalDeserializer(type, valueType, value);
+        }
+      });
+
+      context.addSerializers(new Serializers.Base() {
+        public JsonSerializer<?> findReferenceSerializer(SerializationConfig config,
+            ReferenceType type, BeanDescription description, TypeSerializer valueType,
+            JsonSerializer<Object> value) {
+          if (!type.hasRawClass(Optional.class)) {
+            return null;
+          }
+
+          return new OptionalSerializer(type, valueType, value);
+        }
+      });
+    }
+  }
+
+  public static void main(String[] args) throws Exception {
+    ObjectMapper mapper = new ObjectMapper();
+    mapper.registerModule(new OptionalModule());
+    mapper.setSerializationInclusion(JsonInclude.Include.NON_ABSENT);
+
+    for (int i = 1; i + 1 < args.length; i += 2) {
+      Object output;
+
+      try {
+        Class<?> type = Class.forName(args[0] + "." + args[i]);
+        output = mapper.readValue(new File(args[i + 1]), type);
+      } catch (Exception e) {
+        output = Collections.singletonMap("error", e.toString());
+      }
+
+      System.out.println(mapper.writeValueAsString(output));
+    }
+  }
+}
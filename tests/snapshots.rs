@@ -0,0 +1,249 @@
+//! Snapshot tests of the code generated by every backend and module.
+//!
+//! The specifications in `tests/snapshots/proto` are compiled into memory with each backend, and
+//! every generated file is compared against `tests/snapshots/<name>.snap`. Backends with no other
+//! coverage of the examples also compile `heroic.v1` into `tests/snapshots/<backend>-heroic.snap`.
+//!
+//! The output of the `json-schema` and `protobuf` backends is also imported back, and the
+//! imported specifications are compared against `tests/snapshots/import-<format>.snap`.
+//!
+//! Run with `REPROTO_BLESS=1` to write the current output as the new snapshots.
+
+extern crate reproto;
+
+use reproto::backend::{self, Environment};
+use reproto::backend::models::Package;
+use reproto::backend::output::MemoryOutput;
+use reproto::import;
+use reproto::options::Options;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+const PACKAGE: &[&str] = &["snapshot", "v1"];
+const BLESS: &str = "REPROTO_BLESS";
+/// Prefix of the line which separates the files in a snapshot.
+const SEPARATOR: &str = "--- ";
+
+fn root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("snapshots")
+}
+
+fn read(path: &Path) -> String {
+    let mut content = String::new();
+
+    File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut content))
+        .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+
+    content
+}
+
+/// Compile the snapshot specifications, returning the content of every generated file.
+fn generate(name: &str, backend: &str, modules: &[&str]) -> BTreeMap<String, String> {
+    generate_package(&root().join("proto"), PACKAGE, name, backend, modules)
+}
+
+/// Compile the given package, returning the content of every generated file.
+fn generate_package(path: &Path,
+                    package: &[&str],
+                    name: &str,
+                    backend: &str,
+                    modules: &[&str])
+                    -> BTreeMap<String, String> {
+    let mut env = Environment::new(vec![path.to_owned()]);
+    let package = Package::new(package.iter().map(|p| p.to_string()).collect());
+
+    env.import(&package).unwrap_or_else(|e| panic!("{}: {}", package, e));
+    env.verify().unwrap_or_else(|e| panic!("{}", e));
+
+    let output = MemoryOutput::new();
+
+    let options = Options {
+        output: Box::new(output.clone()),
+        package_prefix: None,
+        id_converter: None,
+        modules: modules.iter().map(|m| m.to_string()).collect(),
+    };
+
+    backend::resolve(backend, options, env)
+        .and_then(|backend| backend.process())
+        .unwrap_or_else(|e| panic!("{}: {}", name, e));
+
+    output.files()
+        .into_iter()
+        .map(|(path, content)| {
+            let parts: Vec<_> = path.components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+
+            let content = String::from_utf8(content).expect("output is not UTF-8");
+            (parts.join("/"), content)
+        })
+        .collect()
+}
+
+fn format_snapshot(files: &BTreeMap<String, String>) -> String {
+    let mut out = String::new();
+
+    for (path, content) in files {
+        out.push_str(SEPARATOR);
+        out.push_str(path);
+        out.push('\n');
+        out.push_str(content);
+
+        if !content.ends_with('\n') {
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+/// Describe the first line in which the two contents differ.
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    let mut line = 1;
+
+    loop {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => line += 1,
+            (None, None) => return "contents are equal".to_owned(),
+            (e, a) => {
+                return format!("line {}:\n  expected: {}\n    actual: {}",
+                               line,
+                               e.unwrap_or("<end of snapshot>"),
+                               a.unwrap_or("<end of output>"));
+            }
+        }
+    }
+}
+
+/// Compare the snapshot with the given name against the actual output.
+fn compare(name: &str, actual: String) {
+    let path = root().join(format!("{}.snap", name));
+
+    if env::var(BLESS).is_ok() {
+        let mut f = File::create(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        f.write_all(actual.as_bytes()).expect("write snapshot");
+        return;
+    }
+
+    if !path.is_file() {
+        panic!("{}: missing snapshot, run with {}=1 to create it", path.display(), BLESS);
+    }
+
+    let expected = read(&path);
+
+    if expected != actual {
+        panic!("{}: output does not match snapshot, run with {}=1 to update it\n{}",
+               path.display(),
+               BLESS,
+               first_difference(&expected, &actual));
+    }
+}
+
+fn check(backend: &str, modules: &[&str]) {
+    let name = modules.iter().fold(backend.to_owned(), |name, m| format!("{}-{}", name, m));
+    compare(&name, format_snapshot(&generate(&name, backend, modules)));
+}
+
+/// Compare the output of the given backend for the heroic example against its snapshot.
+fn check_heroic(backend: &str) {
+    let name = format!("{}-heroic", backend);
+    let examples = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let files = generate_package(&examples, &["heroic", "v1"], &name, backend, &[]);
+    compare(&name, format_snapshot(&files));
+}
+
+/// Write the output of the given backend to a temporary directory, and import it back.
+fn check_import(format: &str) {
+    let name = format!("import-{}", format);
+    let dir = env::temp_dir().join(format!("reproto-snapshots-{}-{}", name, std::process::id()));
+
+    for (path, content) in generate(&name, format, &[]) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).expect("create directory");
+        File::create(&path)
+            .and_then(|mut f| f.write_all(content.as_bytes()))
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+    }
+
+    let inputs = vec![dir.clone()];
+
+    let modules = match format {
+        "json-schema" => import::json_schema::import(&inputs, None),
+        "protobuf" => import::protobuf::import(&inputs, None),
+        format => panic!("unsupported import format: {}", format),
+    };
+
+    let _ = fs::remove_dir_all(&dir);
+
+    let sources = import::render(modules.unwrap_or_else(|e| panic!("{}: {}", name, e)));
+    let out = root().join("imported");
+
+    import::verify(&out, &sources).unwrap_or_else(|e| panic!("{}: {}", name, e));
+
+    let files = sources.into_iter()
+        .map(|source| {
+            let parts: Vec<_> = source.path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+
+            (parts.join("/"), source.content)
+        })
+        .collect();
+
+    compare(&name, format_snapshot(&files));
+}
+
+macro_rules! snapshot {
+    ($name:ident, $backend:expr) => {
+        snapshot!($name, $backend, &[]);
+    };
+
+    ($name:ident, $backend:expr, $modules:expr) => {
+        #[test]
+        fn $name() {
+            check($backend, $modules);
+        }
+    };
+}
+
+snapshot!(doc, "doc");
+snapshot!(java, "java");
+snapshot!(java_builder, "java", &["builder"]);
+snapshot!(java_builder_fasterxml, "java", &["builder", "fasterxml"]);
+snapshot!(java_constructor_properties, "java", &["constructor_properties"]);
+snapshot!(java_fasterxml, "java", &["fasterxml"]);
+snapshot!(java_lombok, "java", &["lombok"]);
+snapshot!(java_mutable, "java", &["mutable"]);
+snapshot!(java_nullable, "java", &["nullable"]);
+snapshot!(js, "js");
+snapshot!(js_bigint, "js", &["bigint"]);
+snapshot!(json_schema, "json-schema");
+snapshot!(protobuf, "protobuf");
+snapshot!(python, "python");
+snapshot!(python_strict, "python", &["strict"]);
+snapshot!(rust, "rust");
+snapshot!(typescript, "typescript");
+snapshot!(typescript_bigint, "typescript", &["bigint"]);
+
+#[test]
+fn rust_heroic() {
+    check_heroic("rust");
+}
+
+#[test]
+fn import_json_schema() {
+    check_import("json-schema");
+}
+
+#[test]
+fn import_protobuf() {
+    check_import("protobuf");
+}